@@ -0,0 +1,50 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::repos::audit_event_repo::AuditEventRow;
+
+/// One `auth_audit_events` row (also one NDJSON line of the export).
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEventResponse {
+    pub id: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub event_type: String,
+    pub outcome: String,
+    pub reason: Option<String>,
+    pub user_id: Option<Uuid>,
+    pub session_id: Option<Uuid>,
+    pub client_id: Option<String>,
+    pub jkt: Option<String>,
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+}
+
+impl From<AuditEventRow> for AuditEventResponse {
+    fn from(row: AuditEventRow) -> Self {
+        Self {
+            id: row.id,
+            occurred_at: row.occurred_at,
+            event_type: row.event_type,
+            outcome: row.outcome,
+            reason: row.reason,
+            user_id: row.user_id,
+            session_id: row.session_id,
+            client_id: row.client_id,
+            jkt: row.jkt,
+            client_ip: row.client_ip,
+            user_agent: row.user_agent,
+            request_id: row.request_id,
+        }
+    }
+}
+
+/// A page of events, newest first.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEventPage {
+    pub events: Vec<AuditEventResponse>,
+    /// Pass as `before` for the next (older) page; absent on the last page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<i64>,
+}
@@ -0,0 +1,64 @@
+use serde::Deserialize;
+
+use crate::services::auth::authorization_code_service::AuthorizationRequest;
+
+/// Query string of `GET /authorize` (RFC 6749 Section 4.1.1).
+///
+/// Everything is optional here; the service decides which errors are shown to the
+/// user and which are redirected back to the client.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuthorizeRequest {
+    pub response_type: Option<String>,
+    pub client_id: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    /// RFC 9449 Section 10: bind the code to this DPoP key thumbprint.
+    pub dpop_jkt: Option<String>,
+    /// Space-separated scopes (RFC 6749 Section 3.3).
+    pub scope: Option<String>,
+}
+
+impl AuthorizeRequest {
+    pub fn params(&self) -> AuthorizationRequest<'_> {
+        AuthorizationRequest {
+            response_type: self.response_type.as_deref(),
+            client_id: self.client_id.as_deref(),
+            redirect_uri: self.redirect_uri.as_deref(),
+            state: self.state.as_deref(),
+            code_challenge: self.code_challenge.as_deref(),
+            code_challenge_method: self.code_challenge_method.as_deref(),
+            dpop_jkt: self.dpop_jkt.as_deref(),
+            scope: self.scope.as_deref(),
+        }
+    }
+}
+
+/// Login/consent form posted to `POST /authorize`.
+///
+/// Carries the original request as hidden fields; it is validated again on submit.
+#[derive(Clone, Deserialize)]
+pub struct AuthorizeForm {
+    #[serde(flatten)]
+    pub request: AuthorizeRequest,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// One-time code, for users with a second factor.
+    pub otp: Option<String>,
+    /// `approve` or `deny`
+    pub decision: Option<String>,
+}
+
+// Keep the password and code out of logs.
+impl std::fmt::Debug for AuthorizeForm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthorizeForm")
+            .field("request", &self.request)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("otp", &self.otp.as_ref().map(|_| "<redacted>"))
+            .field("decision", &self.decision)
+            .finish()
+    }
+}
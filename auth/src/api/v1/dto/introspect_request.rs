@@ -0,0 +1,39 @@
+use serde::Deserialize;
+
+/// Request body for `/introspect` (RFC 7662 Section 2.1).
+///
+/// Registered clients may authenticate with `client_secret` or `client_assertion` in
+/// the body, as at `/token`.
+#[derive(Clone, Deserialize)]
+pub struct IntrospectRequest {
+    /// The access or refresh token to inspect.
+    pub token: String,
+
+    /// `access_token` or `refresh_token`. Optional; unknown values are ignored.
+    pub token_type_hint: Option<String>,
+
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub client_assertion_type: Option<String>,
+    pub client_assertion: Option<String>,
+}
+
+// Keep tokens and client secrets out of logs.
+impl std::fmt::Debug for IntrospectRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IntrospectRequest")
+            .field("token", &"<redacted>")
+            .field("token_type_hint", &self.token_type_hint)
+            .field("client_id", &self.client_id)
+            .field(
+                "client_secret",
+                &self.client_secret.as_ref().map(|_| "<redacted>"),
+            )
+            .field("client_assertion_type", &self.client_assertion_type)
+            .field(
+                "client_assertion",
+                &self.client_assertion.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
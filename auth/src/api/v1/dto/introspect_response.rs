@@ -0,0 +1,62 @@
+use serde::Serialize;
+
+use crate::services::auth::introspection_service::IntrospectedToken;
+use crate::services::auth::revocation_service::TokenTypeHint;
+
+/// RFC 7662 Section 2.2 introspection response.
+///
+/// Inactive tokens are reported as `{"active": false}` with no other members.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IntrospectResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cnf: Option<Confirmation>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Confirmation {
+    pub jkt: String,
+}
+
+impl IntrospectResponse {
+    pub fn inactive() -> Self {
+        Self::default()
+    }
+}
+
+impl From<IntrospectedToken> for IntrospectResponse {
+    fn from(t: IntrospectedToken) -> Self {
+        Self {
+            active: true,
+            // Access tokens are always DPoP-bound; refresh tokens have no RFC 6749 token type.
+            token_type: match t.kind {
+                TokenTypeHint::AccessToken => Some("DPoP"),
+                TokenTypeHint::RefreshToken => None,
+            },
+            scope: t.scope,
+            sub: Some(t.sub),
+            client_id: t.client_id,
+            exp: Some(t.exp),
+            iat: t.iat,
+            jti: t.jti,
+            sid: t.session_id.map(|id| id.to_string()),
+            cnf: t.jkt.map(|jkt| Confirmation { jkt }),
+        }
+    }
+}
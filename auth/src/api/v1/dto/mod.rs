@@ -1,3 +1,12 @@
+pub mod audit_event_response;
+pub mod authorize_request;
+pub mod introspect_request;
+pub mod introspect_response;
+pub mod recovery_codes_response;
 pub mod refresh_request;
+pub mod revoke_request;
+pub mod session_response;
 pub mod token_request;
 pub mod token_response;
+pub mod totp_confirm_request;
+pub mod totp_enrollment_response;
@@ -0,0 +1,7 @@
+use serde::Serialize;
+
+/// Single-use recovery codes; shown once, only their hashes are stored.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
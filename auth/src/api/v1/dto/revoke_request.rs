@@ -0,0 +1,11 @@
+use serde::Deserialize;
+
+/// Request body for `/revoke` (RFC 7009 Section 2.1).
+#[derive(Debug, Clone, Deserialize)]
+pub struct RevokeRequest {
+    /// The access or refresh token to revoke.
+    pub token: String,
+
+    /// `access_token` or `refresh_token`. Optional; unknown values are ignored.
+    pub token_type_hint: Option<String>,
+}
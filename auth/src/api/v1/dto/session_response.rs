@@ -0,0 +1,14 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionResponse {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// Thumbprint of the DPoP key the session is bound to.
+    pub dpop_jkt: Option<String>,
+    /// True for the session the request was made with.
+    pub current: bool,
+}
@@ -1,22 +1,78 @@
 use serde::Deserialize;
 use uuid::Uuid;
 
-/// Request body for `/token`.
+/// Request body for `/token`, form-encoded (RFC 6749 Section 4) or JSON.
 ///
 /// We keep a single endpoint and branch by `grant_type`.
 ///
-/// - Issue (default): omit `grant_type` (or set it to a non-refresh value)
-///   and provide `sub` (+ optional `jkt`).
+/// - Authorization code: set `grant_type` to `"authorization_code"` and provide `code`,
+///   `redirect_uri`, `client_id` and `code_verifier` (PKCE).
+/// - Client credentials: set `grant_type` to `"client_credentials"` and authenticate the
+///   client (HTTP Basic, `client_secret` or `client_assertion`); `scope` is optional.
+/// - Token exchange (RFC 8693): set `grant_type` to
+///   `"urn:ietf:params:oauth:grant-type:token-exchange"`, authenticate the client and provide
+///   `subject_token` (+ optional `actor_token`) and the target `audience` or `resource`.
+/// - Password: set `grant_type` to `"password"` and provide `username` + `password`
+///   (+ `otp` when the user has a second factor).
+/// - Issue (development only): omit `grant_type` and provide `sub` (+ optional `jkt`).
 /// - Refresh: set `grant_type` to `"refresh_token"` and provide `refresh_token`.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Clone, Deserialize)]
 pub struct TokenRequest {
     /// OAuth2-style grant type.
     ///
-    /// When `Some("refresh_token")`, this request is treated as a refresh request.
-    /// Otherwise, it's treated as an issue request.
+    /// Unknown values are `unsupported_grant_type`. Omitted, it's treated as an
+    /// (unauthenticated) issue request when that is enabled.
     pub grant_type: Option<String>,
 
-    /// Subject (user id). Required for issuing a new access token.
+    /// Authorization code from `/authorize`. Required when `grant_type == "authorization_code"`.
+    pub code: Option<String>,
+
+    /// Must equal the `redirect_uri` of the authorization request.
+    pub redirect_uri: Option<String>,
+
+    /// Client making the request (required for public clients).
+    pub client_id: Option<String>,
+
+    /// client_secret_post (RFC 6749 Section 2.3.1). Prefer HTTP Basic.
+    pub client_secret: Option<String>,
+
+    /// private_key_jwt (RFC 7523 Section 2.2).
+    pub client_assertion_type: Option<String>,
+    pub client_assertion: Option<String>,
+
+    /// Space-separated scopes to request. On refresh it may only narrow the session's
+    /// scope (downscoping).
+    pub scope: Option<String>,
+
+    /// Token exchange (RFC 8693 Section 2.1): the user's access token and its type.
+    pub subject_token: Option<String>,
+    pub subject_token_type: Option<String>,
+
+    /// Token exchange: the acting party's access token (delegation), and its type.
+    pub actor_token: Option<String>,
+    pub actor_token_type: Option<String>,
+
+    /// Token exchange: only access tokens are issued.
+    pub requested_token_type: Option<String>,
+
+    /// Token exchange: the target service, by name or by URI (RFC 8707).
+    pub audience: Option<String>,
+    pub resource: Option<String>,
+
+    /// PKCE code verifier (RFC 7636).
+    pub code_verifier: Option<String>,
+
+    /// Login name. Required when `grant_type == "password"`.
+    pub username: Option<String>,
+
+    /// Password. Required when `grant_type == "password"`.
+    pub password: Option<String>,
+
+    /// One-time code (TOTP or recovery code). Required with `password` when the user
+    /// has a second factor.
+    pub otp: Option<String>,
+
+    /// Subject (user id). Required for the unauthenticated issue request.
     pub sub: Option<Uuid>,
 
     /// Optional cnf.jkt for sender-constrained access tokens.
@@ -25,3 +81,51 @@ pub struct TokenRequest {
     /// Opaque refresh token. Required when `grant_type == "refresh_token"`.
     pub refresh_token: Option<String>,
 }
+
+// Keep passwords, secrets and codes out of logs.
+impl std::fmt::Debug for TokenRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenRequest")
+            .field("grant_type", &self.grant_type)
+            .field("code", &self.code.as_ref().map(|_| "<redacted>"))
+            .field("redirect_uri", &self.redirect_uri)
+            .field("client_id", &self.client_id)
+            .field(
+                "client_secret",
+                &self.client_secret.as_ref().map(|_| "<redacted>"),
+            )
+            .field("client_assertion_type", &self.client_assertion_type)
+            .field(
+                "client_assertion",
+                &self.client_assertion.as_ref().map(|_| "<redacted>"),
+            )
+            .field("scope", &self.scope)
+            .field(
+                "subject_token",
+                &self.subject_token.as_ref().map(|_| "<redacted>"),
+            )
+            .field("subject_token_type", &self.subject_token_type)
+            .field(
+                "actor_token",
+                &self.actor_token.as_ref().map(|_| "<redacted>"),
+            )
+            .field("actor_token_type", &self.actor_token_type)
+            .field("requested_token_type", &self.requested_token_type)
+            .field("audience", &self.audience)
+            .field("resource", &self.resource)
+            .field(
+                "code_verifier",
+                &self.code_verifier.as_ref().map(|_| "<redacted>"),
+            )
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("otp", &self.otp.as_ref().map(|_| "<redacted>"))
+            .field("sub", &self.sub)
+            .field("jkt", &self.jkt)
+            .field(
+                "refresh_token",
+                &self.refresh_token.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
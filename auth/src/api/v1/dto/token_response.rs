@@ -4,13 +4,21 @@ use uuid::Uuid;
 #[derive(Debug, Clone, Serialize)]
 pub struct TokenResponse {
     pub access_token: String,
+    /// Token exchange: type of `access_token` (RFC 8693 Section 2.2.1).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issued_token_type: Option<&'static str>,
     /// Usually "Bearer"
     pub token_type: String,
     /// Seconds until expiry.
     pub expires_in: u64,
 
-    /// Present when the server returns a refresh token.
-    pub refresh_token: String,
+    /// Present when the server returns a refresh token (not for client_credentials).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+
+    /// Granted scopes (RFC 6749 Section 5.1); omitted when nothing was granted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
 
     /// Present when the server chooses to retrun a session id.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -0,0 +1,16 @@
+use serde::Deserialize;
+
+/// Confirms a pending TOTP enrollment with a code from the authenticator app.
+#[derive(Clone, Deserialize)]
+pub struct TotpConfirmRequest {
+    pub code: String,
+}
+
+// Keep the code out of logs.
+impl std::fmt::Debug for TotpConfirmRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TotpConfirmRequest")
+            .field("code", &"<redacted>")
+            .finish()
+    }
+}
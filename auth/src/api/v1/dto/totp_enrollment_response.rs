@@ -0,0 +1,10 @@
+use serde::Serialize;
+
+/// A started TOTP enrollment; shown once.
+#[derive(Debug, Clone, Serialize)]
+pub struct TotpEnrollmentResponse {
+    /// Base32 secret, for entering by hand.
+    pub secret: String,
+    /// `otpauth://` URI to render as a QR code.
+    pub otpauth_uri: String,
+}
@@ -0,0 +1,54 @@
+use axum::extract::{FromRequestParts, OriginalUri};
+use axum::http::{header, request::Parts};
+
+use crate::api::v1::extractors::RequestContext;
+use crate::error::AppError;
+use crate::services::auth::access_token_verifier::AuthenticatedSession;
+use crate::state::AppState;
+
+/// Authenticated caller for auth-server endpoints.
+///
+/// Expects `Authorization: DPoP <access_token>` (or `Bearer`, which clients sent before
+/// `/token` reported `token_type: DPoP`) plus a `DPoP` proof for this request.
+pub struct CurrentSession(pub AuthenticatedSession);
+
+impl FromRequestParts<AppState> for CurrentSession {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, AppError> {
+        let Ok(RequestContext(request)) = RequestContext::from_request_parts(parts, state).await;
+
+        let authorization = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .ok_or(AppError::Unauthorized)?;
+
+        let (scheme, token) = authorization
+            .split_once(' ')
+            .ok_or(AppError::Unauthorized)?;
+        if !scheme.eq_ignore_ascii_case("DPoP") && !scheme.eq_ignore_ascii_case("Bearer") {
+            return Err(AppError::Unauthorized);
+        }
+
+        let dpop = parts
+            .headers
+            .get("DPoP")
+            .and_then(|v| v.to_str().ok())
+            .ok_or(AppError::Unauthorized)?;
+
+        // Same URL source as the /token handler (the router may be nested).
+        let url = parts
+            .extensions
+            .get::<OriginalUri>()
+            .map(|u| u.0.to_string())
+            .unwrap_or_else(|| parts.uri.to_string());
+
+        let session = state
+            .access
+            .authenticate(token.trim(), dpop, parts.method.as_str(), &url, &request)
+            .await?;
+
+        Ok(CurrentSession(session))
+    }
+}
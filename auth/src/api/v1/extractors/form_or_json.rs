@@ -0,0 +1,38 @@
+use axum::extract::{FromRequest, Request};
+use axum::http::header;
+use axum::{Form, Json};
+use serde::de::DeserializeOwned;
+
+use crate::error::AppError;
+
+/// Request body accepted as `application/x-www-form-urlencoded` (the OAuth 2.0 wire
+/// format) or as JSON (what our own clients send).
+pub struct FormOrJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for FormOrJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, AppError> {
+        let is_form = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|ct| ct.starts_with("application/x-www-form-urlencoded"));
+
+        if is_form {
+            let Form(v) = Form::<T>::from_request(req, state)
+                .await
+                .map_err(|e| AppError::InvalidRequest(e.body_text()))?;
+            Ok(Self(v))
+        } else {
+            let Json(v) = Json::<T>::from_request(req, state)
+                .await
+                .map_err(|e| AppError::InvalidRequest(e.body_text()))?;
+            Ok(Self(v))
+        }
+    }
+}
@@ -0,0 +1,7 @@
+pub mod auth_session;
+pub mod form_or_json;
+pub mod request_meta;
+
+pub use auth_session::CurrentSession;
+pub use form_or_json::FormOrJson;
+pub use request_meta::RequestContext;
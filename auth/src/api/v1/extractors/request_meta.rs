@@ -0,0 +1,73 @@
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::{HeaderMap, header, request::Parts};
+
+use crate::services::auth::audit::RequestMeta;
+use crate::state::AppState;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+const X_REQUEST_ID: &str = "x-request-id";
+
+/// Client IP, user agent and request id of the request, for audit events.
+///
+/// The request id is the `x-request-id` header (generated by the router when the
+/// client sent none).
+pub struct RequestContext(pub RequestMeta);
+
+impl FromRequestParts<AppState> for RequestContext {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Infallible> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        let forwarded_for = state
+            .audit
+            .trust_forwarded_for()
+            .then(|| header_str(&parts.headers, X_FORWARDED_FOR))
+            .flatten();
+
+        Ok(Self(RequestMeta {
+            client_ip: client_ip(forwarded_for, peer),
+            user_agent: header_str(&parts.headers, header::USER_AGENT.as_str()).map(str::to_string),
+            request_id: header_str(&parts.headers, X_REQUEST_ID).map(str::to_string),
+        }))
+    }
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+// The last X-Forwarded-For entry is the one our proxy appended; earlier ones are
+// whatever the client sent.
+fn client_ip(forwarded_for: Option<&str>, peer: Option<IpAddr>) -> Option<String> {
+    forwarded_for
+        .and_then(|v| v.rsplit(',').next())
+        .and_then(|v| v.trim().parse::<IpAddr>().ok())
+        .or(peer)
+        .map(|ip| ip.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forwarded_for_uses_the_entry_our_proxy_appended() {
+        let peer = Some(IpAddr::from([10, 0, 0, 2]));
+
+        assert_eq!(
+            client_ip(Some("1.2.3.4, 203.0.113.7"), peer).as_deref(),
+            Some("203.0.113.7")
+        );
+        assert_eq!(
+            client_ip(Some("garbage"), peer).as_deref(),
+            Some("10.0.0.2")
+        );
+        assert_eq!(client_ip(None, peer).as_deref(), Some("10.0.0.2"));
+    }
+}
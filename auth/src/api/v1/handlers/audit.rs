@@ -0,0 +1,158 @@
+use axum::Json;
+use axum::body::{Body, Bytes};
+use axum::extract::{Query, State};
+use axum::http::{HeaderValue, header};
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use futures_util::stream;
+use serde::Deserialize;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::api::v1::dto::audit_event_response::{AuditEventPage, AuditEventResponse};
+use crate::api::v1::extractors::CurrentSession;
+use crate::error::AppError;
+use crate::repos::audit_event_repo::AuditEventFilter;
+use crate::services::auth::access_token_verifier::AuthenticatedSession;
+use crate::services::auth::audit::{AuditEventType, AuditOutcome};
+use crate::services::auth::scope;
+use crate::state::AppState;
+
+/// Scope required to read the audit trail.
+pub const AUDIT_READ_SCOPE: &str = "audit:read";
+
+const DEFAULT_PAGE_SIZE: i64 = 100;
+const MAX_PAGE_SIZE: i64 = 1_000;
+// Rows fetched per query while streaming an export.
+const EXPORT_BATCH_SIZE: i64 = 500;
+
+#[derive(Debug, Deserialize)]
+pub struct AuditEventQuery {
+    pub event_type: Option<String>,
+    /// `success` or `failure`.
+    pub outcome: Option<String>,
+    pub user_id: Option<Uuid>,
+    pub session_id: Option<Uuid>,
+    pub client_id: Option<String>,
+    /// Inclusive lower / exclusive upper bound on `occurred_at` (RFC 3339).
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// `next_cursor` of the previous page.
+    pub before: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+impl AuditEventQuery {
+    fn filter(self) -> Result<AuditEventFilter, AppError> {
+        if let Some(t) = &self.event_type
+            && AuditEventType::parse(t).is_none()
+        {
+            return Err(AppError::InvalidRequest(format!("unknown event_type: {t}")));
+        }
+        if let Some(o) = &self.outcome
+            && AuditOutcome::parse(o).is_none()
+        {
+            return Err(AppError::InvalidRequest(
+                "outcome must be success or failure".to_string(),
+            ));
+        }
+
+        Ok(AuditEventFilter {
+            event_type: self.event_type,
+            outcome: self.outcome,
+            user_id: self.user_id,
+            session_id: self.session_id,
+            client_id: self.client_id,
+            since: self.since,
+            until: self.until,
+            before: self.before,
+        })
+    }
+}
+
+/// `GET /admin/audit-events`: one page of the audit trail, newest first.
+pub async fn list_audit_events(
+    State(state): State<AppState>,
+    CurrentSession(me): CurrentSession,
+    Query(q): Query<AuditEventQuery>,
+) -> Result<Json<AuditEventPage>, AppError> {
+    require_audit_read(&me)?;
+
+    let limit = q.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let filter = q.filter()?;
+    let rows = state.audit.list(&filter, limit).await?;
+
+    // A full page may have more behind it.
+    let next_cursor = (rows.len() as i64 == limit)
+        .then(|| rows.last().map(|r| r.id))
+        .flatten();
+    let events = rows.into_iter().map(AuditEventResponse::from).collect();
+
+    Ok(Json(AuditEventPage {
+        events,
+        next_cursor,
+    }))
+}
+
+/// `GET /admin/audit-events/export`: every matching event as NDJSON, newest first.
+///
+/// Streamed in batches; `limit` is ignored. A backend failure midway ends the body
+/// early, so consumers should treat a transfer error as an incomplete export.
+pub async fn export_audit_events(
+    State(state): State<AppState>,
+    CurrentSession(me): CurrentSession,
+    Query(q): Query<AuditEventQuery>,
+) -> Result<Response, AppError> {
+    require_audit_read(&me)?;
+    let filter = q.filter()?;
+    info!(target: "security", event = "audit_export", user_id = %me.user_id, ?filter, "audit events exported");
+
+    let audit = state.audit.clone();
+    let batches = stream::unfold(Some(filter), move |filter| {
+        let audit = audit.clone();
+        async move {
+            let mut filter = filter?;
+            let rows = match audit.list(&filter, EXPORT_BATCH_SIZE).await {
+                Ok(rows) => rows,
+                Err(e) => return Some((Err(std::io::Error::other(e)), None)),
+            };
+            if rows.is_empty() {
+                return None;
+            }
+
+            let mut chunk = Vec::new();
+            for row in &rows {
+                // Serializing plain data into a Vec cannot fail.
+                let _ = serde_json::to_writer(&mut chunk, &AuditEventResponse::from(row.clone()));
+                chunk.push(b'\n');
+            }
+            let next = (rows.len() as i64 == EXPORT_BATCH_SIZE).then(|| {
+                filter.before = rows.last().map(|r| r.id);
+                filter
+            });
+            Some((Ok(Bytes::from(chunk)), next))
+        }
+    });
+
+    let mut res = Body::from_stream(batches).into_response();
+    res.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/x-ndjson"),
+    );
+    res.headers_mut().insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_static("attachment; filename=\"audit-events.ndjson\""),
+    );
+    Ok(res)
+}
+
+fn require_audit_read(me: &AuthenticatedSession) -> Result<(), AppError> {
+    if scope::split(me.scope.as_deref())
+        .iter()
+        .any(|s| s == AUDIT_READ_SCOPE)
+    {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden)
+    }
+}
@@ -0,0 +1,280 @@
+use axum::Form;
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use tracing::{debug, error};
+use uuid::Uuid;
+
+use crate::api::v1::dto::authorize_request::{AuthorizeForm, AuthorizeRequest};
+use crate::api::v1::extractors::RequestContext;
+use crate::api::v1::handlers::token::record_lockout;
+use crate::error::{AppError, OAuthError};
+use crate::services::auth::audit::{AuditEvent, AuditEventType, RequestMeta};
+use crate::services::auth::authorization_code_service::{AuthorizeError, ValidatedAuthorization};
+use crate::services::auth::throttle::ThrottleKeys;
+use crate::state::AppState;
+
+/// `GET /authorize`: validate the request and show the login/consent page.
+pub async fn authorize(
+    State(state): State<AppState>,
+    Query(req): Query<AuthorizeRequest>,
+) -> Response {
+    match state.authorization.validate(req.params()).await {
+        Ok(authz) => login_page(StatusCode::OK, &req, &authz, None),
+        Err(e) => rejection(e),
+    }
+}
+
+/// `POST /authorize`: authenticate the user and redirect back with a code (or an error).
+///
+/// Failed sign-ins are audited and throttled like failed password grants at `/token`
+/// (same IP and username counters; `429` with `Retry-After` while blocked).
+pub async fn authorize_submit(
+    State(state): State<AppState>,
+    RequestContext(request): RequestContext,
+    Form(form): Form<AuthorizeForm>,
+) -> Response {
+    let req = &form.request;
+    let authz = match state.authorization.validate(req.params()).await {
+        Ok(authz) => authz,
+        Err(e) => return rejection(e),
+    };
+
+    if form.decision.as_deref() != Some("approve") {
+        return Redirect::to(&state.authorization.deny(&authz)).into_response();
+    }
+
+    let username = form.username.as_deref().unwrap_or_default();
+    let password = form.password.as_deref().unwrap_or_default();
+    let keys = ThrottleKeys {
+        ip: request.client_ip.clone(),
+        subject: Some(username)
+            .filter(|u| !u.trim().is_empty())
+            .map(ThrottleKeys::user_subject),
+        jkt: None,
+    };
+    if let Some(retry_after) = state.throttle.retry_after(&keys).await {
+        debug!(retry_after, "sign-in throttled");
+        return throttled_page(req, &authz, retry_after);
+    }
+
+    let user_id = match state.password_grant.authenticate(username, password).await {
+        Ok(user_id) => user_id,
+        Err(AppError::Unauthorized) => {
+            let reason = format!("invalid_grant: invalid username or password for {username:?}");
+            record_failure(&state, &authz, None, reason, &request).await;
+            charge_failure(&state, &keys, &request).await;
+            return login_page(
+                StatusCode::UNAUTHORIZED,
+                req,
+                &authz,
+                Some("Invalid username or password."),
+            );
+        }
+        Err(AppError::InvalidRequest(msg)) => {
+            return login_page(StatusCode::BAD_REQUEST, req, &authz, Some(&msg));
+        }
+        Err(_) => return error_page(StatusCode::INTERNAL_SERVER_ERROR, "Something went wrong."),
+    };
+
+    let authn = match state.mfa.verify_sign_in(user_id, form.otp.as_deref()).await {
+        Ok(authn) => authn,
+        Err(AppError::OAuth(e @ OAuthError::OtpRequired)) => {
+            let reason = format!("{}: {e}", e.code());
+            record_failure(&state, &authz, Some(user_id), reason, &request).await;
+            return login_page(
+                StatusCode::UNAUTHORIZED,
+                req,
+                &authz,
+                Some("Enter the one-time code from your authenticator app."),
+            );
+        }
+        Err(AppError::OAuth(e @ OAuthError::InvalidGrant(_))) => {
+            let reason = format!("{}: {e}", e.code());
+            record_failure(&state, &authz, Some(user_id), reason, &request).await;
+            charge_failure(&state, &keys, &request).await;
+            return login_page(
+                StatusCode::UNAUTHORIZED,
+                req,
+                &authz,
+                Some("Invalid one-time code."),
+            );
+        }
+        Err(_) => return error_page(StatusCode::INTERNAL_SERVER_ERROR, "Something went wrong."),
+    };
+
+    match state.authorization.approve(&authz, user_id, &authn).await {
+        // 303 so the browser follows with GET (RFC 9110 Section 15.4.4).
+        Ok(location) => {
+            // The session is only created when the code is redeemed at `/token`.
+            state.throttle.record_success(&keys, false).await;
+            Redirect::to(&location).into_response()
+        }
+        Err(_) => error_page(StatusCode::INTERNAL_SERVER_ERROR, "Something went wrong."),
+    }
+}
+
+async fn record_failure(
+    state: &AppState,
+    authz: &ValidatedAuthorization,
+    user_id: Option<Uuid>,
+    reason: String,
+    request: &RequestMeta,
+) {
+    let mut event = AuditEvent::failure(AuditEventType::TokenIssued, reason)
+        .with_client(Some(&authz.client_id))
+        .with_request(request);
+    if let Some(user_id) = user_id {
+        event = event.with_user(user_id);
+    }
+    state.audit.record(event).await;
+}
+
+// Wrong passwords and one-time codes count; the prompt for a code does not.
+async fn charge_failure(state: &AppState, keys: &ThrottleKeys, request: &RequestMeta) {
+    for lockout in state.throttle.record_failure(keys).await {
+        record_lockout(state, keys, &lockout, request).await;
+    }
+}
+
+fn throttled_page(
+    req: &AuthorizeRequest,
+    authz: &ValidatedAuthorization,
+    retry_after: u64,
+) -> Response {
+    let mut res = login_page(
+        StatusCode::TOO_MANY_REQUESTS,
+        req,
+        authz,
+        Some("Too many failed sign-in attempts. Try again later."),
+    );
+    res.headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    res
+}
+
+fn rejection(err: AuthorizeError) -> Response {
+    match err {
+        AuthorizeError::InvalidClient(reason) => error_page(StatusCode::BAD_REQUEST, reason),
+        AuthorizeError::Redirect(location) => Redirect::to(&location).into_response(),
+        AuthorizeError::Internal => {
+            error!("authorization request failed");
+            error_page(StatusCode::INTERNAL_SERVER_ERROR, "Something went wrong.")
+        }
+    }
+}
+
+fn login_page(
+    status: StatusCode,
+    req: &AuthorizeRequest,
+    authz: &ValidatedAuthorization,
+    error: Option<&str>,
+) -> Response {
+    let hidden = [
+        ("response_type", req.response_type.as_deref()),
+        ("client_id", req.client_id.as_deref()),
+        ("redirect_uri", req.redirect_uri.as_deref()),
+        ("state", req.state.as_deref()),
+        ("code_challenge", req.code_challenge.as_deref()),
+        (
+            "code_challenge_method",
+            req.code_challenge_method.as_deref(),
+        ),
+        ("dpop_jkt", req.dpop_jkt.as_deref()),
+        ("scope", req.scope.as_deref()),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| {
+        value.map(|v| {
+            format!(
+                r#"<input type="hidden" name="{name}" value="{}">"#,
+                escape(v)
+            )
+        })
+    })
+    .collect::<String>();
+
+    let error = error
+        .map(|msg| format!(r#"<p class="error">{}</p>"#, escape(msg)))
+        .unwrap_or_default();
+
+    let scope = authz
+        .scope
+        .as_deref()
+        .map(|s| format!("<p>Requested access: <code>{}</code></p>\n", escape(s)))
+        .unwrap_or_default();
+
+    let body = format!(
+        r#"<h1>Sign in</h1>
+<p><strong>{client}</strong> wants to access your account.</p>
+{scope}{error}<form method="post">
+{hidden}
+<label>Username <input name="username" autocomplete="username" required autofocus></label>
+<label>Password <input name="password" type="password" autocomplete="current-password" required></label>
+<label>One-time code <input name="otp" inputmode="numeric" autocomplete="one-time-code" placeholder="if enabled"></label>
+<button name="decision" value="approve">Sign in and allow</button>
+<button name="decision" value="deny" formnovalidate>Deny</button>
+</form>"#,
+        client = escape(&authz.client_name),
+    );
+    page(status, "Sign in", &body)
+}
+
+fn error_page(status: StatusCode, message: &str) -> Response {
+    let body = format!("<h1>Authorization failed</h1>\n<p>{}</p>", escape(message));
+    page(status, "Authorization failed", &body)
+}
+
+fn page(status: StatusCode, title: &str, body: &str) -> Response {
+    let html = format!(
+        r#"<!doctype html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; max-width: 24rem; margin: 4rem auto; padding: 0 1rem; }}
+label, button {{ display: block; margin: 0.75rem 0; }}
+input {{ display: block; width: 100%; box-sizing: border-box; padding: 0.4rem; }}
+.error {{ color: #b00020; }}
+</style>
+</head>
+<body>
+{body}
+</body>
+</html>"#
+    );
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    // No framing (clickjacking), no scripts, no leaking the query string via Referer.
+    headers.insert(
+        header::CONTENT_SECURITY_POLICY,
+        HeaderValue::from_static(
+            "default-src 'none'; style-src 'unsafe-inline'; frame-ancestors 'none'",
+        ),
+    );
+    headers.insert(header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    headers.insert(
+        header::REFERRER_POLICY,
+        HeaderValue::from_static("no-referrer"),
+    );
+
+    (status, headers, Html(html)).into_response()
+}
+
+fn escape(v: &str) -> String {
+    let mut out = String::with_capacity(v.len());
+    for c in v.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#x27;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,45 @@
+use axum::Json;
+use axum::extract::State;
+use axum::http::{HeaderMap, HeaderValue, header};
+use tracing::debug;
+
+use crate::api::v1::dto::introspect_request::IntrospectRequest;
+use crate::api::v1::dto::introspect_response::IntrospectResponse;
+use crate::api::v1::extractors::FormOrJson;
+use crate::error::AppError;
+use crate::services::auth::client_auth::ClientCredentials;
+use crate::services::auth::revocation_service::TokenTypeHint;
+use crate::state::AppState;
+
+/// RFC 7662 token introspection for authenticated callers.
+pub async fn introspect(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    FormOrJson(req): FormOrJson<IntrospectRequest>,
+) -> Result<(HeaderMap, Json<IntrospectResponse>), AppError> {
+    let creds = ClientCredentials {
+        authorization: headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok()),
+        client_id: req.client_id.as_deref(),
+        client_secret: req.client_secret.as_deref(),
+        client_assertion_type: req.client_assertion_type.as_deref(),
+        client_assertion: req.client_assertion.as_deref(),
+    };
+    let caller = state.introspection.authenticate_caller(creds).await?;
+
+    let hint = req
+        .token_type_hint
+        .as_deref()
+        .and_then(TokenTypeHint::parse);
+    let out = state.introspection.introspect(&req.token, hint).await?;
+    debug!(caller = %caller, active = out.is_some(), "Token introspected");
+
+    let mut res_headers = HeaderMap::new();
+    res_headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+
+    let body = out
+        .map(IntrospectResponse::from)
+        .unwrap_or_else(IntrospectResponse::inactive);
+    Ok((res_headers, Json(body)))
+}
@@ -0,0 +1,74 @@
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+
+use crate::api::v1::dto::recovery_codes_response::RecoveryCodesResponse;
+use crate::api::v1::dto::totp_confirm_request::TotpConfirmRequest;
+use crate::api::v1::dto::totp_enrollment_response::TotpEnrollmentResponse;
+use crate::api::v1::extractors::{CurrentSession, RequestContext};
+use crate::error::AppError;
+use crate::services::auth::audit::{AuditEvent, AuditEventType};
+use crate::services::auth::authn_context::{ACR_MFA, acr_satisfies};
+use crate::state::AppState;
+
+/// `POST /mfa/totp`: start enrollment. The factor is pending until confirmed.
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    CurrentSession(me): CurrentSession,
+) -> Result<Json<TotpEnrollmentResponse>, AppError> {
+    let enrollment = state.mfa.enroll_totp(me.user_id).await?;
+
+    Ok(Json(TotpEnrollmentResponse {
+        secret: enrollment.secret,
+        otpauth_uri: enrollment.otpauth_uri,
+    }))
+}
+
+/// `POST /mfa/totp/confirm`: confirm with a current code; returns the recovery codes.
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    RequestContext(request): RequestContext,
+    CurrentSession(me): CurrentSession,
+    Json(req): Json<TotpConfirmRequest>,
+) -> Result<Json<RecoveryCodesResponse>, AppError> {
+    let recovery_codes = state.mfa.confirm_totp(me.user_id, &req.code).await?;
+
+    state
+        .audit
+        .record(
+            AuditEvent::success(AuditEventType::MfaEnrolled)
+                .with_user(me.user_id)
+                .with_session(me.session_id)
+                .with_jkt(Some(&me.jkt))
+                .with_request(&request),
+        )
+        .await;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// `DELETE /mfa/totp`: remove the factor. Only a session that signed in with it may.
+pub async fn disable_totp(
+    State(state): State<AppState>,
+    RequestContext(request): RequestContext,
+    CurrentSession(me): CurrentSession,
+) -> Result<StatusCode, AppError> {
+    if !acr_satisfies(me.acr.as_deref(), ACR_MFA) {
+        return Err(AppError::InsufficientUserAuthentication(ACR_MFA));
+    }
+
+    state.mfa.disable_totp(me.user_id).await?;
+
+    state
+        .audit
+        .record(
+            AuditEvent::success(AuditEventType::MfaDisabled)
+                .with_user(me.user_id)
+                .with_session(me.session_id)
+                .with_jkt(Some(&me.jkt))
+                .with_request(&request),
+        )
+        .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1 +1,7 @@
+pub mod audit;
+pub mod authorize;
+pub mod introspect;
+pub mod mfa;
+pub mod revoke;
+pub mod sessions;
 pub mod token;
@@ -0,0 +1,94 @@
+use axum::extract::{OriginalUri, State};
+use axum::http::{HeaderMap, Method, StatusCode};
+
+use crate::api::v1::dto::revoke_request::RevokeRequest;
+use crate::api::v1::extractors::{CurrentSession, FormOrJson, RequestContext};
+use crate::api::v1::handlers::token::response_headers;
+use crate::error::AppError;
+use crate::services::auth::audit::{AuditEvent, AuditEventType};
+use crate::services::auth::revocation_service::TokenTypeHint;
+use crate::state::AppState;
+
+/// RFC 7009 token revocation.
+///
+/// Answers 200 whether or not the token was known, revoked or already inactive.
+pub async fn revoke(
+    State(state): State<AppState>,
+    RequestContext(request): RequestContext,
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    FormOrJson(req): FormOrJson<RevokeRequest>,
+) -> Result<(StatusCode, HeaderMap), AppError> {
+    let dpop = headers
+        .get("DPoP")
+        .and_then(|v| v.to_str().ok())
+        .ok_or(AppError::Unauthorized)?;
+
+    let hint = req
+        .token_type_hint
+        .as_deref()
+        .and_then(TokenTypeHint::parse);
+    let url = uri.to_string();
+    let revocation = match state
+        .revocation
+        .revoke(&req.token, hint, dpop, method.as_str(), &url)
+        .await
+    {
+        Ok(v) => v,
+        Err(e) => {
+            if matches!(e, AppError::Unauthorized) {
+                let event = AuditEvent::failure(
+                    AuditEventType::DpopProofRejected,
+                    "invalid DPoP proof (revoke)",
+                );
+                state.audit.record(event.with_request(&request)).await;
+            }
+            return Err(e);
+        }
+    };
+
+    // Unknown tokens are recorded too: someone holding the key tried.
+    let event = match (revocation.session_id, &revocation.jti) {
+        (Some(session_id), _) => {
+            AuditEvent::success(AuditEventType::TokenRevoked).with_session(session_id)
+        }
+        // A client token: nothing but the token itself to revoke.
+        (None, Some(jti)) => AuditEvent::success(AuditEventType::TokenRevoked)
+            .with_reason(format!("access token {jti}")),
+        (None, None) => AuditEvent::failure(
+            AuditEventType::TokenRevoked,
+            "token unknown, inactive or bound to another key",
+        ),
+    };
+    state
+        .audit
+        .record(event.with_jkt(Some(&revocation.jkt)).with_request(&request))
+        .await;
+
+    Ok((StatusCode::OK, response_headers(&state)))
+}
+
+/// Revoke the session the access token belongs to.
+pub async fn logout(
+    State(state): State<AppState>,
+    RequestContext(request): RequestContext,
+    CurrentSession(me): CurrentSession,
+) -> Result<StatusCode, AppError> {
+    state
+        .sessions
+        .revoke(me.user_id, me.session_id, chrono::Utc::now())
+        .await?;
+    state
+        .audit
+        .record(
+            AuditEvent::success(AuditEventType::Logout)
+                .with_user(me.user_id)
+                .with_session(me.session_id)
+                .with_jkt(Some(&me.jkt))
+                .with_request(&request),
+        )
+        .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,98 @@
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use chrono::Utc;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::api::v1::dto::session_response::SessionResponse;
+use crate::api::v1::extractors::{CurrentSession, RequestContext};
+use crate::error::AppError;
+use crate::services::auth::access_token_verifier::AuthenticatedSession;
+use crate::services::auth::audit::{AuditEvent, AuditEventType, RequestMeta};
+use crate::state::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct RevokeSessionsQuery {
+    /// Only `current` is supported: revoke every session except the caller's.
+    pub except: Option<String>,
+}
+
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    CurrentSession(me): CurrentSession,
+) -> Result<Json<Vec<SessionResponse>>, AppError> {
+    let rows = state.sessions.list(me.user_id).await?;
+
+    let res = rows
+        .into_iter()
+        .map(|row| SessionResponse {
+            current: row.id == me.session_id,
+            id: row.id,
+            created_at: row.created_at,
+            last_used_at: row.last_used_at,
+            dpop_jkt: row.dpop_jkt,
+        })
+        .collect();
+
+    Ok(Json(res))
+}
+
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    RequestContext(request): RequestContext,
+    CurrentSession(me): CurrentSession,
+    Path(session_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    state
+        .sessions
+        .revoke(me.user_id, session_id, Utc::now())
+        .await?;
+    record_revoked(&state, &me, &request, &[session_id]).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn revoke_sessions(
+    State(state): State<AppState>,
+    RequestContext(request): RequestContext,
+    CurrentSession(me): CurrentSession,
+    Query(q): Query<RevokeSessionsQuery>,
+) -> Result<StatusCode, AppError> {
+    // Revoking the current session too is a logout, not a bulk delete.
+    if q.except.as_deref() != Some("current") {
+        return Err(AppError::InvalidRequest(
+            "except=current is required".to_string(),
+        ));
+    }
+
+    let revoked = state
+        .sessions
+        .revoke_others(me.user_id, me.session_id, Utc::now())
+        .await?;
+    record_revoked(&state, &me, &request, &revoked).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// One event per revoked session; the jkt is the caller's (who revoked it).
+async fn record_revoked(
+    state: &AppState,
+    me: &AuthenticatedSession,
+    request: &RequestMeta,
+    revoked: &[Uuid],
+) {
+    for &session_id in revoked {
+        state
+            .audit
+            .record(
+                AuditEvent::success(AuditEventType::SessionRevoked)
+                    .with_reason(format!("revoked from session {}", me.session_id))
+                    .with_user(me.user_id)
+                    .with_session(session_id)
+                    .with_jkt(Some(&me.jkt))
+                    .with_request(request),
+            )
+            .await;
+    }
+}
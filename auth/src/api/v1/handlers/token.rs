@@ -1,71 +1,440 @@
 use axum::Json;
 use axum::extract::{OriginalUri, State};
-use axum::http::{HeaderMap, Method, StatusCode};
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode, header};
+use tracing::{debug, warn};
 
 use crate::api::v1::dto::{token_request::TokenRequest, token_response::TokenResponse};
-use crate::error::AppError;
+use crate::api::v1::extractors::{FormOrJson, RequestContext};
+use crate::error::{AppError, DPOP_NONCE, OAuthError};
+use crate::services::auth::audit::{AuditEvent, AuditEventType, RequestMeta};
+use crate::services::auth::authorization_code_service::CodeGrant;
+use crate::services::auth::client_auth::ClientCredentials;
+use crate::services::auth::dpop::thumbprint::jwk_thumbprint_from_dpop_header;
+use crate::services::auth::throttle::{Lockout, ThrottleKeys};
+use crate::services::auth::token_exchange::{ExchangeRequest, TOKEN_EXCHANGE_GRANT};
+use crate::services::auth::token_service::IssuedTokenPair;
 use crate::state::AppState;
 
+// Hand out the current nonce on every response so clients can pick it up
+// before it is required (RFC 9449 Section 8.2).
+pub(crate) fn response_headers(state: &AppState) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(nonce) = state.auth.dpop_nonce()
+        && let Ok(v) = HeaderValue::from_str(&nonce)
+    {
+        headers.insert(DPOP_NONCE, v);
+    }
+    headers
+}
+
+/// `POST /token` (RFC 6749 Section 3.2).
+///
+/// Accepts form-encoded (RFC 6749) or JSON bodies. Every error is answered in the
+/// OAuth shape (Section 5.2), and nothing is cached (Section 5.1). Every grant attempt
+/// is recorded in the audit log, and failures are throttled (`429` with `Retry-After`).
 pub async fn token(
     State(state): State<AppState>,
+    RequestContext(request): RequestContext,
     method: Method,
     OriginalUri(uri): OriginalUri,
     headers: HeaderMap,
-    Json(req): Json<TokenRequest>,
-) -> Result<(StatusCode, Json<TokenResponse>), AppError> {
+    body: Result<FormOrJson<TokenRequest>, AppError>,
+) -> Result<(StatusCode, HeaderMap, Json<TokenResponse>), AppError> {
+    let FormOrJson(req) = body.map_err(oauth_error)?;
+    let grant_type = req.grant_type.clone();
+    let client_id = client_credentials(&req, &headers).claimed_client_id();
+
+    let keys = throttle_keys(&req, client_id.as_deref(), &headers, &request);
+    if let Some(retry_after) = state.throttle.retry_after(&keys).await {
+        debug!(retry_after, "token request throttled");
+        return Err(AppError::TooManyRequests(retry_after));
+    }
+
+    let (res, event) = match grant(&state, &method, &uri.to_string(), &headers, req).await {
+        Ok(v) => v,
+        Err(e) => {
+            let e = oauth_error(e);
+            if counts_as_failure(&e) {
+                let charged = charged_keys(&state, &keys, &headers, &e);
+                for lockout in state.throttle.record_failure(&charged).await {
+                    record_lockout(&state, &charged, &lockout, &request).await;
+                }
+            }
+            if let Some(event) = failure_event(grant_type.as_deref(), &e) {
+                let event = event.with_client(client_id.as_deref());
+                state.audit.record(event.with_request(&request)).await;
+            }
+            return Err(e);
+        }
+    };
+    // Only user token issuance creates a session.
+    let new_session = event.event_type == AuditEventType::TokenIssued;
+    state.throttle.record_success(&keys, new_session).await;
+    state.audit.record(event.with_request(&request)).await;
+
+    let mut res_headers = response_headers(&state);
+    res_headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    res_headers.insert(header::PRAGMA, HeaderValue::from_static("no-cache"));
+    Ok((StatusCode::OK, res_headers, Json(res)))
+}
+
+// Client IP, the subject the request names (username, else client) and the key of its
+// DPoP proof. The proof is not verified yet: the thumbprint is only used to look up
+// blocks, failures are charged to it by `charged_keys`.
+fn throttle_keys(
+    req: &TokenRequest,
+    client_id: Option<&str>,
+    headers: &HeaderMap,
+    request: &RequestMeta,
+) -> ThrottleKeys {
+    let subject = match req.grant_type.as_deref() {
+        Some("password") => req.username.as_deref().map(ThrottleKeys::user_subject),
+        _ => client_id.map(|c| format!("client:{c}")),
+    };
+    let jkt = headers
+        .get("DPoP")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|proof| jsonwebtoken::decode_header(proof).ok())
+        .and_then(|h| jwk_thumbprint_from_dpop_header(&h).ok());
+
+    ThrottleKeys {
+        ip: request.client_ip.clone(),
+        subject,
+        jkt,
+    }
+}
+
+// The keys a failure counts against. Anyone can put someone else's public key in a
+// proof header, so the key is only charged when the proof is signed with it; a bad
+// proof is charged to the IP alone.
+fn charged_keys(
+    state: &AppState,
+    keys: &ThrottleKeys,
+    headers: &HeaderMap,
+    e: &AppError,
+) -> ThrottleKeys {
+    if matches!(e, AppError::OAuth(OAuthError::InvalidDpopProof(_))) {
+        return ThrottleKeys {
+            ip: keys.ip.clone(),
+            ..ThrottleKeys::default()
+        };
+    }
+
+    let jkt = headers
+        .get("DPoP")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|proof| state.auth.dpop_signing_key(proof));
+    ThrottleKeys {
+        jkt,
+        ..keys.clone()
+    }
+}
+
+// Guessing shows up as bad grants, client credentials and proofs; malformed requests,
+// nonce challenges and the prompt for a one-time code don't count.
+fn counts_as_failure(e: &AppError) -> bool {
+    matches!(
+        e,
+        AppError::OAuth(
+            OAuthError::InvalidGrant(_)
+                | OAuthError::InvalidClient
+                | OAuthError::InvalidDpopProof(_)
+                | OAuthError::UnauthorizedClient
+        )
+    )
+}
+
+pub(crate) async fn record_lockout(
+    state: &AppState,
+    keys: &ThrottleKeys,
+    lockout: &Lockout,
+    request: &RequestMeta,
+) {
+    warn!(
+        target: "security",
+        event = "token_lockout",
+        kind = lockout.kind.as_str(),
+        failures = lockout.failures,
+        seconds = lockout.seconds,
+        client_ip = ?keys.ip,
+        subject = ?keys.subject,
+        jkt = ?keys.jkt,
+        "too many failed sign-in or token requests; locked out"
+    );
+
+    let reason = format!(
+        "{} failed attempts by {}; locked out for {}s",
+        lockout.failures,
+        lockout.kind.as_str(),
+        lockout.seconds
+    );
+    let client_id = keys
+        .subject
+        .as_deref()
+        .and_then(|s| s.strip_prefix("client:"));
+    let event = AuditEvent::failure(AuditEventType::TokenLockout, reason)
+        .with_client(client_id)
+        .with_jkt(keys.jkt.as_deref())
+        .with_request(request);
+    state.audit.record(event).await;
+}
+
+async fn grant(
+    state: &AppState,
+    method: &Method,
+    url: &str,
+    headers: &HeaderMap,
+    req: TokenRequest,
+) -> Result<(TokenResponse, AuditEvent), AppError> {
     match req.grant_type.as_deref() {
         Some("refresh_token") => {
-            let refresh_token = req.refresh_token.ok_or(AppError::Internal)?;
+            let refresh_token = req
+                .refresh_token
+                .as_deref()
+                .ok_or_else(|| missing("refresh_token"))?;
+            let dpop = dpop_proof(headers)?;
 
-            // DPoP header
-            let dpop = headers
-                .get("DPoP")
-                .and_then(|v| v.to_str().ok())
-                .ok_or(AppError::Unauthorized)?;
-
-            let url = uri.to_string();
             let out = state
                 .auth
-                .refresh(&refresh_token, dpop, method.as_str(), &url)
+                .refresh(
+                    refresh_token,
+                    req.scope.as_deref(),
+                    dpop,
+                    method.as_str(),
+                    url,
+                )
+                .await?;
+            Ok(pair_response(out, AuditEventType::TokenRefreshed, None))
+        }
+        Some("authorization_code") => {
+            let (Some(code), Some(redirect_uri), Some(code_verifier)) = (
+                req.code.as_deref(),
+                req.redirect_uri.as_deref(),
+                req.code_verifier.as_deref(),
+            ) else {
+                return Err(missing("code, redirect_uri and code_verifier"));
+            };
+            let client = state
+                .clients
+                .authenticate(client_credentials(&req, headers))
                 .await?;
+            let dpop = dpop_proof(headers)?;
 
-            Ok((
-                StatusCode::OK,
-                Json(TokenResponse {
-                    access_token: out.access_token,
-                    token_type: "Bearer".to_string(),
-                    expires_in: out.expires_in,
-                    refresh_token: out.refresh_token,
-                    session_id: Some(out.session_id),
-                }),
+            let out = state
+                .authorization
+                .exchange(
+                    CodeGrant {
+                        code,
+                        client: &client,
+                        redirect_uri,
+                        code_verifier,
+                    },
+                    dpop,
+                    method.as_str(),
+                    url,
+                )
+                .await?;
+            Ok(pair_response(
+                out,
+                AuditEventType::TokenIssued,
+                Some(&client.client_id),
             ))
         }
-        _ => {
-            // Issue access token + refresh token (DPoP-bound session from the start)
-            let sub = req.sub.ok_or(AppError::Internal)?;
+        Some("client_credentials") => {
+            let client = state
+                .clients
+                .authenticate(client_credentials(&req, headers))
+                .await?;
+            let dpop = dpop_proof(headers)?;
+
+            let out = state
+                .auth
+                .issue_client_token(&client, req.scope.as_deref(), dpop, method.as_str(), url)
+                .await?;
+            let event = AuditEvent::success(AuditEventType::ClientTokenIssued)
+                .with_client(Some(&client.client_id))
+                .with_jkt(Some(&out.jkt));
+
+            let res = TokenResponse {
+                access_token: out.access_token,
+                issued_token_type: None,
+                token_type: out.token_type.to_string(),
+                expires_in: out.expires_in,
+                refresh_token: None,
+                scope: out.scope,
+                session_id: None,
+            };
+            Ok((res, event))
+        }
+        Some(TOKEN_EXCHANGE_GRANT) => {
+            let (Some(subject_token), Some(subject_token_type)) = (
+                req.subject_token.as_deref(),
+                req.subject_token_type.as_deref(),
+            ) else {
+                return Err(missing("subject_token and subject_token_type"));
+            };
+            let client = state
+                .clients
+                .authenticate(client_credentials(&req, headers))
+                .await?;
+            let dpop = dpop_proof(headers)?;
+
+            let out = state
+                .token_exchange
+                .exchange(
+                    ExchangeRequest {
+                        client: &client,
+                        subject_token,
+                        subject_token_type,
+                        actor_token: req.actor_token.as_deref(),
+                        actor_token_type: req.actor_token_type.as_deref(),
+                        requested_token_type: req.requested_token_type.as_deref(),
+                        audience: req.audience.as_deref(),
+                        resource: req.resource.as_deref(),
+                        scope: req.scope.as_deref(),
+                    },
+                    dpop,
+                    method.as_str(),
+                    url,
+                )
+                .await?;
+            let event = AuditEvent::success(AuditEventType::TokenExchanged)
+                .with_user(out.user_id)
+                .with_session(out.session_id)
+                .with_client(Some(&client.client_id))
+                .with_jkt(Some(&out.jkt));
+
+            let res = TokenResponse {
+                access_token: out.access_token,
+                issued_token_type: Some(out.issued_token_type),
+                token_type: out.token_type.to_string(),
+                expires_in: out.expires_in,
+                refresh_token: None,
+                scope: out.scope,
+                session_id: None,
+            };
+            Ok((res, event))
+        }
+        Some("password") => {
+            let username = req.username.as_deref().unwrap_or_default();
+            let password = req.password.as_deref().unwrap_or_default();
+            let dpop = dpop_proof(headers)?;
 
-            // DPoP header (required)
-            let dpop = headers
-                .get("DPoP")
-                .and_then(|v| v.to_str().ok())
-                .ok_or(AppError::Unauthorized)?;
+            // Wrong resource owner credentials are `invalid_grant` (RFC 6749 Section 5.2).
+            let sub = match state.password_grant.authenticate(username, password).await {
+                Err(AppError::Unauthorized) => {
+                    return Err(OAuthError::InvalidGrant("invalid username or password").into());
+                }
+                other => other?,
+            };
+            let authn = state.mfa.verify_sign_in(sub, req.otp.as_deref()).await?;
 
-            let url = uri.to_string();
             let out = state
                 .auth
-                .issue_token_pair(sub, dpop, method.as_str(), &url)
+                .issue_token_pair(
+                    sub,
+                    req.scope.as_deref(),
+                    Some(&authn),
+                    dpop,
+                    method.as_str(),
+                    url,
+                )
                 .await?;
+            Ok(pair_response(out, AuditEventType::TokenIssued, None))
+        }
+        None if state.auth.subject_grant_enabled() => {
+            // Issue access token + refresh token for a caller-supplied subject
+            // (development only; the subject is not authenticated).
+            let sub = req.sub.ok_or_else(|| missing("sub"))?;
+            let dpop = dpop_proof(headers)?;
 
-            Ok((
-                StatusCode::OK,
-                Json(TokenResponse {
-                    access_token: out.access_token,
-                    token_type: "Bearer".to_string(),
-                    expires_in: out.expires_in,
-                    refresh_token: out.refresh_token,
-                    session_id: Some(out.session_id),
-                }),
-            ))
+            let out = state
+                .auth
+                .issue_token_pair(sub, req.scope.as_deref(), None, dpop, method.as_str(), url)
+                .await?;
+            Ok(pair_response(out, AuditEventType::TokenIssued, None))
+        }
+        None => Err(missing("grant_type")),
+        Some(_) => Err(OAuthError::UnsupportedGrantType.into()),
+    }
+}
+
+fn pair_response(
+    out: IssuedTokenPair,
+    event_type: AuditEventType,
+    client_id: Option<&str>,
+) -> (TokenResponse, AuditEvent) {
+    let event = AuditEvent::success(event_type)
+        .with_user(out.user_id)
+        .with_session(out.session_id)
+        .with_client(client_id)
+        .with_jkt(out.jkt.as_deref());
+
+    let res = TokenResponse {
+        access_token: out.access_token,
+        issued_token_type: None,
+        token_type: out.token_type.to_string(),
+        expires_in: out.expires_in,
+        refresh_token: Some(out.refresh_token),
+        scope: out.scope,
+        session_id: Some(out.session_id),
+    };
+    (res, event)
+}
+
+// The audit record of a failed grant; none for a nonce challenge (the client simply
+// retries).
+fn failure_event(grant_type: Option<&str>, e: &AppError) -> Option<AuditEvent> {
+    let reason = match e {
+        AppError::UseDpopNonce(_) => return None,
+        AppError::OAuth(OAuthError::InvalidDpopProof(msg)) => {
+            return Some(AuditEvent::failure(
+                AuditEventType::DpopProofRejected,
+                msg.as_str(),
+            ));
+        }
+        AppError::OAuth(e) => format!("{}: {e}", e.code()),
+        other => other.to_string(),
+    };
+    let event_type = match grant_type {
+        Some("refresh_token") => AuditEventType::TokenRefreshed,
+        Some("client_credentials") => AuditEventType::ClientTokenIssued,
+        Some(TOKEN_EXCHANGE_GRANT) => AuditEventType::TokenExchanged,
+        _ => AuditEventType::TokenIssued,
+    };
+    Some(AuditEvent::failure(event_type, reason))
+}
+
+fn dpop_proof(headers: &HeaderMap) -> Result<&str, AppError> {
+    headers
+        .get("DPoP")
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| OAuthError::InvalidDpopProof("DPoP proof is required".into()).into())
+}
+
+fn missing(params: &str) -> AppError {
+    OAuthError::InvalidRequest(format!("{params} is required")).into()
+}
+
+// Errors not raised as `OAuthError` by the grants, in the OAuth shape.
+fn oauth_error(e: AppError) -> AppError {
+    match e {
+        AppError::InvalidRequest(msg) => OAuthError::InvalidRequest(msg).into(),
+        AppError::Unauthorized | AppError::Forbidden | AppError::NotFound => {
+            OAuthError::InvalidGrant("the grant is invalid").into()
         }
+        other => other,
+    }
+}
+
+fn client_credentials<'a>(req: &'a TokenRequest, headers: &'a HeaderMap) -> ClientCredentials<'a> {
+    ClientCredentials {
+        authorization: headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok()),
+        client_id: req.client_id.as_deref(),
+        client_secret: req.client_secret.as_deref(),
+        client_assertion_type: req.client_assertion_type.as_deref(),
+        client_assertion: req.client_assertion.as_deref(),
     }
 }
@@ -1,4 +1,5 @@
 pub mod dto;
+pub mod extractors;
 pub mod handlers;
 pub mod routes;
 
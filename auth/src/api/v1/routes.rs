@@ -1,8 +1,40 @@
-use axum::{Router, routing::post};
+use axum::{
+    Router,
+    routing::{delete, get, post},
+};
 
-use crate::api::v1::handlers::token::token;
+use crate::api::v1::handlers::{
+    audit, authorize, introspect::introspect, mfa, revoke, sessions, token::token,
+};
 use crate::state::AppState;
 
 pub fn routes(state: AppState) -> Router<AppState> {
-    Router::new().route("/token", post(token)).with_state(state)
+    Router::new()
+        .route(
+            "/authorize",
+            get(authorize::authorize).post(authorize::authorize_submit),
+        )
+        .route("/token", post(token))
+        .route("/revoke", post(revoke::revoke))
+        .route("/logout", post(revoke::logout))
+        .route("/introspect", post(introspect))
+        // sessions (access token + DPoP proof required)
+        .route(
+            "/sessions",
+            get(sessions::list_sessions).delete(sessions::revoke_sessions),
+        )
+        .route("/sessions/{session_id}", delete(sessions::revoke_session))
+        // second factor (access token + DPoP proof; removing it needs an MFA sign-in)
+        .route(
+            "/mfa/totp",
+            post(mfa::enroll_totp).delete(mfa::disable_totp),
+        )
+        .route("/mfa/totp/confirm", post(mfa::confirm_totp))
+        // audit trail (access token with the `audit:read` scope + DPoP proof)
+        .route("/admin/audit-events", get(audit::list_audit_events))
+        .route(
+            "/admin/audit-events/export",
+            get(audit::export_audit_events),
+        )
+        .with_state(state)
 }
@@ -0,0 +1,126 @@
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderValue, header},
+    response::IntoResponse,
+};
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::services::auth::authn_context::{ACR_MFA, ACR_PASSWORD};
+use crate::services::auth::dpop::alg::alg_name;
+use crate::services::auth::signing_keys::key_ring::PublicJwkSet;
+use crate::services::auth::token_exchange::TOKEN_EXCHANGE_GRANT;
+use crate::state::AppState;
+
+/// OAuth 2.0 Authorization Server Metadata (RFC 8414).
+///
+/// Built once at startup from `Config` and served as-is. Optional endpoints are only
+/// advertised when they are usable with the current configuration.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthorizationServerMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+    pub response_types_supported: Vec<&'static str>,
+    pub grant_types_supported: Vec<&'static str>,
+    /// RFC 7636: PKCE is required, S256 only
+    pub code_challenge_methods_supported: Vec<&'static str>,
+    /// RFC 9207: authorization responses carry `iss`
+    pub authorization_response_iss_parameter_supported: bool,
+    // Public clients (`none`) prove possession with DPoP; confidential clients also
+    // authenticate with a secret or a signed assertion.
+    pub token_endpoint_auth_methods_supported: Vec<&'static str>,
+    /// RFC 7523 client assertions (private_key_jwt)
+    pub token_endpoint_auth_signing_alg_values_supported: Vec<&'static str>,
+    /// RFC 7009
+    pub revocation_endpoint: String,
+    pub revocation_endpoint_auth_methods_supported: Vec<&'static str>,
+    /// RFC 7662 (only when introspection callers are configured)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub introspection_endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub introspection_endpoint_auth_methods_supported: Vec<&'static str>,
+    /// RFC 9449 Section 5.1
+    pub dpop_signing_alg_values_supported: Vec<&'static str>,
+    /// RFC 9470 step-up: `aal2` once the user signed in with a second factor
+    pub acr_values_supported: Vec<&'static str>,
+}
+
+impl AuthorizationServerMetadata {
+    pub fn from_config(config: &Config) -> Self {
+        // Endpoints are published under the public base URL when it is configured
+        // (proxy/ingress aware); otherwise the issuer identifier is used.
+        let base = config
+            .public_auth_base_url
+            .as_deref()
+            .unwrap_or(&config.issuer)
+            .trim_end_matches('/');
+
+        let introspection_enabled =
+            config.introspection_secret.is_some() || !config.introspection_clients.is_empty();
+        let mut introspection_auth_methods = Vec::new();
+        if !config.introspection_clients.is_empty() {
+            introspection_auth_methods.push("client_secret_basic");
+        }
+
+        Self {
+            issuer: config.issuer.clone(),
+            authorization_endpoint: format!("{base}/api/v1/authorize"),
+            token_endpoint: format!("{base}/api/v1/token"),
+            jwks_uri: format!("{base}/.well-known/jwks.json"),
+            response_types_supported: vec!["code"],
+            grant_types_supported: vec![
+                "authorization_code",
+                "client_credentials",
+                TOKEN_EXCHANGE_GRANT,
+                "password",
+                "refresh_token",
+            ],
+            code_challenge_methods_supported: vec!["S256"],
+            authorization_response_iss_parameter_supported: true,
+            token_endpoint_auth_methods_supported: vec![
+                "none",
+                "client_secret_basic",
+                "client_secret_post",
+                "private_key_jwt",
+            ],
+            token_endpoint_auth_signing_alg_values_supported: config
+                .dpop_allowed_algs
+                .iter()
+                .map(|alg| alg_name(*alg))
+                .collect(),
+            revocation_endpoint: format!("{base}/api/v1/revoke"),
+            revocation_endpoint_auth_methods_supported: vec!["none"],
+            introspection_endpoint: introspection_enabled
+                .then(|| format!("{base}/api/v1/introspect")),
+            introspection_endpoint_auth_methods_supported: introspection_auth_methods,
+            dpop_signing_alg_values_supported: config
+                .dpop_allowed_algs
+                .iter()
+                .map(|alg| alg_name(*alg))
+                .collect(),
+            acr_values_supported: vec![ACR_PASSWORD, ACR_MFA],
+        }
+    }
+}
+
+pub async fn oauth_authorization_server(
+    State(state): State<AppState>,
+) -> Json<AuthorizationServerMetadata> {
+    Json(state.metadata.as_ref().clone())
+}
+
+/// Public keys of the active and retiring signing keys (RFC 7517).
+pub async fn jwks(State(state): State<AppState>) -> impl IntoResponse {
+    let body: PublicJwkSet = state.signing_keys.jwks();
+    // Short enough that a cached set never outlives a rotation's overlap window.
+    (
+        [(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("public, max-age=300"),
+        )],
+        Json(body),
+    )
+}
@@ -1,19 +1,59 @@
 use axum::{Router, routing::get};
+use dpop_nonce::DpopNonceIssuer;
+use http_middleware::{cors::AllowedOrigins, http::HttpLimits};
 use sqlx::postgres::PgPoolOptions;
-use std::{panic, process, sync::Arc};
+use std::{net::SocketAddr, panic, process, sync::Arc, time::Duration};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 use crate::api;
-use crate::config::Config;
+use crate::api::well_known::{self, AuthorizationServerMetadata};
+use crate::config::{Config, PasswordHashConfig};
 use crate::error::AppError;
-use crate::repos::{auth_session_repo::AuthSessionRepo, refresh_token_repo::RefreshTokenRepo};
+use crate::repos::{
+    audit_event_repo::AuditEventRepo,
+    auth_session_repo::{AuthSessionRepo, AuthSessionStore},
+    authorization_code_repo::AuthorizationCodeRepo,
+    dpop_replay_repo::DpopReplayRepo,
+    maintenance_repo::MaintenanceRepo,
+    memory_store::MemoryAuthStore,
+    mfa_repo::MfaRepo,
+    oauth_client_repo::OAuthClientRepo,
+    refresh_token_repo::{RefreshTokenRepo, RefreshTokenStore},
+    role_repo::RoleRepo,
+    signing_key_repo::SigningKeyRepo,
+    user_repo::UserRepo,
+};
 use crate::services::auth::{
     access_token_issuer::AccessTokenService,
-    dpop::{policy::DpopPolicy, verifier::DpopVerifier},
+    access_token_verifier::AccessTokenVerifier,
+    audit::AuditLog,
+    authorization_code_service::AuthorizationCodeService,
+    client_auth::ClientAuthenticator,
+    denylist::Denylist,
+    dpop::{
+        policy::DpopPolicy,
+        replay::{ReplayStore, ValkeyReplayStore},
+        verifier::DpopVerifier,
+    },
+    introspection_service::{IntrospectionCallers, IntrospectionService},
     jwt::JwtIssuer,
-    refresh_token_issuer::{RefreshTokenService, SessionLookup},
+    mfa::service::MfaService,
+    password::PasswordHasher,
+    password_grant::PasswordGrantService,
+    refresh_token_issuer::RefreshTokenService,
+    revocation_service::RevocationService,
+    scope::ScopeService,
+    session_policy::SessionPolicy,
+    session_service::SessionService,
+    signing_keys::{
+        cipher::KeyCipher,
+        rotation::{RotationPolicy, SigningKeyService},
+    },
+    throttle::{ThrottlePolicy, TokenThrottle, ValkeyThrottleStore},
+    token_exchange::TokenExchangeService,
     token_service::TokenService,
 };
+use crate::services::maintenance::{MaintenancePolicy, MaintenanceService};
 use crate::state::AppState;
 
 fn init_tracing() {
@@ -67,72 +107,422 @@ pub async fn run() -> Result<(), AppError> {
     let listener = tokio::net::TcpListener::bind(config.addr)
         .await
         .map_err(|_| AppError::Internal)?;
-    axum::serve(listener, app)
-        .await
+    // Peer addresses feed the audit log's client IP.
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .map_err(|_| AppError::Internal)?;
+
+    Ok(())
+}
+
+/// `auth hash-password`: read a password from stdin and print its PHC hash, for seeding
+/// `user_credentials`.
+pub fn hash_password() -> Result<(), AppError> {
+    let mut password = String::new();
+    std::io::stdin()
+        .read_line(&mut password)
         .map_err(|_| AppError::Internal)?;
+    let password = password.trim_end_matches(['\r', '\n']);
+    if password.is_empty() {
+        return Err(AppError::InvalidRequest("empty password".into()));
+    }
 
+    let hasher = build_password_hasher(&PasswordHashConfig::from_env())?;
+    println!("{}", hasher.hash_blocking(password)?);
     Ok(())
 }
 
+/// `auth maintenance`: purge expired and revoked rows once (e.g. from cron, with
+/// `MAINTENANCE_INTERVAL_SECONDS=0` on the servers).
+pub async fn maintenance() -> Result<(), AppError> {
+    init_tracing();
+    let config = Config::from_env()?;
+
+    let db = connect_db(&config).await?;
+    build_maintenance_service(&config, db)
+        .run_once(chrono::Utc::now())
+        .await?;
+    Ok(())
+}
+
+async fn connect_db(config: &Config) -> Result<sqlx::PgPool, AppError> {
+    PgPoolOptions::new()
+        .connect(&config.database_url)
+        .await
+        .map_err(|e| {
+            tracing::error!(error=%e, "failed to connect to database");
+            AppError::Internal
+        })
+}
+
 async fn build_state(config: &Config) -> Result<AppState, AppError> {
     // Build process-level services here and inject them into the shared application state.
-    // JwtIssuer signs access/refresh tokens using AS private key.
+    // DB connection pool (shared by repos/services). We keep it inside the AuthService via repos for now.
+    let db = connect_db(config).await?;
+
+    // Every instance schedules the purge; only the one holding the lock runs it.
+    if config.maintenance_interval_seconds > 0 {
+        Arc::new(build_maintenance_service(config, db.clone()))
+            .spawn_scheduler(Duration::from_secs(config.maintenance_interval_seconds));
+    } else {
+        tracing::info!(
+            "MAINTENANCE_INTERVAL_SECONDS=0; run `auth maintenance` to purge expired rows"
+        );
+    }
+
+    // Signing keys live (encrypted) in Postgres; every instance follows the same schedule.
+    let signing_keys = Arc::new(build_signing_key_service(config, db.clone())?);
+    signing_keys
+        .bootstrap(config.access_jwt_private_key_pem.as_deref())
+        .await?;
+    signing_keys.spawn_scheduler(Duration::from_secs(config.signing_key_check_seconds));
+    let key_ring = signing_keys.ring();
+
+    // JwtIssuer signs access tokens with the active key from the ring.
     let jwt = JwtIssuer::new(
-        &config.access_jwt_private_key_pem,
+        key_ring.clone(),
         config.issuer.clone(),
         config.audience.clone(),
         config.access_token_ttl_seconds,
-    )?;
+    );
 
-    let access_tokens = AccessTokenService::new(jwt);
+    let access_tokens =
+        AccessTokenService::new(jwt).with_max_ttl_seconds(config.signing_key_overlap_seconds);
+    let exchange_issuer = access_tokens.clone();
 
-    // DB connection pool (shared by repos/services). We keep it inside the AuthService via repos for now.
-    let db = PgPoolOptions::new()
-        .connect(&config.database_url)
-        .await
-        .map_err(|e| {
-            tracing::error!(error=%e, "failed to connect to database");
-            AppError::Internal
-        })?;
-
-    // Note: TokenService needs an AuthSessionRepo (for creating sessions on token issuance),
-    // while RefreshTokenService only needs a trait object for session lookup.
-    // Build two repos backed by the same pool to avoid requiring AuthSessionRepo: Clone.
-    let auth_session_repo = AuthSessionRepo::new(db.clone());
-    let sessions: Arc<dyn SessionLookup> = Arc::new(AuthSessionRepo::new(db.clone()));
-
-    let refresh_token_repo = RefreshTokenRepo::new(db);
-    let dpop_policy = DpopPolicy::default();
-    let dpop_verifier = Arc::new(DpopVerifier::new(
-        dpop_policy,
-        config.public_auth_base_url.clone(),
+    let (sessions, refresh_token_store) = build_session_stores(config, db.clone());
+
+    let password_grant = Arc::new(PasswordGrantService::new(
+        UserRepo::new(db.clone()),
+        build_password_hasher(&config.password_hash)?,
     ));
+
+    let audit = AuditLog::new(AuditEventRepo::new(db.clone()))
+        .with_trust_forwarded_for(config.trust_forwarded_for);
+
+    let replay_store = build_replay_store(config, db.clone()).await?;
+    let denylist = build_denylist(config).await?;
+    let throttle = Arc::new(build_throttle(config).await?);
+    let dpop_policy = DpopPolicy {
+        allowed_algs: config.dpop_allowed_algs.clone(),
+        require_nonce: config.dpop_require_nonce,
+        ..DpopPolicy::default()
+    };
+    let mut dpop_verifier = DpopVerifier::new(dpop_policy, config.public_auth_base_url.clone())
+        .with_replay_store(replay_store.clone());
+    if config.dpop_require_nonce {
+        dpop_verifier = dpop_verifier.with_nonce_issuer(build_nonce_issuer(config));
+    }
+    let dpop_verifier = Arc::new(dpop_verifier);
     let refresh_tokens = RefreshTokenService::new(
-        refresh_token_repo.into(),
-        sessions,
+        refresh_token_store,
+        sessions.clone(),
         config.refresh_token_ttl_seconds,
     )
-    .with_dpop_verifier(dpop_verifier.clone());
+    .with_dpop_verifier(dpop_verifier.clone())
+    .with_reuse_grace_seconds(config.refresh_reuse_grace_seconds)
+    .with_denylist(denylist.clone())
+    .with_audit(audit.clone());
+
+    let access = Arc::new(
+        AccessTokenVerifier::new(
+            key_ring.clone(),
+            &config.issuer,
+            &config.audience,
+            dpop_verifier.clone(),
+            sessions.clone(),
+        )
+        .with_audit(audit.clone()),
+    );
+    let session_service =
+        Arc::new(SessionService::new(sessions.clone()).with_denylist(denylist.clone()));
+    let revocation = Arc::new(
+        RevocationService::new(
+            refresh_tokens.clone(),
+            access.clone(),
+            sessions.clone(),
+            dpop_verifier.clone(),
+        )
+        .with_denylist(denylist.clone()),
+    );
+
+    if config.token_subject_grant_enabled {
+        tracing::warn!(
+            "TOKEN_SUBJECT_GRANT_ENABLED: /token issues tokens for any `sub` without authentication"
+        );
+    }
+    let scopes = ScopeService::new(RoleRepo::new(db.clone()));
+    let auth = Arc::new(
+        TokenService::new(
+            access_tokens,
+            refresh_tokens.clone(),
+            sessions.clone(),
+            dpop_verifier,
+            scopes.clone(),
+        )
+        .with_subject_grant(config.token_subject_grant_enabled)
+        .with_session_policy(SessionPolicy::from_config(config))
+        .with_denylist(denylist.clone())
+        .with_audit(audit.clone()),
+    );
+    let authorization = Arc::new(
+        AuthorizationCodeService::new(
+            OAuthClientRepo::new(db.clone()),
+            AuthorizationCodeRepo::new(db.clone()),
+            sessions.clone(),
+            auth.clone(),
+            scopes.clone(),
+            config.issuer.clone(),
+            config.authorization_code_ttl_seconds,
+        )
+        .with_denylist(denylist),
+    );
+    let token_exchange = Arc::new(TokenExchangeService::new(
+        access.clone(),
+        exchange_issuer,
+        sessions.clone(),
+        scopes,
+        auth.clone(),
+    ));
+
+    // TOTP secrets are sealed with the signing key encryption key.
+    let mfa = Arc::new(
+        MfaService::new(
+            MfaRepo::new(db.clone()),
+            UserRepo::new(db.clone()),
+            key_cipher(config)?,
+            config.mfa_totp_issuer.clone(),
+        )
+        .with_audit(audit.clone()),
+    );
+
+    let metadata = Arc::new(AuthorizationServerMetadata::from_config(config));
 
-    let auth = Arc::new(TokenService::new(
-        access_tokens,
-        refresh_tokens,
-        auth_session_repo,
-        dpop_verifier,
+    // Client assertions (private_key_jwt) may name the token endpoint or the issuer as `aud`.
+    let clients = Arc::new(ClientAuthenticator::new(
+        OAuthClientRepo::new(db),
+        replay_store,
+        config.dpop_allowed_algs.clone(),
+        vec![metadata.token_endpoint.clone(), config.issuer.clone()],
     ));
+    let introspection = Arc::new(
+        IntrospectionService::new(
+            refresh_tokens,
+            access.clone(),
+            sessions,
+            build_introspection_callers(config),
+        )
+        .with_clients(clients.clone()),
+    );
+
+    Ok(AppState {
+        auth,
+        metadata,
+        access,
+        sessions: session_service,
+        revocation,
+        introspection,
+        signing_keys: key_ring,
+        password_grant,
+        authorization,
+        clients,
+        token_exchange,
+        audit: Arc::new(audit),
+        throttle,
+        mfa,
+    })
+}
+
+fn build_password_hasher(config: &PasswordHashConfig) -> Result<PasswordHasher, AppError> {
+    let params = argon2::Params::new(
+        config.memory_kib,
+        config.iterations,
+        config.parallelism,
+        None,
+    )
+    .map_err(|e| {
+        tracing::error!(error = %e, "invalid ARGON2_* parameters");
+        AppError::Internal
+    })?;
+    PasswordHasher::new(params)
+}
+
+fn build_maintenance_service(config: &Config, db: sqlx::PgPool) -> MaintenanceService {
+    MaintenanceService::new(
+        MaintenanceRepo::new(db),
+        MaintenancePolicy {
+            retention: chrono::Duration::seconds(config.maintenance_retention_seconds as i64),
+            batch_size: i64::from(config.maintenance_batch_size),
+        },
+    )
+}
+
+fn build_signing_key_service(
+    config: &Config,
+    db: sqlx::PgPool,
+) -> Result<SigningKeyService, AppError> {
+    let cipher = key_cipher(config)?;
+
+    let policy = RotationPolicy {
+        rotation_interval: (config.signing_key_rotation_seconds > 0)
+            .then(|| chrono::Duration::seconds(config.signing_key_rotation_seconds as i64)),
+        lead_time: chrono::Duration::seconds(config.signing_key_lead_seconds as i64),
+        overlap: chrono::Duration::seconds(config.signing_key_overlap_seconds as i64),
+    };
+
+    Ok(SigningKeyService::new(
+        SigningKeyRepo::new(db),
+        cipher,
+        policy,
+    ))
+}
+
+fn key_cipher(config: &Config) -> Result<KeyCipher, AppError> {
+    KeyCipher::from_base64(&config.signing_key_encryption_key).map_err(|e| {
+        tracing::error!(error = %e, "SIGNING_KEY_ENCRYPTION_KEY must be 32 bytes, base64 encoded");
+        AppError::Internal
+    })
+}
+
+// Services only see the store traits; both stores must share state, since revoking a
+// session also revokes its refresh tokens.
+fn build_session_stores(
+    config: &Config,
+    db: sqlx::PgPool,
+) -> (Arc<dyn AuthSessionStore>, Arc<dyn RefreshTokenStore>) {
+    if config.memory_session_store {
+        tracing::warn!("SESSION_STORE=memory: sessions and refresh tokens are lost on restart");
+        let store = MemoryAuthStore::new();
+        return (Arc::new(store.clone()), Arc::new(store));
+    }
+
+    (
+        Arc::new(AuthSessionRepo::new(db.clone())),
+        Arc::new(RefreshTokenRepo::new(db)),
+    )
+}
+
+// Valkey when configured (shared with the resource server's infrastructure),
+// otherwise the `dpop_proof_jtis` table.
+async fn build_replay_store(
+    config: &Config,
+    db: sqlx::PgPool,
+) -> Result<Arc<dyn ReplayStore>, AppError> {
+    match &config.valkey_url {
+        Some(url) => {
+            let store = ValkeyReplayStore::new(url).await.map_err(|e| {
+                tracing::error!(error = %e, "failed to connect to valkey");
+                AppError::Internal
+            })?;
+            Ok(Arc::new(store))
+        }
+        None => {
+            tracing::info!("VALKEY_URL is not set; using Postgres for DPoP replay protection");
+            Ok(Arc::new(DpopReplayRepo::new(db)))
+        }
+    }
+}
+
+// Resource servers learn about revoked sessions through Valkey only.
+async fn build_denylist(config: &Config) -> Result<Denylist, AppError> {
+    match &config.valkey_url {
+        Some(url) => Denylist::connect(url, config.access_token_ttl_seconds)
+            .await
+            .map_err(|e| {
+                tracing::error!(error = %e, "failed to connect to valkey");
+                AppError::Internal
+            }),
+        None => {
+            tracing::warn!(
+                "VALKEY_URL is not set; revoked sessions stay valid on resource servers until their access tokens expire"
+            );
+            Ok(Denylist::disabled())
+        }
+    }
+}
 
-    Ok(AppState::new(auth))
+// Counters in Valkey are shared by every instance; in memory, each throttles on its own.
+async fn build_throttle(config: &Config) -> Result<TokenThrottle, AppError> {
+    let policy = ThrottlePolicy::from_config(config);
+    match &config.valkey_url {
+        Some(url) => {
+            let store = ValkeyThrottleStore::new(url).await.map_err(|e| {
+                tracing::error!(error = %e, "failed to connect to valkey");
+                AppError::Internal
+            })?;
+            Ok(TokenThrottle::new(Arc::new(store), policy))
+        }
+        None => {
+            tracing::info!("VALKEY_URL is not set; /token throttling is per process");
+            Ok(TokenThrottle::in_memory(policy))
+        }
+    }
 }
 
-fn build_router(state: AppState, _config: &Config) -> Router {
+fn build_introspection_callers(config: &Config) -> IntrospectionCallers {
+    let mut callers = IntrospectionCallers::new();
+    if let Some(secret) = &config.introspection_secret {
+        callers = callers.with_shared_secret(secret);
+    }
+    for (client_id, client_secret) in &config.introspection_clients {
+        callers = callers.with_client(client_id, client_secret);
+    }
+    if callers.is_empty() {
+        tracing::info!(
+            "no introspection callers configured; only registered confidential clients may call /introspect"
+        );
+    }
+    callers
+}
+
+fn build_nonce_issuer(config: &Config) -> DpopNonceIssuer {
+    // The resource server uses its own label (and normally its own secret).
+    const NONCE_LABEL: &str = "dpop-nonce:auth:";
+    match &config.dpop_nonce_secret {
+        Some(secret) => DpopNonceIssuer::new(
+            NONCE_LABEL,
+            secret.as_bytes().to_vec(),
+            config.dpop_nonce_ttl_seconds,
+        ),
+        None => {
+            // Works for a single instance only; replicas would reject each other's nonces.
+            tracing::warn!("DPOP_NONCE_SECRET is not set; using a random per-process nonce secret");
+            DpopNonceIssuer::with_random_secret(NONCE_LABEL, config.dpop_nonce_ttl_seconds)
+        }
+    }
+}
+
+fn build_router(state: AppState, config: &Config) -> Router {
     async fn health() -> &'static str {
         "ok"
     }
 
     let router = Router::new()
         .route("/health", get(health))
+        .route(
+            "/.well-known/oauth-authorization-server",
+            get(well_known::oauth_authorization_server),
+        )
+        .route("/.well-known/jwks.json", get(well_known::jwks))
         .nest("/api/v1", api::v1::routes(state.clone()))
         .with_state(state);
 
-    router
+    // Same stack as the resource server. The request id also ties audit events to logs;
+    // the client's own id is kept if sent.
+    let router = http_middleware::security_headers::apply(router);
+    let router = http_middleware::cors::apply(
+        router,
+        AllowedOrigins::for_env(config.app_env.is_production(), &config.cors_allowed_origins),
+    );
+
+    http_middleware::http::apply(
+        router,
+        &HttpLimits {
+            body_limit_bytes: config.http_body_limit_bytes,
+            timeout: Duration::from_secs(config.http_timeout_seconds),
+        },
+    )
 }
@@ -2,7 +2,11 @@ use std::net::SocketAddr;
 use std::str::FromStr;
 use std::{env, fmt};
 
+use jsonwebtoken::Algorithm;
+
 use crate::error::AppError;
+use crate::services::auth::dpop::alg::{DEFAULT_ALLOWED_ALGS, parse_allowed_algs};
+use crate::services::auth::session_policy::SessionEviction;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AppEnv {
@@ -51,14 +55,108 @@ pub struct Config {
     pub app_env: AppEnv,
     pub issuer: String,
     pub audience: String,
-    // AS signs access tokens with this private key
-    pub access_jwt_private_key_pem: String,
+    // Legacy signing key, imported as the first managed key when none exists yet
+    pub access_jwt_private_key_pem: Option<String>,
+    // Signing key management (keys are stored AES-256-GCM encrypted in Postgres)
+    pub signing_key_encryption_key: String,
+    pub signing_key_rotation_seconds: u64,
+    pub signing_key_lead_seconds: u64,
+    pub signing_key_overlap_seconds: u64,
+    pub signing_key_check_seconds: u64,
     // Token lifetimes (seconds)
     pub access_token_ttl_seconds: u64,
     pub refresh_token_ttl_seconds: u64,
+    // Session policies (0 disables a limit); clients may override them
+    pub session_idle_timeout_seconds: u64,
+    pub session_max_lifetime_seconds: u64,
+    pub session_max_per_user: u32,
+    pub session_eviction: SessionEviction,
 
     pub public_auth_base_url: Option<String>,
     pub refresh_dpop_required: bool,
+    // Grace window for presenting an already-rotated refresh token (concurrent refreshes)
+    pub refresh_reuse_grace_seconds: u64,
+    // Accepted DPoP proof algorithms (asymmetric only)
+    pub dpop_allowed_algs: Vec<Algorithm>,
+    // Server-issued DPoP nonces (RFC 9449 Section 8)
+    pub dpop_require_nonce: bool,
+    pub dpop_nonce_secret: Option<String>,
+    pub dpop_nonce_ttl_seconds: u64,
+    // DPoP replay cache backend; Postgres is used when unset
+    pub valkey_url: Option<String>,
+    // Callers allowed to use /introspect: a shared bearer secret and/or client_id:secret pairs
+    pub introspection_secret: Option<String>,
+    pub introspection_clients: Vec<(String, String)>,
+    // Issue tokens for a caller-supplied `sub` without authentication (never in production)
+    pub token_subject_grant_enabled: bool,
+    // Lifetime of authorization codes from /authorize
+    pub authorization_code_ttl_seconds: u64,
+    // Purge of expired / revoked rows: how often (0: only via `auth maintenance`), how
+    // long they are kept, and rows per delete
+    pub maintenance_interval_seconds: u64,
+    pub maintenance_retention_seconds: u64,
+    pub maintenance_batch_size: u32,
+    // Keep sessions and refresh tokens in process memory instead of Postgres (development only)
+    pub memory_session_store: bool,
+    // Take the audit log's client IP from X-Forwarded-For (only behind a proxy that appends to it)
+    pub trust_forwarded_for: bool,
+    // Browser origins allowed by CORS in production (any origin in development)
+    pub cors_allowed_origins: Vec<String>,
+    // Request body size and handling time limits for every route
+    pub http_body_limit_bytes: usize,
+    pub http_timeout_seconds: u64,
+    // /token throttling: failures before backoff / lockout (per subject and DPoP key, and
+    // per client IP), backoff delays, lockout length, counting window, and new sessions
+    // per IP / DPoP key within the window (0: unlimited)
+    pub token_throttle_enabled: bool,
+    pub token_throttle_free_failures: u32,
+    pub token_throttle_lockout_failures: u32,
+    pub token_throttle_ip_free_failures: u32,
+    pub token_throttle_ip_lockout_failures: u32,
+    pub token_throttle_base_delay_seconds: u64,
+    pub token_throttle_max_delay_seconds: u64,
+    pub token_throttle_lockout_seconds: u64,
+    pub token_throttle_window_seconds: u64,
+    pub token_throttle_max_new_sessions: u32,
+    // Name authenticator apps show for TOTP entries (otpauth `issuer`)
+    pub mfa_totp_issuer: String,
+    pub password_hash: PasswordHashConfig,
+}
+
+/// Argon2id cost parameters for user passwords.
+///
+/// Changing them upgrades stored hashes on the next successful login.
+#[derive(Clone, Copy, Debug)]
+pub struct PasswordHashConfig {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl PasswordHashConfig {
+    // Read on its own so `auth hash-password` works without the server configuration.
+    pub fn from_env() -> Self {
+        dotenvy::dotenv().ok();
+
+        let memory_kib = env::var("ARGON2_MEMORY_KIB")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(19_456); // 19 MiB (OWASP minimum for Argon2id)
+        let iterations = env::var("ARGON2_ITERATIONS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(2);
+        let parallelism = env::var("ARGON2_PARALLELISM")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(1);
+
+        Self {
+            memory_kib,
+            iterations,
+            parallelism,
+        }
+    }
 }
 
 impl Config {
@@ -82,8 +180,9 @@ impl Config {
         let audience =
             env::var("AUTH_AUDIENCE").map_err(|_| ConfigError::Missing("AUTH_AUDIENCE"))?;
         let access_jwt_private_key_pem = env::var("ACCESS_JWT_PRIVATE_KEY_PEM")
-            .map_err(|_| ConfigError::Missing("ACCESS_JWT_PRIVATE_KEY_PEM"))?
-            .replace("\\n", "\n");
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .map(|v| v.replace("\\n", "\n"));
 
         let access_token_ttl_seconds = env::var("ACCESS_TOKEN_TTL_SECONDS")
             .ok()
@@ -94,6 +193,26 @@ impl Config {
             .and_then(|s| s.parse().ok())
             .unwrap_or(2_592_000); // 30 days
 
+        let session_idle_timeout_seconds = env::var("SESSION_IDLE_TIMEOUT_SECONDS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+        let session_max_lifetime_seconds = env::var("SESSION_MAX_LIFETIME_SECONDS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+        let session_max_per_user = env::var("SESSION_MAX_PER_USER")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+        // Which sessions go when a user signs in beyond SESSION_MAX_PER_USER: oldest | lru
+        let session_eviction = match env::var("SESSION_EVICTION") {
+            Ok(v) if !v.trim().is_empty() => {
+                SessionEviction::parse(&v).ok_or(ConfigError::Invalid("SESSION_EVICTION"))?
+            }
+            _ => SessionEviction::LeastRecentlyUsed,
+        };
+
         let public_auth_base_url = std::env::var("PUBLIC_AUTH_BASE_URL")
             .ok()
             .map(|v| v.trim().to_string())
@@ -104,6 +223,202 @@ impl Config {
             .map(|v| v.eq_ignore_ascii_case("true") || v == "1")
             .unwrap_or(false);
 
+        let refresh_reuse_grace_seconds = env::var("REFRESH_REUSE_GRACE_SECONDS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(10);
+
+        let dpop_allowed_algs = match std::env::var("DPOP_ALLOWED_ALGS") {
+            Ok(v) if !v.trim().is_empty() => {
+                parse_allowed_algs(&v).ok_or(ConfigError::Invalid("DPOP_ALLOWED_ALGS"))?
+            }
+            _ => DEFAULT_ALLOWED_ALGS.to_vec(),
+        };
+
+        let dpop_require_nonce = std::env::var("DPOP_REQUIRE_NONCE")
+            .ok()
+            .map(|v| v.eq_ignore_ascii_case("true") || v == "1")
+            .unwrap_or(false);
+
+        // Shared HMAC secret so every instance accepts the others' nonces.
+        let dpop_nonce_secret = std::env::var("DPOP_NONCE_SECRET")
+            .ok()
+            .filter(|v| !v.trim().is_empty());
+
+        let dpop_nonce_ttl_seconds = env::var("DPOP_NONCE_TTL_SECONDS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(300); // 5 min
+
+        let valkey_url = std::env::var("VALKEY_URL")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+
+        let introspection_secret = std::env::var("INTROSPECTION_SECRET")
+            .ok()
+            .filter(|v| !v.trim().is_empty());
+
+        // Comma-separated `client_id:secret` pairs.
+        let introspection_clients = match std::env::var("INTROSPECTION_CLIENTS") {
+            Ok(v) if !v.trim().is_empty() => v
+                .split(',')
+                .map(|pair| {
+                    pair.trim()
+                        .split_once(':')
+                        .filter(|(id, secret)| !id.is_empty() && !secret.is_empty())
+                        .map(|(id, secret)| (id.to_string(), secret.to_string()))
+                        .ok_or(ConfigError::Invalid("INTROSPECTION_CLIENTS"))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => Vec::new(),
+        };
+
+        let signing_key_encryption_key = env::var("SIGNING_KEY_ENCRYPTION_KEY")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .ok_or(ConfigError::Missing("SIGNING_KEY_ENCRYPTION_KEY"))?;
+        let signing_key_rotation_seconds = env::var("SIGNING_KEY_ROTATION_SECONDS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(2_592_000); // 30 days; 0 disables scheduled rotation
+        let signing_key_lead_seconds = env::var("SIGNING_KEY_LEAD_SECONDS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(86_400); // 1 day
+        let signing_key_overlap_seconds = env::var("SIGNING_KEY_OVERLAP_SECONDS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(86_400); // 1 day
+        // A retired key must not outlive tokens it signed (per-client lifetimes are
+        // capped to the overlap when tokens are issued).
+        if signing_key_overlap_seconds < access_token_ttl_seconds {
+            return Err(ConfigError::Invalid("SIGNING_KEY_OVERLAP_SECONDS"));
+        }
+        let signing_key_check_seconds = env::var("SIGNING_KEY_CHECK_SECONDS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(60);
+
+        let token_subject_grant_enabled = match std::env::var("TOKEN_SUBJECT_GRANT_ENABLED") {
+            Ok(v) if !v.trim().is_empty() => v.eq_ignore_ascii_case("true") || v == "1",
+            _ => !app_env.is_production(),
+        };
+        if token_subject_grant_enabled && app_env.is_production() {
+            return Err(ConfigError::Invalid("TOKEN_SUBJECT_GRANT_ENABLED"));
+        }
+
+        let memory_session_store = match env::var("SESSION_STORE") {
+            Ok(v) if v.eq_ignore_ascii_case("memory") => true,
+            Ok(v) if v.trim().is_empty() || v.eq_ignore_ascii_case("postgres") => false,
+            Ok(_) => return Err(ConfigError::Invalid("SESSION_STORE")),
+            Err(_) => false,
+        };
+        if memory_session_store && app_env.is_production() {
+            return Err(ConfigError::Invalid("SESSION_STORE"));
+        }
+
+        let authorization_code_ttl_seconds = env::var("AUTHORIZATION_CODE_TTL_SECONDS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(60);
+
+        let maintenance_interval_seconds = env::var("MAINTENANCE_INTERVAL_SECONDS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(3_600); // 1 hour
+        let maintenance_retention_seconds = env::var("MAINTENANCE_RETENTION_SECONDS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(604_800); // 7 days
+        let maintenance_batch_size = env::var("MAINTENANCE_BATCH_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(1_000);
+
+        let trust_forwarded_for = env::var("TRUST_FORWARDED_FOR")
+            .ok()
+            .map(|v| v.eq_ignore_ascii_case("true") || v == "1")
+            .unwrap_or(false);
+
+        let cors_allowed_origins = env::var("AUTH_CORS_ALLOWED_ORIGINS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+        let http_body_limit_bytes = env::var("AUTH_HTTP_BODY_LIMIT_BYTES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(64 * 1024);
+        let http_timeout_seconds = env::var("AUTH_HTTP_TIMEOUT_SECONDS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(30);
+
+        let token_throttle_enabled = env::var("TOKEN_THROTTLE_ENABLED")
+            .ok()
+            .map(|v| v.eq_ignore_ascii_case("true") || v == "1")
+            .unwrap_or(true);
+        let token_throttle_free_failures = env::var("TOKEN_THROTTLE_FREE_FAILURES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(3);
+        let token_throttle_lockout_failures = env::var("TOKEN_THROTTLE_LOCKOUT_FAILURES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(10);
+        let token_throttle_ip_free_failures = env::var("TOKEN_THROTTLE_IP_FREE_FAILURES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(10);
+        let token_throttle_ip_lockout_failures = env::var("TOKEN_THROTTLE_IP_LOCKOUT_FAILURES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(50);
+        let token_throttle_base_delay_seconds = env::var("TOKEN_THROTTLE_BASE_DELAY_SECONDS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(1);
+        let token_throttle_max_delay_seconds = env::var("TOKEN_THROTTLE_MAX_DELAY_SECONDS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(60);
+        let token_throttle_lockout_seconds = env::var("TOKEN_THROTTLE_LOCKOUT_SECONDS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(900); // 15 minutes
+        let token_throttle_window_seconds = env::var("TOKEN_THROTTLE_WINDOW_SECONDS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(900);
+        let token_throttle_max_new_sessions = env::var("TOKEN_THROTTLE_MAX_NEW_SESSIONS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(30);
+
+        // Defaults to the issuer's host, e.g. "auth.example.com".
+        let mfa_totp_issuer = env::var("MFA_TOTP_ISSUER")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .or_else(|| {
+                url::Url::parse(&issuer)
+                    .ok()
+                    .and_then(|u| u.host_str().map(str::to_string))
+            })
+            .unwrap_or_else(|| issuer.clone());
+
+        let password_hash = PasswordHashConfig::from_env();
+
         Ok(Config {
             addr,
             database_url,
@@ -111,10 +426,49 @@ impl Config {
             issuer,
             audience,
             access_jwt_private_key_pem,
+            signing_key_encryption_key,
+            signing_key_rotation_seconds,
+            signing_key_lead_seconds,
+            signing_key_overlap_seconds,
+            signing_key_check_seconds,
             access_token_ttl_seconds,
             refresh_token_ttl_seconds,
+            session_idle_timeout_seconds,
+            session_max_lifetime_seconds,
+            session_max_per_user,
+            session_eviction,
             public_auth_base_url,
             refresh_dpop_required,
+            refresh_reuse_grace_seconds,
+            dpop_allowed_algs,
+            dpop_require_nonce,
+            dpop_nonce_secret,
+            dpop_nonce_ttl_seconds,
+            valkey_url,
+            introspection_secret,
+            introspection_clients,
+            token_subject_grant_enabled,
+            authorization_code_ttl_seconds,
+            maintenance_interval_seconds,
+            maintenance_retention_seconds,
+            maintenance_batch_size,
+            memory_session_store,
+            trust_forwarded_for,
+            cors_allowed_origins,
+            http_body_limit_bytes,
+            http_timeout_seconds,
+            token_throttle_enabled,
+            token_throttle_free_failures,
+            token_throttle_lockout_failures,
+            token_throttle_ip_free_failures,
+            token_throttle_ip_lockout_failures,
+            token_throttle_base_delay_seconds,
+            token_throttle_max_delay_seconds,
+            token_throttle_lockout_seconds,
+            token_throttle_window_seconds,
+            token_throttle_max_new_sessions,
+            mfa_totp_issuer,
+            password_hash,
         })
     }
 }
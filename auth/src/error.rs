@@ -1,11 +1,14 @@
 use axum::{
     Json,
-    http::StatusCode,
+    http::{HeaderName, HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
 };
 use serde::Serialize;
 use thiserror::Error;
 
+/// Response header carrying a server-issued DPoP nonce (RFC 9449 Section 8).
+pub const DPOP_NONCE: HeaderName = HeaderName::from_static("dpop-nonce");
+
 #[derive(Debug, Error)]
 pub enum AppError {
     #[error("invalid request: {0}")]
@@ -25,6 +28,81 @@ pub enum AppError {
 
     #[error("internal server error")]
     Internal,
+
+    /// Throttled; retry after this many seconds.
+    #[error("too many requests")]
+    TooManyRequests(u64),
+
+    /// RFC 9470: the session is valid but its sign-in was too weak for this operation;
+    /// carries the `acr_values` the client should sign in again with.
+    #[error("a stronger authentication is required")]
+    InsufficientUserAuthentication(&'static str),
+
+    /// RFC 9449 Section 8: the client must retry with the nonce carried here.
+    #[error("authorization server requires nonce in DPoP proof")]
+    UseDpopNonce(String),
+
+    /// Token endpoint error, answered in the OAuth shape.
+    #[error(transparent)]
+    OAuth(#[from] OAuthError),
+}
+
+/// Token endpoint errors (RFC 6749 Section 5.2, RFC 9449 Section 5).
+#[derive(Debug, Error)]
+pub enum OAuthError {
+    #[error("{0}")]
+    InvalidRequest(String),
+
+    /// Client authentication failed; never says which part was wrong.
+    #[error("client authentication failed")]
+    InvalidClient,
+
+    /// The code, refresh token or resource owner credentials are invalid, expired,
+    /// revoked or were issued to another client.
+    #[error("{0}")]
+    InvalidGrant(&'static str),
+
+    /// The password was right, but the user has a second factor and sent no `otp`.
+    #[error("a one-time code is required (otp)")]
+    OtpRequired,
+
+    #[error("the client is not allowed to use this grant type")]
+    UnauthorizedClient,
+
+    #[error("the grant type is not supported")]
+    UnsupportedGrantType,
+
+    #[error("the requested scope is invalid or exceeds the granted scope")]
+    InvalidScope,
+
+    #[error("{0}")]
+    InvalidDpopProof(String),
+
+    /// Token exchange: the requested audience/resource is not allowed (RFC 8693 Section 2.2.2).
+    #[error("the requested audience or resource is not allowed")]
+    InvalidTarget,
+}
+
+impl OAuthError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidRequest(_) => "invalid_request",
+            Self::InvalidClient => "invalid_client",
+            Self::InvalidGrant(_) | Self::OtpRequired => "invalid_grant",
+            Self::UnauthorizedClient => "unauthorized_client",
+            Self::UnsupportedGrantType => "unsupported_grant_type",
+            Self::InvalidScope => "invalid_scope",
+            Self::InvalidDpopProof(_) => "invalid_dpop_proof",
+            Self::InvalidTarget => "invalid_target",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::InvalidClient => StatusCode::UNAUTHORIZED,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -38,15 +116,90 @@ struct ErrorBody {
     message: String,
 }
 
+/// OAuth-style error body (RFC 6749 Section 5.2).
+#[derive(Serialize)]
+struct OAuthErrorBody {
+    error: &'static str,
+    error_description: String,
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        // DPoP clients (and OAuth libraries) key off `error == "use_dpop_nonce"`
+        // and the `DPoP-Nonce` header, so this one uses the OAuth error shape.
+        if let AppError::UseDpopNonce(nonce) = &self {
+            let body = OAuthErrorBody {
+                error: "use_dpop_nonce",
+                error_description: self.to_string(),
+            };
+            let mut res = (StatusCode::BAD_REQUEST, Json(body)).into_response();
+            if let Ok(v) = HeaderValue::from_str(nonce) {
+                res.headers_mut().insert(DPOP_NONCE, v);
+            }
+            res.headers_mut()
+                .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+            return res;
+        }
+
+        if let AppError::OAuth(e) = &self {
+            let body = OAuthErrorBody {
+                error: e.code(),
+                error_description: e.to_string(),
+            };
+            let mut res = (e.status(), Json(body)).into_response();
+            let headers = res.headers_mut();
+            headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+            // RFC 6749 Section 5.2: a 401 must name the scheme to authenticate with.
+            if matches!(e, OAuthError::InvalidClient) {
+                headers.insert(
+                    header::WWW_AUTHENTICATE,
+                    HeaderValue::from_static("Basic realm=\"token\""),
+                );
+            }
+            return res;
+        }
+
+        if let AppError::InsufficientUserAuthentication(acr_values) = &self {
+            let body = ErrorResponseBody {
+                error: ErrorBody {
+                    code: "insufficient_user_authentication",
+                    message: self.to_string(),
+                },
+            };
+            let mut res = (StatusCode::UNAUTHORIZED, Json(body)).into_response();
+            let challenge = format!(
+                "DPoP error=\"insufficient_user_authentication\", error_description=\"{self}\", acr_values=\"{acr_values}\""
+            );
+            if let Ok(v) = HeaderValue::from_str(&challenge) {
+                res.headers_mut().insert(header::WWW_AUTHENTICATE, v);
+            }
+            return res;
+        }
+
+        if let AppError::TooManyRequests(retry_after) = &self {
+            let body = ErrorResponseBody {
+                error: ErrorBody {
+                    code: "TOO_MANY_REQUESTS",
+                    message: self.to_string(),
+                },
+            };
+            let mut res = (StatusCode::TOO_MANY_REQUESTS, Json(body)).into_response();
+            res.headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(*retry_after));
+            return res;
+        }
+
         let (status, code) = match &self {
             AppError::InvalidRequest(_) => (StatusCode::BAD_REQUEST, "BAD_REQUEST"),
             AppError::Unauthorized => (StatusCode::UNAUTHORIZED, "UNAUTHORIZED"),
             AppError::Forbidden => (StatusCode::FORBIDDEN, "FORBIDDEN"),
             AppError::NotFound => (StatusCode::NOT_FOUND, "NOT_FOUND"),
             AppError::Conflict => (StatusCode::CONFLICT, "CONFLICT"),
-            AppError::Internal => (StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL"),
+            AppError::Internal
+            | AppError::TooManyRequests(_)
+            | AppError::InsufficientUserAuthentication(_)
+            | AppError::UseDpopNonce(_)
+            | AppError::OAuth(_) => (StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL"),
         };
 
         let body = ErrorResponseBody {
@@ -10,5 +10,9 @@ use crate::error::AppError;
 
 #[tokio::main]
 async fn main() -> Result<(), AppError> {
-    app::run().await
+    match std::env::args().nth(1).as_deref() {
+        Some("hash-password") => app::hash_password(),
+        Some("maintenance") => app::maintenance().await,
+        _ => app::run().await,
+    }
 }
@@ -0,0 +1,137 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::repos::error::{RepoError, RepoResult};
+
+/// DB access for the security audit trail (`auth_audit_events`, append-only).
+#[derive(Clone, Debug)]
+pub struct AuditEventRepo {
+    pool: PgPool,
+}
+
+impl AuditEventRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn insert(&self, event: &NewAuditEvent<'_>) -> RepoResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO auth_audit_events
+                (event_type, outcome, reason, user_id, session_id, client_id, jkt, client_ip, user_agent, request_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+            event.event_type,
+            event.outcome,
+            event.reason,
+            event.user_id,
+            event.session_id,
+            event.client_id,
+            event.jkt,
+            event.client_ip,
+            event.user_agent,
+            event.request_id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(())
+    }
+
+    // Events matching `filter`, newest first, at most `limit`; older than the `before`
+    // id when given (keyset pagination).
+    pub async fn list(
+        &self,
+        filter: &AuditEventFilter,
+        limit: i64,
+    ) -> RepoResult<Vec<AuditEventRow>> {
+        let rows = sqlx::query_as!(
+            AuditEventRow,
+            r#"
+            SELECT
+                id,
+                occurred_at,
+                event_type,
+                outcome,
+                reason,
+                user_id,
+                session_id,
+                client_id,
+                jkt,
+                client_ip,
+                user_agent,
+                request_id
+            FROM auth_audit_events
+            WHERE ($1::text IS NULL OR event_type = $1)
+                AND ($2::text IS NULL OR outcome = $2)
+                AND ($3::uuid IS NULL OR user_id = $3)
+                AND ($4::uuid IS NULL OR session_id = $4)
+                AND ($5::text IS NULL OR client_id = $5)
+                AND ($6::timestamptz IS NULL OR occurred_at >= $6)
+                AND ($7::timestamptz IS NULL OR occurred_at < $7)
+                AND ($8::bigint IS NULL OR id < $8)
+            ORDER BY id DESC
+            LIMIT $9
+            "#,
+            filter.event_type,
+            filter.outcome,
+            filter.user_id,
+            filter.session_id,
+            filter.client_id,
+            filter.since,
+            filter.until,
+            filter.before,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(rows)
+    }
+}
+
+#[derive(Debug)]
+pub struct NewAuditEvent<'a> {
+    pub event_type: &'a str,
+    pub outcome: &'a str,
+    pub reason: Option<&'a str>,
+    pub user_id: Option<Uuid>,
+    pub session_id: Option<Uuid>,
+    pub client_id: Option<&'a str>,
+    pub jkt: Option<&'a str>,
+    pub client_ip: Option<&'a str>,
+    pub user_agent: Option<&'a str>,
+    pub request_id: Option<&'a str>,
+}
+
+/// Query filters; `None` matches everything.
+#[derive(Clone, Debug, Default)]
+pub struct AuditEventFilter {
+    pub event_type: Option<String>,
+    pub outcome: Option<String>,
+    pub user_id: Option<Uuid>,
+    pub session_id: Option<Uuid>,
+    pub client_id: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub before: Option<i64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AuditEventRow {
+    pub id: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub event_type: String,
+    pub outcome: String,
+    pub reason: Option<String>,
+    pub user_id: Option<Uuid>,
+    pub session_id: Option<Uuid>,
+    pub client_id: Option<String>,
+    pub jkt: Option<String>,
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+}
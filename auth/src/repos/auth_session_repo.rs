@@ -1,9 +1,12 @@
 use chrono::{DateTime, Utc};
 use sqlx::PgPool;
+use std::{future::Future, pin::Pin};
 use uuid::Uuid;
 
 use crate::repos::error::{RepoError, RepoResult};
 
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
 #[derive(Clone, Debug)]
 pub struct AuthSessionRepo {
     pool: PgPool,
@@ -14,61 +17,6 @@ impl AuthSessionRepo {
         Self { pool }
     }
 
-    // Create a new auth session.
-    //
-    // Note: dpop_jkt is nullable for Step1/2
-    pub async fn create(
-        &self,
-        user_id: Uuid,
-        dpop_jkt: Option<String>,
-    ) -> RepoResult<AuthSessionRow> {
-        let row = sqlx::query_as!(
-            AuthSessionRow,
-            r#"
-            INSERT INTO auth_sessions (user_id, dpop_jkt)
-            VALUES ($1, $2)
-            RETURNING
-                id,
-                user_id,
-                dpop_jkt,
-                created_at,
-                last_used_at,
-                revoked_at
-            "#,
-            user_id,
-            dpop_jkt
-        )
-        .fetch_one(&self.pool)
-        .await
-        .map_err(RepoError::Db)?;
-
-        Ok(row)
-    }
-
-    // Fetch an active (not revoked) session by id.
-    pub async fn get_active_by_id(&self, id: Uuid) -> RepoResult<Option<AuthSessionRow>> {
-        let row = sqlx::query_as!(
-            AuthSessionRow,
-            r#"
-            SELECT
-                id,
-                user_id,
-                dpop_jkt,
-                created_at,
-                last_used_at,
-                revoked_at
-            FROM auth_sessions
-            WHERE id = $1 AND revoked_at IS NULL
-            "#,
-            id
-        )
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(RepoError::Db)?;
-
-        Ok(row)
-    }
-
     // Minimal lookup for refresh: returns (user_id, dpop_jkt) if session is active.
     pub async fn lookup_refresh_context(
         &self,
@@ -92,52 +40,6 @@ impl AuthSessionRepo {
         Ok(row)
     }
 
-    // Lookup for refresh when Step2+ requires an existing DPoP binding.
-    //
-    // Returns (user_id, dpop_jkt) only when the session is active AND already bound.
-    // This is useful once BOFU is removed.
-    pub async fn lookup_refresh_context_bound(
-        &self,
-        session_id: Uuid,
-    ) -> RepoResult<Option<AuthSessionRefreshContextBound>> {
-        let row = sqlx::query_as!(
-            AuthSessionRefreshContextBound,
-            r#"
-            SELECT
-                user_id,
-                dpop_jkt as "dpop_jkt!"
-            FROM auth_sessions
-            WHERE id = $1
-              AND revoked_at IS NULL
-              AND dpop_jkt IS NOT NULL
-            "#,
-            session_id
-        )
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(RepoError::Db)?;
-
-        Ok(row)
-    }
-
-    // Update last_used_at. Caller decides what now is.
-    pub async fn touch_last_used(&self, id: Uuid, now: DateTime<Utc>) -> RepoResult<u64> {
-        let res = sqlx::query!(
-            r#"
-            UPDATE auth_sessions
-            SET last_used_at = $2
-            WHERE id = $1 AND revoked_at IS NULL
-            "#,
-            id,
-            now
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(RepoError::Db)?;
-
-        Ok(res.rows_affected())
-    }
-
     // set/overwrite the DPoP binding (cnf.jkt) for a session.
     pub async fn set_dpop_jkt(&self, id: Uuid, dpop_jkt: String) -> RepoResult<u64> {
         let res = sqlx::query!(
@@ -182,6 +84,8 @@ impl AuthSessionRepo {
                 id,
                 user_id,
                 dpop_jkt,
+                acr,
+                amr,
                 created_at,
                 last_used_at,
                 revoked_at
@@ -196,31 +100,457 @@ impl AuthSessionRepo {
 
         Ok(row)
     }
+}
+
+/// Session storage behind the token services.
+///
+/// `AuthSessionRepo` (Postgres) and `MemoryAuthStore` implement it with the same
+/// semantics; revoking a session also revokes its refresh tokens where noted.
+pub trait AuthSessionStore: Send + Sync + std::fmt::Debug {
+    // Create a new auth session.
+    fn create<'a>(
+        &'a self,
+        session: NewAuthSession<'a>,
+    ) -> BoxFuture<'a, RepoResult<AuthSessionRow>>;
+
+    // Fetch an active session by id: not revoked, and neither past its maximum lifetime
+    // nor idle for longer than its idle timeout (even if not yet ended by policy).
+    fn get_active_by_id(&self, id: Uuid) -> BoxFuture<'_, RepoResult<Option<AuthSessionRow>>>;
+
+    // List a user's active sessions (as for `get_active_by_id`), most recently used first.
+    fn list_active_by_user(&self, user_id: Uuid) -> BoxFuture<'_, RepoResult<Vec<AuthSessionRow>>>;
+
+    // Lookup for refresh when Step2+ requires an existing DPoP binding.
+    //
+    // Returns (user_id, dpop_jkt, client_id, scope) and the session policy only when the
+    // session is active AND already bound.
+    // This is useful once BOFU is removed.
+    fn lookup_refresh_context_bound(
+        &self,
+        session_id: Uuid,
+    ) -> BoxFuture<'_, RepoResult<Option<AuthSessionRefreshContextBound>>>;
+
+    // Update last_used_at. Caller decides what now is.
+    fn touch_last_used(&self, id: Uuid, now: DateTime<Utc>) -> BoxFuture<'_, RepoResult<u64>>;
 
     // Revoke a session.
-    pub async fn revoke(&self, id: Uuid, revoked_at: DateTime<Utc>) -> RepoResult<u64> {
-        let res = sqlx::query!(
-            r#"
-            UPDATE auth_sessions
-            SET revoked_at = $2
-            WHERE id = $1 AND revoked_at IS NULL
-            "#,
-            id,
-            revoked_at
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(RepoError::Db)?;
+    fn revoke(&self, id: Uuid, revoked_at: DateTime<Utc>) -> BoxFuture<'_, RepoResult<u64>>;
 
-        Ok(res.rows_affected())
+    // Revoke one of the user's sessions together with its refresh tokens.
+    //
+    // Scoped by user_id so a caller can never revoke someone else's session.
+    // Returns the number of sessions revoked (0 or 1).
+    fn revoke_for_user(
+        &self,
+        id: Uuid,
+        user_id: Uuid,
+        revoked_at: DateTime<Utc>,
+    ) -> BoxFuture<'_, RepoResult<u64>>;
+
+    // Revoke all of the user's sessions except `keep` (and their refresh tokens).
+    // Returns the ids of the sessions revoked.
+    fn revoke_all_for_user_except(
+        &self,
+        user_id: Uuid,
+        keep: Option<Uuid>,
+        revoked_at: DateTime<Utc>,
+    ) -> BoxFuture<'_, RepoResult<Vec<Uuid>>>;
+
+    // End an active session by policy (idle timeout, max lifetime), together with its
+    // refresh tokens, recording why. Returns whether this call ended it.
+    fn end_by_policy<'a>(
+        &'a self,
+        id: Uuid,
+        reason: &'a str,
+        revoked_at: DateTime<Utc>,
+    ) -> BoxFuture<'a, RepoResult<bool>>;
+
+    // Keep the user's `keep` most recent active sessions (only those created through
+    // `client_id` when given) and end the others as 'evicted', with their refresh tokens.
+    //
+    // Recency is `created_at`, or the last refresh when `by_last_use`.
+    // Returns the ids of the sessions ended.
+    fn evict_over_cap<'a>(
+        &'a self,
+        user_id: Uuid,
+        client_id: Option<&'a str>,
+        keep: i64,
+        by_last_use: bool,
+        revoked_at: DateTime<Utc>,
+    ) -> BoxFuture<'a, RepoResult<Vec<Uuid>>>;
+
+    // Why a revoked session was ended by policy (None: active, unknown, or revoked
+    // otherwise).
+    fn find_revoked_reason(&self, id: Uuid) -> BoxFuture<'_, RepoResult<Option<String>>>;
+}
+
+impl AuthSessionStore for AuthSessionRepo {
+    fn create<'a>(
+        &'a self,
+        session: NewAuthSession<'a>,
+    ) -> BoxFuture<'a, RepoResult<AuthSessionRow>> {
+        Box::pin(async move {
+            let row = sqlx::query_as!(
+                AuthSessionRow,
+                r#"
+                INSERT INTO auth_sessions
+                    (user_id, dpop_jkt, client_id, scope, expires_at, idle_timeout_seconds, acr, amr)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                RETURNING
+                    id,
+                    user_id,
+                    dpop_jkt,
+                    acr,
+                    amr,
+                    created_at,
+                    last_used_at,
+                    revoked_at
+                "#,
+                session.user_id,
+                session.dpop_jkt,
+                session.client_id,
+                session.scope,
+                session.expires_at,
+                session.idle_timeout_seconds,
+                session.acr,
+                session.amr
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(RepoError::Db)?;
+
+            Ok(row)
+        })
+    }
+
+    fn get_active_by_id(&self, id: Uuid) -> BoxFuture<'_, RepoResult<Option<AuthSessionRow>>> {
+        Box::pin(async move {
+            let row = sqlx::query_as!(
+                AuthSessionRow,
+                r#"
+                SELECT
+                    id,
+                    user_id,
+                    dpop_jkt,
+                    acr,
+                    amr,
+                    created_at,
+                    last_used_at,
+                    revoked_at
+                FROM auth_sessions
+                WHERE id = $1 AND revoked_at IS NULL
+                  AND (expires_at IS NULL OR expires_at > now())
+                  AND (idle_timeout_seconds IS NULL
+                       OR COALESCE(last_used_at, created_at)
+                          + make_interval(secs => idle_timeout_seconds) > now())
+                "#,
+                id
+            )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(RepoError::Db)?;
+
+            Ok(row)
+        })
+    }
+
+    fn list_active_by_user(&self, user_id: Uuid) -> BoxFuture<'_, RepoResult<Vec<AuthSessionRow>>> {
+        Box::pin(async move {
+            let rows = sqlx::query_as!(
+                AuthSessionRow,
+                r#"
+                SELECT
+                    id,
+                    user_id,
+                    dpop_jkt,
+                    acr,
+                    amr,
+                    created_at,
+                    last_used_at,
+                    revoked_at
+                FROM auth_sessions
+                WHERE user_id = $1 AND revoked_at IS NULL
+                  AND (expires_at IS NULL OR expires_at > now())
+                  AND (idle_timeout_seconds IS NULL
+                       OR COALESCE(last_used_at, created_at)
+                          + make_interval(secs => idle_timeout_seconds) > now())
+                ORDER BY COALESCE(last_used_at, created_at) DESC
+                "#,
+                user_id
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(RepoError::Db)?;
+
+            Ok(rows)
+        })
+    }
+
+    fn lookup_refresh_context_bound(
+        &self,
+        session_id: Uuid,
+    ) -> BoxFuture<'_, RepoResult<Option<AuthSessionRefreshContextBound>>> {
+        Box::pin(async move {
+            let row = sqlx::query_as!(
+                AuthSessionRefreshContextBound,
+                r#"
+                SELECT
+                    user_id,
+                    dpop_jkt as "dpop_jkt!",
+                    client_id,
+                    scope,
+                    acr,
+                    amr,
+                    created_at,
+                    last_used_at,
+                    expires_at,
+                    idle_timeout_seconds
+                FROM auth_sessions
+                WHERE id = $1
+                  AND revoked_at IS NULL
+                  AND dpop_jkt IS NOT NULL
+                "#,
+                session_id
+            )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(RepoError::Db)?;
+
+            Ok(row)
+        })
+    }
+
+    fn touch_last_used(&self, id: Uuid, now: DateTime<Utc>) -> BoxFuture<'_, RepoResult<u64>> {
+        Box::pin(async move {
+            let res = sqlx::query!(
+                r#"
+                UPDATE auth_sessions
+                SET last_used_at = $2
+                WHERE id = $1 AND revoked_at IS NULL
+                "#,
+                id,
+                now
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(RepoError::Db)?;
+
+            Ok(res.rows_affected())
+        })
+    }
+
+    fn revoke(&self, id: Uuid, revoked_at: DateTime<Utc>) -> BoxFuture<'_, RepoResult<u64>> {
+        Box::pin(async move {
+            let res = sqlx::query!(
+                r#"
+                UPDATE auth_sessions
+                SET revoked_at = $2
+                WHERE id = $1 AND revoked_at IS NULL
+                "#,
+                id,
+                revoked_at
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(RepoError::Db)?;
+
+            Ok(res.rows_affected())
+        })
+    }
+
+    fn revoke_for_user(
+        &self,
+        id: Uuid,
+        user_id: Uuid,
+        revoked_at: DateTime<Utc>,
+    ) -> BoxFuture<'_, RepoResult<u64>> {
+        Box::pin(async move {
+            let count = sqlx::query_scalar!(
+                r#"
+                WITH s AS (
+                    UPDATE auth_sessions
+                    SET revoked_at = $3
+                    WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+                    RETURNING id
+                ), t AS (
+                    UPDATE refresh_tokens
+                    SET revoked_at = $3
+                    WHERE session_id IN (SELECT id FROM s) AND revoked_at IS NULL
+                )
+                SELECT COUNT(*) AS "count!" FROM s
+                "#,
+                id,
+                user_id,
+                revoked_at
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(RepoError::Db)?;
+
+            Ok(count as u64)
+        })
+    }
+
+    fn revoke_all_for_user_except(
+        &self,
+        user_id: Uuid,
+        keep: Option<Uuid>,
+        revoked_at: DateTime<Utc>,
+    ) -> BoxFuture<'_, RepoResult<Vec<Uuid>>> {
+        Box::pin(async move {
+            let ids = sqlx::query_scalar!(
+                r#"
+                WITH s AS (
+                    UPDATE auth_sessions
+                    SET revoked_at = $3
+                    WHERE user_id = $1
+                        AND revoked_at IS NULL
+                        AND ($2::uuid IS NULL OR id <> $2)
+                    RETURNING id
+                ), t AS (
+                    UPDATE refresh_tokens
+                    SET revoked_at = $3
+                    WHERE session_id IN (SELECT id FROM s) AND revoked_at IS NULL
+                )
+                SELECT id AS "id!" FROM s
+                "#,
+                user_id,
+                keep,
+                revoked_at
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(RepoError::Db)?;
+
+            Ok(ids)
+        })
+    }
+
+    fn end_by_policy<'a>(
+        &'a self,
+        id: Uuid,
+        reason: &'a str,
+        revoked_at: DateTime<Utc>,
+    ) -> BoxFuture<'a, RepoResult<bool>> {
+        Box::pin(async move {
+            let count = sqlx::query_scalar!(
+                r#"
+                WITH s AS (
+                    UPDATE auth_sessions
+                    SET revoked_at = $3, revoked_reason = $2
+                    WHERE id = $1 AND revoked_at IS NULL
+                    RETURNING id
+                ), t AS (
+                    UPDATE refresh_tokens
+                    SET revoked_at = $3
+                    WHERE session_id IN (SELECT id FROM s) AND revoked_at IS NULL
+                )
+                SELECT COUNT(*) AS "count!" FROM s
+                "#,
+                id,
+                reason,
+                revoked_at
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(RepoError::Db)?;
+
+            Ok(count > 0)
+        })
+    }
+
+    fn evict_over_cap<'a>(
+        &'a self,
+        user_id: Uuid,
+        client_id: Option<&'a str>,
+        keep: i64,
+        by_last_use: bool,
+        revoked_at: DateTime<Utc>,
+    ) -> BoxFuture<'a, RepoResult<Vec<Uuid>>> {
+        Box::pin(async move {
+            let ids = sqlx::query_scalar!(
+                r#"
+                WITH victims AS (
+                    SELECT id
+                    FROM auth_sessions
+                    WHERE user_id = $1
+                        AND revoked_at IS NULL
+                        AND ($2::text IS NULL OR client_id = $2)
+                    ORDER BY
+                        CASE WHEN $4 THEN COALESCE(last_used_at, created_at) ELSE created_at END DESC,
+                        created_at DESC
+                    OFFSET $3
+                    FOR UPDATE
+                ), s AS (
+                    UPDATE auth_sessions
+                    SET revoked_at = $5, revoked_reason = 'evicted'
+                    WHERE id IN (SELECT id FROM victims)
+                    RETURNING id
+                ), t AS (
+                    UPDATE refresh_tokens
+                    SET revoked_at = $5
+                    WHERE session_id IN (SELECT id FROM s) AND revoked_at IS NULL
+                )
+                SELECT id AS "id!" FROM s
+                "#,
+                user_id,
+                client_id,
+                keep,
+                by_last_use,
+                revoked_at
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(RepoError::Db)?;
+
+            Ok(ids)
+        })
+    }
+
+    fn find_revoked_reason(&self, id: Uuid) -> BoxFuture<'_, RepoResult<Option<String>>> {
+        Box::pin(async move {
+            let reason = sqlx::query_scalar!(
+                r#"
+                SELECT revoked_reason
+                FROM auth_sessions
+                WHERE id = $1 AND revoked_at IS NOT NULL
+                "#,
+                id
+            )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(RepoError::Db)?;
+
+            Ok(reason.flatten())
+        })
     }
 }
 
+/// A session to create.
+#[derive(Debug, Clone)]
+pub struct NewAuthSession<'a> {
+    pub user_id: Uuid,
+    // Nullable for Step1/2.
+    pub dpop_jkt: Option<String>,
+    // OAuth client the session was created through, if any.
+    pub client_id: Option<&'a str>,
+    // What was granted to the session (space-separated).
+    pub scope: Option<&'a str>,
+    // Session policy in effect (None: no limit).
+    pub expires_at: Option<DateTime<Utc>>,
+    pub idle_timeout_seconds: Option<i32>,
+    // How the user authenticated (None / empty: not recorded).
+    pub acr: Option<&'a str>,
+    pub amr: &'a [String],
+}
+
 #[derive(Clone, Debug)]
 pub struct AuthSessionRow {
     pub id: Uuid,
     pub user_id: Uuid,
     pub dpop_jkt: Option<String>,
+    pub acr: Option<String>,
+    pub amr: Vec<String>,
     pub created_at: DateTime<Utc>,
     pub last_used_at: Option<DateTime<Utc>>,
     pub revoked_at: Option<DateTime<Utc>>,
@@ -236,4 +566,12 @@ pub struct AuthSessionRefreshContext {
 pub struct AuthSessionRefreshContextBound {
     pub user_id: Uuid,
     pub dpop_jkt: String,
+    pub client_id: Option<String>,
+    pub scope: Option<String>,
+    pub acr: Option<String>,
+    pub amr: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub idle_timeout_seconds: Option<i32>,
 }
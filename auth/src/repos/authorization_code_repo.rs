@@ -0,0 +1,142 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::repos::error::{RepoError, RepoResult};
+
+/// DB access for authorization codes (`authorization_codes`).
+///
+/// Codes are looked up by the SHA-256 of the code; the plaintext is never stored.
+#[derive(Clone, Debug)]
+pub struct AuthorizationCodeRepo {
+    pool: PgPool,
+}
+
+impl AuthorizationCodeRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn insert(&self, code: NewAuthorizationCode<'_>) -> RepoResult<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO authorization_codes
+                (code_hash, client_id, user_id, redirect_uri, code_challenge, dpop_jkt, scope, expires_at, acr, amr)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+            code.code_hash,
+            code.client_id,
+            code.user_id,
+            code.redirect_uri,
+            code.code_challenge,
+            code.dpop_jkt,
+            code.scope,
+            code.expires_at,
+            code.acr,
+            code.amr
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(())
+    }
+
+    // Mark the code consumed and return it, only if it was unused and not expired.
+    // Concurrent redemptions of the same code: exactly one gets the row.
+    pub async fn consume(
+        &self,
+        code_hash: &[u8],
+        now: DateTime<Utc>,
+    ) -> RepoResult<Option<AuthorizationCodeRow>> {
+        let row = sqlx::query_as!(
+            AuthorizationCodeRow,
+            r#"
+            UPDATE authorization_codes
+            SET consumed_at = $2
+            WHERE code_hash = $1
+              AND consumed_at IS NULL
+              AND expires_at > $2
+            RETURNING
+                client_id,
+                user_id,
+                redirect_uri,
+                code_challenge,
+                dpop_jkt,
+                scope,
+                acr,
+                amr
+            "#,
+            code_hash,
+            now
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(row)
+    }
+
+    // Record the session a consumed code was exchanged for.
+    pub async fn set_session(&self, code_hash: &[u8], session_id: Uuid) -> RepoResult<()> {
+        sqlx::query!(
+            r#"
+            UPDATE authorization_codes
+            SET session_id = $2
+            WHERE code_hash = $1
+            "#,
+            code_hash,
+            session_id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(())
+    }
+
+    // Session issued for an already consumed code (used to revoke it on replay).
+    pub async fn find_consumed_session(&self, code_hash: &[u8]) -> RepoResult<Option<Uuid>> {
+        let session_id = sqlx::query_scalar!(
+            r#"
+            SELECT session_id
+            FROM authorization_codes
+            WHERE code_hash = $1
+              AND consumed_at IS NOT NULL
+            "#,
+            code_hash
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(session_id.flatten())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NewAuthorizationCode<'a> {
+    pub code_hash: &'a [u8],
+    pub client_id: &'a str,
+    pub user_id: Uuid,
+    pub redirect_uri: &'a str,
+    pub code_challenge: &'a str,
+    pub dpop_jkt: Option<&'a str>,
+    pub scope: Option<&'a str>,
+    pub expires_at: DateTime<Utc>,
+    // How the user signed in at /authorize; becomes the session's.
+    pub acr: &'a str,
+    pub amr: &'a [String],
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthorizationCodeRow {
+    pub client_id: String,
+    pub user_id: Uuid,
+    pub redirect_uri: String,
+    pub code_challenge: String,
+    pub dpop_jkt: Option<String>,
+    pub scope: Option<String>,
+    pub acr: Option<String>,
+    pub amr: Vec<String>,
+}
@@ -0,0 +1,49 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::repos::error::{RepoError, RepoResult};
+
+/// DB access for the DPoP proof replay cache (`dpop_proof_jtis`).
+///
+/// Used when Valkey is not configured. Expired rows are treated as absent, so the
+/// table does not need to be purged for correctness (only for size).
+#[derive(Clone, Debug)]
+pub struct DpopReplayRepo {
+    pool: PgPool,
+}
+
+impl DpopReplayRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // Record `key` until `expires_at` unless a live entry already exists.
+    //
+    // Returns true when the key was recorded (first use), false on replay.
+    // A single statement keeps this atomic across concurrent requests.
+    pub async fn insert_if_absent(
+        &self,
+        key: &str,
+        expires_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> RepoResult<bool> {
+        let row = sqlx::query_scalar!(
+            r#"
+            INSERT INTO dpop_proof_jtis (key, expires_at)
+            VALUES ($1, $2)
+            ON CONFLICT (key) DO UPDATE
+                SET expires_at = EXCLUDED.expires_at
+                WHERE dpop_proof_jtis.expires_at <= $3
+            RETURNING key
+            "#,
+            key,
+            expires_at,
+            now
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(row.is_some())
+    }
+}
@@ -4,6 +4,10 @@ use thiserror::Error;
 pub enum RepoError {
     #[error("db error")]
     Db(#[from] sqlx::Error),
+
+    // A uniqueness or reference constraint the in-memory store enforces itself.
+    #[error("constraint violation: {0}")]
+    Constraint(&'static str),
 }
 
 pub type RepoResult<T> = Result<T, RepoError>;
@@ -0,0 +1,165 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use sqlx::pool::PoolConnection;
+use sqlx::postgres::Postgres;
+
+use crate::repos::error::{RepoError, RepoResult};
+
+// Postgres advisory lock key held while a purge runs ("authmain").
+const MAINTENANCE_LOCK_KEY: i64 = 0x6175_7468_6d61_696e;
+
+/// DB access for purging expired and revoked rows.
+///
+/// Every purge deletes at most `limit` rows per call so a run never holds many row
+/// locks or one long transaction; callers repeat until fewer than `limit` come back.
+#[derive(Clone, Debug)]
+pub struct MaintenanceRepo {
+    pool: PgPool,
+}
+
+/// The session-level advisory lock; give it back with `unlock`.
+pub struct MaintenanceLock {
+    conn: PoolConnection<Postgres>,
+}
+
+impl MaintenanceRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // Take the maintenance lock if no other instance holds it.
+    //
+    // The lock belongs to one pooled connection, which is kept until `unlock`.
+    pub async fn try_lock(&self) -> RepoResult<Option<MaintenanceLock>> {
+        let mut conn = self.pool.acquire().await.map_err(RepoError::Db)?;
+        let locked = sqlx::query_scalar!(
+            r#"SELECT pg_try_advisory_lock($1) AS "locked!""#,
+            MAINTENANCE_LOCK_KEY
+        )
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(locked.then_some(MaintenanceLock { conn }))
+    }
+
+    pub async fn unlock(&self, lock: MaintenanceLock) -> RepoResult<()> {
+        let mut conn = lock.conn;
+        let res = sqlx::query_scalar!(
+            r#"SELECT pg_advisory_unlock($1) AS "unlocked!""#,
+            MAINTENANCE_LOCK_KEY
+        )
+        .fetch_one(&mut *conn)
+        .await;
+
+        if res.is_err() {
+            // Never hand a connection that may still hold the lock back to the pool;
+            // closing it releases the lock.
+            drop(conn.detach());
+        }
+        res.map(|_| ()).map_err(RepoError::Db)
+    }
+
+    // Refresh tokens expired or revoked before `cutoff`.
+    pub async fn purge_refresh_tokens(&self, cutoff: DateTime<Utc>, limit: i64) -> RepoResult<u64> {
+        let res = sqlx::query!(
+            r#"
+            DELETE FROM refresh_tokens
+            WHERE id IN (
+                SELECT id
+                FROM refresh_tokens
+                WHERE expires_at < $1 OR revoked_at < $1
+                LIMIT $2
+                FOR UPDATE SKIP LOCKED
+            )
+            "#,
+            cutoff,
+            limit
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(res.rows_affected())
+    }
+
+    // Sessions revoked or past their maximum lifetime before `cutoff`, and sessions
+    // without refresh tokens left that were last used before it. Their remaining refresh
+    // tokens go with them.
+    pub async fn purge_sessions(&self, cutoff: DateTime<Utc>, limit: i64) -> RepoResult<u64> {
+        let res = sqlx::query!(
+            r#"
+            DELETE FROM auth_sessions
+            WHERE id IN (
+                SELECT s.id
+                FROM auth_sessions s
+                WHERE s.revoked_at < $1
+                    OR s.expires_at < $1
+                    OR (
+                        COALESCE(s.last_used_at, s.created_at) < $1
+                        AND NOT EXISTS (SELECT 1 FROM refresh_tokens t WHERE t.session_id = s.id)
+                    )
+                LIMIT $2
+                FOR UPDATE SKIP LOCKED
+            )
+            "#,
+            cutoff,
+            limit
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(res.rows_affected())
+    }
+
+    // Authorization codes expired before `cutoff` (kept until then to detect reuse).
+    pub async fn purge_authorization_codes(
+        &self,
+        cutoff: DateTime<Utc>,
+        limit: i64,
+    ) -> RepoResult<u64> {
+        let res = sqlx::query!(
+            r#"
+            DELETE FROM authorization_codes
+            WHERE code_hash IN (
+                SELECT code_hash
+                FROM authorization_codes
+                WHERE expires_at < $1
+                LIMIT $2
+                FOR UPDATE SKIP LOCKED
+            )
+            "#,
+            cutoff,
+            limit
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(res.rows_affected())
+    }
+
+    // DPoP replay entries expired before `cutoff` (they no longer prevent anything).
+    pub async fn purge_dpop_replay(&self, cutoff: DateTime<Utc>, limit: i64) -> RepoResult<u64> {
+        let res = sqlx::query!(
+            r#"
+            DELETE FROM dpop_proof_jtis
+            WHERE key IN (
+                SELECT key
+                FROM dpop_proof_jtis
+                WHERE expires_at < $1
+                LIMIT $2
+                FOR UPDATE SKIP LOCKED
+            )
+            "#,
+            cutoff,
+            limit
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(res.rows_affected())
+    }
+}
@@ -0,0 +1,615 @@
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::{future::Future, pin::Pin};
+use uuid::Uuid;
+
+use crate::repos::auth_session_repo::{
+    AuthSessionRefreshContextBound, AuthSessionRow, AuthSessionStore, NewAuthSession,
+};
+use crate::repos::error::{RepoError, RepoResult};
+use crate::repos::refresh_token_repo::{RefreshTokenRow, RefreshTokenStore};
+use crate::services::auth::session_policy;
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Sessions and refresh tokens in process memory, for tests and Postgres-less development.
+///
+/// Mirrors `AuthSessionRepo` / `RefreshTokenRepo`, including the unique token hash, the
+/// one-current-token-per-session index and the session foreign key. Each call holds one
+/// lock, so multi-row updates are atomic like their SQL counterparts. Clones share state.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryAuthStore {
+    inner: Arc<Mutex<Tables>>,
+}
+
+#[derive(Debug, Default)]
+struct Tables {
+    sessions: HashMap<Uuid, SessionRecord>,
+    refresh_tokens: HashMap<Uuid, RefreshTokenRow>,
+    // token_hash -> refresh_tokens id
+    token_hashes: HashMap<Vec<u8>, Uuid>,
+}
+
+#[derive(Clone, Debug)]
+struct SessionRecord {
+    id: Uuid,
+    user_id: Uuid,
+    dpop_jkt: Option<String>,
+    client_id: Option<String>,
+    scope: Option<String>,
+    acr: Option<String>,
+    amr: Vec<String>,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
+    idle_timeout_seconds: Option<i32>,
+    revoked_at: Option<DateTime<Utc>>,
+    revoked_reason: Option<String>,
+}
+
+impl SessionRecord {
+    fn is_active(&self) -> bool {
+        self.revoked_at.is_none()
+    }
+
+    // Active and not yet past its lifetime or idle timeout.
+    fn is_live(&self, now: DateTime<Utc>) -> bool {
+        self.is_active()
+            && session_policy::ended_by_policy(
+                self.expires_at,
+                self.idle_timeout_seconds
+                    .and_then(|v| u64::try_from(v).ok()),
+                self.last_used_at.unwrap_or(self.created_at),
+                now,
+            )
+            .is_none()
+    }
+
+    fn row(&self) -> AuthSessionRow {
+        AuthSessionRow {
+            id: self.id,
+            user_id: self.user_id,
+            dpop_jkt: self.dpop_jkt.clone(),
+            acr: self.acr.clone(),
+            amr: self.amr.clone(),
+            created_at: self.created_at,
+            last_used_at: self.last_used_at,
+            revoked_at: self.revoked_at,
+        }
+    }
+}
+
+impl Tables {
+    // Revoke the session if active, optionally recording why, together with its
+    // refresh tokens. Returns whether the session was active.
+    fn revoke_session(
+        &mut self,
+        id: Uuid,
+        reason: Option<&str>,
+        revoked_at: DateTime<Utc>,
+    ) -> bool {
+        let Some(session) = self.sessions.get_mut(&id).filter(|s| s.is_active()) else {
+            return false;
+        };
+        session.revoked_at = Some(revoked_at);
+        session.revoked_reason = reason.map(str::to_string);
+        self.revoke_tokens_of(id, revoked_at);
+        true
+    }
+
+    // Returns the number of tokens revoked.
+    fn revoke_tokens_of(&mut self, session_id: Uuid, revoked_at: DateTime<Utc>) -> u64 {
+        let mut revoked = 0;
+        for token in self.refresh_tokens.values_mut() {
+            if token.session_id == session_id && token.revoked_at.is_none() {
+                token.revoked_at = Some(revoked_at);
+                revoked += 1;
+            }
+        }
+        revoked
+    }
+
+    fn has_current_token(&self, session_id: Uuid) -> bool {
+        self.refresh_tokens.values().any(|t| {
+            t.session_id == session_id && t.revoked_at.is_none() && t.replaced_by.is_none()
+        })
+    }
+
+    // Insert after the same checks Postgres applies to `refresh_tokens`.
+    fn insert_token(&mut self, row: RefreshTokenRow) -> RepoResult<Uuid> {
+        if !self.sessions.contains_key(&row.session_id) {
+            return Err(RepoError::Constraint("refresh_tokens_session_id_fkey"));
+        }
+        if self.token_hashes.contains_key(&row.token_hash) {
+            return Err(RepoError::Constraint("uq_refresh_tokens_token_hash"));
+        }
+        if self.has_current_token(row.session_id) {
+            return Err(RepoError::Constraint(
+                "uq_refresh_tokens_current_per_session",
+            ));
+        }
+
+        let id = row.id;
+        self.token_hashes.insert(row.token_hash.clone(), id);
+        self.refresh_tokens.insert(id, row);
+        Ok(id)
+    }
+
+    fn token_by_hash(&self, token_hash: &[u8]) -> Option<&RefreshTokenRow> {
+        self.token_hashes
+            .get(token_hash)
+            .and_then(|id| self.refresh_tokens.get(id))
+    }
+}
+
+impl MemoryAuthStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        // A panic while holding the lock leaves no half-applied update behind: every
+        // mutation validates before it writes.
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl AuthSessionStore for MemoryAuthStore {
+    fn create<'a>(
+        &'a self,
+        session: NewAuthSession<'a>,
+    ) -> BoxFuture<'a, RepoResult<AuthSessionRow>> {
+        Box::pin(async move {
+            let session = SessionRecord {
+                id: Uuid::new_v4(),
+                user_id: session.user_id,
+                dpop_jkt: session.dpop_jkt,
+                client_id: session.client_id.map(str::to_string),
+                scope: session.scope.map(str::to_string),
+                acr: session.acr.map(str::to_string),
+                amr: session.amr.to_vec(),
+                created_at: Utc::now(),
+                last_used_at: None,
+                expires_at: session.expires_at,
+                idle_timeout_seconds: session.idle_timeout_seconds,
+                revoked_at: None,
+                revoked_reason: None,
+            };
+            let row = session.row();
+            self.tables().sessions.insert(session.id, session);
+            Ok(row)
+        })
+    }
+
+    fn get_active_by_id(&self, id: Uuid) -> BoxFuture<'_, RepoResult<Option<AuthSessionRow>>> {
+        Box::pin(async move {
+            Ok(self
+                .tables()
+                .sessions
+                .get(&id)
+                .filter(|s| s.is_live(Utc::now()))
+                .map(SessionRecord::row))
+        })
+    }
+
+    fn list_active_by_user(&self, user_id: Uuid) -> BoxFuture<'_, RepoResult<Vec<AuthSessionRow>>> {
+        Box::pin(async move {
+            let now = Utc::now();
+            let mut rows: Vec<AuthSessionRow> = self
+                .tables()
+                .sessions
+                .values()
+                .filter(|s| s.user_id == user_id && s.is_live(now))
+                .map(SessionRecord::row)
+                .collect();
+            rows.sort_by_key(|r| std::cmp::Reverse(r.last_used_at.unwrap_or(r.created_at)));
+            Ok(rows)
+        })
+    }
+
+    fn lookup_refresh_context_bound(
+        &self,
+        session_id: Uuid,
+    ) -> BoxFuture<'_, RepoResult<Option<AuthSessionRefreshContextBound>>> {
+        Box::pin(async move {
+            let tables = self.tables();
+            let Some(s) = tables.sessions.get(&session_id).filter(|s| s.is_active()) else {
+                return Ok(None);
+            };
+            Ok(s.dpop_jkt
+                .clone()
+                .map(|dpop_jkt| AuthSessionRefreshContextBound {
+                    user_id: s.user_id,
+                    dpop_jkt,
+                    client_id: s.client_id.clone(),
+                    scope: s.scope.clone(),
+                    acr: s.acr.clone(),
+                    amr: s.amr.clone(),
+                    created_at: s.created_at,
+                    last_used_at: s.last_used_at,
+                    expires_at: s.expires_at,
+                    idle_timeout_seconds: s.idle_timeout_seconds,
+                }))
+        })
+    }
+
+    fn touch_last_used(&self, id: Uuid, now: DateTime<Utc>) -> BoxFuture<'_, RepoResult<u64>> {
+        Box::pin(async move {
+            let mut tables = self.tables();
+            match tables.sessions.get_mut(&id).filter(|s| s.is_active()) {
+                Some(s) => {
+                    s.last_used_at = Some(now);
+                    Ok(1)
+                }
+                None => Ok(0),
+            }
+        })
+    }
+
+    fn revoke(&self, id: Uuid, revoked_at: DateTime<Utc>) -> BoxFuture<'_, RepoResult<u64>> {
+        Box::pin(async move {
+            // Like the SQL: the session only, its refresh tokens are left alone.
+            let mut tables = self.tables();
+            match tables.sessions.get_mut(&id).filter(|s| s.is_active()) {
+                Some(s) => {
+                    s.revoked_at = Some(revoked_at);
+                    Ok(1)
+                }
+                None => Ok(0),
+            }
+        })
+    }
+
+    fn revoke_for_user(
+        &self,
+        id: Uuid,
+        user_id: Uuid,
+        revoked_at: DateTime<Utc>,
+    ) -> BoxFuture<'_, RepoResult<u64>> {
+        Box::pin(async move {
+            let mut tables = self.tables();
+            let owned = tables
+                .sessions
+                .get(&id)
+                .is_some_and(|s| s.user_id == user_id);
+            Ok(u64::from(
+                owned && tables.revoke_session(id, None, revoked_at),
+            ))
+        })
+    }
+
+    fn revoke_all_for_user_except(
+        &self,
+        user_id: Uuid,
+        keep: Option<Uuid>,
+        revoked_at: DateTime<Utc>,
+    ) -> BoxFuture<'_, RepoResult<Vec<Uuid>>> {
+        Box::pin(async move {
+            let mut tables = self.tables();
+            let ids: Vec<Uuid> = tables
+                .sessions
+                .values()
+                .filter(|s| s.user_id == user_id && s.is_active() && Some(s.id) != keep)
+                .map(|s| s.id)
+                .collect();
+            for id in &ids {
+                tables.revoke_session(*id, None, revoked_at);
+            }
+            Ok(ids)
+        })
+    }
+
+    fn end_by_policy<'a>(
+        &'a self,
+        id: Uuid,
+        reason: &'a str,
+        revoked_at: DateTime<Utc>,
+    ) -> BoxFuture<'a, RepoResult<bool>> {
+        Box::pin(async move { Ok(self.tables().revoke_session(id, Some(reason), revoked_at)) })
+    }
+
+    fn evict_over_cap<'a>(
+        &'a self,
+        user_id: Uuid,
+        client_id: Option<&'a str>,
+        keep: i64,
+        by_last_use: bool,
+        revoked_at: DateTime<Utc>,
+    ) -> BoxFuture<'a, RepoResult<Vec<Uuid>>> {
+        Box::pin(async move {
+            let mut tables = self.tables();
+            let mut candidates: Vec<&SessionRecord> = tables
+                .sessions
+                .values()
+                .filter(|s| {
+                    s.user_id == user_id
+                        && s.is_active()
+                        && client_id.is_none_or(|c| s.client_id.as_deref() == Some(c))
+                })
+                .collect();
+            // Same order as the SQL: most recent first, newest created breaking ties.
+            candidates.sort_by_key(|s| {
+                let recency = if by_last_use {
+                    s.last_used_at.unwrap_or(s.created_at)
+                } else {
+                    s.created_at
+                };
+                std::cmp::Reverse((recency, s.created_at))
+            });
+            let victims: Vec<Uuid> = candidates
+                .into_iter()
+                .skip(usize::try_from(keep).unwrap_or(0))
+                .map(|s| s.id)
+                .collect();
+
+            for id in &victims {
+                tables.revoke_session(*id, Some("evicted"), revoked_at);
+            }
+            Ok(victims)
+        })
+    }
+
+    fn find_revoked_reason(&self, id: Uuid) -> BoxFuture<'_, RepoResult<Option<String>>> {
+        Box::pin(async move {
+            Ok(self
+                .tables()
+                .sessions
+                .get(&id)
+                .filter(|s| !s.is_active())
+                .and_then(|s| s.revoked_reason.clone()))
+        })
+    }
+}
+
+impl RefreshTokenStore for MemoryAuthStore {
+    fn insert(
+        &self,
+        session_id: Uuid,
+        token_hash: Vec<u8>,
+        expires_at: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<Uuid, RepoError>> {
+        Box::pin(async move {
+            self.tables().insert_token(RefreshTokenRow {
+                id: Uuid::new_v4(),
+                session_id,
+                token_hash,
+                issued_at: Utc::now(),
+                expires_at,
+                used_at: None,
+                revoked_at: None,
+                replaced_by: None,
+            })
+        })
+    }
+
+    fn find_active_by_hash(
+        &self,
+        token_hash: Vec<u8>,
+        now: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<Option<RefreshTokenRow>, RepoError>> {
+        Box::pin(async move {
+            Ok(self
+                .tables()
+                .token_by_hash(&token_hash)
+                .filter(|t| t.revoked_at.is_none() && t.expires_at > now)
+                .cloned())
+        })
+    }
+
+    fn find_by_hash(
+        &self,
+        token_hash: Vec<u8>,
+    ) -> BoxFuture<'_, Result<Option<RefreshTokenRow>, RepoError>> {
+        Box::pin(async move { Ok(self.tables().token_by_hash(&token_hash).cloned()) })
+    }
+
+    fn rotate(
+        &self,
+        id: Uuid,
+        new_token_hash: Vec<u8>,
+        expires_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<Option<Uuid>, RepoError>> {
+        Box::pin(async move {
+            let mut tables = self.tables();
+            let Some(current) = tables.refresh_tokens.get(&id).filter(|t| {
+                t.used_at.is_none()
+                    && t.replaced_by.is_none()
+                    && t.revoked_at.is_none()
+                    && t.expires_at > now
+            }) else {
+                return Ok(None);
+            };
+            if tables.token_hashes.contains_key(&new_token_hash) {
+                return Err(RepoError::Constraint("uq_refresh_tokens_token_hash"));
+            }
+
+            let new_id = Uuid::new_v4();
+            let successor = RefreshTokenRow {
+                id: new_id,
+                session_id: current.session_id,
+                token_hash: new_token_hash,
+                issued_at: now,
+                expires_at,
+                used_at: None,
+                revoked_at: None,
+                replaced_by: None,
+            };
+            if let Some(current) = tables.refresh_tokens.get_mut(&id) {
+                current.used_at = Some(now);
+                current.replaced_by = Some(new_id);
+            }
+            tables.insert_token(successor)?;
+
+            Ok(Some(new_id))
+        })
+    }
+
+    fn revoke_family(
+        &self,
+        session_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<u64, RepoError>> {
+        Box::pin(async move {
+            let mut tables = self.tables();
+            if let Some(s) = tables
+                .sessions
+                .get_mut(&session_id)
+                .filter(|s| s.is_active())
+            {
+                s.revoked_at = Some(now);
+            }
+            Ok(tables.revoke_tokens_of(session_id, now))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    async fn session(store: &MemoryAuthStore, user_id: Uuid) -> Uuid {
+        let new = NewAuthSession {
+            user_id,
+            dpop_jkt: Some("jkt".into()),
+            client_id: None,
+            scope: None,
+            expires_at: None,
+            idle_timeout_seconds: None,
+            acr: None,
+            amr: &[],
+        };
+        AuthSessionStore::create(store, new).await.unwrap().id
+    }
+
+    #[tokio::test]
+    async fn one_current_refresh_token_per_session() {
+        let store = MemoryAuthStore::new();
+        let now = Utc::now();
+        let later = now + Duration::hours(1);
+        let sid = session(&store, Uuid::new_v4()).await;
+
+        let first = store.insert(sid, vec![1], later).await.unwrap();
+        assert!(matches!(
+            store.insert(sid, vec![2], later).await,
+            Err(RepoError::Constraint(_))
+        ));
+        assert!(matches!(
+            store.insert(Uuid::new_v4(), vec![3], later).await,
+            Err(RepoError::Constraint(_))
+        ));
+
+        // Rotation hands "current" to the successor; the loser of a race gets None.
+        let second = store.rotate(first, vec![2], later, now).await.unwrap();
+        assert!(second.is_some());
+        assert_eq!(
+            store.rotate(first, vec![3], later, now).await.unwrap(),
+            None
+        );
+        let old = store.find_by_hash(vec![1]).await.unwrap().unwrap();
+        assert_eq!(old.replaced_by, second);
+        assert!(matches!(
+            store.insert(sid, vec![4], later).await,
+            Err(RepoError::Constraint(_))
+        ));
+
+        // Revoking the family ends the session and frees the slot.
+        assert_eq!(store.revoke_family(sid, now).await.unwrap(), 2);
+        assert!(store.get_active_by_id(sid).await.unwrap().is_none());
+        assert!(
+            store
+                .find_active_by_hash(vec![2], now)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn eviction_keeps_the_newest_sessions_and_records_why() {
+        let store = MemoryAuthStore::new();
+        let user = Uuid::new_v4();
+        let oldest = session(&store, user).await;
+        let middle = session(&store, user).await;
+        let newest = session(&store, user).await;
+        let other_user = session(&store, Uuid::new_v4()).await;
+        store
+            .insert(oldest, vec![1], Utc::now() + Duration::hours(1))
+            .await
+            .unwrap();
+
+        // Using `oldest` most recently keeps it under least-recently-used eviction.
+        store
+            .touch_last_used(oldest, Utc::now() + Duration::seconds(1))
+            .await
+            .unwrap();
+        let evicted = store
+            .evict_over_cap(user, None, 2, true, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(evicted, vec![middle]);
+        assert_eq!(
+            store.find_revoked_reason(middle).await.unwrap().as_deref(),
+            Some("evicted")
+        );
+
+        let evicted = store
+            .evict_over_cap(user, None, 1, false, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(evicted, vec![oldest]);
+        assert!(
+            store
+                .find_by_hash(vec![1])
+                .await
+                .unwrap()
+                .unwrap()
+                .revoked_at
+                .is_some()
+        );
+        assert!(store.get_active_by_id(newest).await.unwrap().is_some());
+        assert!(store.get_active_by_id(other_user).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn expired_and_idle_sessions_are_not_active() {
+        let store = MemoryAuthStore::new();
+        let user_id = Uuid::new_v4();
+        let now = Utc::now();
+        let create = |expires_at, idle_timeout_seconds| {
+            let store = store.clone();
+            async move {
+                let new = NewAuthSession {
+                    user_id,
+                    dpop_jkt: Some("jkt".into()),
+                    client_id: None,
+                    scope: None,
+                    expires_at,
+                    idle_timeout_seconds,
+                    acr: None,
+                    amr: &[],
+                };
+                AuthSessionStore::create(&store, new).await.unwrap().id
+            }
+        };
+
+        let expired = create(Some(now - Duration::seconds(1)), None).await;
+        let idle = create(None, Some(0)).await;
+        let live = create(Some(now + Duration::hours(1)), Some(600)).await;
+
+        assert!(store.get_active_by_id(expired).await.unwrap().is_none());
+        assert!(store.get_active_by_id(idle).await.unwrap().is_none());
+        assert!(store.get_active_by_id(live).await.unwrap().is_some());
+        let listed: Vec<Uuid> = store
+            .list_active_by_user(user_id)
+            .await
+            .unwrap()
+            .iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(listed, vec![live]);
+    }
+}
@@ -0,0 +1,214 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::repos::error::{RepoError, RepoResult};
+
+/// DB access for second factors (`user_totp_factors`, `user_recovery_codes`).
+///
+/// TOTP secrets are stored sealed and recovery codes as SHA-256 hashes; this repo
+/// never sees either in the clear.
+#[derive(Clone, Debug)]
+pub struct MfaRepo {
+    pool: PgPool,
+}
+
+impl MfaRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn find_totp(&self, user_id: Uuid) -> RepoResult<Option<TotpFactorRow>> {
+        let row = sqlx::query_as!(
+            TotpFactorRow,
+            r#"
+            SELECT
+                secret_ciphertext,
+                confirmed_at,
+                last_used_step
+            FROM user_totp_factors
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(row)
+    }
+
+    // Store a new pending factor, replacing an unconfirmed one (enrollment restarted).
+    //
+    // Returns false when the user already has a confirmed factor.
+    pub async fn upsert_pending_totp(
+        &self,
+        user_id: Uuid,
+        secret_ciphertext: &[u8],
+        now: DateTime<Utc>,
+    ) -> RepoResult<bool> {
+        let row = sqlx::query_scalar!(
+            r#"
+            INSERT INTO user_totp_factors (user_id, secret_ciphertext, created_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id) DO UPDATE
+            SET secret_ciphertext = EXCLUDED.secret_ciphertext,
+                created_at = EXCLUDED.created_at,
+                last_used_step = NULL
+            WHERE user_totp_factors.confirmed_at IS NULL
+            RETURNING user_id
+            "#,
+            user_id,
+            secret_ciphertext,
+            now
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(row.is_some())
+    }
+
+    // Confirm a pending factor with the step of the code the user entered, and replace
+    // the user's recovery codes, atomically.
+    //
+    // Returns false when there is no pending factor (or it was confirmed concurrently).
+    pub async fn confirm_totp(
+        &self,
+        user_id: Uuid,
+        step: i64,
+        recovery_code_hashes: &[Vec<u8>],
+        now: DateTime<Utc>,
+    ) -> RepoResult<bool> {
+        let mut tx = self.pool.begin().await.map_err(RepoError::Db)?;
+
+        let confirmed = sqlx::query!(
+            r#"
+            UPDATE user_totp_factors
+            SET confirmed_at = $3,
+                last_used_step = $2
+            WHERE user_id = $1
+              AND confirmed_at IS NULL
+            "#,
+            user_id,
+            step,
+            now
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(RepoError::Db)?
+        .rows_affected()
+            > 0;
+        if !confirmed {
+            return Ok(false);
+        }
+
+        sqlx::query!(
+            r#"
+            DELETE FROM user_recovery_codes
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(RepoError::Db)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO user_recovery_codes (user_id, code_hash, created_at)
+            SELECT $1, code_hash, $3
+            FROM unnest($2::bytea[]) AS code_hash
+            "#,
+            user_id,
+            recovery_code_hashes,
+            now
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(RepoError::Db)?;
+
+        tx.commit().await.map_err(RepoError::Db)?;
+        Ok(true)
+    }
+
+    // Record a confirmed factor's accepted step; each step is accepted at most once.
+    //
+    // Returns false when the step (or a later one) was already used.
+    pub async fn use_totp_step(&self, user_id: Uuid, step: i64) -> RepoResult<bool> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE user_totp_factors
+            SET last_used_step = $2
+            WHERE user_id = $1
+              AND confirmed_at IS NOT NULL
+              AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+            user_id,
+            step
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    // Mark an unused recovery code used. Returns false when there is no such code.
+    pub async fn use_recovery_code(
+        &self,
+        user_id: Uuid,
+        code_hash: &[u8],
+        now: DateTime<Utc>,
+    ) -> RepoResult<bool> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE user_recovery_codes
+            SET used_at = $3
+            WHERE user_id = $1
+              AND code_hash = $2
+              AND used_at IS NULL
+            "#,
+            user_id,
+            code_hash,
+            now
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    // Remove the factor (pending or confirmed) and the recovery codes.
+    //
+    // Returns false when the user had no factor.
+    pub async fn delete_totp(&self, user_id: Uuid) -> RepoResult<bool> {
+        let deleted = sqlx::query_scalar!(
+            r#"
+            WITH f AS (
+                DELETE FROM user_totp_factors
+                WHERE user_id = $1
+                RETURNING user_id
+            ), c AS (
+                DELETE FROM user_recovery_codes
+                WHERE user_id = $1
+            )
+            SELECT COUNT(*) AS "count!" FROM f
+            "#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(deleted > 0)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TotpFactorRow {
+    pub secret_ciphertext: Vec<u8>,
+    pub confirmed_at: Option<DateTime<Utc>>,
+    pub last_used_step: Option<i64>,
+}
@@ -1,3 +1,13 @@
+pub mod audit_event_repo;
 pub mod auth_session_repo;
+pub mod authorization_code_repo;
+pub mod dpop_replay_repo;
 pub mod error;
+pub mod maintenance_repo;
+pub mod memory_store;
+pub mod mfa_repo;
+pub mod oauth_client_repo;
 pub mod refresh_token_repo;
+pub mod role_repo;
+pub mod signing_key_repo;
+pub mod user_repo;
@@ -0,0 +1,60 @@
+use sqlx::PgPool;
+
+use crate::repos::error::{RepoError, RepoResult};
+
+/// DB access for registered OAuth clients (`oauth_clients`).
+#[derive(Clone, Debug)]
+pub struct OAuthClientRepo {
+    pool: PgPool,
+}
+
+impl OAuthClientRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn find(&self, client_id: &str) -> RepoResult<Option<OAuthClientRow>> {
+        let row = sqlx::query_as!(
+            OAuthClientRow,
+            r#"
+            SELECT
+                client_id,
+                client_name,
+                redirect_uris,
+                client_secret_hash,
+                jwk,
+                grant_types,
+                scopes,
+                access_token_ttl_seconds,
+                audiences,
+                session_idle_timeout_seconds,
+                session_max_lifetime_seconds,
+                max_sessions_per_user
+            FROM oauth_clients
+            WHERE client_id = $1
+            "#,
+            client_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(row)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OAuthClientRow {
+    pub client_id: String,
+    pub client_name: String,
+    pub redirect_uris: Vec<String>,
+    pub client_secret_hash: Option<Vec<u8>>,
+    pub jwk: Option<String>,
+    pub grant_types: Vec<String>,
+    pub scopes: Vec<String>,
+    pub access_token_ttl_seconds: Option<i32>,
+    pub audiences: Vec<String>,
+    pub session_idle_timeout_seconds: Option<i32>,
+    pub session_max_lifetime_seconds: Option<i32>,
+    pub max_sessions_per_user: Option<i32>,
+}
@@ -1,9 +1,12 @@
 use chrono::{DateTime, Utc};
 use sqlx::PgPool;
+use std::{future::Future, pin::Pin};
 use uuid::Uuid;
 
 use crate::repos::error::RepoError;
 
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
 /// DB access for refresh token persistence.
 ///
 /// Notes:
@@ -27,61 +30,6 @@ impl RefreshTokenRepo {
         Self { pool }
     }
 
-    /// Insert a newly issued refresh token.
-    pub async fn insert(
-        &self,
-        session_id: Uuid,
-        token_hash: Vec<u8>,
-        expires_at: DateTime<Utc>,
-    ) -> Result<Uuid, RepoError> {
-        let id = sqlx::query_scalar::<_, Uuid>(
-            r#"
-            INSERT INTO refresh_tokens (session_id, token_hash, expires_at)
-            VALUES ($1, $2, $3)
-            RETURNING id
-            "#,
-        )
-        .bind(session_id)
-        .bind(token_hash)
-        .bind(expires_at)
-        .fetch_one(&self.pool)
-        .await?;
-
-        Ok(id)
-    }
-
-    /// Fetch a refresh token row by hash, only if it is not revoked and not expired.
-    pub async fn find_active_by_hash(
-        &self,
-        token_hash: Vec<u8>,
-        now: DateTime<Utc>,
-    ) -> Result<Option<RefreshTokenRow>, RepoError> {
-        let row = sqlx::query_as::<_, RefreshTokenRow>(
-            r#"
-            SELECT
-                id,
-                session_id,
-                token_hash,
-                issued_at,
-                expires_at,
-                used_at,
-                revoked_at,
-                replaced_by
-            FROM refresh_tokens
-            WHERE token_hash = $1
-                AND revoked_at IS NULL
-                AND expires_at > $2
-            LIMIT 1
-            "#,
-        )
-        .bind(token_hash)
-        .bind(now)
-        .fetch_optional(&self.pool)
-        .await?;
-
-        Ok(row)
-    }
-
     /// Revoke a refresh token.
     ///
     /// For rotation step later, replaced_by can be filled.
@@ -110,6 +58,240 @@ impl RefreshTokenRepo {
     }
 }
 
+/// Refresh token storage behind `RefreshTokenService`.
+///
+/// `RefreshTokenRepo` (Postgres) and `MemoryAuthStore` implement it with the same
+/// semantics. At most one token per session is current (not revoked and not replaced);
+/// inserting a second one is an error.
+pub trait RefreshTokenStore: Send + Sync + std::fmt::Debug {
+    /// Insert a newly issued refresh token.
+    fn insert(
+        &self,
+        session_id: Uuid,
+        token_hash: Vec<u8>,
+        expires_at: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<Uuid, RepoError>>;
+
+    /// Fetch a refresh token row by hash, only if it is not revoked and not expired.
+    fn find_active_by_hash(
+        &self,
+        token_hash: Vec<u8>,
+        now: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<Option<RefreshTokenRow>, RepoError>>;
+
+    /// Fetch a refresh token row by hash regardless of its state (used/revoked/expired).
+    ///
+    /// Rotation needs to see used tokens to detect reuse.
+    fn find_by_hash(
+        &self,
+        token_hash: Vec<u8>,
+    ) -> BoxFuture<'_, Result<Option<RefreshTokenRow>, RepoError>>;
+
+    /// Rotate: mark `id` used, link it to a new successor and insert the successor.
+    ///
+    /// Returns `Ok(None)` when `id` is no longer current (already used, replaced or
+    /// revoked), e.g. a concurrent refresh won the race. Both writes commit together;
+    /// the `replaced_by` foreign key is deferred until commit.
+    fn rotate(
+        &self,
+        id: Uuid,
+        new_token_hash: Vec<u8>,
+        expires_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<Option<Uuid>, RepoError>>;
+
+    /// Revoke a session and every refresh token issued for it (the token family).
+    ///
+    /// Used when a rotated token is presented again. Returns the number of tokens revoked.
+    fn revoke_family(
+        &self,
+        session_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<u64, RepoError>>;
+}
+
+impl RefreshTokenStore for RefreshTokenRepo {
+    fn insert(
+        &self,
+        session_id: Uuid,
+        token_hash: Vec<u8>,
+        expires_at: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<Uuid, RepoError>> {
+        Box::pin(async move {
+            let id = sqlx::query_scalar::<_, Uuid>(
+                r#"
+                INSERT INTO refresh_tokens (session_id, token_hash, expires_at)
+                VALUES ($1, $2, $3)
+                RETURNING id
+                "#,
+            )
+            .bind(session_id)
+            .bind(token_hash)
+            .bind(expires_at)
+            .fetch_one(&self.pool)
+            .await?;
+
+            Ok(id)
+        })
+    }
+
+    fn find_active_by_hash(
+        &self,
+        token_hash: Vec<u8>,
+        now: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<Option<RefreshTokenRow>, RepoError>> {
+        Box::pin(async move {
+            let row = sqlx::query_as::<_, RefreshTokenRow>(
+                r#"
+                SELECT
+                    id,
+                    session_id,
+                    token_hash,
+                    issued_at,
+                    expires_at,
+                    used_at,
+                    revoked_at,
+                    replaced_by
+                FROM refresh_tokens
+                WHERE token_hash = $1
+                    AND revoked_at IS NULL
+                    AND expires_at > $2
+                LIMIT 1
+                "#,
+            )
+            .bind(token_hash)
+            .bind(now)
+            .fetch_optional(&self.pool)
+            .await?;
+
+            Ok(row)
+        })
+    }
+
+    fn find_by_hash(
+        &self,
+        token_hash: Vec<u8>,
+    ) -> BoxFuture<'_, Result<Option<RefreshTokenRow>, RepoError>> {
+        Box::pin(async move {
+            let row = sqlx::query_as::<_, RefreshTokenRow>(
+                r#"
+                SELECT
+                    id,
+                    session_id,
+                    token_hash,
+                    issued_at,
+                    expires_at,
+                    used_at,
+                    revoked_at,
+                    replaced_by
+                FROM refresh_tokens
+                WHERE token_hash = $1
+                LIMIT 1
+                "#,
+            )
+            .bind(token_hash)
+            .fetch_optional(&self.pool)
+            .await?;
+
+            Ok(row)
+        })
+    }
+
+    fn rotate(
+        &self,
+        id: Uuid,
+        new_token_hash: Vec<u8>,
+        expires_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<Option<Uuid>, RepoError>> {
+        Box::pin(async move {
+            let mut tx = self.pool.begin().await?;
+            let new_id = Uuid::new_v4();
+
+            let session_id = sqlx::query_scalar::<_, Uuid>(
+                r#"
+                UPDATE refresh_tokens
+                SET used_at = $2,
+                    replaced_by = $3
+                WHERE id = $1
+                    AND used_at IS NULL
+                    AND replaced_by IS NULL
+                    AND revoked_at IS NULL
+                    AND expires_at > $2
+                RETURNING session_id
+                "#,
+            )
+            .bind(id)
+            .bind(now)
+            .bind(new_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            let Some(session_id) = session_id else {
+                tx.rollback().await?;
+                return Ok(None);
+            };
+
+            sqlx::query(
+                r#"
+                INSERT INTO refresh_tokens (id, session_id, token_hash, issued_at, expires_at)
+                VALUES ($1, $2, $3, $4, $5)
+                "#,
+            )
+            .bind(new_id)
+            .bind(session_id)
+            .bind(new_token_hash)
+            .bind(now)
+            .bind(expires_at)
+            .execute(&mut *tx)
+            .await?;
+
+            tx.commit().await?;
+
+            Ok(Some(new_id))
+        })
+    }
+
+    fn revoke_family(
+        &self,
+        session_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> BoxFuture<'_, Result<u64, RepoError>> {
+        Box::pin(async move {
+            let mut tx = self.pool.begin().await?;
+
+            sqlx::query(
+                r#"
+                UPDATE auth_sessions
+                SET revoked_at = $2
+                WHERE id = $1 AND revoked_at IS NULL
+                "#,
+            )
+            .bind(session_id)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+
+            let done = sqlx::query(
+                r#"
+                UPDATE refresh_tokens
+                SET revoked_at = $2
+                WHERE session_id = $1
+                    AND revoked_at IS NULL
+                "#,
+            )
+            .bind(session_id)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+
+            tx.commit().await?;
+
+            Ok(done.rows_affected())
+        })
+    }
+}
+
 #[derive(Debug, Clone, sqlx::FromRow)]
 pub struct RefreshTokenRow {
     pub id: Uuid,
@@ -0,0 +1,52 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::repos::error::{RepoError, RepoResult};
+
+/// DB access for role assignments (`user_roles`) and what they grant (`role_scopes`).
+#[derive(Clone, Debug)]
+pub struct RoleRepo {
+    pool: PgPool,
+}
+
+impl RoleRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn roles_for_user(&self, user_id: Uuid) -> RepoResult<Vec<String>> {
+        let roles = sqlx::query_scalar!(
+            r#"
+            SELECT role
+            FROM user_roles
+            WHERE user_id = $1
+            ORDER BY role
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(roles)
+    }
+
+    // Union of the scopes granted by the user's roles.
+    pub async fn scopes_for_user(&self, user_id: Uuid) -> RepoResult<Vec<String>> {
+        let scopes = sqlx::query_scalar!(
+            r#"
+            SELECT DISTINCT rs.scope
+            FROM user_roles ur
+            JOIN role_scopes rs ON rs.role = ur.role
+            WHERE ur.user_id = $1
+            ORDER BY rs.scope
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(scopes)
+    }
+}
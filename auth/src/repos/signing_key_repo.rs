@@ -0,0 +1,172 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::repos::error::{RepoError, RepoResult};
+
+/// DB access for access-token signing keys (`signing_keys`).
+///
+/// State transitions are single statements or short transactions guarded by the
+/// partial unique indexes, so several auth server instances can run the rotation
+/// schedule concurrently without coordinating.
+#[derive(Clone, Debug)]
+pub struct SigningKeyRepo {
+    pool: PgPool,
+}
+
+impl SigningKeyRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // Pending, active and retiring keys (everything that isn't retired), oldest first.
+    pub async fn list_live(&self) -> RepoResult<Vec<SigningKeyRow>> {
+        let rows = sqlx::query_as!(
+            SigningKeyRow,
+            r#"
+            SELECT
+                kid,
+                public_x,
+                private_key_enc,
+                state,
+                activates_at,
+                activated_at
+            FROM signing_keys
+            WHERE state IN ('pending', 'active', 'retiring')
+            ORDER BY activates_at
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(rows)
+    }
+
+    // Insert a new key as `pending` or `active`.
+    //
+    // Returns false when another key already holds that state (another instance won).
+    pub async fn insert(
+        &self,
+        kid: &str,
+        public_x: &str,
+        private_key_enc: &[u8],
+        state: &str,
+        activates_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> RepoResult<bool> {
+        let activated_at = (state == "active").then_some(now);
+        let row = sqlx::query_scalar!(
+            r#"
+            INSERT INTO signing_keys (kid, public_x, private_key_enc, state, created_at, activates_at, activated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT DO NOTHING
+            RETURNING kid
+            "#,
+            kid,
+            public_x,
+            private_key_enc,
+            state,
+            now,
+            activates_at,
+            activated_at
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(row.is_some())
+    }
+
+    // Promote the pending key once it is due: the current active key becomes retiring.
+    //
+    // Returns the newly active kid, or None when nothing was due.
+    pub async fn activate_due(&self, now: DateTime<Utc>) -> RepoResult<Option<String>> {
+        let mut tx = self.pool.begin().await.map_err(RepoError::Db)?;
+
+        // Concurrent instances skip the row instead of promoting it twice.
+        let kid = sqlx::query_scalar!(
+            r#"
+            SELECT kid
+            FROM signing_keys
+            WHERE state = 'pending'
+              AND activates_at <= $1
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+            "#,
+            now
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(RepoError::Db)?;
+
+        let Some(kid) = kid else {
+            return Ok(None);
+        };
+
+        sqlx::query!(
+            r#"
+            UPDATE signing_keys
+            SET state = 'retiring',
+                retiring_at = $1
+            WHERE state = 'active'
+            "#,
+            now
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(RepoError::Db)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE signing_keys
+            SET state = 'active',
+                activated_at = $2
+            WHERE kid = $1
+            "#,
+            kid,
+            now
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(RepoError::Db)?;
+
+        tx.commit().await.map_err(RepoError::Db)?;
+        Ok(Some(kid))
+    }
+
+    // Retire keys that have been retiring since `cutoff` or earlier and erase their
+    // private key material. Returns how many keys were retired.
+    pub async fn retire_before(
+        &self,
+        cutoff: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> RepoResult<u64> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE signing_keys
+            SET state = 'retired',
+                retired_at = $2,
+                private_key_enc = NULL
+            WHERE state = 'retiring'
+              AND retiring_at <= $1
+            "#,
+            cutoff,
+            now
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(res.rows_affected())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SigningKeyRow {
+    pub kid: String,
+    pub public_x: String,
+    pub private_key_enc: Option<Vec<u8>>,
+    pub state: String,
+    pub activates_at: DateTime<Utc>,
+    pub activated_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,93 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::repos::error::{RepoError, RepoResult};
+
+/// DB access for user credentials (`users` + `user_credentials`).
+#[derive(Clone, Debug)]
+pub struct UserRepo {
+    pool: PgPool,
+}
+
+impl UserRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // Look up the password hash by login name. None when the user doesn't exist or
+    // has no password set.
+    pub async fn find_credential_by_username(
+        &self,
+        username: &str,
+    ) -> RepoResult<Option<UserCredentialRow>> {
+        let row = sqlx::query_as!(
+            UserCredentialRow,
+            r#"
+            SELECT
+                u."userId" AS "user_id!",
+                c.password_hash
+            FROM users u
+            JOIN user_credentials c ON c.user_id = u."userId"
+            WHERE u."userName" = $1
+            "#,
+            username
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(row)
+    }
+
+    // Replace the hash only if it is still `current_hash` (a concurrent password change wins).
+    pub async fn update_password_hash(
+        &self,
+        user_id: Uuid,
+        current_hash: &str,
+        new_hash: &str,
+        now: DateTime<Utc>,
+    ) -> RepoResult<bool> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE user_credentials
+            SET password_hash = $3,
+                updated_at = $4
+            WHERE user_id = $1
+              AND password_hash = $2
+            "#,
+            user_id,
+            current_hash,
+            new_hash,
+            now
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    // Login name of a user (e.g. the account label of an authenticator app entry).
+    pub async fn find_user_name(&self, user_id: Uuid) -> RepoResult<Option<String>> {
+        let name = sqlx::query_scalar!(
+            r#"
+            SELECT "userName" AS "user_name!"
+            FROM users
+            WHERE "userId" = $1
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(RepoError::Db)?;
+
+        Ok(name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UserCredentialRow {
+    pub user_id: Uuid,
+    pub password_hash: String,
+}
@@ -1,18 +1,88 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
 use uuid::Uuid;
 
 use crate::error::AppError;
+use crate::services::auth::authn_context::AuthnContext;
 use crate::services::auth::jwt::JwtIssuer;
 
 #[derive(Debug, Serialize)]
 struct AccessTokenClaims {
     iss: String,
     aud: String,
+    // User id (UUID) for user tokens, client_id for client_credentials tokens.
     sub: String,
+    iat: i64,
+    nbf: i64,
     exp: i64,
     jti: String,
+    // OAuth client the token was issued to (RFC 9068 Section 2.2).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_id: Option<String>,
+    // Auth session this token belongs to (used by the session API to find "current").
+    // Client tokens have no session.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+    // User's roles at issue time (re-read on every refresh).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    roles: Vec<String>,
+    // How the user signed in to the session (RFC 9068 Section 2.2.1); absent for
+    // client tokens and sessions from before it was recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    acr: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    amr: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     cnf: Option<CnfClaim>,
+    // Delegation (token exchange): who is acting for `sub`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    act: Option<ActorClaim>,
+}
+
+/// `act` claim (RFC 8693 Section 4.1): the current actor, with prior actors nested.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActorClaim {
+    pub sub: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Box<ActorClaim>>,
+}
+
+/// What goes into a user's (session-bound) access token.
+#[derive(Debug, Clone)]
+pub struct UserAccessToken<'a> {
+    /// Must be a UUID string.
+    pub sub: &'a str,
+    /// Becomes the `sid` claim.
+    pub session_id: Uuid,
+    /// `cnf.jkt`; optional for now (DPoP binding later/optional)
+    pub jkt: Option<String>,
+    /// Client the session was created through, if any.
+    pub client_id: Option<String>,
+    pub scope: Option<String>,
+    pub roles: Vec<String>,
+    /// `acr` / `amr` of the session's sign-in.
+    pub authn: Option<AuthnContext>,
+}
+
+/// What goes into an access token minted by token exchange.
+#[derive(Debug, Clone)]
+pub struct DelegatedAccessToken<'a> {
+    pub sub: Uuid,
+    pub session_id: Uuid,
+    /// Target service (`aud`).
+    pub audience: &'a str,
+    /// The client that performed the exchange.
+    pub client_id: &'a str,
+    pub scope: Option<String>,
+    pub roles: Vec<String>,
+    /// The subject's sign-in, carried over from its session.
+    pub authn: Option<AuthnContext>,
+    pub jkt: String,
+    pub act: ActorClaim,
+    /// Never outlive the subject token.
+    pub not_after: i64,
 }
 
 #[derive(Debug, Serialize)]
@@ -23,43 +93,207 @@ struct CnfClaim {
 #[derive(Clone)]
 pub struct AccessTokenService {
     jwt: JwtIssuer,
+    // Upper bound for per-client lifetimes.
+    max_ttl_seconds: u64,
 }
 
 impl AccessTokenService {
     pub fn new(jwt: JwtIssuer) -> Self {
-        Self { jwt }
+        Self {
+            jwt,
+            max_ttl_seconds: u64::MAX,
+        }
     }
 
-    /// Issue an access token.
-    ///
-    /// - `sub` must be UUID string.
-    /// - `jkt` is optional for now (DPoP binding later/optional)
-    pub async fn issue_access_token(
-        &self,
-        sub: &str,
-        jkt: Option<String>,
-    ) -> Result<String, AppError> {
+    // A signing key is only published for the rotation overlap after it is retired, so
+    // no token may live longer than that.
+    pub fn with_max_ttl_seconds(mut self, seconds: u64) -> Self {
+        self.max_ttl_seconds = seconds;
+        self
+    }
+
+    /// Issue an access token for a user session.
+    pub async fn issue_access_token(&self, token: UserAccessToken<'_>) -> Result<String, AppError> {
         // Validate `sub` is a UUID (fail closed).
-        let sub_uuid = Uuid::parse_str(sub)
+        let sub_uuid = Uuid::parse_str(token.sub)
             .map_err(|_| AppError::InvalidRequest("sub must be a UUID string".to_string()))?;
 
         let now = chrono::Utc::now().timestamp();
         let exp = now + self.jwt.ttl_seconds() as i64;
+        let (acr, amr) = split_authn(token.authn);
 
         let claims = AccessTokenClaims {
             iss: self.jwt.issuer().to_string(),
             aud: self.jwt.audience().to_string(),
             sub: sub_uuid.to_string(),
+            iat: now,
+            nbf: now,
             exp,
             jti: Uuid::new_v4().to_string(),
-            cnf: jkt.map(|jkt| CnfClaim { jkt }),
+            client_id: token.client_id,
+            sid: Some(token.session_id.to_string()),
+            scope: token.scope,
+            roles: token.roles,
+            acr,
+            amr,
+            cnf: token.jkt.map(|jkt| CnfClaim { jkt }),
+            act: None,
         };
 
         let access_token = self.jwt.sign(&claims)?;
         Ok(access_token)
     }
 
+    /// Issue an access token for a client acting on its own behalf (client_credentials).
+    ///
+    /// - `sub` and `client_id` are both the client id; there is no `sid`.
+    /// - `ttl_seconds` overrides the default lifetime (per-client setting, see
+    ///   `client_access_token_ttl_seconds`).
+    pub async fn issue_client_access_token(
+        &self,
+        client_id: &str,
+        scope: Option<String>,
+        jkt: String,
+        ttl_seconds: Option<u64>,
+    ) -> Result<String, AppError> {
+        let now = chrono::Utc::now().timestamp();
+        let exp = now + self.client_access_token_ttl_seconds(client_id, ttl_seconds) as i64;
+
+        let claims = AccessTokenClaims {
+            iss: self.jwt.issuer().to_string(),
+            aud: self.jwt.audience().to_string(),
+            sub: client_id.to_string(),
+            iat: now,
+            nbf: now,
+            exp,
+            jti: Uuid::new_v4().to_string(),
+            client_id: Some(client_id.to_string()),
+            sid: None,
+            scope,
+            roles: Vec::new(),
+            acr: None,
+            amr: Vec::new(),
+            cnf: Some(CnfClaim { jkt }),
+            act: None,
+        };
+
+        self.jwt.sign(&claims)
+    }
+
+    /// Issue an access token for another service, on behalf of a user (token exchange).
+    ///
+    /// Returns the token and its lifetime in seconds.
+    pub async fn issue_delegated_access_token(
+        &self,
+        token: DelegatedAccessToken<'_>,
+    ) -> Result<(String, u64), AppError> {
+        let now = chrono::Utc::now().timestamp();
+        let exp = (now + self.jwt.ttl_seconds() as i64).min(token.not_after);
+        let (acr, amr) = split_authn(token.authn);
+
+        let claims = AccessTokenClaims {
+            iss: self.jwt.issuer().to_string(),
+            aud: token.audience.to_string(),
+            sub: token.sub.to_string(),
+            iat: now,
+            nbf: now,
+            exp,
+            jti: Uuid::new_v4().to_string(),
+            client_id: Some(token.client_id.to_string()),
+            sid: Some(token.session_id.to_string()),
+            scope: token.scope,
+            roles: token.roles,
+            acr,
+            amr,
+            cnf: Some(CnfClaim { jkt: token.jkt }),
+            act: Some(token.act),
+        };
+
+        let access_token = self.jwt.sign(&claims)?;
+        Ok((access_token, (exp - now).max(0) as u64))
+    }
+
     pub fn access_token_ttl_seconds(&self) -> u64 {
         self.jwt.ttl_seconds()
     }
+
+    /// Lifetime of a client's tokens: its own setting, capped to the key overlap.
+    pub fn client_access_token_ttl_seconds(
+        &self,
+        client_id: &str,
+        ttl_seconds: Option<u64>,
+    ) -> u64 {
+        let ttl = ttl_seconds.unwrap_or(self.jwt.ttl_seconds());
+        if ttl > self.max_ttl_seconds {
+            warn!(client_id = %client_id, ttl, max = self.max_ttl_seconds, "client access_token_ttl_seconds exceeds SIGNING_KEY_OVERLAP_SECONDS; capping");
+            return self.max_ttl_seconds;
+        }
+        ttl
+    }
+}
+
+fn split_authn(authn: Option<AuthnContext>) -> (Option<String>, Vec<String>) {
+    authn.map_or((None, Vec::new()), |a| (Some(a.acr), a.amr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::auth::test_support;
+
+    #[tokio::test]
+    async fn delegated_tokens_never_outlive_the_subject_token() {
+        let tokens = AccessTokenService::new(test_support::jwt_issuer(test_support::key_ring()));
+
+        let not_after = chrono::Utc::now().timestamp() + 60;
+        let (token, expires_in) = tokens
+            .issue_delegated_access_token(DelegatedAccessToken {
+                sub: Uuid::new_v4(),
+                session_id: Uuid::new_v4(),
+                audience: "orders",
+                client_id: "bff",
+                scope: Some("read".into()),
+                roles: Vec::new(),
+                authn: None,
+                jkt: "jkt".into(),
+                act: ActorClaim {
+                    sub: "bff".into(),
+                    act: Some(Box::new(ActorClaim {
+                        sub: "gateway".into(),
+                        act: None,
+                    })),
+                },
+                not_after,
+            })
+            .await
+            .unwrap();
+
+        let claims = jsonwebtoken::dangerous::insecure_decode::<serde_json::Value>(&token)
+            .unwrap()
+            .claims;
+        assert_eq!(claims["exp"], not_after);
+        assert!(expires_in <= 60);
+        assert_eq!(claims["aud"], "orders");
+        assert_eq!(claims["client_id"], "bff");
+        assert_eq!(
+            claims["act"],
+            serde_json::json!({ "sub": "bff", "act": { "sub": "gateway" } })
+        );
+    }
+
+    #[test]
+    fn client_lifetimes_are_capped_to_the_key_overlap() {
+        let tokens = AccessTokenService::new(test_support::jwt_issuer(test_support::key_ring()))
+            .with_max_ttl_seconds(3_600);
+
+        assert_eq!(tokens.client_access_token_ttl_seconds("batch", None), 300);
+        assert_eq!(
+            tokens.client_access_token_ttl_seconds("batch", Some(1_800)),
+            1_800
+        );
+        assert_eq!(
+            tokens.client_access_token_ttl_seconds("batch", Some(86_400)),
+            3_600
+        );
+    }
 }
@@ -0,0 +1,269 @@
+use chrono::Utc;
+use jsonwebtoken::{Algorithm, Validation};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::{debug, error, warn};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::repos::auth_session_repo::AuthSessionStore;
+use crate::services::auth::access_token_issuer::ActorClaim;
+use crate::services::auth::audit::{AuditEvent, AuditEventType, AuditLog, RequestMeta};
+use crate::services::auth::dpop::{error::DpopError, verifier::DpopVerifier};
+use crate::services::auth::signing_keys::key_ring::SigningKeyRing;
+
+#[derive(Debug, Deserialize)]
+struct AccessTokenClaims {
+    sub: String,
+    exp: i64,
+    #[serde(default)]
+    iat: Option<i64>,
+    #[serde(default)]
+    jti: Option<String>,
+    #[serde(default)]
+    sid: Option<String>,
+    #[serde(default)]
+    scope: Option<String>,
+    #[serde(default)]
+    client_id: Option<String>,
+    #[serde(default)]
+    cnf: Option<CnfClaim>,
+    #[serde(default)]
+    act: Option<ActorClaim>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CnfClaim {
+    jkt: String,
+}
+
+/// Caller identity for auth-server endpoints that take an access token (e.g. the session API).
+#[derive(Clone, Debug)]
+pub struct AuthenticatedSession {
+    pub user_id: Uuid,
+    pub session_id: Uuid,
+    // Thumbprint of the session's DPoP key.
+    pub jkt: String,
+    pub scope: Option<String>,
+    // How the session signed in (`aal1`, `aal2`); none for sessions predating MFA.
+    pub acr: Option<String>,
+}
+
+/// Claims of a valid access token that tie it to a session and a DPoP key.
+#[derive(Clone, Debug)]
+pub struct BoundAccessToken {
+    pub user_id: Uuid,
+    pub session_id: Uuid,
+    pub jkt: String,
+    pub exp: i64,
+    pub scope: Option<String>,
+    pub act: Option<ActorClaim>,
+}
+
+/// Claims of any valid access token, including client (client_credentials) tokens.
+#[derive(Clone, Debug)]
+pub struct VerifiedAccessToken {
+    pub sub: String,
+    pub client_id: Option<String>,
+    // None for client tokens (and user tokens minted before `sid` existed).
+    pub session_id: Option<Uuid>,
+    pub jkt: Option<String>,
+    pub exp: i64,
+    // Older tokens may lack these.
+    pub iat: Option<i64>,
+    pub jti: Option<String>,
+    pub scope: Option<String>,
+}
+
+/// Verifies access tokens issued by this server when they are presented back to it.
+///
+/// Only DPoP-bound tokens are accepted: the proof must match `cnf.jkt` and carry `ath`,
+/// and the token's session (`sid`) must still be active, so revocation takes effect
+/// immediately rather than at token expiry.
+#[derive(Clone)]
+pub struct AccessTokenVerifier {
+    keys: Arc<SigningKeyRing>,
+    validation: Validation,
+    dpop_verifier: Arc<DpopVerifier>,
+    sessions: Arc<dyn AuthSessionStore>,
+    audit: AuditLog,
+}
+
+impl AccessTokenVerifier {
+    /// Verification keys (by `kid`) come from the same ring the issuer signs with.
+    pub fn new(
+        keys: Arc<SigningKeyRing>,
+        issuer: &str,
+        audience: &str,
+        dpop_verifier: Arc<DpopVerifier>,
+        sessions: Arc<dyn AuthSessionStore>,
+    ) -> Self {
+        let mut validation = Validation::new(Algorithm::EdDSA);
+        validation.set_issuer(&[issuer]);
+        validation.set_audience(&[audience]);
+
+        Self {
+            keys,
+            validation,
+            dpop_verifier,
+            sessions,
+            audit: AuditLog::disabled(),
+        }
+    }
+
+    // Record rejected DPoP proofs.
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// Verify signature/iss/aud/exp and extract the session binding.
+    ///
+    /// This does NOT check the DPoP proof or the session state; see `authenticate`.
+    pub fn decode(&self, access_token: &str) -> Result<BoundAccessToken, AppError> {
+        let claims = self.claims(access_token)?;
+
+        let user_id = Uuid::parse_str(&claims.sub).map_err(|_| AppError::Unauthorized)?;
+        // Tokens minted before `sid` existed cannot be tied to a session.
+        let session_id = claims
+            .sid
+            .as_deref()
+            .and_then(|s| Uuid::parse_str(s).ok())
+            .ok_or(AppError::Unauthorized)?;
+        let jkt = claims.cnf.map(|c| c.jkt).ok_or(AppError::Unauthorized)?;
+
+        Ok(BoundAccessToken {
+            user_id,
+            session_id,
+            jkt,
+            exp: claims.exp,
+            scope: claims.scope,
+            act: claims.act,
+        })
+    }
+
+    /// Verify signature/iss/aud/exp of a user or client token; no session binding.
+    pub fn verify(&self, access_token: &str) -> Result<VerifiedAccessToken, AppError> {
+        let claims = self.claims(access_token)?;
+        let session_id = claims
+            .sid
+            .as_deref()
+            .map(Uuid::parse_str)
+            .transpose()
+            .map_err(|_| AppError::Unauthorized)?;
+
+        Ok(VerifiedAccessToken {
+            sub: claims.sub,
+            client_id: claims.client_id,
+            session_id,
+            jkt: claims.cnf.map(|c| c.jkt),
+            exp: claims.exp,
+            iat: claims.iat,
+            jti: claims.jti,
+            scope: claims.scope,
+        })
+    }
+
+    fn claims(&self, access_token: &str) -> Result<AccessTokenClaims, AppError> {
+        let header = jsonwebtoken::decode_header(access_token).map_err(|e| {
+            debug!(error = %e, "access token header is malformed");
+            AppError::Unauthorized
+        })?;
+        let decoding_key = self
+            .keys
+            .decoding_key(header.kid.as_deref())
+            .ok_or_else(|| {
+                debug!(kid = ?header.kid, "access token signed with an unknown or retired key");
+                AppError::Unauthorized
+            })?;
+
+        let claims = jsonwebtoken::decode::<AccessTokenClaims>(
+            access_token,
+            &decoding_key,
+            &self.validation,
+        )
+        .map_err(|e| {
+            debug!(error = %e, "access token verification failed");
+            AppError::Unauthorized
+        })?
+        .claims;
+
+        Ok(claims)
+    }
+
+    /// Authenticate a request carrying `access_token` and its DPoP proof.
+    ///
+    /// - `method`/`url`: the incoming request (checked against the proof's `htm`/`htu`)
+    /// - `request`: client details recorded when the proof is rejected
+    pub async fn authenticate(
+        &self,
+        access_token: &str,
+        dpop_proof: &str,
+        method: &str,
+        url: &str,
+        request: &RequestMeta,
+    ) -> Result<AuthenticatedSession, AppError> {
+        let BoundAccessToken {
+            user_id,
+            session_id,
+            jkt,
+            scope,
+            ..
+        } = self.decode(access_token)?;
+
+        let proof = self
+            .dpop_verifier
+            .verify_proof(
+                dpop_proof,
+                method,
+                url,
+                Some(access_token),
+                Some(jkt.as_str()),
+                Utc::now(),
+            )
+            .await;
+        if let Err(e) = proof {
+            if !matches!(e, DpopError::UseNonce(_)) {
+                warn!(session_id = %session_id, error = ?e, "DPoP proof verification failed (access)");
+            }
+            if !matches!(
+                e,
+                DpopError::UseNonce(_) | DpopError::ReplayCheckUnavailable
+            ) {
+                self.audit
+                    .record(
+                        AuditEvent::failure(AuditEventType::DpopProofRejected, e.to_string())
+                            .with_user(user_id)
+                            .with_session(session_id)
+                            .with_jkt(Some(&jkt))
+                            .with_request(request),
+                    )
+                    .await;
+            }
+            return Err(AppError::from(e));
+        }
+
+        let session = self
+            .sessions
+            .get_active_by_id(session_id)
+            .await
+            .map_err(|e| {
+                error!(session_id = %session_id, error = %e, "Failed to load auth session");
+                AppError::Internal
+            })?
+            .ok_or(AppError::Unauthorized)?;
+
+        if session.user_id != user_id || session.dpop_jkt.as_deref() != Some(jkt.as_str()) {
+            warn!(session_id = %session_id, "access token does not match its session");
+            return Err(AppError::Unauthorized);
+        }
+
+        Ok(AuthenticatedSession {
+            user_id,
+            session_id,
+            jkt,
+            scope,
+            acr: session.acr,
+        })
+    }
+}
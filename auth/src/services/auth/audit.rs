@@ -0,0 +1,270 @@
+use tracing::error;
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::repos::audit_event_repo::{
+    AuditEventFilter, AuditEventRepo, AuditEventRow, NewAuditEvent,
+};
+
+/// What happened (`auth_audit_events.event_type`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditEventType {
+    /// Token pair for a user: authorization code, password or subject grant.
+    TokenIssued,
+    TokenRefreshed,
+    TokenExchanged,
+    /// client_credentials token.
+    ClientTokenIssued,
+    DpopProofRejected,
+    /// RFC 7009 revocation.
+    TokenRevoked,
+    Logout,
+    /// Revoked through the session API.
+    SessionRevoked,
+    RefreshTokenReuse,
+    /// Idle timeout, maximum lifetime or eviction over the per-user cap.
+    SessionEnded,
+    /// Too many failed grants or sign-ins; `/token` and `/authorize` refuse the client
+    /// IP, subject or key for a while.
+    TokenLockout,
+    /// A TOTP factor was confirmed (recovery codes issued).
+    MfaEnrolled,
+    /// The TOTP factor and recovery codes were removed.
+    MfaDisabled,
+    /// Sign-in with a recovery code instead of a TOTP code.
+    RecoveryCodeUsed,
+}
+
+impl AuditEventType {
+    pub const ALL: [Self; 14] = [
+        Self::TokenIssued,
+        Self::TokenRefreshed,
+        Self::TokenExchanged,
+        Self::ClientTokenIssued,
+        Self::DpopProofRejected,
+        Self::TokenRevoked,
+        Self::Logout,
+        Self::SessionRevoked,
+        Self::RefreshTokenReuse,
+        Self::SessionEnded,
+        Self::TokenLockout,
+        Self::MfaEnrolled,
+        Self::MfaDisabled,
+        Self::RecoveryCodeUsed,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::TokenIssued => "token_issued",
+            Self::TokenRefreshed => "token_refreshed",
+            Self::TokenExchanged => "token_exchanged",
+            Self::ClientTokenIssued => "client_token_issued",
+            Self::DpopProofRejected => "dpop_proof_rejected",
+            Self::TokenRevoked => "token_revoked",
+            Self::Logout => "logout",
+            Self::SessionRevoked => "session_revoked",
+            Self::RefreshTokenReuse => "refresh_token_reuse",
+            Self::SessionEnded => "session_ended",
+            Self::TokenLockout => "token_lockout",
+            Self::MfaEnrolled => "mfa_enrolled",
+            Self::MfaDisabled => "mfa_disabled",
+            Self::RecoveryCodeUsed => "recovery_code_used",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.as_str() == value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+impl AuditOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Failure => "failure",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "success" => Some(Self::Success),
+            "failure" => Some(Self::Failure),
+            _ => None,
+        }
+    }
+}
+
+/// The HTTP request behind an event.
+#[derive(Debug, Clone, Default)]
+pub struct RequestMeta {
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+}
+
+/// One audit record; build with `success` / `failure` and the `with_*` methods.
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    pub event_type: AuditEventType,
+    pub outcome: AuditOutcome,
+    pub reason: Option<String>,
+    pub user_id: Option<Uuid>,
+    pub session_id: Option<Uuid>,
+    pub client_id: Option<String>,
+    pub jkt: Option<String>,
+    pub request: RequestMeta,
+}
+
+impl AuditEvent {
+    pub fn success(event_type: AuditEventType) -> Self {
+        Self {
+            event_type,
+            outcome: AuditOutcome::Success,
+            reason: None,
+            user_id: None,
+            session_id: None,
+            client_id: None,
+            jkt: None,
+            request: RequestMeta::default(),
+        }
+    }
+
+    pub fn failure(event_type: AuditEventType, reason: impl Into<String>) -> Self {
+        Self {
+            outcome: AuditOutcome::Failure,
+            reason: Some(reason.into()),
+            ..Self::success(event_type)
+        }
+    }
+
+    // Why the server acted on its own (e.g. which session policy ended a session).
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    pub fn with_user(mut self, user_id: Uuid) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+
+    pub fn with_session(mut self, session_id: Uuid) -> Self {
+        self.session_id = Some(session_id);
+        self
+    }
+
+    pub fn with_client(mut self, client_id: Option<&str>) -> Self {
+        self.client_id = client_id.map(str::to_string);
+        self
+    }
+
+    pub fn with_jkt(mut self, jkt: Option<&str>) -> Self {
+        self.jkt = jkt.map(str::to_string);
+        self
+    }
+
+    pub fn with_request(mut self, request: &RequestMeta) -> Self {
+        self.request = request.clone();
+        self
+    }
+}
+
+/// Append-only security audit trail (`auth_audit_events`).
+///
+/// Recording is best effort: a failed write is logged and never fails the request that
+/// caused the event.
+#[derive(Clone, Debug, Default)]
+pub struct AuditLog {
+    repo: Option<AuditEventRepo>,
+    trust_forwarded_for: bool,
+}
+
+impl AuditLog {
+    pub fn new(repo: AuditEventRepo) -> Self {
+        Self {
+            repo: Some(repo),
+            trust_forwarded_for: false,
+        }
+    }
+
+    /// Records nothing (services built without an audit log).
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    // Take the client IP from X-Forwarded-For instead of the TCP peer.
+    pub fn with_trust_forwarded_for(mut self, trust: bool) -> Self {
+        self.trust_forwarded_for = trust;
+        self
+    }
+
+    pub fn trust_forwarded_for(&self) -> bool {
+        self.trust_forwarded_for
+    }
+
+    pub async fn record(&self, event: AuditEvent) {
+        let Some(repo) = &self.repo else {
+            return;
+        };
+
+        let row = NewAuditEvent {
+            event_type: event.event_type.as_str(),
+            outcome: event.outcome.as_str(),
+            reason: event.reason.as_deref(),
+            user_id: event.user_id,
+            session_id: event.session_id,
+            client_id: event.client_id.as_deref(),
+            jkt: event.jkt.as_deref(),
+            client_ip: event.request.client_ip.as_deref(),
+            user_agent: event.request.user_agent.as_deref(),
+            request_id: event.request.request_id.as_deref(),
+        };
+        if let Err(e) = repo.insert(&row).await {
+            error!(
+                target: "security",
+                event = "audit_write_failed",
+                event_type = row.event_type,
+                outcome = row.outcome,
+                user_id = ?row.user_id,
+                session_id = ?row.session_id,
+                error = %e,
+                "failed to record audit event"
+            );
+        }
+    }
+
+    /// Events matching `filter`, newest first.
+    pub async fn list(
+        &self,
+        filter: &AuditEventFilter,
+        limit: i64,
+    ) -> Result<Vec<AuditEventRow>, AppError> {
+        let Some(repo) = &self.repo else {
+            return Ok(Vec::new());
+        };
+
+        repo.list(filter, limit).await.map_err(|e| {
+            error!(error = %e, "failed to list audit events");
+            AppError::Internal
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_types_round_trip() {
+        for t in AuditEventType::ALL {
+            assert_eq!(AuditEventType::parse(t.as_str()), Some(t));
+        }
+        assert_eq!(AuditEventType::parse("TOKEN_ISSUED"), None);
+    }
+}
@@ -0,0 +1,64 @@
+/// Assurance level of a password sign-in.
+pub const ACR_PASSWORD: &str = "aal1";
+/// Assurance level of a password sign-in confirmed with a one-time code.
+pub const ACR_MFA: &str = "aal2";
+
+// Known levels, weakest first.
+const ACR_LEVELS: [&str; 3] = ["aal1", "aal2", "aal3"];
+
+/// How the user authenticated when a session was created.
+///
+/// Stored on the session and copied into its access tokens as `acr` (assurance level)
+/// and `amr` (methods, RFC 8176), so resource servers can ask for a step-up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthnContext {
+    pub acr: String,
+    pub amr: Vec<String>,
+}
+
+impl AuthnContext {
+    /// Username and password.
+    pub fn password() -> Self {
+        Self {
+            acr: ACR_PASSWORD.to_string(),
+            amr: vec!["pwd".to_string()],
+        }
+    }
+
+    /// Username and password plus a TOTP or recovery code.
+    pub fn password_and_otp() -> Self {
+        Self {
+            acr: ACR_MFA.to_string(),
+            amr: vec!["pwd".to_string(), "otp".to_string()],
+        }
+    }
+
+    /// What the session recorded; None for sessions created before acr was tracked.
+    pub fn from_session(acr: Option<String>, amr: Vec<String>) -> Option<Self> {
+        acr.map(|acr| Self { acr, amr })
+    }
+}
+
+/// Whether `acr` is at least `required`; unknown or missing levels never are.
+pub fn acr_satisfies(acr: Option<&str>, required: &str) -> bool {
+    let rank = |v: &str| ACR_LEVELS.iter().position(|l| *l == v);
+    match (acr.and_then(rank), rank(required)) {
+        (Some(have), Some(need)) => have >= need,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stronger_levels_satisfy_weaker_ones() {
+        let mfa = AuthnContext::password_and_otp();
+        assert!(acr_satisfies(Some(&mfa.acr), ACR_PASSWORD));
+        assert!(acr_satisfies(Some(&mfa.acr), ACR_MFA));
+        assert!(!acr_satisfies(Some(&AuthnContext::password().acr), ACR_MFA));
+        assert!(!acr_satisfies(None, ACR_PASSWORD));
+        assert!(!acr_satisfies(Some("urn:example:custom"), ACR_PASSWORD));
+    }
+}
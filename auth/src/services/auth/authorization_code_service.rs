@@ -0,0 +1,483 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::{error, info, warn};
+use url::Url;
+use uuid::Uuid;
+
+use crate::error::{AppError, OAuthError};
+use crate::repos::auth_session_repo::AuthSessionStore;
+use crate::repos::authorization_code_repo::{AuthorizationCodeRepo, NewAuthorizationCode};
+use crate::repos::oauth_client_repo::OAuthClientRepo;
+use crate::services::auth::authn_context::AuthnContext;
+use crate::services::auth::client_auth::AuthenticatedClient;
+use crate::services::auth::denylist::Denylist;
+use crate::services::auth::scope::{self, ScopeService};
+use crate::services::auth::token_service::{IssuedTokenPair, TokenService};
+
+/// Parameters of an authorization request (RFC 6749 Section 4.1.1 + RFC 7636 + RFC 9449 Section 10).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AuthorizationRequest<'a> {
+    pub response_type: Option<&'a str>,
+    pub client_id: Option<&'a str>,
+    pub redirect_uri: Option<&'a str>,
+    pub state: Option<&'a str>,
+    pub code_challenge: Option<&'a str>,
+    pub code_challenge_method: Option<&'a str>,
+    pub dpop_jkt: Option<&'a str>,
+    pub scope: Option<&'a str>,
+}
+
+/// `/token` parameters of the `authorization_code` grant (RFC 6749 Section 4.1.3 + RFC 7636).
+#[derive(Debug, Clone, Copy)]
+pub struct CodeGrant<'a> {
+    pub code: &'a str,
+    /// The client authenticated at `/token` (public clients by `client_id` only).
+    pub client: &'a AuthenticatedClient,
+    pub redirect_uri: &'a str,
+    pub code_verifier: &'a str,
+}
+
+/// An authorization request that passed validation and may be shown to the user.
+#[derive(Debug, Clone)]
+pub struct ValidatedAuthorization {
+    pub client_id: String,
+    pub client_name: String,
+    pub redirect_uri: String,
+    pub state: Option<String>,
+    pub code_challenge: String,
+    pub dpop_jkt: Option<String>,
+    /// Requested scope (already within the client's scopes); None: whatever the user may have.
+    pub scope: Option<String>,
+    pub client_scopes: Vec<String>,
+}
+
+/// Why an authorization request was rejected.
+#[derive(Debug)]
+pub enum AuthorizeError {
+    /// Unknown client or unregistered redirect URI: report to the user, never redirect
+    /// (RFC 6749 Section 4.1.2.1).
+    InvalidClient(&'static str),
+    /// Report to the client: redirect the user agent here.
+    Redirect(String),
+    Internal,
+}
+
+/// Authorization code grant with PKCE for registered clients.
+///
+/// - `/authorize` validates the request, authenticates the user and issues a code
+/// - `/token` (`grant_type=authorization_code`) redeems the code exactly once
+///
+/// Codes are short-lived, stored hashed, and bound to the PKCE challenge and
+/// optionally to a DPoP key (`dpop_jkt`).
+#[derive(Clone)]
+pub struct AuthorizationCodeService {
+    clients: OAuthClientRepo,
+    codes: AuthorizationCodeRepo,
+    sessions: Arc<dyn AuthSessionStore>,
+    tokens: Arc<TokenService>,
+    scopes: ScopeService,
+    issuer: String,
+    code_ttl: Duration,
+    denylist: Denylist,
+}
+
+impl AuthorizationCodeService {
+    pub fn new(
+        clients: OAuthClientRepo,
+        codes: AuthorizationCodeRepo,
+        sessions: Arc<dyn AuthSessionStore>,
+        tokens: Arc<TokenService>,
+        scopes: ScopeService,
+        issuer: String,
+        code_ttl_seconds: u64,
+    ) -> Self {
+        Self {
+            clients,
+            codes,
+            sessions,
+            tokens,
+            scopes,
+            issuer,
+            code_ttl: Duration::seconds(code_ttl_seconds as i64),
+            denylist: Denylist::disabled(),
+        }
+    }
+
+    // Publish sessions revoked on code reuse so resource servers stop accepting
+    // their access tokens.
+    pub fn with_denylist(mut self, denylist: Denylist) -> Self {
+        self.denylist = denylist;
+        self
+    }
+
+    /// Validate an authorization request before showing the login page (and again
+    /// when it is submitted; hidden form fields are not trusted).
+    pub async fn validate(
+        &self,
+        req: AuthorizationRequest<'_>,
+    ) -> Result<ValidatedAuthorization, AuthorizeError> {
+        let client_id = req
+            .client_id
+            .filter(|v| !v.is_empty())
+            .ok_or(AuthorizeError::InvalidClient("client_id is required"))?;
+        let client = self
+            .clients
+            .find(client_id)
+            .await
+            .map_err(|e| {
+                error!(error = %e, "failed to load oauth client");
+                AuthorizeError::Internal
+            })?
+            .ok_or(AuthorizeError::InvalidClient("unknown client_id"))?;
+
+        // Exact string match against the registration (no prefix or wildcard matching).
+        let redirect_uri = req
+            .redirect_uri
+            .filter(|v| client.redirect_uris.iter().any(|r| r == v))
+            .ok_or(AuthorizeError::InvalidClient(
+                "redirect_uri is not registered for this client",
+            ))?;
+
+        // From here on errors go back to the client.
+        let state = req.state.map(str::to_string);
+        let fail = |error: &str, description: &str| {
+            AuthorizeError::Redirect(error_redirect(
+                redirect_uri,
+                error,
+                description,
+                state.as_deref(),
+                &self.issuer,
+            ))
+        };
+
+        if !client.grant_types.iter().any(|g| g == "authorization_code") {
+            return Err(fail(
+                "unauthorized_client",
+                "the client may not use the authorization code grant",
+            ));
+        }
+        if req.response_type != Some("code") {
+            return Err(fail(
+                "unsupported_response_type",
+                "only response_type=code is supported",
+            ));
+        }
+        let code_challenge = req
+            .code_challenge
+            .ok_or_else(|| fail("invalid_request", "code_challenge is required"))?;
+        if req.code_challenge_method != Some("S256") {
+            return Err(fail(
+                "invalid_request",
+                "code_challenge_method must be S256",
+            ));
+        }
+        if !is_sha256_b64url(code_challenge) {
+            return Err(fail("invalid_request", "invalid code_challenge"));
+        }
+        if let Some(jkt) = req.dpop_jkt
+            && !is_sha256_b64url(jkt)
+        {
+            return Err(fail("invalid_request", "invalid dpop_jkt"));
+        }
+        if scope::narrow(&client.scopes, req.scope).is_err() {
+            return Err(fail(
+                "invalid_scope",
+                "the client may not request this scope",
+            ));
+        }
+
+        Ok(ValidatedAuthorization {
+            client_id: client.client_id,
+            client_name: client.client_name,
+            redirect_uri: redirect_uri.to_string(),
+            state,
+            code_challenge: code_challenge.to_string(),
+            dpop_jkt: req.dpop_jkt.map(str::to_string),
+            scope: req.scope.map(str::to_string),
+            client_scopes: client.scopes,
+        })
+    }
+
+    /// Issue a code for the authenticated user; returns the redirect to the client.
+    ///
+    /// `authn` (how the user just signed in) is kept with the code for the session.
+    /// The redirect carries `invalid_scope` instead when the user may not be granted the
+    /// requested scope.
+    pub async fn approve(
+        &self,
+        authz: &ValidatedAuthorization,
+        user_id: Uuid,
+        authn: &AuthnContext,
+    ) -> Result<String, AppError> {
+        let grant = match self
+            .scopes
+            .grant_for_user(user_id, Some(&authz.client_scopes), authz.scope.as_deref())
+            .await
+        {
+            Ok(grant) => grant,
+            Err(AppError::OAuth(OAuthError::InvalidScope)) => {
+                return Ok(error_redirect(
+                    &authz.redirect_uri,
+                    "invalid_scope",
+                    "the requested scope is not available to this user",
+                    authz.state.as_deref(),
+                    &self.issuer,
+                ));
+            }
+            Err(e) => return Err(e),
+        };
+
+        let code = generate_code();
+        let code_hash = hash_code(&code);
+
+        self.codes
+            .insert(NewAuthorizationCode {
+                code_hash: &code_hash,
+                client_id: &authz.client_id,
+                user_id,
+                redirect_uri: &authz.redirect_uri,
+                code_challenge: &authz.code_challenge,
+                dpop_jkt: authz.dpop_jkt.as_deref(),
+                scope: grant.scope.as_deref(),
+                expires_at: Utc::now() + self.code_ttl,
+                acr: &authn.acr,
+                amr: &authn.amr,
+            })
+            .await
+            .map_err(|e| {
+                error!(client_id = %authz.client_id, error = %e, "failed to store authorization code");
+                AppError::Internal
+            })?;
+
+        info!(client_id = %authz.client_id, user_id = %user_id, "authorization code issued");
+        Ok(redirect_with(
+            &authz.redirect_uri,
+            &[("code", &code)],
+            authz.state.as_deref(),
+            &self.issuer,
+        ))
+    }
+
+    /// The user declined: redirect back with `access_denied`.
+    pub fn deny(&self, authz: &ValidatedAuthorization) -> String {
+        error_redirect(
+            &authz.redirect_uri,
+            "access_denied",
+            "the user denied the request",
+            authz.state.as_deref(),
+            &self.issuer,
+        )
+    }
+
+    /// Redeem a code at `/token` and create a DPoP-bound session.
+    ///
+    /// The DPoP proof is verified before the code is consumed, so a nonce challenge
+    /// doesn't burn the code.
+    pub async fn exchange(
+        &self,
+        grant: CodeGrant<'_>,
+        dpop_proof: &str,
+        method: &str,
+        url: &str,
+    ) -> Result<IssuedTokenPair, AppError> {
+        let jkt = self
+            .tokens
+            .verify_issue_proof(dpop_proof, method, url)
+            .await?;
+
+        let CodeGrant {
+            code,
+            client,
+            redirect_uri,
+            code_verifier,
+        } = grant;
+        let client_id = client.client_id.as_str();
+        if !client.allows_grant("authorization_code") {
+            return Err(OAuthError::UnauthorizedClient.into());
+        }
+
+        let now = Utc::now();
+        let code_hash = hash_code(code);
+        let row = match self.codes.consume(&code_hash, now).await.map_err(|e| {
+            error!(error = %e, "failed to redeem authorization code");
+            AppError::Internal
+        })? {
+            Some(row) => row,
+            None => {
+                self.handle_reuse(&code_hash, now).await;
+                return Err(invalid_grant());
+            }
+        };
+
+        if row.client_id != client_id || row.redirect_uri != redirect_uri {
+            warn!(target: "security", event = "authorization_code_mismatch", client_id = %client_id, "authorization code presented by another client or redirect_uri");
+            return Err(invalid_grant());
+        }
+        if !verify_pkce(code_verifier, &row.code_challenge) {
+            warn!(target: "security", event = "pkce_verification_failed", client_id = %client_id, "PKCE verification failed");
+            return Err(invalid_grant());
+        }
+        if row.dpop_jkt.as_deref().is_some_and(|bound| bound != jkt) {
+            warn!(target: "security", event = "authorization_code_key_mismatch", client_id = %client_id, "DPoP key differs from dpop_jkt of the authorization request");
+            return Err(invalid_grant());
+        }
+
+        let out = self
+            .tokens
+            // Exactly what was approved; an empty grant stays empty.
+            .issue_token_pair_for_key(
+                row.user_id,
+                jkt,
+                Some(client),
+                Some(row.scope.as_deref().unwrap_or_default()),
+                AuthnContext::from_session(row.acr, row.amr).as_ref(),
+            )
+            .await?;
+
+        // Best effort: only used to revoke the session if the code is replayed.
+        if let Err(e) = self.codes.set_session(&code_hash, out.session_id).await {
+            warn!(session_id = %out.session_id, error = %e, "failed to record session for authorization code");
+        }
+
+        Ok(out)
+    }
+
+    // A consumed code presented again: the code leaked, so revoke what it was exchanged for.
+    async fn handle_reuse(&self, code_hash: &[u8], now: DateTime<Utc>) {
+        let session_id = match self.codes.find_consumed_session(code_hash).await {
+            Ok(Some(id)) => id,
+            Ok(None) => return,
+            Err(e) => {
+                error!(error = %e, "failed to look up reused authorization code");
+                return;
+            }
+        };
+
+        warn!(target: "security", event = "authorization_code_reuse", session_id = %session_id, "authorization code reused; revoking session");
+        match self.sessions.revoke(session_id, now).await {
+            Ok(_) => self.denylist.deny_session(session_id).await,
+            Err(e) => {
+                error!(session_id = %session_id, error = %e, "failed to revoke session for reused authorization code")
+            }
+        }
+    }
+}
+
+fn invalid_grant() -> AppError {
+    OAuthError::InvalidGrant("invalid authorization code").into()
+}
+
+fn generate_code() -> String {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).expect("getrandom failed");
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn hash_code(code: &str) -> Vec<u8> {
+    Sha256::digest(code.as_bytes()).to_vec()
+}
+
+// base64url (no padding) of a SHA-256 digest: PKCE S256 challenges and JWK thumbprints.
+fn is_sha256_b64url(v: &str) -> bool {
+    v.len() == 43
+        && URL_SAFE_NO_PAD
+            .decode(v)
+            .is_ok_and(|bytes| bytes.len() == 32)
+}
+
+// RFC 7636 Section 4.1 (verifier syntax) and 4.6 (S256).
+fn verify_pkce(code_verifier: &str, code_challenge: &str) -> bool {
+    let well_formed = (43..=128).contains(&code_verifier.len())
+        && code_verifier
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~'));
+    well_formed
+        && URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes())) == code_challenge
+}
+
+// Append response parameters, keeping any query the registered URI already has.
+// `iss` lets the client detect mix-up attacks (RFC 9207).
+fn redirect_with(
+    redirect_uri: &str,
+    params: &[(&str, &str)],
+    state: Option<&str>,
+    issuer: &str,
+) -> String {
+    let Ok(mut url) = Url::parse(redirect_uri) else {
+        // Registered URIs are absolute; fall back to plain concatenation otherwise.
+        let sep = if redirect_uri.contains('?') { '&' } else { '?' };
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        query.extend_pairs(params);
+        if let Some(state) = state {
+            query.append_pair("state", state);
+        }
+        query.append_pair("iss", issuer);
+        return format!("{redirect_uri}{sep}{}", query.finish());
+    };
+
+    {
+        let mut query = url.query_pairs_mut();
+        query.extend_pairs(params);
+        if let Some(state) = state {
+            query.append_pair("state", state);
+        }
+        query.append_pair("iss", issuer);
+    }
+    url.to_string()
+}
+
+fn error_redirect(
+    redirect_uri: &str,
+    error: &str,
+    description: &str,
+    state: Option<&str>,
+    issuer: &str,
+) -> String {
+    redirect_with(
+        redirect_uri,
+        &[("error", error), ("error_description", description)],
+        state,
+        issuer,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 7636 Appendix B
+    const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+    const CHALLENGE: &str = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+    #[test]
+    fn verifies_s256_pkce() {
+        assert!(is_sha256_b64url(CHALLENGE));
+        assert!(verify_pkce(VERIFIER, CHALLENGE));
+        assert!(!verify_pkce(
+            "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXx",
+            CHALLENGE
+        ));
+        // Too short to be a valid verifier, even if it hashed to the challenge.
+        assert!(!verify_pkce("short", CHALLENGE));
+        assert!(!is_sha256_b64url("plain-challenge"));
+    }
+
+    #[test]
+    fn redirect_keeps_registered_query() {
+        let url = redirect_with(
+            "https://app.example.com/cb?tenant=a",
+            &[("code", "abc")],
+            Some("x y"),
+            "https://issuer.test",
+        );
+        assert_eq!(
+            url,
+            "https://app.example.com/cb?tenant=a&code=abc&state=x+y&iss=https%3A%2F%2Fissuer.test"
+        );
+
+        let url = redirect_with("com.example.app:/cb", &[("code", "abc")], None, "iss");
+        assert_eq!(url, "com.example.app:/cb?code=abc&iss=iss");
+    }
+}
@@ -0,0 +1,364 @@
+use aws_lc_rs::constant_time::verify_slices_are_equal;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::Utc;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, jwk::Jwk};
+use percent_encoding::percent_decode_str;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::{debug, error, warn};
+
+use crate::error::{AppError, OAuthError};
+use crate::repos::oauth_client_repo::{OAuthClientRepo, OAuthClientRow};
+use crate::services::auth::dpop::{alg::ensure_jwk_matches_alg, replay::ReplayStore};
+use crate::services::auth::session_policy::ClientSessionLimits;
+
+/// `client_assertion_type` for private_key_jwt (RFC 7523 Section 2.2).
+pub const JWT_BEARER_ASSERTION: &str = "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+
+// Accepted clock skew for client assertions.
+const ASSERTION_LEEWAY_SECONDS: u64 = 60;
+
+/// How a client authenticated at `/token` (RFC 7591 Section 2 names).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientAuthMethod {
+    /// Public client: identified by `client_id` only.
+    None,
+    ClientSecretBasic,
+    ClientSecretPost,
+    PrivateKeyJwt,
+}
+
+/// Client credentials as presented on a `/token` request.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClientCredentials<'a> {
+    /// `Authorization` header value (for client_secret_basic).
+    pub authorization: Option<&'a str>,
+    pub client_id: Option<&'a str>,
+    pub client_secret: Option<&'a str>,
+    pub client_assertion_type: Option<&'a str>,
+    pub client_assertion: Option<&'a str>,
+}
+
+impl ClientCredentials<'_> {
+    /// The client the request claims to be, before it is authenticated (throttling,
+    /// audit).
+    pub fn claimed_client_id(&self) -> Option<String> {
+        self.authorization
+            .and_then(|v| v.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("Basic"))
+            .and_then(|(_, credentials)| parse_basic(credentials.trim()).ok())
+            .map(|(client_id, _)| client_id)
+            .or_else(|| self.client_id.map(str::to_string))
+    }
+}
+
+/// A registered client that passed authentication.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedClient {
+    pub client_id: String,
+    pub method: ClientAuthMethod,
+    pub grant_types: Vec<String>,
+    pub scopes: Vec<String>,
+    pub access_token_ttl_seconds: Option<u64>,
+    /// Audiences the client may request tokens for (token exchange).
+    pub audiences: Vec<String>,
+    /// Overrides of the server's session policy for sessions created through the client.
+    pub session_limits: ClientSessionLimits,
+}
+
+impl AuthenticatedClient {
+    pub fn allows_grant(&self, grant_type: &str) -> bool {
+        self.grant_types.iter().any(|g| g == grant_type)
+    }
+
+    /// Confidential clients proved possession of a secret or key.
+    pub fn is_confidential(&self) -> bool {
+        self.method != ClientAuthMethod::None
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct AssertionSubject {
+    sub: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AssertionClaims {
+    exp: u64,
+    jti: String,
+}
+
+/// Authenticates OAuth clients at the token endpoint.
+///
+/// Clients registered with a secret or a JWK must use it; clients without either are
+/// public and are identified by `client_id` alone. Every failure is `Unauthorized`
+/// (`invalid_client`) without saying which part was wrong.
+#[derive(Clone)]
+pub struct ClientAuthenticator {
+    clients: OAuthClientRepo,
+    replay_store: Arc<dyn ReplayStore>,
+    allowed_algs: Vec<Algorithm>,
+    // Accepted `aud` values of client assertions: the token endpoint URL and the issuer.
+    audiences: Vec<String>,
+}
+
+impl std::fmt::Debug for ClientAuthenticator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientAuthenticator")
+            .field("allowed_algs", &self.allowed_algs)
+            .field("audiences", &self.audiences)
+            .finish()
+    }
+}
+
+impl ClientAuthenticator {
+    pub fn new(
+        clients: OAuthClientRepo,
+        replay_store: Arc<dyn ReplayStore>,
+        allowed_algs: Vec<Algorithm>,
+        audiences: Vec<String>,
+    ) -> Self {
+        Self {
+            clients,
+            replay_store,
+            allowed_algs,
+            audiences,
+        }
+    }
+
+    /// Authenticate the client of a `/token` request.
+    pub async fn authenticate(
+        &self,
+        creds: ClientCredentials<'_>,
+    ) -> Result<AuthenticatedClient, AppError> {
+        let basic = creds
+            .authorization
+            .and_then(|v| v.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("Basic"))
+            .map(|(_, credentials)| parse_basic(credentials.trim()))
+            .transpose()?;
+
+        // RFC 6749 Section 2.3: a client uses exactly one authentication method.
+        let methods = [
+            basic.is_some(),
+            creds.client_secret.is_some(),
+            creds.client_assertion.is_some(),
+        ];
+        if methods.iter().filter(|m| **m).count() > 1 {
+            return Err(OAuthError::InvalidRequest(
+                "multiple client authentication methods".into(),
+            )
+            .into());
+        }
+
+        if let Some((client_id, secret)) = basic {
+            if creds.client_id.is_some_and(|id| id != client_id) {
+                return Err(OAuthError::InvalidRequest("client_id mismatch".into()).into());
+            }
+            let client = self.load(&client_id).await?;
+            return self.check_secret(client, &secret, ClientAuthMethod::ClientSecretBasic);
+        }
+
+        if let Some(secret) = creds.client_secret {
+            let client_id = creds
+                .client_id
+                .ok_or_else(|| OAuthError::InvalidRequest("client_id is required".into()))?;
+            let client = self.load(client_id).await?;
+            return self.check_secret(client, secret, ClientAuthMethod::ClientSecretPost);
+        }
+
+        if let Some(assertion) = creds.client_assertion {
+            if creds.client_assertion_type != Some(JWT_BEARER_ASSERTION) {
+                return Err(
+                    OAuthError::InvalidRequest("unsupported client_assertion_type".into()).into(),
+                );
+            }
+            return self.check_assertion(creds.client_id, assertion).await;
+        }
+
+        // No credentials: only public clients.
+        let client_id = creds
+            .client_id
+            .ok_or_else(|| OAuthError::InvalidRequest("client_id is required".into()))?;
+        let client = self.load(client_id).await?;
+        if client.client_secret_hash.is_some() || client.jwk.is_some() {
+            warn!(target: "security", event = "client_auth_failed", client_id = %client_id, "confidential client did not authenticate");
+            return Err(OAuthError::InvalidClient.into());
+        }
+        Ok(authenticated(client, ClientAuthMethod::None))
+    }
+
+    async fn load(&self, client_id: &str) -> Result<OAuthClientRow, AppError> {
+        self.clients
+            .find(client_id)
+            .await
+            .map_err(|e| {
+                error!(error = %e, "failed to load oauth client");
+                AppError::Internal
+            })?
+            .ok_or_else(|| {
+                warn!(target: "security", event = "client_auth_failed", client_id = %client_id, "unknown client");
+                AppError::from(OAuthError::InvalidClient)
+            })
+    }
+
+    fn check_secret(
+        &self,
+        client: OAuthClientRow,
+        secret: &str,
+        method: ClientAuthMethod,
+    ) -> Result<AuthenticatedClient, AppError> {
+        let presented = Sha256::digest(secret.as_bytes());
+        match client.client_secret_hash.as_deref() {
+            Some(expected) if verify_slices_are_equal(expected, &presented).is_ok() => {
+                Ok(authenticated(client, method))
+            }
+            _ => {
+                warn!(target: "security", event = "client_auth_failed", client_id = %client.client_id, "invalid client secret");
+                Err(OAuthError::InvalidClient.into())
+            }
+        }
+    }
+
+    // RFC 7523 Section 3: iss = sub = client_id, aud = us, exp required, jti single-use.
+    async fn check_assertion(
+        &self,
+        client_id: Option<&str>,
+        assertion: &str,
+    ) -> Result<AuthenticatedClient, AppError> {
+        // The signature can only be checked with the client's key, so find the client
+        // from the (unverified) `sub` first.
+        let subject = jsonwebtoken::dangerous::insecure_decode::<AssertionSubject>(assertion)
+            .map_err(|_| OAuthError::InvalidClient)?
+            .claims
+            .sub
+            .ok_or(OAuthError::InvalidClient)?;
+        if client_id.is_some_and(|id| id != subject) {
+            return Err(OAuthError::InvalidRequest("client_id mismatch".into()).into());
+        }
+
+        let client = self.load(&subject).await?;
+        let fail = |reason: &str| {
+            warn!(target: "security", event = "client_auth_failed", client_id = %subject, reason, "invalid client assertion");
+            AppError::from(OAuthError::InvalidClient)
+        };
+
+        let jwk: Jwk = client
+            .jwk
+            .as_deref()
+            .and_then(|raw| serde_json::from_str(raw).ok())
+            .ok_or_else(|| fail("no usable jwk registered"))?;
+        let header = jsonwebtoken::decode_header(assertion).map_err(|_| fail("malformed"))?;
+        if !self.allowed_algs.contains(&header.alg) {
+            return Err(fail("alg not allowed"));
+        }
+        ensure_jwk_matches_alg(header.alg, &jwk).map_err(|_| fail("alg does not match jwk"))?;
+        let key = DecodingKey::from_jwk(&jwk).map_err(|_| fail("unusable jwk"))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_required_spec_claims(&["exp", "iss", "sub", "aud"]);
+        validation.set_issuer(&[&subject]);
+        validation.sub = Some(subject.clone());
+        validation.set_audience(&self.audiences);
+        validation.leeway = ASSERTION_LEEWAY_SECONDS;
+
+        let claims = jsonwebtoken::decode::<AssertionClaims>(assertion, &key, &validation)
+            .map_err(|e| {
+                debug!(error = %e, "client assertion verification failed");
+                fail("verification failed")
+            })?
+            .claims;
+        if claims.jti.trim().is_empty() {
+            return Err(fail("missing jti"));
+        }
+
+        // Remember the jti until the assertion expires.
+        let now = Utc::now().timestamp().max(0) as u64;
+        let ttl = claims.exp.saturating_sub(now) + ASSERTION_LEEWAY_SECONDS;
+        let key = format!("client_assertion:{}:{}", subject, claims.jti);
+        let first_time = self
+            .replay_store
+            .check_and_store(&key, ttl)
+            .await
+            .map_err(|e| {
+                error!(error = %e, "client assertion replay check failed");
+                AppError::Internal
+            })?;
+        if !first_time {
+            return Err(fail("replayed"));
+        }
+
+        Ok(authenticated(client, ClientAuthMethod::PrivateKeyJwt))
+    }
+}
+
+fn authenticated(client: OAuthClientRow, method: ClientAuthMethod) -> AuthenticatedClient {
+    AuthenticatedClient {
+        client_id: client.client_id,
+        method,
+        grant_types: client.grant_types,
+        scopes: client.scopes,
+        access_token_ttl_seconds: client
+            .access_token_ttl_seconds
+            .and_then(|v| u64::try_from(v).ok()),
+        audiences: client.audiences,
+        session_limits: ClientSessionLimits {
+            idle_timeout_seconds: client
+                .session_idle_timeout_seconds
+                .and_then(|v| u64::try_from(v).ok()),
+            max_lifetime_seconds: client
+                .session_max_lifetime_seconds
+                .and_then(|v| u64::try_from(v).ok()),
+            max_sessions_per_user: client
+                .max_sessions_per_user
+                .and_then(|v| u32::try_from(v).ok()),
+        },
+    }
+}
+
+// RFC 6749 Section 2.3.1: client_id and secret are form-urlencoded before being
+// joined with ':' and base64-encoded.
+fn parse_basic(credentials: &str) -> Result<(String, String), AppError> {
+    STANDARD
+        .decode(credentials)
+        .ok()
+        .and_then(|b| String::from_utf8(b).ok())
+        .and_then(|s| {
+            let (id, secret) = s.split_once(':')?;
+            Some((form_urldecode(id)?, form_urldecode(secret)?))
+        })
+        .ok_or(OAuthError::InvalidClient.into())
+}
+
+fn form_urldecode(s: &str) -> Option<String> {
+    percent_decode_str(&s.replace('+', " "))
+        .decode_utf8()
+        .ok()
+        .map(|s| s.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_basic_credentials() {
+        // base64("batch:s3:cret") -- the secret may itself contain ':'
+        assert_eq!(
+            parse_basic("YmF0Y2g6czM6Y3JldA==").unwrap(),
+            ("batch".to_string(), "s3:cret".to_string())
+        );
+        assert!(parse_basic("bm8tY29sb24=").is_err());
+        assert!(parse_basic("not base64!").is_err());
+    }
+
+    #[test]
+    fn basic_credentials_are_form_urldecoded() {
+        // base64("batch%2Fjobs:p%25ss+w%3Ard%2B")
+        assert_eq!(
+            parse_basic("YmF0Y2glMkZqb2JzOnAlMjVzcyt3JTNBcmQlMkI=").unwrap(),
+            ("batch/jobs".to_string(), "p%ss w:rd+".to_string())
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! Access-token denylist shared with the resource servers.
+//!
+//! Access tokens are self-contained JWTs: revoking a session in Postgres stops this
+//! server from accepting its tokens, but resource servers only check signature and
+//! claims. On every session revocation the revoked `sid` (and on every access token
+//! revocation the token's `jti`) is therefore
+//! - stored in Valkey under `auth:denylist:<kind>:<id>` until the tokens it covers have
+//!   expired, so resource servers can (re)load the current entries, and
+//! - published on the `auth:denylist` channel, so they can update a local copy
+//!   without a round trip per request.
+//!
+//! Message (JSON): `{"kind":"sid"|"jti","id":"<sid or jti>","exp":<unix seconds>}`.
+//!
+//! Publishing is best effort: the session or token is already revoked here when this
+//! runs, and a failure only means resource servers accept its tokens until they expire.
+
+use serde::Serialize;
+use tracing::{debug, error};
+use uuid::Uuid;
+
+const DENYLIST_CHANNEL: &str = "auth:denylist";
+const DENYLIST_PREFIX: &str = "auth:denylist";
+
+// Margin for clock skew / verification leeway on the resource server.
+const EXPIRY_LEEWAY_SECONDS: u64 = 60;
+
+#[derive(Debug, Serialize)]
+struct DenylistMessage<'a> {
+    kind: &'static str,
+    id: &'a str,
+    exp: i64,
+}
+
+/// Publishes revoked sessions and access tokens to Valkey; a no-op when Valkey is not configured.
+#[derive(Clone)]
+pub struct Denylist {
+    manager: Option<redis::aio::ConnectionManager>,
+    // Longest lifetime of a user access token: a session's tokens are all expired
+    // this long after it was revoked.
+    access_token_ttl_seconds: u64,
+}
+
+impl std::fmt::Debug for Denylist {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Denylist")
+            .field("enabled", &self.manager.is_some())
+            .field("access_token_ttl_seconds", &self.access_token_ttl_seconds)
+            .finish()
+    }
+}
+
+impl Denylist {
+    pub async fn connect(
+        redis_url: &str,
+        access_token_ttl_seconds: u64,
+    ) -> Result<Self, redis::RedisError> {
+        let client = redis::Client::open(redis_url)?;
+        let manager = client.get_connection_manager().await?;
+
+        Ok(Self {
+            manager: Some(manager),
+            access_token_ttl_seconds,
+        })
+    }
+
+    /// Without Valkey there is nothing to publish to (resource servers require it).
+    pub fn disabled() -> Self {
+        Self {
+            manager: None,
+            access_token_ttl_seconds: 0,
+        }
+    }
+
+    /// Deny every access token issued for `session_id`.
+    pub async fn deny_session(&self, session_id: Uuid) {
+        let ttl = self.access_token_ttl_seconds + EXPIRY_LEEWAY_SECONDS;
+        self.publish("sid", &session_id.to_string(), ttl).await;
+    }
+
+    pub async fn deny_sessions(&self, session_ids: &[Uuid]) {
+        for id in session_ids {
+            self.deny_session(*id).await;
+        }
+    }
+
+    /// Deny the single access token `jti` until it expires (`exp`, unix seconds).
+    pub async fn deny_token(&self, jti: &str, exp: i64) {
+        let ttl = token_ttl_seconds(exp, chrono::Utc::now().timestamp());
+        self.publish("jti", jti, ttl).await;
+    }
+
+    async fn publish(&self, kind: &'static str, id: &str, ttl_seconds: u64) {
+        let Some(manager) = &self.manager else {
+            debug!(kind, id, "denylist disabled; not publishing");
+            return;
+        };
+
+        let exp = chrono::Utc::now().timestamp() + ttl_seconds as i64;
+        let message = match serde_json::to_string(&DenylistMessage { kind, id, exp }) {
+            Ok(m) => m,
+            Err(e) => {
+                error!(error = %e, "failed to encode denylist message");
+                return;
+            }
+        };
+
+        // The key first, so a resource server that (re)subscribes right now still sees it.
+        let mut conn = manager.clone();
+        let res: Result<(), redis::RedisError> = redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(format!("{DENYLIST_PREFIX}:{kind}:{id}"))
+            .arg(exp)
+            .arg("EX")
+            .arg(ttl_seconds.max(1))
+            .ignore()
+            .cmd("PUBLISH")
+            .arg(DENYLIST_CHANNEL)
+            .arg(message)
+            .ignore()
+            .query_async(&mut conn)
+            .await;
+
+        match res {
+            Ok(()) => debug!(kind, id, exp, "denylist entry published"),
+            Err(e) => {
+                error!(kind, id, error = %e, "failed to publish denylist entry; tokens stay valid on resource servers until they expire")
+            }
+        }
+    }
+}
+
+// Entries outlive the token by the resource server's verification leeway.
+fn token_ttl_seconds(exp: i64, now: i64) -> u64 {
+    u64::try_from(exp - now).unwrap_or(0) + EXPIRY_LEEWAY_SECONDS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_entries_live_until_the_token_expires() {
+        assert_eq!(token_ttl_seconds(1_000, 700), 300 + EXPIRY_LEEWAY_SECONDS);
+        // Expired but still within the leeway.
+        assert_eq!(token_ttl_seconds(1_000, 1_030), EXPIRY_LEEWAY_SECONDS);
+    }
+}
@@ -0,0 +1,137 @@
+//! DPoP proof signing algorithms.
+//!
+//! DPoP proofs are signed with a key pair generated by the client, so only
+//! asymmetric JWS algorithms are meaningful here:
+//! - `none` is not representable in jsonwebtoken and never parses.
+//! - HMAC (`HS*`) is rejected explicitly (a shared secret cannot prove possession).
+//!
+//! The allowlist itself lives in [`super::policy::DpopPolicy`].
+
+use base64::Engine as _;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::Algorithm;
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk};
+
+use super::error::DpopError;
+
+/// Algorithms accepted when `DPOP_ALLOWED_ALGS` is not set.
+///
+/// ES256 is what browsers produce with WebCrypto non-extractable keys.
+pub const DEFAULT_ALLOWED_ALGS: &[Algorithm] = &[
+    Algorithm::EdDSA,
+    Algorithm::ES256,
+    Algorithm::RS256,
+    Algorithm::PS256,
+];
+
+/// Smallest RSA modulus we accept for RS*/PS* proofs.
+const MIN_RSA_MODULUS_BITS: usize = 2048;
+
+/// Parse a JWS `alg` name into an algorithm usable for DPoP proofs.
+///
+/// Returns `None` for unknown names, `none`, and symmetric (HMAC) algorithms.
+pub fn parse_alg(name: &str) -> Option<Algorithm> {
+    let alg: Algorithm = name.trim().parse().ok()?;
+    match alg {
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => None,
+        _ => Some(alg),
+    }
+}
+
+/// Parse a comma-separated allowlist (e.g. `EdDSA,ES256`).
+///
+/// Fails on the first unknown or disallowed name, and on an empty list.
+pub fn parse_allowed_algs(raw: &str) -> Option<Vec<Algorithm>> {
+    let mut out = Vec::new();
+    for name in raw.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let alg = parse_alg(name)?;
+        if !out.contains(&alg) {
+            out.push(alg);
+        }
+    }
+
+    if out.is_empty() { None } else { Some(out) }
+}
+
+/// JWS name of the algorithm (as used in `alg` headers and metadata documents).
+pub fn alg_name(alg: Algorithm) -> &'static str {
+    match alg {
+        Algorithm::HS256 => "HS256",
+        Algorithm::HS384 => "HS384",
+        Algorithm::HS512 => "HS512",
+        Algorithm::ES256 => "ES256",
+        Algorithm::ES384 => "ES384",
+        Algorithm::RS256 => "RS256",
+        Algorithm::RS384 => "RS384",
+        Algorithm::RS512 => "RS512",
+        Algorithm::PS256 => "PS256",
+        Algorithm::PS384 => "PS384",
+        Algorithm::PS512 => "PS512",
+        Algorithm::EdDSA => "EdDSA",
+    }
+}
+
+/// Ensure the embedded `jwk` is a key that can legitimately produce `alg`.
+///
+/// This prevents e.g. an `ES384` header paired with a P-256 key, or an RSA key
+/// that is too small to be trusted.
+pub fn ensure_jwk_matches_alg(alg: Algorithm, jwk: &Jwk) -> Result<(), DpopError> {
+    match (&jwk.algorithm, alg) {
+        (AlgorithmParameters::OctetKeyPair(p), Algorithm::EdDSA) => match p.curve {
+            EllipticCurve::Ed25519 => Ok(()),
+            _ => Err(DpopError::UnsupportedKey),
+        },
+        (AlgorithmParameters::EllipticCurve(p), Algorithm::ES256) => match p.curve {
+            EllipticCurve::P256 => Ok(()),
+            _ => Err(DpopError::UnsupportedKey),
+        },
+        (AlgorithmParameters::EllipticCurve(p), Algorithm::ES384) => match p.curve {
+            EllipticCurve::P384 => Ok(()),
+            _ => Err(DpopError::UnsupportedKey),
+        },
+        (
+            AlgorithmParameters::RSA(p),
+            Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512,
+        ) => {
+            let n = URL_SAFE_NO_PAD
+                .decode(&p.n)
+                .map_err(|_| DpopError::InvalidJwk)?;
+            // Leading zero bytes do not count towards the modulus size.
+            let significant = n.iter().skip_while(|b| **b == 0).count();
+            if significant * 8 < MIN_RSA_MODULUS_BITS {
+                return Err(DpopError::UnsupportedKey);
+            }
+            Ok(())
+        }
+        _ => Err(DpopError::UnsupportedKey),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rejects_symmetric_and_none() {
+        assert_eq!(parse_alg("ES256"), Some(Algorithm::ES256));
+        assert_eq!(parse_alg(" EdDSA "), Some(Algorithm::EdDSA));
+        assert_eq!(parse_alg("HS256"), None);
+        assert_eq!(parse_alg("none"), None);
+        assert_eq!(parse_alg("es256"), None);
+    }
+
+    #[test]
+    fn parse_allowed_algs_fails_closed() {
+        assert_eq!(
+            parse_allowed_algs("EdDSA, ES256,EdDSA"),
+            Some(vec![Algorithm::EdDSA, Algorithm::ES256])
+        );
+        assert_eq!(parse_allowed_algs("EdDSA,HS256"), None);
+        assert_eq!(parse_allowed_algs(" , "), None);
+    }
+}
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::error::{AppError, OAuthError};
+
 #[derive(Debug)]
 pub enum IatRangeReason {
     TooNew,
@@ -32,11 +34,25 @@ pub enum DpopError {
     #[error("cnf.jkt and DPoP JWK thumbprint mismatch")]
     JktMismatch,
 
+    #[error("unsupported DPoP alg: {0:?}")]
+    UnsupportedAlg(jsonwebtoken::Algorithm),
+
     #[error("unsupported JWK key type or curve")]
     UnsupportedKey,
 
     #[error("invalid JWK structure")]
     InvalidJwk,
+
+    #[error("DPoP proof replayed")]
+    Replayed,
+
+    /// The replay store could not be reached; the proof is rejected (fail-closed).
+    #[error("DPoP replay check unavailable")]
+    ReplayCheckUnavailable,
+
+    /// The proof carries no nonce or a stale one. Holds a fresh nonce for the client to retry with.
+    #[error("DPoP nonce missing or stale")]
+    UseNonce(String),
 }
 
 // Keep conversions minimal and conservative.
@@ -46,3 +62,26 @@ impl From<jsonwebtoken::errors::Error> for DpopError {
         DpopError::InvalidJwt
     }
 }
+
+// Nonce challenges must reach the client (RFC 9449 Section 8); every other
+// proof failure stays an opaque 401 (backend failures are ours, not the client's).
+impl From<DpopError> for AppError {
+    fn from(e: DpopError) -> Self {
+        match e {
+            DpopError::UseNonce(nonce) => AppError::UseDpopNonce(nonce),
+            DpopError::ReplayCheckUnavailable => AppError::Internal,
+            _ => AppError::Unauthorized,
+        }
+    }
+}
+
+impl DpopError {
+    /// Like `AppError::from`, for the token endpoint: proof failures are answered with
+    /// `invalid_dpop_proof` (RFC 9449 Section 5) rather than an opaque 401.
+    pub fn into_token_error(self) -> AppError {
+        match self {
+            DpopError::UseNonce(_) | DpopError::ReplayCheckUnavailable => AppError::from(self),
+            e => OAuthError::InvalidDpopProof(e.to_string()).into(),
+        }
+    }
+}
@@ -1,6 +1,8 @@
+pub mod alg;
 pub mod ath;
 pub mod error;
 pub mod normalize;
 pub mod policy;
+pub mod replay;
 pub mod thumbprint;
 pub mod verifier;
@@ -4,6 +4,10 @@
 //! Step3（rotation / nonce / replay など）で増える前提の設定値は
 //! ここに集約しておく。
 
+use jsonwebtoken::Algorithm;
+
+use super::alg::DEFAULT_ALLOWED_ALGS;
+
 #[derive(Clone, Debug)]
 pub struct DpopPolicy {
     /// Require `ath` claim in DPoP proof.
@@ -17,6 +21,9 @@ pub struct DpopPolicy {
 
     /// Step3: require nonce (not used yet).
     pub require_nonce: bool,
+
+    /// Accepted proof signing algorithms (`alg` header). Asymmetric only.
+    pub allowed_algs: Vec<Algorithm>,
 }
 
 impl DpopPolicy {
@@ -25,14 +32,20 @@ impl DpopPolicy {
         iat_leeway_seconds: i64,
         max_age_seconds: i64,
         require_nonce: bool,
+        allowed_algs: Vec<Algorithm>,
     ) -> Self {
         Self {
             require_ath,
             iat_leeway_seconds,
             max_age_seconds,
             require_nonce,
+            allowed_algs,
         }
     }
+
+    pub fn is_alg_allowed(&self, alg: Algorithm) -> bool {
+        self.allowed_algs.contains(&alg)
+    }
 }
 
 impl Default for DpopPolicy {
@@ -42,6 +55,7 @@ impl Default for DpopPolicy {
             iat_leeway_seconds: 60,
             max_age_seconds: 300,
             require_nonce: false,
+            allowed_algs: DEFAULT_ALLOWED_ALGS.to_vec(),
         }
     }
 }
@@ -0,0 +1,127 @@
+//! DPoP proof replay protection (RFC 9449 Section 11.1).
+//!
+//! The verifier records every accepted proof under `<jkt>:<jti>` for as long as the
+//! proof could still pass the `iat` checks. Backends:
+//! - Valkey (`SET NX EX`), preferred when `VALKEY_URL` is configured
+//! - Postgres (`dpop_proof_jtis`), so the auth server can run without Valkey
+//!
+//! Any backend failure is returned as an error and must be treated as a rejection
+//! (fail-closed).
+
+use chrono::{DateTime, Duration, Utc};
+use std::{future::Future, pin::Pin};
+
+use crate::repos::dpop_replay_repo::DpopReplayRepo;
+use crate::repos::error::RepoError;
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReplayError {
+    #[error("valkey error: {0}")]
+    Valkey(#[from] redis::RedisError),
+
+    #[error(transparent)]
+    Repo(#[from] RepoError),
+}
+
+/// Replay check result:
+/// - `Ok(true)`: first time (stored)
+/// - `Ok(false)`: replay detected (already exists)
+/// - `Err(_)`: store failure (treat as fail-closed)
+pub trait ReplayStore: Send + Sync {
+    fn check_and_store<'a>(
+        &'a self,
+        key: &'a str,
+        ttl_secs: u64,
+    ) -> BoxFuture<'a, Result<bool, ReplayError>>;
+}
+
+/// Valkey-backed replay store (Redis protocol).
+#[derive(Clone)]
+pub struct ValkeyReplayStore {
+    manager: redis::aio::ConnectionManager,
+    // Key prefix to avoid collisions with the resource server's `dpop:replay`
+    prefix: String,
+}
+
+impl std::fmt::Debug for ValkeyReplayStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ValkeyReplayStore")
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+impl ValkeyReplayStore {
+    pub async fn new(redis_url: &str) -> Result<Self, ReplayError> {
+        let client = redis::Client::open(redis_url)?;
+        let manager = client.get_connection_manager().await?;
+
+        Ok(Self {
+            manager,
+            prefix: "auth:dpop:replay".to_string(),
+        })
+    }
+}
+
+impl ReplayStore for ValkeyReplayStore {
+    fn check_and_store<'a>(
+        &'a self,
+        key: &'a str,
+        ttl_secs: u64,
+    ) -> BoxFuture<'a, Result<bool, ReplayError>> {
+        Box::pin(async move {
+            let mut conn = self.manager.clone();
+
+            // SET <key> "1" NX EX <ttl> -> OK when newly set, Nil when it already exists
+            let res: Option<String> = redis::cmd("SET")
+                .arg(format!("{}:{}", self.prefix, key))
+                .arg("1")
+                .arg("NX")
+                .arg("EX")
+                .arg(ttl_secs.max(1))
+                .query_async(&mut conn)
+                .await?;
+
+            Ok(res.is_some())
+        })
+    }
+}
+
+impl ReplayStore for DpopReplayRepo {
+    fn check_and_store<'a>(
+        &'a self,
+        key: &'a str,
+        ttl_secs: u64,
+    ) -> BoxFuture<'a, Result<bool, ReplayError>> {
+        Box::pin(async move {
+            let now = Utc::now();
+            Ok(self
+                .insert_if_absent(key, expires_at(now, ttl_secs), now)
+                .await?)
+        })
+    }
+}
+
+// Rows at or past `expires_at` count as absent; keep every entry for at least a second.
+fn expires_at(now: DateTime<Utc>, ttl_secs: u64) -> DateTime<Utc> {
+    now + Duration::seconds(ttl_secs.clamp(1, i32::MAX as u64) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn postgres_entries_expire_after_the_ttl() {
+        let now = Utc::now();
+        assert_eq!(expires_at(now, 360), now + Duration::seconds(360));
+        // A zero TTL would make the entry expire as it is written.
+        assert_eq!(expires_at(now, 0), now + Duration::seconds(1));
+        assert_eq!(
+            expires_at(now, u64::MAX),
+            now + Duration::seconds(i64::from(i32::MAX))
+        );
+    }
+}
@@ -1,15 +1,19 @@
 use std::collections::HashSet;
+use std::sync::Arc;
 
 use axum::http::Uri;
 use chrono::{DateTime, Duration, Utc};
-use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode};
+use dpop_nonce::DpopNonceIssuer;
+use jsonwebtoken::{DecodingKey, Validation, decode};
 use serde::Deserialize;
-use tracing::{debug, warn};
+use tracing::{debug, error, warn};
 
+use super::alg::ensure_jwk_matches_alg;
 use super::ath::compute_ath;
 use super::error::{DpopError, IatRangeReason::TooNew, IatRangeReason::TooOld};
 use super::normalize::{build_expected_htu, normalize_htu};
 use super::policy::DpopPolicy;
+use super::replay::ReplayStore;
 use super::thumbprint::jwk_thumbprint_from_jwk;
 
 /// Result of a successful DPoP proof verification.
@@ -39,7 +43,7 @@ struct DpopClaims {
     #[serde(default)]
     pub ath: Option<String>,
 
-    /// Optional nonce (future/Step3)
+    /// Server-provided nonce (required when `policy.require_nonce`)
     #[serde(default)]
     pub nonce: Option<String>,
 }
@@ -47,17 +51,35 @@ struct DpopClaims {
 /// Verifies a DPoP proof JWT.
 ///
 /// This verifier:
+/// - Accepts only the asymmetric `alg` values allowed by the policy (EdDSA, ES256, RS256, PS256, ...).
 /// - Validates the JWT signature using the public key embedded as `jwk` in the header.
 /// - Enforces `htm`/`htu` match (with normalization).
 /// - Enforces `iat` leeway and max-age.
 /// - Optionally enforces `ath`.
+/// - Optionally enforces a server-issued `nonce` (RFC 9449 Section 8).
+/// - Rejects replayed proofs (`jkt` + `jti`) when a replay store is configured.
 /// - Optionally enforces sender-constrained binding by checking the proof's JWK thumbprint
 ///   against the expected `cnf.jkt` from the access token.
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct DpopVerifier {
     policy: DpopPolicy,
     /// Public base URL of this service used for htu normalization (optional).
     public_base_url: Option<String>,
+    /// Mints/validates nonces. Required when `policy.require_nonce` is set.
+    nonce_issuer: Option<DpopNonceIssuer>,
+    /// Remembers accepted proofs until they expire.
+    replay_store: Option<Arc<dyn ReplayStore>>,
+}
+
+impl std::fmt::Debug for DpopVerifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DpopVerifier")
+            .field("policy", &self.policy)
+            .field("public_base_url", &self.public_base_url)
+            .field("nonce_issuer", &self.nonce_issuer)
+            .field("has_replay_store", &self.replay_store.is_some())
+            .finish()
+    }
 }
 
 impl DpopVerifier {
@@ -65,9 +87,28 @@ impl DpopVerifier {
         Self {
             policy,
             public_base_url,
+            nonce_issuer: None,
+            replay_store: None,
         }
     }
 
+    // Enable replay protection.
+    pub fn with_replay_store(mut self, store: Arc<dyn ReplayStore>) -> Self {
+        self.replay_store = Some(store);
+        self
+    }
+
+    // Enable server-issued nonces.
+    pub fn with_nonce_issuer(mut self, issuer: DpopNonceIssuer) -> Self {
+        self.nonce_issuer = Some(issuer);
+        self
+    }
+
+    /// Fresh nonce to hand out on responses (`DPoP-Nonce` header), if nonces are enabled.
+    pub fn issue_nonce(&self, now: DateTime<Utc>) -> Option<String> {
+        self.nonce_issuer.as_ref().map(|n| n.issue(now.timestamp()))
+    }
+
     pub fn policy(&self) -> &DpopPolicy {
         &self.policy
     }
@@ -76,9 +117,9 @@ impl DpopVerifier {
     ///
     /// - `method`: incoming HTTP method (e.g. "GET")
     /// - `url`: incoming full request URL (as seen by the server/router)
-    /// - `access_token`: required if `policy.require_ath == true`
+    /// - `access_token`: required if `policy.require_ath == true`; when given, `ath` must match
     /// - `expected_jkt`: the `cnf.jkt` from the access token (sender-constrained binding)
-    pub fn verify_proof(
+    pub async fn verify_proof(
         &self,
         proof_jwt: &str,
         method: &str,
@@ -138,8 +179,8 @@ impl DpopVerifier {
             return Err(DpopError::InvalidJwt);
         }
 
-        // ath
-        if self.policy.require_ath {
+        // ath (always when the proof accompanies an access token, RFC 9449 Section 4.3)
+        if self.policy.require_ath || access_token.is_some() {
             let at = access_token.ok_or(DpopError::AthMismatch)?;
             let expected_ath = compute_ath(at);
             let got_ath = claims.ath.as_deref().ok_or(DpopError::AthMismatch)?;
@@ -149,6 +190,28 @@ impl DpopVerifier {
             }
         }
 
+        // nonce (checked last so that a fresh nonce is only handed out for otherwise valid proofs)
+        if self.policy.require_nonce {
+            let issuer = self.nonce_issuer.as_ref().ok_or_else(|| {
+                warn!("DPoP nonce required but no nonce issuer is configured");
+                DpopError::InvalidJwt
+            })?;
+            let valid = claims
+                .nonce
+                .as_deref()
+                .is_some_and(|n| issuer.verify(n, now.timestamp()));
+            if !valid {
+                debug!(
+                    has_nonce = claims.nonce.is_some(),
+                    "DPoP nonce missing or stale"
+                );
+                return Err(DpopError::UseNonce(issuer.issue(now.timestamp())));
+            }
+        }
+
+        // replay (last, so that only fully valid proofs consume their jti)
+        self.check_replay(&jkt, &claims.jti).await?;
+
         Ok(VerifiedDpop {
             jti: claims.jti,
             iat: claims.iat,
@@ -159,6 +222,38 @@ impl DpopVerifier {
         })
     }
 
+    /// Thumbprint of the key that signed `proof_jwt`, when the signature is valid.
+    ///
+    /// Only the signature is checked (no claims, no replay), so this proves possession
+    /// of the key and nothing more.
+    pub fn signing_key_thumbprint(&self, proof_jwt: &str) -> Option<String> {
+        let (header, _) = self.decode_and_verify_signature(proof_jwt).ok()?;
+        jwk_thumbprint_from_jwk(header.jwk.as_ref()?).ok()
+    }
+
+    async fn check_replay(&self, jkt: &str, jti: &str) -> Result<(), DpopError> {
+        let Some(store) = &self.replay_store else {
+            return Ok(());
+        };
+
+        // A proof passes the iat checks for at most `max_age + leeway` seconds.
+        let ttl = self.policy.max_age_seconds + self.policy.iat_leeway_seconds;
+        let ttl = u64::try_from(ttl).unwrap_or(0).max(1);
+
+        let key = format!("{jkt}:{jti}");
+        match store.check_and_store(&key, ttl).await {
+            Ok(true) => Ok(()),
+            Ok(false) => {
+                warn!(jkt = %jkt, "DPoP proof replay detected");
+                Err(DpopError::Replayed)
+            }
+            Err(e) => {
+                error!(error = %e, "DPoP replay store failure");
+                Err(DpopError::ReplayCheckUnavailable)
+            }
+        }
+    }
+
     fn check_iat(&self, now: DateTime<Utc>, iat: i64) -> Result<(), DpopError> {
         let iat_dt = DateTime::<Utc>::from_timestamp(iat, 0).ok_or(DpopError::InvalidJwt)?;
 
@@ -181,16 +276,24 @@ impl DpopVerifier {
         &self,
         proof_jwt: &str,
     ) -> Result<(jsonwebtoken::Header, DpopClaims), DpopError> {
+        // First decode header to obtain alg/jwk.
+        // `alg: none` does not parse at all, so it ends up as InvalidJwt here.
+        let header = jsonwebtoken::decode_header(proof_jwt).map_err(|_| DpopError::InvalidJwt)?;
+
+        if !self.policy.is_alg_allowed(header.alg) {
+            warn!(alg = ?header.alg, "DPoP alg not allowed");
+            return Err(DpopError::UnsupportedAlg(header.alg));
+        }
+
+        let jwk = header.jwk.as_ref().ok_or(DpopError::InvalidJwt)?;
+        ensure_jwk_matches_alg(header.alg, jwk)?;
+
         // We want to avoid requiring `exp` for DPoP proofs.
-        let mut validation = Validation::new(Algorithm::EdDSA);
+        let mut validation = Validation::new(header.alg);
         validation.validate_exp = false;
         validation.validate_nbf = false;
         validation.required_spec_claims = HashSet::new();
 
-        // First decode header to obtain jwk
-        let header = jsonwebtoken::decode_header(proof_jwt).map_err(|_| DpopError::InvalidJwt)?;
-        let jwk = header.jwk.as_ref().ok_or(DpopError::InvalidJwt)?;
-
         let key = DecodingKey::from_jwk(jwk).map_err(|_| DpopError::InvalidJwt)?;
         let token = decode::<DpopClaims>(proof_jwt, &key, &validation)
             .map_err(|_| DpopError::InvalidJwt)?;
@@ -198,3 +301,176 @@ impl DpopVerifier {
         Ok((header, token.claims))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::repos::error::RepoError;
+    use crate::services::auth::dpop::replay::ReplayError;
+    use crate::services::auth::test_support::{ProofKey, TOKEN_URL};
+
+    type BoxFuture<'a, T> = std::pin::Pin<Box<dyn std::future::Future<Output = T> + Send + 'a>>;
+
+    // Same semantics as the Postgres table: an entry lives for `ttl` seconds from the
+    // clock it was stored at, and an expired entry counts as absent.
+    #[derive(Default)]
+    struct ClockedStore {
+        now: Mutex<i64>,
+        entries: Mutex<HashMap<String, i64>>,
+        ttls: Mutex<Vec<u64>>,
+    }
+
+    impl ClockedStore {
+        fn set_now(&self, now: DateTime<Utc>) {
+            *self.now.lock().unwrap() = now.timestamp();
+        }
+    }
+
+    impl ReplayStore for ClockedStore {
+        fn check_and_store<'a>(
+            &'a self,
+            key: &'a str,
+            ttl_secs: u64,
+        ) -> BoxFuture<'a, Result<bool, ReplayError>> {
+            Box::pin(async move {
+                let now = *self.now.lock().unwrap();
+                self.ttls.lock().unwrap().push(ttl_secs);
+                let mut entries = self.entries.lock().unwrap();
+                if entries.get(key).is_some_and(|expires| *expires > now) {
+                    return Ok(false);
+                }
+                entries.insert(key.to_string(), now + ttl_secs as i64);
+                Ok(true)
+            })
+        }
+    }
+
+    struct FailingStore;
+
+    impl ReplayStore for FailingStore {
+        fn check_and_store<'a>(
+            &'a self,
+            _key: &'a str,
+            _ttl_secs: u64,
+        ) -> BoxFuture<'a, Result<bool, ReplayError>> {
+            Box::pin(async { Err(RepoError::Db(sqlx::Error::PoolTimedOut).into()) })
+        }
+    }
+
+    fn verifier(store: Arc<dyn ReplayStore>) -> DpopVerifier {
+        DpopVerifier::new(DpopPolicy::default(), None).with_replay_store(store)
+    }
+
+    async fn verify(
+        verifier: &DpopVerifier,
+        proof: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(), DpopError> {
+        verifier
+            .verify_proof(proof, "POST", TOKEN_URL, None, None, now)
+            .await
+            .map(|_| ())
+    }
+
+    #[tokio::test]
+    async fn replayed_proof_is_rejected() {
+        let store = Arc::new(ClockedStore::default());
+        let verifier = verifier(store.clone());
+        let key = ProofKey::generate();
+        let now = Utc::now();
+        store.set_now(now);
+
+        let proof = key.proof_at("jti-1", now);
+        assert!(verify(&verifier, &proof, now).await.is_ok());
+        assert!(matches!(
+            verify(&verifier, &proof, now).await,
+            Err(DpopError::Replayed)
+        ));
+
+        // The jti is scoped to the key: another key may use the same value.
+        let other = ProofKey::generate();
+        assert!(
+            verify(&verifier, &other.proof_at("jti-1", now), now)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn store_failure_rejects_the_proof() {
+        let verifier = verifier(Arc::new(FailingStore));
+        let key = ProofKey::generate();
+        let now = Utc::now();
+
+        assert!(matches!(
+            verify(&verifier, &key.proof_at("jti-1", now), now).await,
+            Err(DpopError::ReplayCheckUnavailable)
+        ));
+    }
+
+    #[tokio::test]
+    async fn jti_is_remembered_for_as_long_as_a_proof_is_acceptable() {
+        let store = Arc::new(ClockedStore::default());
+        let verifier = verifier(store.clone());
+        let key = ProofKey::generate();
+        let policy = DpopPolicy::default();
+        let ttl = policy.max_age_seconds + policy.iat_leeway_seconds;
+
+        let start = Utc::now();
+        store.set_now(start);
+        assert!(
+            verify(&verifier, &key.proof_at("jti-1", start), start)
+                .await
+                .is_ok()
+        );
+        assert_eq!(*store.ttls.lock().unwrap(), vec![ttl as u64]);
+
+        // Just before the entry expires the jti is still taken.
+        let before = start + Duration::seconds(ttl - 1);
+        store.set_now(before);
+        assert!(matches!(
+            verify(&verifier, &key.proof_at("jti-1", before), before).await,
+            Err(DpopError::Replayed)
+        ));
+
+        // After it, the original proof is too old to pass anyway, and the key is free.
+        let after = start + Duration::seconds(ttl);
+        store.set_now(after);
+        assert!(matches!(
+            verify(&verifier, &key.proof_at("jti-1", start), after).await,
+            Err(DpopError::IatOutOfRange(_))
+        ));
+        assert!(
+            verify(&verifier, &key.proof_at("jti-1", after), after)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn signing_key_is_only_reported_for_a_valid_signature() {
+        let verifier = DpopVerifier::new(DpopPolicy::default(), None);
+        let victim = ProofKey::generate();
+        let attacker = ProofKey::generate();
+
+        // Stale, unknown-htu claims don't matter; the signature does.
+        let proof = victim.proof_at("a", Utc::now() - Duration::days(1));
+        assert_eq!(
+            verifier.signing_key_thumbprint(&proof),
+            Some(jwk_thumbprint_from_jwk(&victim.jwk).unwrap())
+        );
+
+        // The victim's public key in the header, signed by someone else.
+        let forged = ProofKey {
+            encoding: attacker.encoding,
+            jwk: victim.jwk.clone(),
+            jkt: victim.jkt.clone(),
+        }
+        .proof_at("b", Utc::now());
+        assert_eq!(verifier.signing_key_thumbprint(&forged), None);
+        assert_eq!(verifier.signing_key_thumbprint("not-a-jwt"), None);
+    }
+}
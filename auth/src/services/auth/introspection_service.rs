@@ -0,0 +1,472 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::{debug, error, warn};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::repos::auth_session_repo::AuthSessionStore;
+use crate::services::auth::{
+    access_token_verifier::{AccessTokenVerifier, VerifiedAccessToken},
+    client_auth::{ClientAuthenticator, ClientCredentials},
+    refresh_token_issuer::RefreshTokenService,
+    revocation_service::TokenTypeHint,
+};
+
+/// Callers of `/introspect` configured outside the client registry.
+///
+/// - `Authorization: Bearer <shared secret>`
+/// - `Authorization: Basic base64(client_id:client_secret)` (RFC 6749 Section 2.3.1)
+///
+/// Only SHA-256 digests of the secrets are kept in memory. Confidential clients from
+/// the registry are accepted as well (see `IntrospectionService::with_clients`).
+#[derive(Clone, Default)]
+pub struct IntrospectionCallers {
+    shared_secret: Option<[u8; 32]>,
+    clients: Vec<(String, [u8; 32])>,
+}
+
+impl std::fmt::Debug for IntrospectionCallers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IntrospectionCallers")
+            .field("has_shared_secret", &self.shared_secret.is_some())
+            .field(
+                "clients",
+                &self.clients.iter().map(|(id, _)| id).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl IntrospectionCallers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_shared_secret(mut self, secret: &str) -> Self {
+        self.shared_secret = Some(digest(secret));
+        self
+    }
+
+    pub fn with_client(mut self, client_id: &str, client_secret: &str) -> Self {
+        self.clients
+            .push((client_id.to_string(), digest(client_secret)));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shared_secret.is_none() && self.clients.is_empty()
+    }
+
+    /// Authenticate the caller from its `Authorization` header value.
+    ///
+    /// Returns a label for logs: the client_id, or `"shared-secret"`.
+    pub fn authenticate(&self, authorization: Option<&str>) -> Option<String> {
+        let (scheme, credentials) = authorization.and_then(|v| v.split_once(' '))?;
+        let credentials = credentials.trim();
+
+        if scheme.eq_ignore_ascii_case("Bearer") {
+            let expected = self.shared_secret?;
+            if digest(credentials) == expected {
+                return Some("shared-secret".to_string());
+            }
+        } else if scheme.eq_ignore_ascii_case("Basic") {
+            let decoded = STANDARD
+                .decode(credentials)
+                .ok()
+                .and_then(|b| String::from_utf8(b).ok())?;
+            let (client_id, client_secret) = decoded.split_once(':')?;
+
+            let presented = digest(client_secret);
+            if self
+                .clients
+                .iter()
+                .any(|(id, secret)| id == client_id && *secret == presented)
+            {
+                return Some(client_id.to_string());
+            }
+        }
+
+        None
+    }
+}
+
+// Comparing fixed-size digests rather than the secrets keeps timing independent of
+// how much of the secret matched.
+fn digest(secret: &str) -> [u8; 32] {
+    Sha256::digest(secret.as_bytes()).into()
+}
+
+/// What introspection knows about an active token.
+#[derive(Clone, Debug)]
+pub struct IntrospectedToken {
+    pub kind: TokenTypeHint,
+    /// User id, or the client_id for client (client_credentials) tokens.
+    pub sub: String,
+    pub client_id: Option<String>,
+    /// None for client tokens.
+    pub session_id: Option<Uuid>,
+    pub jkt: Option<String>,
+    pub exp: i64,
+    pub iat: Option<i64>,
+    pub jti: Option<String>,
+    pub scope: Option<String>,
+}
+
+/// Token introspection (RFC 7662).
+///
+/// User access tokens are active while their signature/expiry are valid and their
+/// session is still active; client (client_credentials) tokens have no session and are
+/// active until they expire. Refresh tokens are active while they are the current token
+/// of an active session. Anything else is simply inactive.
+#[derive(Clone)]
+pub struct IntrospectionService {
+    refresh_tokens: RefreshTokenService,
+    access: Arc<AccessTokenVerifier>,
+    sessions: Arc<dyn AuthSessionStore>,
+    callers: IntrospectionCallers,
+    clients: Option<Arc<ClientAuthenticator>>,
+}
+
+impl IntrospectionService {
+    pub fn new(
+        refresh_tokens: RefreshTokenService,
+        access: Arc<AccessTokenVerifier>,
+        sessions: Arc<dyn AuthSessionStore>,
+        callers: IntrospectionCallers,
+    ) -> Self {
+        Self {
+            refresh_tokens,
+            access,
+            sessions,
+            callers,
+            clients: None,
+        }
+    }
+
+    // Let confidential clients from the registry call `/introspect`.
+    pub fn with_clients(mut self, clients: Arc<ClientAuthenticator>) -> Self {
+        self.clients = Some(clients);
+        self
+    }
+
+    /// Authenticate the caller: a configured caller, or a confidential registered client.
+    ///
+    /// Returns a label for logs: the client_id, or `"shared-secret"`.
+    pub async fn authenticate_caller(
+        &self,
+        creds: ClientCredentials<'_>,
+    ) -> Result<String, AppError> {
+        if let Some(caller) = self.callers.authenticate(creds.authorization) {
+            return Ok(caller);
+        }
+
+        if let Some(clients) = &self.clients {
+            match clients.authenticate(creds).await {
+                // Public clients prove nothing about who is asking.
+                Ok(client) if client.is_confidential() => return Ok(client.client_id),
+                Ok(_) => {}
+                Err(AppError::Internal) => return Err(AppError::Internal),
+                Err(_) => {}
+            }
+        }
+
+        warn!(target: "security", event = "introspection_auth_failed", client_id = ?creds.claimed_client_id(), "Introspection caller authentication failed");
+        Err(AppError::Unauthorized)
+    }
+
+    pub async fn introspect(
+        &self,
+        token: &str,
+        hint: Option<TokenTypeHint>,
+    ) -> Result<Option<IntrospectedToken>, AppError> {
+        let now = Utc::now();
+
+        let order = match hint {
+            Some(TokenTypeHint::RefreshToken) => {
+                [TokenTypeHint::RefreshToken, TokenTypeHint::AccessToken]
+            }
+            _ => [TokenTypeHint::AccessToken, TokenTypeHint::RefreshToken],
+        };
+
+        for kind in order {
+            let found = match kind {
+                TokenTypeHint::AccessToken => self.inspect_access_token(token).await?,
+                TokenTypeHint::RefreshToken => self.inspect_refresh_token(token, now).await?,
+            };
+            if found.is_some() {
+                return Ok(found);
+            }
+        }
+
+        debug!("Introspected token is not active");
+        Ok(None)
+    }
+
+    async fn inspect_access_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<IntrospectedToken>, AppError> {
+        let Ok(verified) = self.access.verify(token) else {
+            return Ok(None);
+        };
+
+        match verified.session_id {
+            Some(session_id) => {
+                let Ok(user_id) = Uuid::parse_str(&verified.sub) else {
+                    return Ok(None);
+                };
+                let session = self
+                    .sessions
+                    .get_active_by_id(session_id)
+                    .await
+                    .map_err(|e| {
+                        error!(session_id = %session_id, error = %e, "Failed to load auth session");
+                        AppError::Internal
+                    })?;
+                if session.is_none_or(|s| s.user_id != user_id) {
+                    return Ok(None);
+                }
+            }
+            // Client tokens are issued with `sub` = `client_id`; a token with neither
+            // a session nor that shape (e.g. minted before `sid`) is not accepted.
+            None => {
+                if verified.client_id.as_deref() != Some(verified.sub.as_str()) {
+                    return Ok(None);
+                }
+            }
+        }
+
+        Ok(Some(access_token(verified)))
+    }
+
+    async fn inspect_refresh_token(
+        &self,
+        token: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<IntrospectedToken>, AppError> {
+        let Some(active) = self.refresh_tokens.inspect(token, now).await? else {
+            return Ok(None);
+        };
+
+        Ok(Some(IntrospectedToken {
+            kind: TokenTypeHint::RefreshToken,
+            sub: active.user_id.to_string(),
+            client_id: None,
+            session_id: Some(active.session_id),
+            jkt: active.jkt,
+            exp: active.expires_at.timestamp(),
+            iat: Some(active.issued_at.timestamp()),
+            jti: None,
+            scope: None,
+        }))
+    }
+}
+
+fn access_token(verified: VerifiedAccessToken) -> IntrospectedToken {
+    IntrospectedToken {
+        kind: TokenTypeHint::AccessToken,
+        sub: verified.sub,
+        client_id: verified.client_id,
+        session_id: verified.session_id,
+        jkt: verified.jkt,
+        exp: verified.exp,
+        iat: verified.iat,
+        jti: verified.jti,
+        scope: verified.scope,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::repos::auth_session_repo::NewAuthSession;
+    use crate::repos::memory_store::MemoryAuthStore;
+    use crate::services::auth::access_token_issuer::{AccessTokenService, UserAccessToken};
+    use crate::services::auth::jwt::JwtIssuer;
+    use crate::services::auth::test_support::{self, AUDIENCE, ISSUER};
+
+    const JKT: &str = "0ZcOCORZNYy-DWpqq30jZyJGHTN0d2HglBV3uiguA4I";
+
+    struct Fixture {
+        store: MemoryAuthStore,
+        jwt: JwtIssuer,
+        service: IntrospectionService,
+    }
+
+    fn fixture() -> Fixture {
+        let keys = test_support::key_ring();
+        let store = MemoryAuthStore::new();
+        let sessions: Arc<dyn AuthSessionStore> = Arc::new(store.clone());
+        let access = test_support::access_token_verifier(
+            keys.clone(),
+            test_support::dpop_verifier(),
+            sessions.clone(),
+        );
+        let refresh_tokens =
+            RefreshTokenService::new(Arc::new(store.clone()), sessions.clone(), 3_600);
+
+        Fixture {
+            jwt: test_support::jwt_issuer(keys),
+            service: IntrospectionService::new(
+                refresh_tokens,
+                access,
+                sessions,
+                IntrospectionCallers::new().with_shared_secret("s3cret"),
+            ),
+            store,
+        }
+    }
+
+    impl Fixture {
+        async fn session(&self) -> (Uuid, Uuid) {
+            let user_id = Uuid::new_v4();
+            let session = self
+                .store
+                .create(NewAuthSession {
+                    user_id,
+                    dpop_jkt: Some(JKT.to_string()),
+                    client_id: None,
+                    scope: Some("read"),
+                    expires_at: None,
+                    idle_timeout_seconds: None,
+                    acr: Some("aal1"),
+                    amr: &["pwd".to_string()],
+                })
+                .await
+                .unwrap();
+            (user_id, session.id)
+        }
+
+        async fn user_token(&self, user_id: Uuid, session_id: Uuid) -> String {
+            AccessTokenService::new(self.jwt.clone())
+                .issue_access_token(UserAccessToken {
+                    sub: &user_id.to_string(),
+                    session_id,
+                    jkt: Some(JKT.to_string()),
+                    client_id: Some("web".into()),
+                    scope: Some("read".into()),
+                    roles: Vec::new(),
+                    authn: None,
+                })
+                .await
+                .unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn user_access_token_is_active_while_its_session_is() {
+        let f = fixture();
+        let (user_id, session_id) = f.session().await;
+        let token = f.user_token(user_id, session_id).await;
+
+        let active = f.service.introspect(&token, None).await.unwrap().unwrap();
+        assert!(matches!(active.kind, TokenTypeHint::AccessToken));
+        assert_eq!(active.sub, user_id.to_string());
+        assert_eq!(active.session_id, Some(session_id));
+        assert_eq!(active.client_id.as_deref(), Some("web"));
+        assert_eq!(active.jkt.as_deref(), Some(JKT));
+        assert_eq!(active.scope.as_deref(), Some("read"));
+
+        f.store.revoke(session_id, Utc::now()).await.unwrap();
+        assert!(f.service.introspect(&token, None).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn client_credentials_token_is_active_without_a_session() {
+        let f = fixture();
+        let token = AccessTokenService::new(f.jwt.clone())
+            .issue_client_access_token("batch", Some("read".into()), JKT.into(), None)
+            .await
+            .unwrap();
+
+        let active = f.service.introspect(&token, None).await.unwrap().unwrap();
+        assert_eq!(active.sub, "batch");
+        assert_eq!(active.client_id.as_deref(), Some("batch"));
+        assert_eq!(active.session_id, None);
+        assert_eq!(active.jkt.as_deref(), Some(JKT));
+    }
+
+    #[tokio::test]
+    async fn expired_and_foreign_tokens_are_inactive() {
+        let f = fixture();
+        let now = Utc::now().timestamp();
+        let expired = f
+            .jwt
+            .sign(&json!({
+                "iss": ISSUER,
+                "aud": AUDIENCE,
+                "sub": "batch",
+                "client_id": "batch",
+                "iat": now - 3_600,
+                "exp": now - 600,
+                "cnf": { "jkt": JKT },
+            }))
+            .unwrap();
+        assert!(
+            f.service
+                .introspect(&expired, None)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // A user-shaped token without `sid` is neither a session nor a client token.
+        let unbound = f
+            .jwt
+            .sign(&json!({
+                "iss": ISSUER,
+                "aud": AUDIENCE,
+                "sub": Uuid::new_v4().to_string(),
+                "client_id": "web",
+                "iat": now,
+                "exp": now + 300,
+            }))
+            .unwrap();
+        assert!(
+            f.service
+                .introspect(&unbound, None)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        assert!(
+            f.service
+                .introspect("not-a-token", None)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn configured_callers_are_accepted_and_others_rejected() {
+        let f = fixture();
+        let creds = |authorization| ClientCredentials {
+            authorization,
+            ..ClientCredentials::default()
+        };
+
+        assert_eq!(
+            f.service
+                .authenticate_caller(creds(Some("Bearer s3cret")))
+                .await
+                .unwrap(),
+            "shared-secret"
+        );
+        assert!(matches!(
+            f.service
+                .authenticate_caller(creds(Some("Bearer wrong")))
+                .await,
+            Err(AppError::Unauthorized)
+        ));
+        assert!(matches!(
+            f.service.authenticate_caller(creds(None)).await,
+            Err(AppError::Unauthorized)
+        ));
+    }
+}
@@ -1,37 +1,33 @@
-use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use jsonwebtoken::{Algorithm, Header};
 use serde::Serialize;
-use tracing::{error, warn};
+use std::sync::Arc;
+use tracing::error;
 
 use crate::error::AppError;
+use crate::services::auth::signing_keys::key_ring::SigningKeyRing;
 
 #[derive(Clone)]
 pub struct JwtIssuer {
     issuer: String,
     audience: String,
     ttl_seconds: u64,
-    encoding_key: EncodingKey,
+    keys: Arc<SigningKeyRing>,
 }
 
 impl JwtIssuer {
-    /// `Private_key_pem` must be an Ed25519 private key in PKCS#8 PEM format.
+    /// Tokens are signed with the ring's active key and carry its `kid`.
     pub fn new(
-        private_key_pem: &str,
+        keys: Arc<SigningKeyRing>,
         issuer: String,
         audience: String,
         ttl_seconds: u64,
-    ) -> Result<Self, AppError> {
-        let encoding_key = EncodingKey::from_ed_pem(private_key_pem.as_bytes())
-            .map_err(|e| {
-                warn!(error = %e, "failed to parse access JWT private key PEM (expected Ed25519 PKCS#8 PEM)");
-                AppError::Internal
-            })?;
-
-        Ok(Self {
+    ) -> Self {
+        Self {
             issuer,
             audience,
             ttl_seconds,
-            encoding_key,
-        })
+            keys,
+        }
     }
 
     pub fn issuer(&self) -> &str {
@@ -47,9 +43,15 @@ impl JwtIssuer {
     }
 
     pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, AppError> {
+        let (kid, encoding_key) = self.keys.signing_key().ok_or_else(|| {
+            error!("no active signing key");
+            AppError::Internal
+        })?;
+
         let mut header = Header::new(Algorithm::EdDSA);
         header.typ = Some("JWT".to_string());
-        jsonwebtoken::encode(&header, claims, &self.encoding_key).map_err(|e| {
+        header.kid = Some(kid);
+        jsonwebtoken::encode(&header, claims, &encoding_key).map_err(|e| {
             error!(error = %e, "failed to sign JWT");
             AppError::Internal
         })
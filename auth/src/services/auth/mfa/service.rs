@@ -0,0 +1,267 @@
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::error::{AppError, OAuthError};
+use crate::repos::mfa_repo::{MfaRepo, TotpFactorRow};
+use crate::repos::user_repo::UserRepo;
+use crate::services::auth::audit::{AuditEvent, AuditEventType, AuditLog};
+use crate::services::auth::authn_context::AuthnContext;
+use crate::services::auth::mfa::totp::{self, Totp};
+use crate::services::auth::signing_keys::cipher::KeyCipher;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+// 40 bits each, shown as `XXXX-XXXX`; they are single use and failed sign-ins at
+// `/token` and `/authorize` are throttled per username and IP.
+const RECOVERY_CODE_BYTES: usize = 5;
+
+/// A started TOTP enrollment, shown to the user once.
+#[derive(Debug, Clone)]
+pub struct TotpEnrollment {
+    /// Base32 secret for manual entry.
+    pub secret: String,
+    /// `otpauth://` URI for a QR code.
+    pub otpauth_uri: String,
+}
+
+/// TOTP second factor and recovery codes.
+///
+/// - enrollment is pending until the user enters a code from the authenticator
+/// - confirming issues recovery codes (shown once, stored hashed)
+/// - sign-in with a confirmed factor requires a TOTP or recovery code
+///
+/// Secrets are sealed with the signing key encryption key, bound to the user id.
+#[derive(Clone, Debug)]
+pub struct MfaService {
+    repo: MfaRepo,
+    users: UserRepo,
+    cipher: KeyCipher,
+    issuer: String,
+    audit: AuditLog,
+}
+
+impl MfaService {
+    /// `issuer` names the entry in authenticator apps.
+    pub fn new(repo: MfaRepo, users: UserRepo, cipher: KeyCipher, issuer: String) -> Self {
+        Self {
+            repo,
+            users,
+            cipher,
+            issuer,
+            audit: AuditLog::disabled(),
+        }
+    }
+
+    // Record sign-ins with a recovery code.
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// Start (or restart) enrollment with a fresh secret.
+    ///
+    /// `Conflict` when the user already has a confirmed factor; it must be removed first.
+    pub async fn enroll_totp(&self, user_id: Uuid) -> Result<TotpEnrollment, AppError> {
+        let user_name = self
+            .users
+            .find_user_name(user_id)
+            .await
+            .map_err(|e| {
+                error!(user_id = %user_id, error = %e, "failed to load user");
+                AppError::Internal
+            })?
+            .ok_or(AppError::NotFound)?;
+
+        let mut secret = [0u8; totp::SECRET_LEN];
+        getrandom::fill(&mut secret).expect("getrandom failed");
+        let sealed = self.cipher.seal(&aad(user_id), &secret).map_err(|e| {
+            error!(user_id = %user_id, error = %e, "failed to seal TOTP secret");
+            AppError::Internal
+        })?;
+
+        let stored = self
+            .repo
+            .upsert_pending_totp(user_id, &sealed, Utc::now())
+            .await
+            .map_err(|e| {
+                error!(user_id = %user_id, error = %e, "failed to store TOTP factor");
+                AppError::Internal
+            })?;
+        if !stored {
+            return Err(AppError::Conflict);
+        }
+
+        Ok(TotpEnrollment {
+            secret: totp::base32(&secret),
+            otpauth_uri: totp::provisioning_uri(&self.issuer, &user_name, &secret),
+        })
+    }
+
+    /// Confirm the pending factor with a current code; returns new recovery codes.
+    ///
+    /// `NotFound` without a pending factor, `InvalidRequest` for a wrong code.
+    pub async fn confirm_totp(&self, user_id: Uuid, code: &str) -> Result<Vec<String>, AppError> {
+        let factor = self
+            .load(user_id)
+            .await?
+            .filter(|f| f.confirmed_at.is_none())
+            .ok_or(AppError::NotFound)?;
+
+        let step = self
+            .totp(user_id, &factor.secret_ciphertext)?
+            .verify(code, Utc::now().timestamp())
+            .ok_or_else(|| AppError::InvalidRequest("invalid one-time code".into()))?;
+
+        let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| generate_recovery_code())
+            .collect();
+        let hashes: Vec<Vec<u8>> = codes.iter().map(|c| hash_recovery_code(c)).collect();
+
+        let confirmed = self
+            .repo
+            .confirm_totp(user_id, step, &hashes, Utc::now())
+            .await
+            .map_err(|e| {
+                error!(user_id = %user_id, error = %e, "failed to confirm TOTP factor");
+                AppError::Internal
+            })?;
+        if !confirmed {
+            return Err(AppError::NotFound);
+        }
+
+        info!(user_id = %user_id, "TOTP factor confirmed");
+        Ok(codes)
+    }
+
+    /// Remove the factor and recovery codes. `NotFound` when there is none.
+    pub async fn disable_totp(&self, user_id: Uuid) -> Result<(), AppError> {
+        let deleted = self.repo.delete_totp(user_id).await.map_err(|e| {
+            error!(user_id = %user_id, error = %e, "failed to delete TOTP factor");
+            AppError::Internal
+        })?;
+        if !deleted {
+            return Err(AppError::NotFound);
+        }
+
+        info!(user_id = %user_id, "TOTP factor removed");
+        Ok(())
+    }
+
+    /// Second step of a password sign-in; returns how the user authenticated.
+    ///
+    /// - no confirmed factor: password only (`otp` is ignored)
+    /// - confirmed factor: `otp` must be a current TOTP code (each accepted once) or an
+    ///   unused recovery code
+    pub async fn verify_sign_in(
+        &self,
+        user_id: Uuid,
+        otp: Option<&str>,
+    ) -> Result<AuthnContext, AppError> {
+        let Some(factor) = self
+            .load(user_id)
+            .await?
+            .filter(|f| f.confirmed_at.is_some())
+        else {
+            return Ok(AuthnContext::password());
+        };
+        let Some(otp) = otp.map(str::trim).filter(|v| !v.is_empty()) else {
+            return Err(OAuthError::OtpRequired.into());
+        };
+
+        let now = Utc::now();
+        if let Some(step) = self
+            .totp(user_id, &factor.secret_ciphertext)?
+            .verify(otp, now.timestamp())
+        {
+            // A code seen before (or older than the last one) is a replay.
+            let fresh = factor.last_used_step.is_none_or(|last| step > last)
+                && self.repo.use_totp_step(user_id, step).await.map_err(|e| {
+                    error!(user_id = %user_id, error = %e, "failed to record TOTP step");
+                    AppError::Internal
+                })?;
+            if fresh {
+                return Ok(AuthnContext::password_and_otp());
+            }
+            warn!(target: "security", event = "totp_replay", user_id = %user_id, "TOTP code presented again");
+            return Err(invalid_otp());
+        }
+
+        let used = self
+            .repo
+            .use_recovery_code(user_id, &hash_recovery_code(otp), now)
+            .await
+            .map_err(|e| {
+                error!(user_id = %user_id, error = %e, "failed to use recovery code");
+                AppError::Internal
+            })?;
+        if !used {
+            warn!(target: "security", event = "otp_rejected", user_id = %user_id, "one-time code rejected");
+            return Err(invalid_otp());
+        }
+
+        self.audit
+            .record(AuditEvent::success(AuditEventType::RecoveryCodeUsed).with_user(user_id))
+            .await;
+        info!(user_id = %user_id, "signed in with a recovery code");
+        Ok(AuthnContext::password_and_otp())
+    }
+
+    async fn load(&self, user_id: Uuid) -> Result<Option<TotpFactorRow>, AppError> {
+        self.repo.find_totp(user_id).await.map_err(|e| {
+            error!(user_id = %user_id, error = %e, "failed to load TOTP factor");
+            AppError::Internal
+        })
+    }
+
+    fn totp(&self, user_id: Uuid, sealed: &[u8]) -> Result<Totp, AppError> {
+        let secret = self.cipher.open(&aad(user_id), sealed).map_err(|e| {
+            error!(user_id = %user_id, error = %e, "failed to open TOTP secret");
+            AppError::Internal
+        })?;
+        Ok(Totp::new(&secret))
+    }
+}
+
+fn invalid_otp() -> AppError {
+    OAuthError::InvalidGrant("invalid one-time code").into()
+}
+
+// Binds a sealed secret to its user (and apart from signing keys, which use the kid).
+fn aad(user_id: Uuid) -> String {
+    format!("totp:{user_id}")
+}
+
+fn generate_recovery_code() -> String {
+    let mut bytes = [0u8; RECOVERY_CODE_BYTES];
+    getrandom::fill(&mut bytes).expect("getrandom failed");
+    let code = totp::base32(&bytes);
+    format!("{}-{}", &code[..4], &code[4..])
+}
+
+// Case, spaces and dashes don't matter when the user types a code back.
+fn hash_recovery_code(code: &str) -> Vec<u8> {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    Sha256::digest(normalized.as_bytes()).to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovery_codes_are_typed_back_loosely() {
+        let code = generate_recovery_code();
+        assert_eq!(code.len(), 9);
+        assert_eq!(code.as_bytes()[4], b'-');
+
+        let hash = hash_recovery_code(&code);
+        assert_eq!(hash_recovery_code(&code.to_lowercase()), hash);
+        assert_eq!(hash_recovery_code(&code.replace('-', " ")), hash);
+        assert_ne!(hash_recovery_code(&generate_recovery_code()), hash);
+    }
+}
@@ -0,0 +1,168 @@
+use aws_lc_rs::constant_time::verify_slices_are_equal;
+use aws_lc_rs::hmac;
+
+/// Secret length in bytes (160 bits, RFC 4226 Section 4).
+pub const SECRET_LEN: usize = 20;
+pub const DIGITS: u32 = 6;
+pub const PERIOD_SECONDS: i64 = 30;
+// Steps accepted on either side of the current one (clock drift, typing time).
+const SKEW_STEPS: i64 = 1;
+
+/// Time-based one-time passwords (RFC 6238): HMAC-SHA1, 6 digits, 30 second steps.
+///
+/// These are what authenticator apps expect by default; SHA-1 is fine here since
+/// HOTP only relies on HMAC's PRF property.
+#[derive(Clone)]
+pub struct Totp {
+    key: hmac::Key,
+}
+
+impl std::fmt::Debug for Totp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Totp").finish_non_exhaustive()
+    }
+}
+
+impl Totp {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret),
+        }
+    }
+
+    /// The time step `unix_seconds` falls in.
+    pub fn step_at(unix_seconds: i64) -> i64 {
+        unix_seconds.div_euclid(PERIOD_SECONDS)
+    }
+
+    /// HOTP value (RFC 4226 Section 5.3) for a time step, zero-padded.
+    pub fn code(&self, step: i64) -> String {
+        let mac = hmac::sign(&self.key, &step.to_be_bytes());
+        let mac = mac.as_ref();
+        let offset = usize::from(mac[mac.len() - 1] & 0x0f);
+        let binary = u32::from_be_bytes([
+            mac[offset] & 0x7f,
+            mac[offset + 1],
+            mac[offset + 2],
+            mac[offset + 3],
+        ]);
+        format!(
+            "{:0width$}",
+            binary % 10u32.pow(DIGITS),
+            width = DIGITS as usize
+        )
+    }
+
+    /// The step `code` is valid for around `unix_seconds`, if any.
+    ///
+    /// Callers must reject steps at or before the last accepted one (replay).
+    pub fn verify(&self, code: &str, unix_seconds: i64) -> Option<i64> {
+        let code = code.trim();
+        if code.len() != DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        let now = Self::step_at(unix_seconds);
+        // Check every candidate so timing does not reveal which step matched.
+        let mut matched = None;
+        for step in (now - SKEW_STEPS)..=(now + SKEW_STEPS) {
+            let expected = self.code(step);
+            if verify_slices_are_equal(expected.as_bytes(), code.as_bytes()).is_ok() {
+                matched = Some(step);
+            }
+        }
+        matched
+    }
+}
+
+/// `otpauth://` URI for authenticator apps (rendered as a QR code by the client).
+pub fn provisioning_uri(issuer: &str, account: &str, secret: &[u8]) -> String {
+    let issuer = percent_encode(issuer);
+    format!(
+        "otpauth://totp/{issuer}:{}?secret={}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={PERIOD_SECONDS}",
+        percent_encode(account),
+        base32(secret)
+    )
+}
+
+// Everything but RFC 3986 unreserved characters (spaces as %20, which apps expect).
+fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(char::from(b))
+            }
+            _ => out.push_str(&format!("%{b:02X}")),
+        }
+    }
+    out
+}
+
+/// RFC 4648 base32 without padding (the form authenticator apps take).
+pub fn base32(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+    let mut out = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for &b in bytes {
+        buffer = (buffer << 8) | u32::from(b);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(char::from(ALPHABET[((buffer >> bits) & 0x1f) as usize]));
+        }
+    }
+    if bits > 0 {
+        out.push(char::from(
+            ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize],
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 Appendix B (SHA-1 secret), truncated to 6 digits.
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn matches_rfc6238_vectors() {
+        let totp = Totp::new(RFC_SECRET);
+        for (time, code) in [
+            (59, "287082"),
+            (1_111_111_109, "081804"),
+            (1_234_567_890, "005924"),
+            (2_000_000_000, "279037"),
+        ] {
+            assert_eq!(totp.code(Totp::step_at(time)), code);
+        }
+    }
+
+    #[test]
+    fn verify_accepts_adjacent_steps_only() {
+        let totp = Totp::new(RFC_SECRET);
+        let step = Totp::step_at(1_111_111_109);
+
+        assert_eq!(totp.verify("081804", 1_111_111_109), Some(step));
+        assert_eq!(totp.verify(" 081804 ", 1_111_111_109 + 30), Some(step));
+        assert_eq!(totp.verify("081804", 1_111_111_109 + 90), None);
+        assert_eq!(totp.verify("81804", 1_111_111_109), None);
+    }
+
+    #[test]
+    fn encodes_base32_and_provisioning_uri() {
+        assert_eq!(base32(b""), "");
+        assert_eq!(base32(b"f"), "MY");
+        assert_eq!(base32(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32(RFC_SECRET), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+
+        assert_eq!(
+            provisioning_uri("Example Co", "alice@example.com", b"foobar"),
+            "otpauth://totp/Example%20Co:alice%40example.com?secret=MZXW6YTBOI&issuer=Example%20Co&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}
@@ -1,5 +1,24 @@
 pub mod access_token_issuer;
+pub mod access_token_verifier;
+pub mod audit;
+pub mod authn_context;
+pub mod authorization_code_service;
+pub mod client_auth;
+pub mod denylist;
 pub mod dpop;
+pub mod introspection_service;
 pub mod jwt;
+pub mod mfa;
+pub mod password;
+pub mod password_grant;
 pub mod refresh_token_issuer;
+pub mod revocation_service;
+pub mod scope;
+pub mod session_policy;
+pub mod session_service;
+pub mod signing_keys;
+#[cfg(test)]
+pub mod test_support;
+pub mod throttle;
+pub mod token_exchange;
 pub mod token_service;
@@ -0,0 +1,164 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher as _, PasswordVerifier as _, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use std::sync::Arc;
+use tracing::error;
+
+use crate::error::AppError;
+
+/// Argon2id password hashing (PHC string format).
+///
+/// Verification always runs exactly one Argon2 computation, also for unknown users
+/// (against a dummy hash), so response time doesn't reveal whether an account exists.
+/// Hashing is CPU-bound and runs on the blocking pool.
+#[derive(Clone)]
+pub struct PasswordHasher {
+    params: Params,
+    dummy_hash: Arc<str>,
+}
+
+impl std::fmt::Debug for PasswordHasher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PasswordHasher")
+            .field("m_cost", &self.params.m_cost())
+            .field("t_cost", &self.params.t_cost())
+            .field("p_cost", &self.params.p_cost())
+            .finish()
+    }
+}
+
+/// Result of checking a password.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordCheck {
+    pub matched: bool,
+    // The stored hash uses other parameters than the current ones; rehash it.
+    pub needs_rehash: bool,
+}
+
+impl PasswordHasher {
+    pub fn new(params: Params) -> Result<Self, AppError> {
+        let mut hasher = Self {
+            params,
+            dummy_hash: Arc::from(""),
+        };
+
+        let mut random = [0u8; 32];
+        getrandom::fill(&mut random).expect("getrandom failed");
+        hasher.dummy_hash = Arc::from(hasher.hash_blocking(&hex::encode(random))?);
+        Ok(hasher)
+    }
+
+    /// Hash a password with the current parameters.
+    pub fn hash_blocking(&self, password: &str) -> Result<String, AppError> {
+        let mut salt = [0u8; 16];
+        getrandom::fill(&mut salt).expect("getrandom failed");
+        let salt = SaltString::encode_b64(&salt).map_err(|e| {
+            error!(error = %e, "failed to encode password salt");
+            AppError::Internal
+        })?;
+
+        self.argon2()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|h| h.to_string())
+            .map_err(|e| {
+                error!(error = %e, "failed to hash password");
+                AppError::Internal
+            })
+    }
+
+    pub async fn hash(&self, password: String) -> Result<String, AppError> {
+        let hasher = self.clone();
+        tokio::task::spawn_blocking(move || hasher.hash_blocking(&password))
+            .await
+            .map_err(|_| AppError::Internal)?
+    }
+
+    /// Check `password` against `stored` (None = unknown user; always fails).
+    pub async fn verify(
+        &self,
+        password: String,
+        stored: Option<String>,
+    ) -> Result<PasswordCheck, AppError> {
+        let hasher = self.clone();
+        tokio::task::spawn_blocking(move || hasher.verify_blocking(&password, stored.as_deref()))
+            .await
+            .map_err(|_| AppError::Internal)
+    }
+
+    fn verify_blocking(&self, password: &str, stored: Option<&str>) -> PasswordCheck {
+        let parsed = stored.and_then(|h| PasswordHash::new(h).ok());
+        let Some(parsed) = parsed else {
+            // Unknown user or unparsable hash: burn the same work and fail.
+            if let Ok(dummy) = PasswordHash::new(&self.dummy_hash) {
+                let _ = self.argon2().verify_password(password.as_bytes(), &dummy);
+            }
+            return PasswordCheck {
+                matched: false,
+                needs_rehash: false,
+            };
+        };
+
+        // Verification uses the algorithm and parameters recorded in the hash.
+        let matched = self
+            .argon2()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok();
+
+        PasswordCheck {
+            matched,
+            needs_rehash: matched && self.is_outdated(&parsed),
+        }
+    }
+
+    fn is_outdated(&self, hash: &PasswordHash<'_>) -> bool {
+        if hash.algorithm != Algorithm::Argon2id.ident()
+            || hash.version != Some(Version::V0x13.into())
+        {
+            return true;
+        }
+        match Params::try_from(hash) {
+            Ok(p) => {
+                p.m_cost() != self.params.m_cost()
+                    || p.t_cost() != self.params.t_cost()
+                    || p.p_cost() != self.params.p_cost()
+            }
+            Err(_) => true,
+        }
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hasher(m_cost: u32) -> PasswordHasher {
+        PasswordHasher::new(Params::new(m_cost, 1, 1, None).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn verifies_and_rejects() {
+        let h = hasher(1024);
+        let stored = h.hash_blocking("correct horse").unwrap();
+        assert!(stored.starts_with("$argon2id$v=19$m=1024,t=1,p=1$"));
+
+        let ok = h.verify_blocking("correct horse", Some(&stored));
+        assert!(ok.matched && !ok.needs_rehash);
+        assert!(!h.verify_blocking("wrong", Some(&stored)).matched);
+        assert!(!h.verify_blocking("correct horse", None).matched);
+        assert!(
+            !h.verify_blocking("correct horse", Some("not a phc string"))
+                .matched
+        );
+    }
+
+    #[test]
+    fn flags_outdated_parameters_for_rehash() {
+        let stored = hasher(1024).hash_blocking("pw").unwrap();
+
+        let check = hasher(2048).verify_blocking("pw", Some(&stored));
+        assert!(check.matched && check.needs_rehash);
+    }
+}
@@ -0,0 +1,93 @@
+use chrono::Utc;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::repos::user_repo::UserRepo;
+use crate::services::auth::password::PasswordHasher;
+
+// Argon2 cost grows with input length; cap it so a huge password can't tie up a worker.
+const MAX_PASSWORD_LEN: usize = 1024;
+const MAX_USERNAME_LEN: usize = 256;
+
+/// Authenticates a subject with username + password (`grant_type=password`).
+///
+/// Unknown users, users without a password and wrong passwords all fail the same way
+/// and take the same time (one Argon2 verification each).
+#[derive(Clone, Debug)]
+pub struct PasswordGrantService {
+    users: UserRepo,
+    hasher: PasswordHasher,
+}
+
+impl PasswordGrantService {
+    pub fn new(users: UserRepo, hasher: PasswordHasher) -> Self {
+        Self { users, hasher }
+    }
+
+    /// Verify the credentials and return the user id.
+    ///
+    /// Hashes created with older Argon2 parameters are upgraded on success.
+    pub async fn authenticate(&self, username: &str, password: &str) -> Result<Uuid, AppError> {
+        if username.is_empty() || password.is_empty() {
+            return Err(AppError::InvalidRequest(
+                "username and password are required".into(),
+            ));
+        }
+        if username.len() > MAX_USERNAME_LEN || password.len() > MAX_PASSWORD_LEN {
+            return Err(AppError::InvalidRequest(
+                "username or password is too long".into(),
+            ));
+        }
+
+        let credential = self
+            .users
+            .find_credential_by_username(username)
+            .await
+            .map_err(|e| {
+                error!(error = %e, "failed to load user credential");
+                AppError::Internal
+            })?;
+
+        let check = self
+            .hasher
+            .verify(
+                password.to_string(),
+                credential.as_ref().map(|c| c.password_hash.clone()),
+            )
+            .await?;
+
+        let credential = match credential {
+            Some(c) if check.matched => c,
+            _ => {
+                warn!(target: "security", event = "login_failed", "password grant rejected");
+                return Err(AppError::Unauthorized);
+            }
+        };
+
+        if check.needs_rehash {
+            self.upgrade_hash(credential.user_id, &credential.password_hash, password)
+                .await;
+        }
+
+        Ok(credential.user_id)
+    }
+
+    // Best-effort: a failed upgrade doesn't fail the login; it is retried next time.
+    async fn upgrade_hash(&self, user_id: Uuid, current_hash: &str, password: &str) {
+        let new_hash = match self.hasher.hash(password.to_string()).await {
+            Ok(h) => h,
+            Err(_) => return,
+        };
+
+        match self
+            .users
+            .update_password_hash(user_id, current_hash, &new_hash, Utc::now())
+            .await
+        {
+            Ok(true) => info!(user_id = %user_id, "password hash upgraded"),
+            Ok(false) => {}
+            Err(e) => error!(user_id = %user_id, error = %e, "failed to upgrade password hash"),
+        }
+    }
+}
@@ -1,63 +1,59 @@
 use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
 use chrono::{DateTime, Duration as ChronoDuration, Utc};
 use sha2::{Digest, Sha256};
-use std::{future::Future, pin::Pin, sync::Arc};
-use tracing::{debug, error};
+use std::sync::Arc;
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-use crate::error::AppError;
-use crate::repos::auth_session_repo::AuthSessionRepo;
-use crate::repos::refresh_token_repo::{RefreshTokenRepo, RefreshTokenRow};
-use crate::services::auth::dpop::verifier::DpopVerifier;
-
-/// Service-layer representation of a refresh token that has been validated
-/// and enriched with the data required to mint a new access token.
-///
-/// NOTE: This type is intentionally decoupled from DB schema (RefreshTokenRow)
+use crate::error::{AppError, OAuthError};
+use crate::repos::auth_session_repo::AuthSessionStore;
+use crate::repos::refresh_token_repo::{RefreshTokenRow, RefreshTokenStore};
+use crate::services::auth::audit::{AuditEvent, AuditEventType, AuditLog};
+use crate::services::auth::authn_context::AuthnContext;
+use crate::services::auth::denylist::Denylist;
+use crate::services::auth::dpop::{error::DpopError, verifier::DpopVerifier};
+use crate::services::auth::scope;
+use crate::services::auth::session_policy::{self, SessionEnd};
+use crate::services::auth::token_service::RotatedRefreshToken;
+
+/// What a session is bound to: its user, DPoP key, (optionally) OAuth client and
+/// the scope granted when it was created, plus the session policy it was created with.
 #[derive(Clone, Debug)]
-pub struct ValidatedRefreshToken {
+pub struct SessionBinding {
     pub user_id: Uuid,
-    pub session_id: Uuid,
     pub jkt: Option<String>,
+    pub client_id: Option<String>,
+    pub scope: Option<String>,
+    pub authn: Option<AuthnContext>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub idle_timeout_seconds: Option<u64>,
+    // Last refresh, or sign-in before the first one.
+    pub last_active_at: DateTime<Utc>,
 }
 
-type SessionUserAndJkt = Option<(Uuid, Option<String>)>;
-type SessionLookupOutput = Result<SessionUserAndJkt, AppError>;
-type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
-
-/// Minimal lookup interface for resolving session-bound data.
-///
-/// Step1 (no DPoP binding): implementations may return Ok(None) for jkt
-/// Step2/3: implementations should return the bound jkt stored for the session.
-pub trait SessionLookup: Send + Sync {
-    fn get_user_and_jkt_by_session_id(
-        &self,
-        session_id: Uuid,
-    ) -> BoxFuture<'_, SessionLookupOutput>;
-}
-
-impl SessionLookup for AuthSessionRepo {
-    fn get_user_and_jkt_by_session_id(
-        &self,
-        session_id: Uuid,
-    ) -> BoxFuture<'_, SessionLookupOutput> {
-        Box::pin(async move {
-            let ctx_opt = self.lookup_refresh_context_bound(session_id).await.map_err(|e| {
-                error!(session_id = %session_id, error = %e, "Failed to lookup refresh context");
-                AppError::Internal
-            })?;
-            Ok(ctx_opt.map(|c| (c.user_id, Some(c.dpop_jkt))))
-        })
+impl SessionBinding {
+    /// Whether the session's idle timeout or maximum lifetime has passed.
+    pub fn ended_by_policy(&self, now: DateTime<Utc>) -> Option<SessionEnd> {
+        session_policy::ended_by_policy(
+            self.expires_at,
+            self.idle_timeout_seconds,
+            self.last_active_at,
+            now,
+        )
     }
 }
 
 #[derive(Clone)]
 pub struct RefreshTokenService {
-    repo: Arc<RefreshTokenRepo>,
-    sessions: Arc<dyn SessionLookup>,
+    repo: Arc<dyn RefreshTokenStore>,
+    sessions: Arc<dyn AuthSessionStore>,
     // Optional in Step1, Set in Step2+ to enforce DPoP binding on refresh.
     dpop_verifier: Option<Arc<DpopVerifier>>,
     ttl_seconds: u64,
+    // Reuse of a rotated token within this window is not treated as theft.
+    reuse_grace_seconds: u64,
+    denylist: Denylist,
+    audit: AuditLog,
 }
 
 impl std::fmt::Debug for RefreshTokenService {
@@ -65,14 +61,15 @@ impl std::fmt::Debug for RefreshTokenService {
         f.debug_struct("RefreshTokenService")
             .field("has_dpop_verifier", &self.dpop_verifier.is_some())
             .field("ttl_seconds", &self.ttl_seconds)
+            .field("reuse_grace_seconds", &self.reuse_grace_seconds)
             .finish()
     }
 }
 
 impl RefreshTokenService {
     pub fn new(
-        repo: Arc<RefreshTokenRepo>,
-        sessions: Arc<dyn SessionLookup>,
+        repo: Arc<dyn RefreshTokenStore>,
+        sessions: Arc<dyn AuthSessionStore>,
         ttl_seconds: u64,
     ) -> Self {
         Self {
@@ -80,9 +77,31 @@ impl RefreshTokenService {
             sessions,
             dpop_verifier: None,
             ttl_seconds,
+            reuse_grace_seconds: 0,
+            denylist: Denylist::disabled(),
+            audit: AuditLog::disabled(),
         }
     }
 
+    // Publish sessions revoked on token reuse so resource servers stop accepting
+    // their access tokens.
+    pub fn with_denylist(mut self, denylist: Denylist) -> Self {
+        self.denylist = denylist;
+        self
+    }
+
+    // Record token reuse and sessions ended by policy.
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    // Tolerate concurrent refreshes with the same token for a few seconds.
+    pub fn with_reuse_grace_seconds(mut self, seconds: u64) -> Self {
+        self.reuse_grace_seconds = seconds;
+        self
+    }
+
     // Enable DPoP verification for refresh flows (Step2+).
     pub fn with_dpop_verifier(mut self, verifier: Arc<DpopVerifier>) -> Self {
         self.dpop_verifier = Some(verifier);
@@ -91,12 +110,17 @@ impl RefreshTokenService {
 
     /// Issue a new refresh token for (user_id, session_id) and store it.
     ///
+    /// `session_expires_at` is the session's absolute end; the token never outlives it.
     /// Returns the opaque refresh token string.
-    pub async fn issue_refresh_token(&self, session_id: Uuid) -> Result<String, AppError> {
+    pub async fn issue_refresh_token(
+        &self,
+        session_id: Uuid,
+        session_expires_at: Option<DateTime<Utc>>,
+    ) -> Result<String, AppError> {
         let refresh_token = generate_refresh_token();
         let token_hash = hash_refresh_token(&refresh_token);
 
-        let expires_at = Utc::now() + ChronoDuration::seconds(self.ttl_seconds as i64);
+        let expires_at = self.expires_at(Utc::now(), session_expires_at);
 
         debug!(
             session_id = %session_id,
@@ -120,42 +144,79 @@ impl RefreshTokenService {
         Ok(refresh_token)
     }
 
-    /// Validate the presented refresh token and return the data required for refresh.
+    /// Rotate the presented refresh token.
     ///
-    /// Step2+: This requires a valid DPoP proof for this request and enforces the
-    /// session-bound DPoP key binding (cnf.jkt).
-    pub async fn validate_refresh_token(
+    /// Step3: the presented token is marked used and replaced by a fresh one in a single
+    /// transaction. Presenting an already-rotated token is treated as theft: the session
+    /// and its whole token family are revoked, except within `reuse_grace_seconds` of the
+    /// rotation (concurrent refreshes from the same client).
+    ///
+    /// This requires a valid DPoP proof for this request and enforces the session-bound
+    /// DPoP key binding (cnf.jkt). Reuse is only evaluated after the proof checks out, so
+    /// a leaked token alone cannot be used to revoke someone's session.
+    ///
+    /// `requested_scope` may narrow the session's scope for the new access token, never
+    /// widen it (`invalid_scope`).
+    ///
+    /// Sessions past their idle timeout or maximum lifetime are ended here. Every
+    /// rejection is `invalid_grant`, naming the policy when one ended the session.
+    pub async fn rotate(
         &self,
         refresh_token: &str,
+        requested_scope: Option<&str>,
         now: DateTime<Utc>,
         dpop_proof: &str,
         method: &str,
         url: &str,
-    ) -> Result<Option<ValidatedRefreshToken>, AppError> {
-        let row_opt = self.find_active_by_token(refresh_token, now).await?;
+    ) -> Result<RotatedRefreshToken, AppError> {
+        let token_hash = hash_refresh_token(refresh_token);
+        let row_opt = self.repo.find_by_hash(token_hash).await.map_err(|e| {
+            error!(error = ?e, now = %now, "Failed to find refresh token");
+            AppError::Internal
+        })?;
 
         let row = match row_opt {
-            Some(r) => r,
+            Some(r) if r.revoked_at.is_none() => r,
+            Some(r) => {
+                debug!(session_id = %r.session_id, "Refresh token revoked");
+                return Err(self.session_ended(r.session_id).await);
+            }
             None => {
-                debug!(now = %now, "Refresh token not found or inactive");
-                return Ok(None);
+                debug!(now = %now, "Refresh token not found");
+                return Err(invalid_refresh_token());
             }
         };
 
-        let sess_opt = self
-            .sessions
-            .get_user_and_jkt_by_session_id(row.session_id)
-            .await?;
-
-        let (user_id, jkt) = match sess_opt {
+        let binding = match self.lookup_binding(row.session_id).await? {
             Some(v) => v,
             None => {
                 // Token exists but session is missing/inactive -> treat as invalid.
                 debug!(session_id = %row.session_id, "Session not found for refresh token");
-                return Ok(None);
+                return Err(self.session_ended(row.session_id).await);
             }
         };
 
+        // Session policy: an idle or over-age session is over whoever asks.
+        if let Some(end) = binding.ended_by_policy(now) {
+            self.end_session(binding.user_id, row.session_id, end, now)
+                .await?;
+            return Err(end.invalid_grant().into());
+        }
+        if row.expires_at <= now {
+            debug!(session_id = %row.session_id, "Refresh token expired");
+            return Err(invalid_refresh_token());
+        }
+
+        let SessionBinding {
+            user_id,
+            jkt,
+            client_id,
+            scope: session_scope,
+            authn,
+            expires_at: session_expires_at,
+            ..
+        } = binding;
+
         // Step2+: enforce DPoP binding on refresh.
         // - We expect the session to have a bound `jkt`.
         // - We expect the caller to present a valid DPoP proof for this refresh request.
@@ -173,24 +234,27 @@ impl RefreshTokenService {
                         Some(expected_jkt.as_str()),
                         now,
                     )
+                    .await
                     .map_err(|e| {
-                        error!(
-                            session_id = %row.session_id,
-                            error = ?e,
-                            "DPoP proof verification failed for refresh"
-                        );
-                        AppError::Unauthorized
+                        if !matches!(e, DpopError::UseNonce(_)) {
+                            error!(
+                                session_id = %row.session_id,
+                                error = ?e,
+                                "DPoP proof verification failed for refresh"
+                            );
+                        }
+                        e.into_token_error()
                     })?;
             }
 
             (Some(_), None) => {
                 // Step2+: sessions must already be bound to a DPoP key at issue-time.
                 debug!(session_id = %row.session_id, "Session is not bound to dpop_jkt");
-                return Ok(None);
+                return Err(invalid_refresh_token());
             }
 
             (None, _) => {
-                // Misconfiguration: validate_refresh_token in Step2+ requires DPoP verification.
+                // Misconfiguration: refresh in Step2+ requires DPoP verification.
                 error!(
                     session_id = %row.session_id,
                     "DPoP verifier is not configured (expected in Step2+)"
@@ -199,13 +263,194 @@ impl RefreshTokenService {
             }
         }
 
-        Ok(Some(ValidatedRefreshToken {
-            user_id,
+        if row.used_at.is_some() || row.replaced_by.is_some() {
+            self.handle_reuse(user_id, &row, now).await?;
+            return Err(invalid_refresh_token());
+        }
+
+        // Downscoping (RFC 6749 Section 6): checked before rotating so a bad request
+        // doesn't cost the client its refresh token.
+        let granted = scope::split(session_scope.as_deref());
+        let scope = scope::join(scope::narrow(&granted, requested_scope).inspect_err(|_| {
+            debug!(session_id = %row.session_id, "Refresh requested scope beyond the session's grant");
+        })?);
+
+        let new_token = generate_refresh_token();
+        // Rotation never extends the session past its maximum lifetime.
+        let expires_at = self.expires_at(now, session_expires_at);
+
+        let rotated = self
+            .repo
+            .rotate(row.id, hash_refresh_token(&new_token), expires_at, now)
+            .await
+            .map_err(|e| {
+                error!(session_id = %row.session_id, error = ?e, "Failed to rotate refresh token");
+                AppError::Internal
+            })?;
+
+        if rotated.is_none() {
+            // Lost the race against another refresh with the same token: re-read to see
+            // when it was used and apply the same reuse policy.
+            let current = self
+                .repo
+                .find_by_hash(row.token_hash.clone())
+                .await
+                .map_err(|e| {
+                    error!(error = ?e, "Failed to re-read refresh token");
+                    AppError::Internal
+                })?;
+            if let Some(current) = current {
+                self.handle_reuse(user_id, &current, now).await?;
+            }
+            return Err(invalid_refresh_token());
+        }
+
+        debug!(session_id = %row.session_id, "Refresh token rotated");
+
+        Ok(RotatedRefreshToken {
+            refresh_token: new_token,
             session_id: row.session_id,
+            sub: user_id.to_string(),
             jkt,
+            client_id,
+            scope,
+            authn,
+        })
+    }
+
+    fn expires_at(
+        &self,
+        now: DateTime<Utc>,
+        session_expires_at: Option<DateTime<Utc>>,
+    ) -> DateTime<Utc> {
+        let expires_at = now + ChronoDuration::seconds(self.ttl_seconds as i64);
+        session_expires_at.map_or(expires_at, |s| s.min(expires_at))
+    }
+
+    // The refresh error for a token whose session is no longer active.
+    async fn session_ended(&self, session_id: Uuid) -> AppError {
+        let reason = match self.sessions.find_revoked_reason(session_id).await {
+            Ok(reason) => reason,
+            Err(e) => {
+                error!(session_id = %session_id, error = %e, "Failed to lookup session end reason");
+                return AppError::Internal;
+            }
+        };
+        match reason.as_deref().and_then(SessionEnd::parse) {
+            Some(end) => end.invalid_grant().into(),
+            None => invalid_refresh_token(),
+        }
+    }
+
+    // The session's binding and policy, if it is active and bound to a DPoP key.
+    async fn lookup_binding(&self, session_id: Uuid) -> Result<Option<SessionBinding>, AppError> {
+        let ctx_opt = self
+            .sessions
+            .lookup_refresh_context_bound(session_id)
+            .await
+            .map_err(|e| {
+                error!(session_id = %session_id, error = %e, "Failed to lookup refresh context");
+                AppError::Internal
+            })?;
+        Ok(ctx_opt.map(|c| SessionBinding {
+            user_id: c.user_id,
+            jkt: Some(c.dpop_jkt),
+            client_id: c.client_id,
+            scope: c.scope,
+            authn: AuthnContext::from_session(c.acr, c.amr),
+            expires_at: c.expires_at,
+            idle_timeout_seconds: c.idle_timeout_seconds.and_then(|v| u64::try_from(v).ok()),
+            last_active_at: c.last_used_at.unwrap_or(c.created_at),
         }))
     }
 
+    async fn end_session(
+        &self,
+        user_id: Uuid,
+        session_id: Uuid,
+        end: SessionEnd,
+        now: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let ended = self
+            .sessions
+            .end_by_policy(session_id, end.as_str(), now)
+            .await
+            .map_err(|e| {
+                error!(session_id = %session_id, error = %e, "Failed to end session");
+                AppError::Internal
+            })?;
+        if ended {
+            self.denylist.deny_session(session_id).await;
+            info!(session_id = %session_id, reason = end.as_str(), "Session ended by policy");
+            self.audit
+                .record(
+                    AuditEvent::success(AuditEventType::SessionEnded)
+                        .with_reason(end.as_str())
+                        .with_user(user_id)
+                        .with_session(session_id),
+                )
+                .await;
+        }
+        Ok(())
+    }
+
+    // A used token was presented again.
+    //
+    // Within the grace window this is most likely the same client refreshing twice in
+    // parallel; the request is rejected but nothing is revoked. Outside it, revoke the
+    // session and every token derived from it.
+    async fn handle_reuse(
+        &self,
+        user_id: Uuid,
+        row: &RefreshTokenRow,
+        now: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let grace = ChronoDuration::seconds(self.reuse_grace_seconds as i64);
+        if let Some(used_at) = row.used_at
+            && now - used_at <= grace
+        {
+            debug!(
+                session_id = %row.session_id,
+                used_at = %used_at,
+                "Rotated refresh token presented within grace window"
+            );
+            return Ok(());
+        }
+
+        let revoked = self
+            .repo
+            .revoke_family(row.session_id, now)
+            .await
+            .map_err(|e| {
+                error!(session_id = %row.session_id, error = ?e, "Failed to revoke token family");
+                AppError::Internal
+            })?;
+        self.denylist.deny_session(row.session_id).await;
+
+        // Security event: someone holds a token that was already rotated.
+        warn!(
+            target: "security",
+            event = "refresh_token_reuse",
+            session_id = %row.session_id,
+            token_id = %row.id,
+            used_at = ?row.used_at,
+            revoked_tokens = revoked,
+            "Refresh token reuse detected; session and token family revoked"
+        );
+        self.audit
+            .record(
+                AuditEvent::failure(
+                    AuditEventType::RefreshTokenReuse,
+                    "rotated refresh token presented again; session revoked",
+                )
+                .with_user(user_id)
+                .with_session(row.session_id),
+            )
+            .await;
+
+        Ok(())
+    }
+
     /// Look up an active refresh token by the raw token.
     ///
     /// This is intended for the refresh endpoint to validate presented tokens.
@@ -227,12 +472,53 @@ impl RefreshTokenService {
             })
     }
 
-    /// Revoke a refresh token by the raw token.
+    /// Look up a refresh token for introspection.
+    ///
+    /// Returns `None` unless the token is current (not expired, revoked or rotated away)
+    /// and its session is still active.
+    pub async fn inspect(
+        &self,
+        refresh_token: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<ActiveRefreshToken>, AppError> {
+        let Some(row) = self.find_active_by_token(refresh_token, now).await? else {
+            return Ok(None);
+        };
+        // A rotated token may still be inside the reuse grace window, but it is no
+        // longer the token the client is supposed to hold.
+        if row.used_at.is_some() {
+            return Ok(None);
+        }
+
+        let Some(binding) = self.lookup_binding(row.session_id).await? else {
+            return Ok(None);
+        };
+        if binding.ended_by_policy(now).is_some() {
+            return Ok(None);
+        }
+        let SessionBinding { user_id, jkt, .. } = binding;
+
+        Ok(Some(ActiveRefreshToken {
+            session_id: row.session_id,
+            user_id,
+            jkt,
+            issued_at: row.issued_at,
+            expires_at: row.expires_at,
+        }))
+    }
+
+    /// Revoke the session (and whole token family) a refresh token belongs to.
+    ///
+    /// `jkt` is the thumbprint of the key that signed the caller's DPoP proof; only the
+    /// holder of the session's key may revoke it. Returns `Some(session_id)` when a
+    /// session was revoked, `None` when the token is unknown/inactive or the key doesn't
+    /// match (callers must not reveal which).
     pub async fn revoke_by_token(
         &self,
         refresh_token: &str,
+        jkt: &str,
         revoked_at: DateTime<Utc>,
-    ) -> Result<u64, AppError> {
+    ) -> Result<Option<Uuid>, AppError> {
         let now = revoked_at;
         let row_opt = self.find_active_by_token(refresh_token, now).await?;
 
@@ -240,22 +526,47 @@ impl RefreshTokenService {
             Some(r) => r,
             None => {
                 debug!("Refresh token not found or already inactive");
-                return Ok(0);
+                return Ok(None);
             }
         };
 
-        debug!(id = %row.id, session_id = %row.session_id, revoked_at = %revoked_at, "Revoking refresh token");
+        let bound_jkt = self
+            .lookup_binding(row.session_id)
+            .await?
+            .and_then(|b| b.jkt);
+        if bound_jkt.as_deref() != Some(jkt) {
+            debug!(session_id = %row.session_id, "Revocation proof key does not match session");
+            return Ok(None);
+        }
+
+        debug!(id = %row.id, session_id = %row.session_id, revoked_at = %revoked_at, "Revoking refresh token family");
 
         self.repo
-            .revoke(row.id, None, revoked_at)
+            .revoke_family(row.session_id, revoked_at)
             .await
             .map_err(|e| {
                 error!(error = %e, "Failed to revoke refresh token");
                 AppError::Internal
-            })
+            })?;
+
+        Ok(Some(row.session_id))
     }
 }
 
+/// A current refresh token and the session it belongs to.
+#[derive(Clone, Debug)]
+pub struct ActiveRefreshToken {
+    pub session_id: Uuid,
+    pub user_id: Uuid,
+    pub jkt: Option<String>,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+fn invalid_refresh_token() -> AppError {
+    OAuthError::InvalidGrant("invalid refresh token").into()
+}
+
 fn generate_refresh_token() -> String {
     // 32 bytes of entropy -> URL-safe base64 without padding.
     let mut bytes = [0u8; 32];
@@ -270,3 +581,129 @@ fn hash_refresh_token(token: &str) -> Vec<u8> {
     hasher.update(token.as_bytes());
     hasher.finalize().to_vec()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repos::auth_session_repo::NewAuthSession;
+    use crate::repos::memory_store::MemoryAuthStore;
+    use crate::services::auth::dpop::policy::DpopPolicy;
+    use crate::services::auth::test_support::{ProofKey, TOKEN_URL};
+
+    fn service(store: &MemoryAuthStore) -> RefreshTokenService {
+        let verifier = DpopVerifier::new(DpopPolicy::default(), None);
+        RefreshTokenService::new(Arc::new(store.clone()), Arc::new(store.clone()), 3_600)
+            .with_dpop_verifier(Arc::new(verifier))
+    }
+
+    async fn bound_session(store: &MemoryAuthStore, key: &ProofKey) -> Uuid {
+        store
+            .create(NewAuthSession {
+                user_id: Uuid::new_v4(),
+                dpop_jkt: Some(key.jkt.clone()),
+                client_id: None,
+                scope: Some("read write"),
+                expires_at: None,
+                idle_timeout_seconds: None,
+                acr: Some("aal1"),
+                amr: &["pwd".to_string()],
+            })
+            .await
+            .unwrap()
+            .id
+    }
+
+    #[tokio::test]
+    async fn rotation_reuse_revokes_the_token_family() {
+        let store = MemoryAuthStore::new();
+        let service = service(&store);
+        let key = ProofKey::generate();
+        let session_id = bound_session(&store, &key).await;
+        let first = service.issue_refresh_token(session_id, None).await.unwrap();
+
+        let now = Utc::now();
+        let rotated = service
+            .rotate(&first, Some("read"), now, &key.proof(), "POST", TOKEN_URL)
+            .await
+            .unwrap();
+        assert_eq!(rotated.session_id, session_id);
+        assert_eq!(rotated.scope.as_deref(), Some("read"));
+        // The sign-in's acr/amr survive rotation.
+        assert_eq!(rotated.authn, Some(AuthnContext::password()));
+        assert!(service.inspect(&first, now).await.unwrap().is_none());
+        assert!(
+            service
+                .inspect(&rotated.refresh_token, now)
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        // Someone else's key can neither use nor revoke the current token.
+        let thief = ProofKey::generate();
+        assert!(
+            service
+                .rotate(
+                    &rotated.refresh_token,
+                    None,
+                    now,
+                    &thief.proof(),
+                    "POST",
+                    TOKEN_URL
+                )
+                .await
+                .is_err()
+        );
+        assert_eq!(
+            service
+                .revoke_by_token(&rotated.refresh_token, &thief.jkt, now)
+                .await
+                .unwrap(),
+            None
+        );
+
+        // The rotated token presented again, past the grace window: the session is gone.
+        let later = now + ChronoDuration::seconds(1);
+        assert!(
+            service
+                .rotate(&first, None, later, &key.proof(), "POST", TOKEN_URL)
+                .await
+                .is_err()
+        );
+        assert!(store.get_active_by_id(session_id).await.unwrap().is_none());
+        assert!(
+            service
+                .inspect(&rotated.refresh_token, later)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn revocation_by_the_key_holder_ends_the_session() {
+        let store = MemoryAuthStore::new();
+        let service = service(&store);
+        let key = ProofKey::generate();
+        let session_id = bound_session(&store, &key).await;
+        let token = service.issue_refresh_token(session_id, None).await.unwrap();
+
+        let now = Utc::now();
+        assert_eq!(
+            service
+                .revoke_by_token(&token, &key.jkt, now)
+                .await
+                .unwrap(),
+            Some(session_id)
+        );
+        assert!(
+            service
+                .rotate(&token, None, now, &key.proof(), "POST", TOKEN_URL)
+                .await
+                .is_err()
+        );
+        // A new session can hold a current token again.
+        let next = bound_session(&store, &key).await;
+        assert!(service.issue_refresh_token(next, None).await.is_ok());
+    }
+}
@@ -0,0 +1,323 @@
+use chrono::Utc;
+use std::sync::Arc;
+use tracing::{debug, error, info, warn};
+
+use crate::error::AppError;
+use crate::repos::auth_session_repo::AuthSessionStore;
+use crate::services::auth::{
+    access_token_verifier::AccessTokenVerifier,
+    denylist::Denylist,
+    dpop::{error::DpopError, verifier::DpopVerifier},
+    refresh_token_issuer::RefreshTokenService,
+};
+
+/// `token_type_hint` values (RFC 7009 Section 2.1).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenTypeHint {
+    AccessToken,
+    RefreshToken,
+}
+
+impl TokenTypeHint {
+    // Unknown hints are ignored (the server searches all token types anyway).
+    pub fn parse(v: &str) -> Option<Self> {
+        match v {
+            "access_token" => Some(Self::AccessToken),
+            "refresh_token" => Some(Self::RefreshToken),
+            _ => None,
+        }
+    }
+}
+
+/// Outcome of a revocation request.
+#[derive(Clone, Debug)]
+pub struct Revocation {
+    /// Thumbprint of the key that signed the request's proof.
+    pub jkt: String,
+    /// The session that was revoked, if the token matched one.
+    pub session_id: Option<uuid::Uuid>,
+    /// `jti` of the access token that was revoked, if the token was one.
+    pub jti: Option<String>,
+}
+
+// What a token matched: an access token, its session, or both.
+#[derive(Default)]
+struct Revoked {
+    session_id: Option<uuid::Uuid>,
+    jti: Option<String>,
+}
+
+/// Token revocation (RFC 7009).
+///
+/// Revoking either token type revokes the whole session: its refresh token family,
+/// through the session check any access token presented back to this server, and
+/// through the denylist its access tokens on the resource servers. A revoked access
+/// token is also denylisted by `jti`, which is all there is to revoke for client
+/// (`client_credentials`) tokens: they have no session.
+///
+/// The caller must prove possession of the session's DPoP key. Unknown tokens and key
+/// mismatches are silently ignored so the endpoint cannot be used to probe tokens.
+#[derive(Clone)]
+pub struct RevocationService {
+    refresh_tokens: RefreshTokenService,
+    access: Arc<AccessTokenVerifier>,
+    sessions: Arc<dyn AuthSessionStore>,
+    dpop_verifier: Arc<DpopVerifier>,
+    denylist: Denylist,
+}
+
+impl RevocationService {
+    pub fn new(
+        refresh_tokens: RefreshTokenService,
+        access: Arc<AccessTokenVerifier>,
+        sessions: Arc<dyn AuthSessionStore>,
+        dpop_verifier: Arc<DpopVerifier>,
+    ) -> Self {
+        Self {
+            refresh_tokens,
+            access,
+            sessions,
+            dpop_verifier,
+            denylist: Denylist::disabled(),
+        }
+    }
+
+    // Publish revoked sessions so resource servers stop accepting their access tokens.
+    pub fn with_denylist(mut self, denylist: Denylist) -> Self {
+        self.denylist = denylist;
+        self
+    }
+
+    /// Revoke `token`. Errors only for a bad DPoP proof (`Unauthorized`, or a nonce
+    /// challenge) or a backend failure.
+    pub async fn revoke(
+        &self,
+        token: &str,
+        hint: Option<TokenTypeHint>,
+        dpop_proof: &str,
+        method: &str,
+        url: &str,
+    ) -> Result<Revocation, AppError> {
+        let now = Utc::now();
+
+        // The proof itself doesn't depend on the token, so failing here reveals nothing.
+        let verified = self
+            .dpop_verifier
+            .verify_proof(dpop_proof, method, url, None, None, now)
+            .await
+            .map_err(|e| {
+                if !matches!(e, DpopError::UseNonce(_)) {
+                    warn!(error = ?e, "DPoP proof verification failed (revoke)");
+                }
+                AppError::from(e)
+            })?;
+
+        // Try the hinted type first, then fall back to the other one.
+        let order = match hint {
+            Some(TokenTypeHint::AccessToken) => {
+                [TokenTypeHint::AccessToken, TokenTypeHint::RefreshToken]
+            }
+            _ => [TokenTypeHint::RefreshToken, TokenTypeHint::AccessToken],
+        };
+
+        for kind in order {
+            let revoked = match kind {
+                TokenTypeHint::RefreshToken => self
+                    .refresh_tokens
+                    .revoke_by_token(token, &verified.jkt, now)
+                    .await?
+                    .map(|session_id| Revoked {
+                        session_id: Some(session_id),
+                        jti: None,
+                    }),
+                TokenTypeHint::AccessToken => {
+                    self.revoke_access_token(token, &verified.jkt).await?
+                }
+            };
+
+            if let Some(revoked) = revoked {
+                if let Some(session_id) = revoked.session_id {
+                    self.denylist.deny_session(session_id).await;
+                    info!(session_id = %session_id, kind = ?kind, "Session revoked via token revocation");
+                } else {
+                    info!(jti = ?revoked.jti, "Access token revoked via token revocation");
+                }
+                return Ok(Revocation {
+                    jkt: verified.jkt,
+                    session_id: revoked.session_id,
+                    jti: revoked.jti,
+                });
+            }
+        }
+
+        debug!("Revocation request did not match any revocable token");
+        Ok(Revocation {
+            jkt: verified.jkt,
+            session_id: None,
+            jti: None,
+        })
+    }
+
+    // The token's `jti` goes on the denylist; a user token's session is revoked too.
+    async fn revoke_access_token(
+        &self,
+        token: &str,
+        jkt: &str,
+    ) -> Result<Option<Revoked>, AppError> {
+        // Invalid/expired JWTs are simply "not an access token we can revoke".
+        let Ok(verified) = self.access.verify(token) else {
+            return Ok(None);
+        };
+        if verified.jkt.as_deref() != Some(jkt) {
+            debug!(session_id = ?verified.session_id, "Revocation proof key does not match token");
+            return Ok(None);
+        }
+
+        let mut revoked = Revoked::default();
+        if let Some(jti) = verified.jti {
+            self.denylist.deny_token(&jti, verified.exp).await;
+            revoked.jti = Some(jti);
+        }
+
+        let user_id = uuid::Uuid::parse_str(&verified.sub).ok();
+        if let (Some(session_id), Some(user_id)) = (verified.session_id, user_id) {
+            let n = self
+                .sessions
+                .revoke_for_user(session_id, user_id, Utc::now())
+                .await
+                .map_err(|e| {
+                    error!(session_id = %session_id, error = %e, "Failed to revoke session");
+                    AppError::Internal
+                })?;
+            revoked.session_id = (n > 0).then_some(session_id);
+        }
+
+        Ok((revoked.session_id.is_some() || revoked.jti.is_some()).then_some(revoked))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repos::auth_session_repo::NewAuthSession;
+    use crate::repos::memory_store::MemoryAuthStore;
+    use crate::services::auth::access_token_issuer::{AccessTokenService, UserAccessToken};
+    use crate::services::auth::test_support::{self, ProofKey, TOKEN_URL};
+
+    struct Fixture {
+        store: MemoryAuthStore,
+        tokens: AccessTokenService,
+        service: RevocationService,
+    }
+
+    fn fixture() -> Fixture {
+        let keys = test_support::key_ring();
+        let store = MemoryAuthStore::new();
+        let sessions: Arc<dyn AuthSessionStore> = Arc::new(store.clone());
+        let dpop = test_support::dpop_verifier();
+        let access =
+            test_support::access_token_verifier(keys.clone(), dpop.clone(), sessions.clone());
+
+        Fixture {
+            tokens: AccessTokenService::new(test_support::jwt_issuer(keys)),
+            service: RevocationService::new(
+                RefreshTokenService::new(Arc::new(store.clone()), sessions.clone(), 3_600),
+                access,
+                sessions,
+                dpop,
+            ),
+            store,
+        }
+    }
+
+    fn jti(token: &str) -> String {
+        let claims = jsonwebtoken::dangerous::insecure_decode::<serde_json::Value>(token)
+            .unwrap()
+            .claims;
+        claims["jti"].as_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn client_tokens_are_revoked_by_jti() {
+        let f = fixture();
+        let key = ProofKey::generate();
+        let token = f
+            .tokens
+            .issue_client_access_token("batch", Some("read".into()), key.jkt.clone(), None)
+            .await
+            .unwrap();
+
+        let revocation = f
+            .service
+            .revoke(
+                &token,
+                Some(TokenTypeHint::AccessToken),
+                &key.proof(),
+                "POST",
+                TOKEN_URL,
+            )
+            .await
+            .unwrap();
+        assert_eq!(revocation.session_id, None);
+        assert_eq!(revocation.jti, Some(jti(&token)));
+
+        // Only the key the token is bound to can revoke it.
+        let other = ProofKey::generate();
+        let revocation = f
+            .service
+            .revoke(&token, None, &other.proof(), "POST", TOKEN_URL)
+            .await
+            .unwrap();
+        assert_eq!(revocation.jti, None);
+    }
+
+    #[tokio::test]
+    async fn user_tokens_revoke_their_session_and_jti() {
+        let f = fixture();
+        let key = ProofKey::generate();
+        let user_id = uuid::Uuid::new_v4();
+        let session_id = f
+            .store
+            .create(NewAuthSession {
+                user_id,
+                dpop_jkt: Some(key.jkt.clone()),
+                client_id: Some("web"),
+                scope: Some("read"),
+                expires_at: None,
+                idle_timeout_seconds: None,
+                acr: Some("aal1"),
+                amr: &["pwd".to_string()],
+            })
+            .await
+            .unwrap()
+            .id;
+        let token = f
+            .tokens
+            .issue_access_token(UserAccessToken {
+                sub: &user_id.to_string(),
+                session_id,
+                jkt: Some(key.jkt.clone()),
+                client_id: Some("web".into()),
+                scope: Some("read".into()),
+                roles: Vec::new(),
+                authn: None,
+            })
+            .await
+            .unwrap();
+
+        let revocation = f
+            .service
+            .revoke(&token, None, &key.proof(), "POST", TOKEN_URL)
+            .await
+            .unwrap();
+        assert_eq!(revocation.session_id, Some(session_id));
+        assert_eq!(revocation.jti, Some(jti(&token)));
+        assert!(
+            f.store
+                .get_active_by_id(session_id)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::error::{AppError, OAuthError};
+use crate::repos::role_repo::RoleRepo;
+
+/// Scopes and roles that go into a user's access token.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UserGrant {
+    /// Space-separated (RFC 6749 Section 3.3); None when nothing was granted.
+    pub scope: Option<String>,
+    pub roles: Vec<String>,
+}
+
+/// Decides which scopes a user may be granted, from their roles.
+///
+/// - allowed = scopes of the user's roles, further limited to the client's scopes when
+///   the user signs in through a client
+/// - no `scope` requested: everything allowed is granted
+/// - requesting anything not allowed fails with `invalid_scope`
+#[derive(Clone, Debug)]
+pub struct ScopeService {
+    roles: RoleRepo,
+}
+
+impl ScopeService {
+    pub fn new(roles: RoleRepo) -> Self {
+        Self { roles }
+    }
+
+    /// Grant for a new session.
+    pub async fn grant_for_user(
+        &self,
+        user_id: Uuid,
+        client_scopes: Option<&[String]>,
+        requested: Option<&str>,
+    ) -> Result<UserGrant, AppError> {
+        let (roles, mut allowed) = self.load(user_id).await?;
+        if let Some(client_scopes) = client_scopes {
+            allowed.retain(|s| client_scopes.contains(s));
+        }
+
+        let scope = narrow(&allowed, requested).inspect_err(|_| {
+            warn!(user_id = %user_id, requested = ?requested, "requested scope exceeds what the user may be granted");
+        })?;
+        Ok(UserGrant {
+            scope: join(scope),
+            roles,
+        })
+    }
+
+    /// Grant for a refreshed access token.
+    ///
+    /// `scope` was already narrowed against the session (see `narrow`); roles and scopes
+    /// revoked since the session started are dropped here.
+    pub async fn regrant_for_user(
+        &self,
+        user_id: Uuid,
+        scope: Option<&str>,
+    ) -> Result<UserGrant, AppError> {
+        let (roles, allowed) = self.load(user_id).await?;
+        let scope = split(scope)
+            .into_iter()
+            .filter(|s| allowed.contains(s))
+            .collect();
+        Ok(UserGrant {
+            scope: join(scope),
+            roles,
+        })
+    }
+
+    async fn load(&self, user_id: Uuid) -> Result<(Vec<String>, Vec<String>), AppError> {
+        let fail = |e| {
+            error!(user_id = %user_id, error = %e, "failed to load user roles");
+            AppError::Internal
+        };
+        let roles = self.roles.roles_for_user(user_id).await.map_err(fail)?;
+        let scopes = self.roles.scopes_for_user(user_id).await.map_err(fail)?;
+        Ok((roles, scopes))
+    }
+}
+
+/// Scopes to grant given what is `allowed` and what was `requested`.
+///
+/// Nothing requested means everything allowed. A request for anything else is
+/// `invalid_scope`, never silently widened.
+pub fn narrow(allowed: &[String], requested: Option<&str>) -> Result<Vec<String>, AppError> {
+    let Some(requested) = requested else {
+        return Ok(allowed.to_vec());
+    };
+
+    let requested = split(Some(requested));
+    if requested.iter().any(|s| !allowed.contains(s)) {
+        return Err(invalid_scope());
+    }
+    Ok(requested)
+}
+
+/// Split a space-separated scope string, dropping duplicates.
+pub fn split(scope: Option<&str>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for s in scope.unwrap_or_default().split_whitespace() {
+        if !out.iter().any(|o| o == s) {
+            out.push(s.to_string());
+        }
+    }
+    out
+}
+
+/// Join scopes back into the wire format; None when empty.
+pub fn join(scopes: Vec<String>) -> Option<String> {
+    (!scopes.is_empty()).then(|| scopes.join(" "))
+}
+
+pub fn invalid_scope() -> AppError {
+    OAuthError::InvalidScope.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scopes(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn narrows_but_never_widens() {
+        let allowed = scopes(&["posts:read", "posts:write"]);
+
+        assert_eq!(narrow(&allowed, None).unwrap(), allowed);
+        assert_eq!(
+            narrow(&allowed, Some("posts:read  posts:read")).unwrap(),
+            scopes(&["posts:read"])
+        );
+        assert!(narrow(&allowed, Some("posts:read admin")).is_err());
+        assert!(narrow(&[], Some("posts:read")).is_err());
+        assert_eq!(join(narrow(&allowed, Some("")).unwrap()), None);
+    }
+}
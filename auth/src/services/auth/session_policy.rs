@@ -0,0 +1,240 @@
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+
+use crate::config::Config;
+use crate::error::OAuthError;
+use crate::services::auth::client_auth::AuthenticatedClient;
+
+/// Which sessions to end when a user goes over the session cap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEviction {
+    /// Earliest created first.
+    Oldest,
+    /// Least recently refreshed first.
+    LeastRecentlyUsed,
+}
+
+impl SessionEviction {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "oldest" => Some(Self::Oldest),
+            "lru" | "least_recently_used" => Some(Self::LeastRecentlyUsed),
+            _ => None,
+        }
+    }
+}
+
+/// Why a policy ended a session (`auth_sessions.revoked_reason`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEnd {
+    IdleTimeout,
+    MaxLifetime,
+    Evicted,
+}
+
+impl SessionEnd {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::IdleTimeout => "idle_timeout",
+            Self::MaxLifetime => "max_lifetime",
+            Self::Evicted => "evicted",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "idle_timeout" => Some(Self::IdleTimeout),
+            "max_lifetime" => Some(Self::MaxLifetime),
+            "evicted" => Some(Self::Evicted),
+            _ => None,
+        }
+    }
+
+    /// The refresh error for a session ended this way.
+    pub fn invalid_grant(self) -> OAuthError {
+        OAuthError::InvalidGrant(match self {
+            Self::IdleTimeout => "session expired after inactivity",
+            Self::MaxLifetime => "session reached its maximum lifetime",
+            Self::Evicted => "session was ended by a newer sign-in (session limit reached)",
+        })
+    }
+}
+
+/// Server-wide session policy (`SESSION_*`), overridable per client.
+///
+/// - idle timeout: a session not refreshed for this long ends
+/// - max lifetime: a session ends this long after sign-in, however often it is refreshed
+/// - max sessions: signing in beyond the cap ends the user's other sessions (see
+///   `SessionEviction`)
+///
+/// `0` disables a limit.
+#[derive(Debug, Clone, Copy)]
+pub struct SessionPolicy {
+    pub idle_timeout_seconds: u64,
+    pub max_lifetime_seconds: u64,
+    pub max_sessions_per_user: u32,
+    pub eviction: SessionEviction,
+}
+
+impl Default for SessionPolicy {
+    fn default() -> Self {
+        Self {
+            idle_timeout_seconds: 0,
+            max_lifetime_seconds: 0,
+            max_sessions_per_user: 0,
+            eviction: SessionEviction::LeastRecentlyUsed,
+        }
+    }
+}
+
+impl SessionPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            idle_timeout_seconds: config.session_idle_timeout_seconds,
+            max_lifetime_seconds: config.session_max_lifetime_seconds,
+            max_sessions_per_user: config.session_max_per_user,
+            eviction: config.session_eviction,
+        }
+    }
+
+    /// Limits for a session created now, through `client` if any.
+    pub fn for_session(
+        &self,
+        client: Option<&AuthenticatedClient>,
+        now: DateTime<Utc>,
+    ) -> SessionLimits {
+        let overrides = client.map(|c| &c.session_limits);
+        let idle = overrides
+            .and_then(|o| o.idle_timeout_seconds)
+            .unwrap_or(self.idle_timeout_seconds);
+        let lifetime = overrides
+            .and_then(|o| o.max_lifetime_seconds)
+            .unwrap_or(self.max_lifetime_seconds);
+
+        // A client cap counts that client's sessions only; the server cap counts all.
+        let cap = match client.and_then(|c| c.session_limits.max_sessions_per_user.map(|m| (c, m)))
+        {
+            Some((c, max)) => Some(SessionCap {
+                max,
+                client_id: Some(c.client_id.clone()),
+            }),
+            None => (self.max_sessions_per_user > 0).then_some(SessionCap {
+                max: self.max_sessions_per_user,
+                client_id: None,
+            }),
+        };
+
+        SessionLimits {
+            expires_at: (lifetime > 0).then(|| now + ChronoDuration::seconds(lifetime as i64)),
+            idle_timeout_seconds: (idle > 0).then_some(idle),
+            cap,
+            eviction: self.eviction,
+        }
+    }
+}
+
+/// Per-client overrides of `SessionPolicy` (`oauth_clients.session_*`).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClientSessionLimits {
+    pub idle_timeout_seconds: Option<u64>,
+    pub max_lifetime_seconds: Option<u64>,
+    pub max_sessions_per_user: Option<u32>,
+}
+
+/// The effective policy for one new session.
+#[derive(Debug, Clone)]
+pub struct SessionLimits {
+    pub expires_at: Option<DateTime<Utc>>,
+    pub idle_timeout_seconds: Option<u64>,
+    pub cap: Option<SessionCap>,
+    pub eviction: SessionEviction,
+}
+
+/// At most `max` active sessions per user (of `client_id` when set).
+#[derive(Debug, Clone)]
+pub struct SessionCap {
+    pub max: u32,
+    pub client_id: Option<String>,
+}
+
+/// Whether a session stored with these limits has ended by `now`.
+pub fn ended_by_policy(
+    expires_at: Option<DateTime<Utc>>,
+    idle_timeout_seconds: Option<u64>,
+    last_active_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Option<SessionEnd> {
+    if expires_at.is_some_and(|e| e <= now) {
+        return Some(SessionEnd::MaxLifetime);
+    }
+    if let Some(idle) = idle_timeout_seconds
+        && now - last_active_at >= ChronoDuration::seconds(idle as i64)
+    {
+        return Some(SessionEnd::IdleTimeout);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::auth::client_auth::ClientAuthMethod;
+
+    fn client(limits: ClientSessionLimits) -> AuthenticatedClient {
+        AuthenticatedClient {
+            client_id: "kiosk".into(),
+            method: ClientAuthMethod::None,
+            grant_types: vec![],
+            scopes: vec![],
+            access_token_ttl_seconds: None,
+            audiences: vec![],
+            session_limits: limits,
+        }
+    }
+
+    #[test]
+    fn client_overrides_server_policy() {
+        let now = Utc::now();
+        let policy = SessionPolicy {
+            idle_timeout_seconds: 3600,
+            max_lifetime_seconds: 0,
+            max_sessions_per_user: 5,
+            eviction: SessionEviction::Oldest,
+        };
+
+        let limits = policy.for_session(None, now);
+        assert_eq!(limits.idle_timeout_seconds, Some(3600));
+        assert_eq!(limits.expires_at, None);
+        let cap = limits.cap.unwrap();
+        assert_eq!((cap.max, cap.client_id), (5, None));
+
+        let limits = policy.for_session(
+            Some(&client(ClientSessionLimits {
+                idle_timeout_seconds: None,
+                max_lifetime_seconds: Some(60),
+                max_sessions_per_user: Some(1),
+            })),
+            now,
+        );
+        assert_eq!(limits.idle_timeout_seconds, Some(3600));
+        assert_eq!(limits.expires_at, Some(now + ChronoDuration::seconds(60)));
+        let cap = limits.cap.unwrap();
+        assert_eq!((cap.max, cap.client_id.as_deref()), (1, Some("kiosk")));
+    }
+
+    #[test]
+    fn lifetime_wins_over_idle() {
+        let now = Utc::now();
+        let hour_ago = now - ChronoDuration::hours(1);
+
+        assert_eq!(ended_by_policy(None, None, hour_ago, now), None);
+        assert_eq!(ended_by_policy(None, Some(7200), hour_ago, now), None);
+        assert_eq!(
+            ended_by_policy(None, Some(600), hour_ago, now),
+            Some(SessionEnd::IdleTimeout)
+        );
+        assert_eq!(
+            ended_by_policy(Some(now), Some(600), hour_ago, now),
+            Some(SessionEnd::MaxLifetime)
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::repos::auth_session_repo::{AuthSessionRow, AuthSessionStore};
+use crate::services::auth::denylist::Denylist;
+
+/// Self-service session management ("where am I logged in?").
+///
+/// Every operation is scoped to the calling user.
+#[derive(Clone, Debug)]
+pub struct SessionService {
+    repo: Arc<dyn AuthSessionStore>,
+    denylist: Denylist,
+}
+
+impl SessionService {
+    pub fn new(repo: Arc<dyn AuthSessionStore>) -> Self {
+        Self {
+            repo,
+            denylist: Denylist::disabled(),
+        }
+    }
+
+    // Publish revoked sessions so resource servers stop accepting their access tokens.
+    pub fn with_denylist(mut self, denylist: Denylist) -> Self {
+        self.denylist = denylist;
+        self
+    }
+
+    pub async fn list(&self, user_id: Uuid) -> Result<Vec<AuthSessionRow>, AppError> {
+        self.repo.list_active_by_user(user_id).await.map_err(|e| {
+            error!(user_id = %user_id, error = %e, "Failed to list sessions");
+            AppError::Internal
+        })
+    }
+
+    /// Revoke one session. `NotFound` when it doesn't exist, is already revoked,
+    /// or belongs to another user (indistinguishable on purpose).
+    pub async fn revoke(
+        &self,
+        user_id: Uuid,
+        session_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let revoked = self
+            .repo
+            .revoke_for_user(session_id, user_id, now)
+            .await
+            .map_err(|e| {
+                error!(session_id = %session_id, error = %e, "Failed to revoke session");
+                AppError::Internal
+            })?;
+
+        if revoked == 0 {
+            return Err(AppError::NotFound);
+        }
+        self.denylist.deny_session(session_id).await;
+
+        info!(user_id = %user_id, session_id = %session_id, "Session revoked");
+        Ok(())
+    }
+
+    /// Revoke all of the user's sessions except `keep`. Returns the revoked sessions.
+    pub async fn revoke_others(
+        &self,
+        user_id: Uuid,
+        keep: Uuid,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Uuid>, AppError> {
+        let revoked_ids = self
+            .repo
+            .revoke_all_for_user_except(user_id, Some(keep), now)
+            .await
+            .map_err(|e| {
+                error!(user_id = %user_id, error = %e, "Failed to revoke sessions");
+                AppError::Internal
+            })?;
+        self.denylist.deny_sessions(&revoked_ids).await;
+        let revoked = revoked_ids.len() as u64;
+
+        info!(user_id = %user_id, kept = %keep, revoked, "Other sessions revoked");
+        Ok(revoked_ids)
+    }
+}
@@ -0,0 +1,108 @@
+use aws_lc_rs::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use base64::Engine as _;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum KeyCipherError {
+    #[error("invalid key encryption key")]
+    InvalidKey,
+    #[error("failed to encrypt signing key")]
+    Seal,
+    #[error("failed to decrypt signing key")]
+    Open,
+}
+
+/// Encrypts signing keys at rest with AES-256-GCM.
+///
+/// Output is `nonce || ciphertext || tag`. The `kid` is bound as associated data, so a
+/// ciphertext copied onto another row fails to decrypt.
+#[derive(Clone)]
+pub struct KeyCipher {
+    key: [u8; 32],
+}
+
+impl std::fmt::Debug for KeyCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyCipher").finish_non_exhaustive()
+    }
+}
+
+impl KeyCipher {
+    pub fn new(key: [u8; 32]) -> Self {
+        Self { key }
+    }
+
+    /// Parse a base64 (standard or url-safe) encoded 32-byte key.
+    pub fn from_base64(encoded: &str) -> Result<Self, KeyCipherError> {
+        let encoded = encoded.trim();
+        let bytes = STANDARD
+            .decode(encoded)
+            .or_else(|_| URL_SAFE_NO_PAD.decode(encoded))
+            .map_err(|_| KeyCipherError::InvalidKey)?;
+        let key: [u8; 32] = bytes.try_into().map_err(|_| KeyCipherError::InvalidKey)?;
+        Ok(Self::new(key))
+    }
+
+    pub fn seal(&self, kid: &str, plaintext: &[u8]) -> Result<Vec<u8>, KeyCipherError> {
+        let mut nonce = [0u8; NONCE_LEN];
+        getrandom::fill(&mut nonce).map_err(|_| KeyCipherError::Seal)?;
+
+        let mut in_out = plaintext.to_vec();
+        self.aead()?
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(kid.as_bytes()),
+                &mut in_out,
+            )
+            .map_err(|_| KeyCipherError::Seal)?;
+
+        let mut out = Vec::with_capacity(NONCE_LEN + in_out.len());
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&in_out);
+        Ok(out)
+    }
+
+    pub fn open(&self, kid: &str, sealed: &[u8]) -> Result<Vec<u8>, KeyCipherError> {
+        if sealed.len() < NONCE_LEN {
+            return Err(KeyCipherError::Open);
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| KeyCipherError::Open)?;
+
+        let mut in_out = ciphertext.to_vec();
+        let plaintext = self
+            .aead()?
+            .open_in_place(nonce, Aad::from(kid.as_bytes()), &mut in_out)
+            .map_err(|_| KeyCipherError::Open)?;
+        Ok(plaintext.to_vec())
+    }
+
+    fn aead(&self) -> Result<LessSafeKey, KeyCipherError> {
+        let key =
+            UnboundKey::new(&AES_256_GCM, &self.key).map_err(|_| KeyCipherError::InvalidKey)?;
+        Ok(LessSafeKey::new(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_and_binds_kid() {
+        let cipher = KeyCipher::new([7u8; 32]);
+        let sealed = cipher.seal("kid-1", b"secret key").unwrap();
+
+        assert_eq!(cipher.open("kid-1", &sealed).unwrap(), b"secret key");
+        assert!(cipher.open("kid-2", &sealed).is_err());
+        assert!(KeyCipher::new([8u8; 32]).open("kid-1", &sealed).is_err());
+    }
+
+    #[test]
+    fn parses_base64_key() {
+        let encoded = STANDARD.encode([1u8; 32]);
+        assert!(KeyCipher::from_base64(&encoded).is_ok());
+        assert!(KeyCipher::from_base64("c2hvcnQ=").is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use jsonwebtoken::{DecodingKey, EncodingKey};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Public JWK for the JWKS endpoint (RFC 7517, OKP per RFC 8037).
+#[derive(Debug, Clone, Serialize)]
+pub struct PublicJwk {
+    pub kty: &'static str,
+    pub crv: &'static str,
+    pub x: String,
+    pub kid: String,
+    #[serde(rename = "use")]
+    pub use_: &'static str,
+    pub alg: &'static str,
+}
+
+impl PublicJwk {
+    pub fn ed25519(kid: String, x: String) -> Self {
+        Self {
+            kty: "OKP",
+            crv: "Ed25519",
+            x,
+            kid,
+            use_: "sig",
+            alg: "EdDSA",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PublicJwkSet {
+    pub keys: Vec<PublicJwk>,
+}
+
+#[derive(Default)]
+struct KeyRingState {
+    signer: Option<(String, EncodingKey)>,
+    verifiers: HashMap<String, DecodingKey>,
+    published: PublicJwkSet,
+}
+
+/// In-memory view of the signing keys, shared by the issuer, the verifier and the
+/// JWKS endpoint. Rebuilt wholesale by `SigningKeyService::reload`.
+#[derive(Default)]
+pub struct SigningKeyRing {
+    state: RwLock<Arc<KeyRingState>>,
+}
+
+impl std::fmt::Debug for SigningKeyRing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Do not print key material
+        let state = self.snapshot();
+        f.debug_struct("SigningKeyRing")
+            .field("active_kid", &state.signer.as_ref().map(|(kid, _)| kid))
+            .field("published", &state.published.keys.len())
+            .finish()
+    }
+}
+
+impl SigningKeyRing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the key set.
+    ///
+    /// - `signer`: the active key (kid + private key)
+    /// - `public`: (kid, x) of every key tokens may still be signed with (active + retiring)
+    pub fn replace(&self, signer: Option<(String, EncodingKey)>, public: Vec<(String, String)>) {
+        let mut verifiers = HashMap::new();
+        let mut published = PublicJwkSet::default();
+        for (kid, x) in public {
+            match DecodingKey::from_ed_components(&x) {
+                Ok(key) => {
+                    verifiers.insert(kid.clone(), key);
+                    published.keys.push(PublicJwk::ed25519(kid, x));
+                }
+                Err(e) => {
+                    tracing::error!(kid = %kid, error = %e, "invalid signing key public component")
+                }
+            }
+        }
+
+        let next = Arc::new(KeyRingState {
+            signer,
+            verifiers,
+            published,
+        });
+        *self.state.write().unwrap_or_else(|e| e.into_inner()) = next;
+    }
+
+    /// The active key to sign new tokens with.
+    pub fn signing_key(&self) -> Option<(String, EncodingKey)> {
+        self.snapshot().signer.clone()
+    }
+
+    /// Verification key for a token's `kid`.
+    ///
+    /// Tokens without `kid` predate key management and were signed with the key that
+    /// was imported as the first active key, so they are checked against the active key.
+    pub fn decoding_key(&self, kid: Option<&str>) -> Option<DecodingKey> {
+        let state = self.snapshot();
+        let kid = match kid {
+            Some(kid) => kid,
+            None => state.signer.as_ref().map(|(kid, _)| kid.as_str())?,
+        };
+        state.verifiers.get(kid).cloned()
+    }
+
+    pub fn jwks(&self) -> PublicJwkSet {
+        self.snapshot().published.clone()
+    }
+
+    fn snapshot(&self) -> Arc<KeyRingState> {
+        self.state.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
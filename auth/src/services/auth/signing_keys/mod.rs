@@ -0,0 +1,3 @@
+pub mod cipher;
+pub mod key_ring;
+pub mod rotation;
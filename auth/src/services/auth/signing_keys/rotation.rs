@@ -0,0 +1,287 @@
+use aws_lc_rs::{
+    rand::SystemRandom,
+    signature::{Ed25519KeyPair, KeyPair},
+};
+use base64::Engine as _;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::EncodingKey;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use crate::error::AppError;
+use crate::repos::signing_key_repo::{SigningKeyRepo, SigningKeyRow};
+use crate::services::auth::dpop::thumbprint::jwk_thumbprint_okp_ed25519;
+use crate::services::auth::signing_keys::{cipher::KeyCipher, key_ring::SigningKeyRing};
+
+/// Lifecycle state of a signing key (stored as text in `signing_keys.state`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyState {
+    Pending,
+    Active,
+    Retiring,
+    Retired,
+}
+
+impl KeyState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Active => "active",
+            Self::Retiring => "retiring",
+            Self::Retired => "retired",
+        }
+    }
+
+    pub fn parse(v: &str) -> Option<Self> {
+        match v {
+            "pending" => Some(Self::Pending),
+            "active" => Some(Self::Active),
+            "retiring" => Some(Self::Retiring),
+            "retired" => Some(Self::Retired),
+            _ => None,
+        }
+    }
+}
+
+/// Rotation schedule.
+#[derive(Debug, Clone)]
+pub struct RotationPolicy {
+    /// How long a key stays active. `None` disables scheduled rotation.
+    pub rotation_interval: Option<Duration>,
+    /// How far ahead of activation the next key is generated (as `pending`).
+    pub lead_time: Duration,
+    /// How long a replaced key stays published; must cover the access token TTL.
+    pub overlap: Duration,
+}
+
+/// Generates, stores and rotates access-token signing keys.
+///
+/// Every instance runs `tick` on a timer; transitions are idempotent in the repo, so
+/// whichever instance gets there first does the work and the others just reload.
+#[derive(Debug)]
+pub struct SigningKeyService {
+    repo: SigningKeyRepo,
+    cipher: KeyCipher,
+    ring: Arc<SigningKeyRing>,
+    policy: RotationPolicy,
+}
+
+impl SigningKeyService {
+    pub fn new(repo: SigningKeyRepo, cipher: KeyCipher, policy: RotationPolicy) -> Self {
+        Self {
+            repo,
+            cipher,
+            ring: Arc::new(SigningKeyRing::new()),
+            policy,
+        }
+    }
+
+    pub fn ring(&self) -> Arc<SigningKeyRing> {
+        self.ring.clone()
+    }
+
+    /// Make sure an active key exists, then run the schedule once.
+    ///
+    /// `import_pem` (the legacy `ACCESS_JWT_PRIVATE_KEY_PEM`) becomes the first active key,
+    /// so tokens issued before key management keep verifying; otherwise a key is generated.
+    pub async fn bootstrap(&self, import_pem: Option<&str>) -> Result<(), AppError> {
+        let now = Utc::now();
+        let live = self.list_live().await?;
+
+        if !live.iter().any(|k| k.state == KeyState::Active.as_str()) {
+            let key = match import_pem {
+                Some(pem) => KeyMaterial::from_pem(pem)?,
+                None => KeyMaterial::generate()?,
+            };
+            if self.store(&key, KeyState::Active, now, now).await? {
+                info!(kid = %key.kid, imported = import_pem.is_some(), "initial signing key stored");
+            }
+        }
+
+        self.tick(now).await
+    }
+
+    /// Advance the schedule: activate a due pending key, prepare the next one, retire
+    /// keys whose overlap window ended. Then reload the in-memory ring.
+    pub async fn tick(&self, now: DateTime<Utc>) -> Result<(), AppError> {
+        if let Some(kid) = self.repo.activate_due(now).await.map_err(|e| {
+            error!(error = %e, "failed to activate pending signing key");
+            AppError::Internal
+        })? {
+            info!(kid = %kid, "signing key activated");
+        }
+
+        let live = self.list_live().await?;
+        let active = live.iter().find(|k| k.state == KeyState::Active.as_str());
+        let has_pending = live.iter().any(|k| k.state == KeyState::Pending.as_str());
+
+        match (active, self.policy.rotation_interval) {
+            // Only reachable if the table was edited by hand; never run without a key.
+            (None, _) => {
+                warn!("no active signing key; generating one");
+                let key = KeyMaterial::generate()?;
+                self.store(&key, KeyState::Active, now, now).await?;
+            }
+            (Some(active), Some(interval)) if !has_pending => {
+                let activated_at = active.activated_at.unwrap_or(active.activates_at);
+                let due_at = activated_at + interval;
+                if due_at - self.policy.lead_time <= now {
+                    let key = KeyMaterial::generate()?;
+                    if self
+                        .store(&key, KeyState::Pending, due_at.max(now), now)
+                        .await?
+                    {
+                        info!(kid = %key.kid, activates_at = %due_at.max(now), "next signing key scheduled");
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        let retired = self
+            .repo
+            .retire_before(now - self.policy.overlap, now)
+            .await
+            .map_err(|e| {
+                error!(error = %e, "failed to retire signing keys");
+                AppError::Internal
+            })?;
+        if retired > 0 {
+            info!(retired, "signing keys retired");
+        }
+
+        self.reload().await
+    }
+
+    /// Rebuild the in-memory ring from the database.
+    pub async fn reload(&self) -> Result<(), AppError> {
+        let live = self.list_live().await?;
+
+        let mut signer = None;
+        let mut public = Vec::new();
+        for row in live {
+            match KeyState::parse(&row.state) {
+                Some(KeyState::Active) => {
+                    signer = Some((row.kid.clone(), self.decrypt(&row)?));
+                    public.push((row.kid, row.public_x));
+                }
+                Some(KeyState::Retiring) => public.push((row.kid, row.public_x)),
+                _ => {}
+            }
+        }
+
+        if signer.is_none() {
+            error!("no active signing key loaded; token issuance will fail");
+        }
+        self.ring.replace(signer, public);
+        Ok(())
+    }
+
+    /// Run `tick` every `every` in the background.
+    pub fn spawn_scheduler(self: &Arc<Self>, every: std::time::Duration) {
+        let service = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(every);
+            ticker.tick().await; // the first tick completes immediately
+            loop {
+                ticker.tick().await;
+                // Errors are already logged; keep serving with the current ring.
+                let _ = service.tick(Utc::now()).await;
+            }
+        });
+    }
+
+    async fn list_live(&self) -> Result<Vec<SigningKeyRow>, AppError> {
+        self.repo.list_live().await.map_err(|e| {
+            error!(error = %e, "failed to load signing keys");
+            AppError::Internal
+        })
+    }
+
+    async fn store(
+        &self,
+        key: &KeyMaterial,
+        state: KeyState,
+        activates_at: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let sealed = self.cipher.seal(&key.kid, &key.pkcs8).map_err(|e| {
+            error!(kid = %key.kid, error = %e, "failed to encrypt signing key");
+            AppError::Internal
+        })?;
+
+        self.repo
+            .insert(
+                &key.kid,
+                &key.public_x,
+                &sealed,
+                state.as_str(),
+                activates_at,
+                now,
+            )
+            .await
+            .map_err(|e| {
+                error!(kid = %key.kid, error = %e, "failed to store signing key");
+                AppError::Internal
+            })
+    }
+
+    fn decrypt(&self, row: &SigningKeyRow) -> Result<EncodingKey, AppError> {
+        let sealed = row.private_key_enc.as_deref().ok_or_else(|| {
+            error!(kid = %row.kid, "signing key has no private key material");
+            AppError::Internal
+        })?;
+        let pkcs8 = self.cipher.open(&row.kid, sealed).map_err(|e| {
+            // Most likely SIGNING_KEY_ENCRYPTION_KEY differs from the one used to store it.
+            error!(kid = %row.kid, error = %e, "failed to decrypt signing key");
+            AppError::Internal
+        })?;
+        Ok(EncodingKey::from_ed_der(&pkcs8))
+    }
+}
+
+/// A freshly generated or imported Ed25519 key.
+struct KeyMaterial {
+    kid: String,
+    public_x: String,
+    pkcs8: Vec<u8>,
+}
+
+impl KeyMaterial {
+    fn generate() -> Result<Self, AppError> {
+        let doc = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).map_err(|_| {
+            error!("failed to generate signing key");
+            AppError::Internal
+        })?;
+        Self::from_pkcs8(doc.as_ref().to_vec())
+    }
+
+    fn from_pem(pem: &str) -> Result<Self, AppError> {
+        let body = pem
+            .lines()
+            .filter(|l| !l.starts_with("-----"))
+            .collect::<String>();
+        let der = STANDARD.decode(body.trim()).map_err(|_| {
+            error!("ACCESS_JWT_PRIVATE_KEY_PEM is not valid PEM");
+            AppError::Internal
+        })?;
+        Self::from_pkcs8(der)
+    }
+
+    fn from_pkcs8(pkcs8: Vec<u8>) -> Result<Self, AppError> {
+        let pair = Ed25519KeyPair::from_pkcs8(&pkcs8).map_err(|e| {
+            error!(error = %e, "signing key is not an Ed25519 PKCS#8 key");
+            AppError::Internal
+        })?;
+        let public_x = URL_SAFE_NO_PAD.encode(pair.public_key().as_ref());
+        // kid = RFC 7638 thumbprint, so the same key always gets the same kid.
+        let kid = jwk_thumbprint_okp_ed25519(&public_x).map_err(|_| AppError::Internal)?;
+
+        Ok(Self {
+            kid,
+            public_x,
+            pkcs8,
+        })
+    }
+}
@@ -0,0 +1,102 @@
+//! Fixtures shared by the auth service tests: clients' DPoP proof keys, and a signing
+//! key ring with the issuer and verifier that use it.
+
+use std::sync::Arc;
+
+use aws_lc_rs::rand::SystemRandom;
+use aws_lc_rs::signature::{Ed25519KeyPair, KeyPair};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{Algorithm, EncodingKey, Header, jwk::Jwk};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::repos::auth_session_repo::AuthSessionStore;
+use crate::services::auth::access_token_verifier::AccessTokenVerifier;
+use crate::services::auth::dpop::{
+    policy::DpopPolicy, thumbprint::jwk_thumbprint_from_jwk, verifier::DpopVerifier,
+};
+use crate::services::auth::jwt::JwtIssuer;
+use crate::services::auth::signing_keys::key_ring::SigningKeyRing;
+
+pub const ISSUER: &str = "https://auth.example.com";
+pub const AUDIENCE: &str = "api";
+pub const TOKEN_URL: &str = "https://auth.example.com/api/v1/token";
+
+/// A fresh Ed25519 key: PKCS#8 DER and the base64url public key (JWK `x`).
+pub fn ed25519() -> (Vec<u8>, String) {
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+    let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+    let x = URL_SAFE_NO_PAD.encode(pair.public_key().as_ref());
+    (pkcs8.as_ref().to_vec(), x)
+}
+
+/// A client's DPoP key.
+pub struct ProofKey {
+    pub encoding: EncodingKey,
+    pub jwk: Jwk,
+    pub jkt: String,
+}
+
+impl ProofKey {
+    pub fn generate() -> Self {
+        let (pkcs8, x) = ed25519();
+        let jwk: Jwk =
+            serde_json::from_value(json!({ "kty": "OKP", "crv": "Ed25519", "x": x })).unwrap();
+        Self {
+            encoding: EncodingKey::from_ed_der(&pkcs8),
+            jkt: jwk_thumbprint_from_jwk(&jwk).unwrap(),
+            jwk,
+        }
+    }
+
+    /// A fresh proof for `POST` to the token endpoint.
+    pub fn proof(&self) -> String {
+        self.proof_at(&Uuid::new_v4().to_string(), Utc::now())
+    }
+
+    pub fn proof_at(&self, jti: &str, iat: DateTime<Utc>) -> String {
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.typ = Some("dpop+jwt".into());
+        header.jwk = Some(self.jwk.clone());
+        let claims = json!({
+            "htm": "POST",
+            "htu": TOKEN_URL,
+            "iat": iat.timestamp(),
+            "jti": jti,
+        });
+        jsonwebtoken::encode(&header, &claims, &self.encoding).unwrap()
+    }
+}
+
+/// A key ring with one active signing key, `k1`.
+pub fn key_ring() -> Arc<SigningKeyRing> {
+    let (pkcs8, x) = ed25519();
+    let keys = Arc::new(SigningKeyRing::new());
+    keys.replace(
+        Some(("k1".into(), EncodingKey::from_ed_der(&pkcs8))),
+        vec![("k1".into(), x)],
+    );
+    keys
+}
+
+/// Signs access tokens for `ISSUER`/`AUDIENCE` with a 5 minute lifetime.
+pub fn jwt_issuer(keys: Arc<SigningKeyRing>) -> JwtIssuer {
+    JwtIssuer::new(keys, ISSUER.into(), AUDIENCE.into(), 300)
+}
+
+/// Verifies tokens signed by `keys` against `sessions`.
+pub fn access_token_verifier(
+    keys: Arc<SigningKeyRing>,
+    dpop: Arc<DpopVerifier>,
+    sessions: Arc<dyn AuthSessionStore>,
+) -> Arc<AccessTokenVerifier> {
+    Arc::new(AccessTokenVerifier::new(
+        keys, ISSUER, AUDIENCE, dpop, sessions,
+    ))
+}
+
+/// A DPoP verifier with the default policy and no replay store.
+pub fn dpop_verifier() -> Arc<DpopVerifier> {
+    Arc::new(DpopVerifier::new(DpopPolicy::default(), None))
+}
@@ -0,0 +1,555 @@
+//! Brute-force and abuse protection for the token endpoint and the sign-in form.
+//!
+//! Failed grants and sign-ins are counted per client IP, subject (username or
+//! client_id) and DPoP key thumbprint within a window; `/token` and `/authorize`
+//! share the counters of a username. Past a few free failures each further one blocks
+//! the key for an exponentially growing delay; past the lockout threshold it is
+//! blocked for the lockout period. Blocked requests are answered `429` with
+//! `Retry-After`. New sessions are also budgeted per IP and key, so the endpoint can't
+//! be used to mint sessions without bound.
+//!
+//! State lives in Valkey (`auth:throttle:*`) when configured so every instance sees
+//! the same counters; otherwise, and whenever Valkey fails, in process memory.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{future::Future, pin::Pin};
+
+use tracing::{error, warn};
+
+use crate::config::Config;
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+// In-memory entries are pruned once there are this many.
+const MEMORY_PRUNE_THRESHOLD: usize = 10_000;
+
+/// Counters and blocks by key; every entry expires on its own.
+pub trait ThrottleStore: Send + Sync {
+    /// Count one hit on `key`; the count restarts `window_secs` after the first hit.
+    fn hit<'a>(
+        &'a self,
+        key: &'a str,
+        window_secs: u64,
+    ) -> BoxFuture<'a, Result<u32, redis::RedisError>>;
+
+    fn clear<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), redis::RedisError>>;
+
+    /// Refuse `key` for `secs`.
+    fn block<'a>(&'a self, key: &'a str, secs: u64)
+    -> BoxFuture<'a, Result<(), redis::RedisError>>;
+
+    /// Seconds left on the block of `key`, if any.
+    fn blocked_for<'a>(
+        &'a self,
+        key: &'a str,
+    ) -> BoxFuture<'a, Result<Option<u64>, redis::RedisError>>;
+}
+
+/// Valkey-backed store, shared by all instances.
+#[derive(Clone)]
+pub struct ValkeyThrottleStore {
+    manager: redis::aio::ConnectionManager,
+    prefix: String,
+}
+
+impl std::fmt::Debug for ValkeyThrottleStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ValkeyThrottleStore")
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+impl ValkeyThrottleStore {
+    pub async fn new(redis_url: &str) -> Result<Self, redis::RedisError> {
+        let client = redis::Client::open(redis_url)?;
+        let manager = client.get_connection_manager().await?;
+
+        Ok(Self {
+            manager,
+            prefix: "auth:throttle".to_string(),
+        })
+    }
+}
+
+impl ThrottleStore for ValkeyThrottleStore {
+    fn hit<'a>(
+        &'a self,
+        key: &'a str,
+        window_secs: u64,
+    ) -> BoxFuture<'a, Result<u32, redis::RedisError>> {
+        Box::pin(async move {
+            let mut conn = self.manager.clone();
+            let key = format!("{}:n:{key}", self.prefix);
+
+            // INCR, then start the window on the first hit only (EXPIRE NX).
+            let (count,): (u32,) = redis::pipe()
+                .atomic()
+                .incr(&key, 1)
+                .cmd("EXPIRE")
+                .arg(&key)
+                .arg(window_secs.max(1))
+                .arg("NX")
+                .ignore()
+                .query_async(&mut conn)
+                .await?;
+            Ok(count)
+        })
+    }
+
+    fn clear<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), redis::RedisError>> {
+        Box::pin(async move {
+            let mut conn = self.manager.clone();
+            redis::cmd("DEL")
+                .arg(format!("{}:n:{key}", self.prefix))
+                .query_async::<()>(&mut conn)
+                .await
+        })
+    }
+
+    fn block<'a>(
+        &'a self,
+        key: &'a str,
+        secs: u64,
+    ) -> BoxFuture<'a, Result<(), redis::RedisError>> {
+        Box::pin(async move {
+            let mut conn = self.manager.clone();
+            redis::cmd("SET")
+                .arg(format!("{}:b:{key}", self.prefix))
+                .arg("1")
+                .arg("EX")
+                .arg(secs.max(1))
+                .query_async::<()>(&mut conn)
+                .await
+        })
+    }
+
+    fn blocked_for<'a>(
+        &'a self,
+        key: &'a str,
+    ) -> BoxFuture<'a, Result<Option<u64>, redis::RedisError>> {
+        Box::pin(async move {
+            let mut conn = self.manager.clone();
+            // TTL: seconds left, -2 when the key doesn't exist.
+            let ttl: i64 = redis::cmd("TTL")
+                .arg(format!("{}:b:{key}", self.prefix))
+                .query_async(&mut conn)
+                .await?;
+            Ok((ttl > 0).then_some(ttl as u64))
+        })
+    }
+}
+
+#[derive(Debug)]
+struct MemoryEntry {
+    count: u32,
+    window_ends: Instant,
+    blocked_until: Option<Instant>,
+}
+
+impl MemoryEntry {
+    fn expired(&self, now: Instant) -> bool {
+        self.window_ends <= now && self.blocked_until.is_none_or(|b| b <= now)
+    }
+}
+
+/// Per-process store: without Valkey, and while Valkey is failing.
+#[derive(Debug, Default)]
+pub struct MemoryThrottleStore {
+    entries: Mutex<HashMap<String, MemoryEntry>>,
+}
+
+impl MemoryThrottleStore {
+    fn with_entry<T>(&self, key: &str, f: impl FnOnce(&mut MemoryEntry, Instant) -> T) -> T {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= MEMORY_PRUNE_THRESHOLD {
+            entries.retain(|_, e| !e.expired(now));
+        }
+        let entry = entries.entry(key.to_string()).or_insert(MemoryEntry {
+            count: 0,
+            window_ends: now,
+            blocked_until: None,
+        });
+        f(entry, now)
+    }
+}
+
+impl ThrottleStore for MemoryThrottleStore {
+    fn hit<'a>(
+        &'a self,
+        key: &'a str,
+        window_secs: u64,
+    ) -> BoxFuture<'a, Result<u32, redis::RedisError>> {
+        let count = self.with_entry(key, |e, now| {
+            if e.window_ends <= now {
+                e.count = 0;
+                e.window_ends = now + Duration::from_secs(window_secs.max(1));
+            }
+            e.count = e.count.saturating_add(1);
+            e.count
+        });
+        Box::pin(async move { Ok(count) })
+    }
+
+    fn clear<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Result<(), redis::RedisError>> {
+        self.with_entry(key, |e, _| e.count = 0);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn block<'a>(
+        &'a self,
+        key: &'a str,
+        secs: u64,
+    ) -> BoxFuture<'a, Result<(), redis::RedisError>> {
+        self.with_entry(key, |e, now| {
+            e.blocked_until = Some(now + Duration::from_secs(secs.max(1)));
+        });
+        Box::pin(async { Ok(()) })
+    }
+
+    fn blocked_for<'a>(
+        &'a self,
+        key: &'a str,
+    ) -> BoxFuture<'a, Result<Option<u64>, redis::RedisError>> {
+        let left = self.with_entry(key, |e, now| {
+            e.blocked_until
+                .filter(|b| *b > now)
+                // Round up so `Retry-After` never says 0.
+                .map(|b| (b - now).as_millis().div_ceil(1000) as u64)
+        });
+        Box::pin(async move { Ok(left) })
+    }
+}
+
+/// What a key is derived from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThrottleKind {
+    Ip,
+    Subject,
+    Jkt,
+}
+
+impl ThrottleKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Ip => "ip",
+            Self::Subject => "subject",
+            Self::Jkt => "jkt",
+        }
+    }
+}
+
+/// Failures tolerated before backoff and before lockout.
+#[derive(Debug, Clone, Copy)]
+pub struct FailureLimits {
+    pub free_failures: u32,
+    pub lockout_failures: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ThrottlePolicy {
+    pub enabled: bool,
+    /// Subject and DPoP key.
+    pub limits: FailureLimits,
+    /// Client IP; higher, since many users may share one (NAT, proxies).
+    pub ip_limits: FailureLimits,
+    pub base_delay_seconds: u64,
+    pub max_delay_seconds: u64,
+    pub lockout_seconds: u64,
+    /// Failures (and new sessions) are counted over this long.
+    pub window_seconds: u64,
+    /// New sessions per IP and per DPoP key within the window (0: unlimited).
+    pub max_new_sessions: u32,
+}
+
+impl Default for ThrottlePolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            limits: FailureLimits {
+                free_failures: 3,
+                lockout_failures: 10,
+            },
+            ip_limits: FailureLimits {
+                free_failures: 10,
+                lockout_failures: 50,
+            },
+            base_delay_seconds: 1,
+            max_delay_seconds: 60,
+            lockout_seconds: 900,
+            window_seconds: 900,
+            max_new_sessions: 30,
+        }
+    }
+}
+
+impl ThrottlePolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            enabled: config.token_throttle_enabled,
+            limits: FailureLimits {
+                free_failures: config.token_throttle_free_failures,
+                lockout_failures: config.token_throttle_lockout_failures,
+            },
+            ip_limits: FailureLimits {
+                free_failures: config.token_throttle_ip_free_failures,
+                lockout_failures: config.token_throttle_ip_lockout_failures,
+            },
+            base_delay_seconds: config.token_throttle_base_delay_seconds,
+            max_delay_seconds: config.token_throttle_max_delay_seconds,
+            lockout_seconds: config.token_throttle_lockout_seconds,
+            window_seconds: config.token_throttle_window_seconds,
+            max_new_sessions: config.token_throttle_max_new_sessions,
+        }
+    }
+
+    fn limits(&self, kind: ThrottleKind) -> FailureLimits {
+        match kind {
+            ThrottleKind::Ip => self.ip_limits,
+            ThrottleKind::Subject | ThrottleKind::Jkt => self.limits,
+        }
+    }
+
+    /// How long to block a key after its `failures`-th failure.
+    pub fn penalty(&self, kind: ThrottleKind, failures: u32) -> Option<Penalty> {
+        let limits = self.limits(kind);
+        if failures >= limits.lockout_failures {
+            return Some(Penalty::Lockout(self.lockout_seconds));
+        }
+        let over = failures.checked_sub(limits.free_failures)?.checked_sub(1)?;
+        let delay = self
+            .base_delay_seconds
+            .saturating_mul(1u64.checked_shl(over).unwrap_or(u64::MAX))
+            .min(self.max_delay_seconds);
+        (delay > 0).then_some(Penalty::Backoff(delay))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Penalty {
+    Backoff(u64),
+    Lockout(u64),
+}
+
+/// The keys of one `/token` or `/authorize` request.
+#[derive(Debug, Clone, Default)]
+pub struct ThrottleKeys {
+    pub ip: Option<String>,
+    pub subject: Option<String>,
+    pub jkt: Option<String>,
+}
+
+impl ThrottleKeys {
+    /// Subject key of a user signing in with a username.
+    pub fn user_subject(username: &str) -> String {
+        format!("user:{}", username.trim().to_lowercase())
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (ThrottleKind, &str)> {
+        [
+            (ThrottleKind::Ip, self.ip.as_deref()),
+            (ThrottleKind::Subject, self.subject.as_deref()),
+            (ThrottleKind::Jkt, self.jkt.as_deref()),
+        ]
+        .into_iter()
+        .filter_map(|(kind, v)| v.map(|v| (kind, v)))
+    }
+}
+
+/// A key that reached the lockout threshold.
+#[derive(Debug, Clone)]
+pub struct Lockout {
+    pub kind: ThrottleKind,
+    pub failures: u32,
+    pub seconds: u64,
+}
+
+/// Applies the throttle policy to `/token` requests.
+pub struct TokenThrottle {
+    store: Arc<dyn ThrottleStore>,
+    fallback: Arc<MemoryThrottleStore>,
+    policy: ThrottlePolicy,
+}
+
+impl std::fmt::Debug for TokenThrottle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenThrottle")
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+impl TokenThrottle {
+    pub fn new(store: Arc<dyn ThrottleStore>, policy: ThrottlePolicy) -> Self {
+        Self {
+            store,
+            fallback: Arc::new(MemoryThrottleStore::default()),
+            policy,
+        }
+    }
+
+    /// Per-process only (no Valkey).
+    pub fn in_memory(policy: ThrottlePolicy) -> Self {
+        let fallback = Arc::new(MemoryThrottleStore::default());
+        Self {
+            store: fallback.clone(),
+            fallback,
+            policy,
+        }
+    }
+
+    /// Seconds until the request may be retried, when any of its keys is blocked.
+    pub async fn retry_after(&self, keys: &ThrottleKeys) -> Option<u64> {
+        if !self.policy.enabled {
+            return None;
+        }
+
+        let mut longest = None;
+        for (kind, value) in keys.iter() {
+            let key = key(kind, "fail", value);
+            let left = match self.store.blocked_for(&key).await {
+                Ok(v) => v,
+                Err(e) => {
+                    warn!(error = %e, "throttle store failed; using the in-memory fallback");
+                    self.fallback.blocked_for(&key).await.unwrap_or(None)
+                }
+            };
+            longest = longest.max(left);
+        }
+        longest
+    }
+
+    /// Count a failed grant; returns the keys this failure locked out.
+    pub async fn record_failure(&self, keys: &ThrottleKeys) -> Vec<Lockout> {
+        if !self.policy.enabled {
+            return Vec::new();
+        }
+
+        let mut lockouts = Vec::new();
+        for (kind, value) in keys.iter() {
+            let key = key(kind, "fail", value);
+            let failures = self.hit(&key).await;
+            let Some(penalty) = self.policy.penalty(kind, failures) else {
+                continue;
+            };
+            let seconds = match penalty {
+                Penalty::Backoff(s) => s,
+                Penalty::Lockout(s) => {
+                    lockouts.push(Lockout {
+                        kind,
+                        failures,
+                        seconds: s,
+                    });
+                    s
+                }
+            };
+            self.block(&key, seconds).await;
+        }
+        lockouts
+    }
+
+    /// A grant succeeded: forget the subject's and key's failures (not the IP's, which
+    /// one valid account would otherwise reset for everyone behind it). When it created
+    /// a session, count it against the IP's and key's budget.
+    pub async fn record_success(&self, keys: &ThrottleKeys, new_session: bool) {
+        if !self.policy.enabled {
+            return;
+        }
+
+        for (kind, value) in keys.iter() {
+            if kind != ThrottleKind::Ip {
+                let key = key(kind, "fail", value);
+                if let Err(e) = self.store.clear(&key).await {
+                    error!(error = %e, "failed to clear throttle counter");
+                    let _ = self.fallback.clear(&key).await;
+                }
+            }
+
+            if new_session && kind != ThrottleKind::Subject && self.policy.max_new_sessions > 0 {
+                let sessions = self.hit(&key(kind, "sessions", value)).await;
+                if sessions >= self.policy.max_new_sessions {
+                    // Out of budget until the window ends.
+                    warn!(target: "security", event = "token_session_budget_exhausted", kind = kind.as_str(), sessions, "new session budget exhausted");
+                    self.block(&key(kind, "fail", value), self.policy.window_seconds)
+                        .await;
+                }
+            }
+        }
+    }
+
+    async fn hit(&self, key: &str) -> u32 {
+        match self.store.hit(key, self.policy.window_seconds).await {
+            Ok(n) => n,
+            Err(e) => {
+                warn!(error = %e, "throttle store failed; using the in-memory fallback");
+                self.fallback
+                    .hit(key, self.policy.window_seconds)
+                    .await
+                    .unwrap_or(0)
+            }
+        }
+    }
+
+    async fn block(&self, key: &str, seconds: u64) {
+        if let Err(e) = self.store.block(key, seconds).await {
+            warn!(error = %e, "throttle store failed; using the in-memory fallback");
+            let _ = self.fallback.block(key, seconds).await;
+        }
+    }
+}
+
+fn key(kind: ThrottleKind, counter: &str, value: &str) -> String {
+    format!("{counter}:{}:{value}", kind.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_then_locks_out() {
+        let policy = ThrottlePolicy::default();
+
+        assert_eq!(policy.penalty(ThrottleKind::Subject, 3), None);
+        assert_eq!(
+            policy.penalty(ThrottleKind::Subject, 4),
+            Some(Penalty::Backoff(1))
+        );
+        assert_eq!(
+            policy.penalty(ThrottleKind::Subject, 6),
+            Some(Penalty::Backoff(4))
+        );
+        assert_eq!(
+            policy.penalty(ThrottleKind::Subject, 10),
+            Some(Penalty::Lockout(900))
+        );
+        // The IP tolerates more before backing off.
+        assert_eq!(policy.penalty(ThrottleKind::Ip, 6), None);
+    }
+
+    #[tokio::test]
+    async fn memory_store_blocks_until_expiry() {
+        let throttle = TokenThrottle::in_memory(ThrottlePolicy {
+            limits: FailureLimits {
+                free_failures: 0,
+                lockout_failures: 2,
+            },
+            ..ThrottlePolicy::default()
+        });
+        let keys = ThrottleKeys {
+            subject: Some("alice".into()),
+            ..ThrottleKeys::default()
+        };
+
+        assert!(throttle.record_failure(&keys).await.is_empty());
+        assert_eq!(throttle.retry_after(&keys).await, Some(1));
+
+        let lockouts = throttle.record_failure(&keys).await;
+        assert_eq!(lockouts.len(), 1);
+        assert_eq!(lockouts[0].kind, ThrottleKind::Subject);
+        assert_eq!(throttle.retry_after(&keys).await, Some(900));
+    }
+}
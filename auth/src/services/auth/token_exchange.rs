@@ -0,0 +1,476 @@
+use std::sync::Arc;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::error::{AppError, OAuthError};
+use crate::repos::auth_session_repo::AuthSessionStore;
+use crate::services::auth::{
+    access_token_issuer::{AccessTokenService, ActorClaim, DelegatedAccessToken},
+    access_token_verifier::AccessTokenVerifier,
+    authn_context::AuthnContext,
+    client_auth::AuthenticatedClient,
+    scope::{self, ScopeService},
+    token_service::{TokenService, token_type},
+};
+
+/// `grant_type` of token exchange (RFC 8693 Section 2.1).
+pub const TOKEN_EXCHANGE_GRANT: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+
+/// The only token type we accept and issue (RFC 8693 Section 3).
+pub const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+
+/// `/token` parameters of a token exchange request.
+#[derive(Debug, Clone, Copy)]
+pub struct ExchangeRequest<'a> {
+    /// The client performing the exchange (the backend-for-frontend).
+    pub client: &'a AuthenticatedClient,
+    pub subject_token: &'a str,
+    pub subject_token_type: &'a str,
+    pub actor_token: Option<&'a str>,
+    pub actor_token_type: Option<&'a str>,
+    pub requested_token_type: Option<&'a str>,
+    pub audience: Option<&'a str>,
+    pub resource: Option<&'a str>,
+    pub scope: Option<&'a str>,
+}
+
+/// Access token minted by token exchange; there is no refresh token.
+#[derive(Clone, Debug)]
+pub struct ExchangedToken {
+    pub access_token: String,
+    pub issued_token_type: &'static str,
+    pub token_type: &'static str,
+    pub expires_in: u64,
+    pub scope: Option<String>,
+    // The subject's user and session, and the proof key the new token is bound to.
+    pub user_id: Uuid,
+    pub session_id: Uuid,
+    pub jkt: String,
+}
+
+/// OAuth 2.0 Token Exchange (RFC 8693) for calling other services on a user's behalf.
+///
+/// - subject: a user access token of an active session
+/// - actor: the `actor_token`'s subject when given (it must have been issued to the
+///   exchanging client and be bound to the same DPoP key), otherwise the client itself
+/// - the new token is addressed to one of the client's registered audiences, carries
+///   at most the scopes of both the subject token and the client, the same `sid`
+///   (session revocation applies) and an `act` claim, and never outlives the subject
+///   token
+#[derive(Clone)]
+pub struct TokenExchangeService {
+    access: Arc<AccessTokenVerifier>,
+    issuer: AccessTokenService,
+    sessions: Arc<dyn AuthSessionStore>,
+    scopes: ScopeService,
+    tokens: Arc<TokenService>,
+}
+
+impl TokenExchangeService {
+    pub fn new(
+        access: Arc<AccessTokenVerifier>,
+        issuer: AccessTokenService,
+        sessions: Arc<dyn AuthSessionStore>,
+        scopes: ScopeService,
+        tokens: Arc<TokenService>,
+    ) -> Self {
+        Self {
+            access,
+            issuer,
+            sessions,
+            scopes,
+            tokens,
+        }
+    }
+
+    pub async fn exchange(
+        &self,
+        req: ExchangeRequest<'_>,
+        dpop_proof: &str,
+        method: &str,
+        url: &str,
+    ) -> Result<ExchangedToken, AppError> {
+        let client = req.client;
+        if !client.is_confidential() || !client.allows_grant(TOKEN_EXCHANGE_GRANT) {
+            warn!(target: "security", event = "unauthorized_client", client_id = %client.client_id, "client may not use token exchange");
+            return Err(OAuthError::UnauthorizedClient.into());
+        }
+
+        if req.subject_token_type != ACCESS_TOKEN_TYPE {
+            return Err(invalid_request("unsupported subject_token_type"));
+        }
+        if req
+            .requested_token_type
+            .is_some_and(|t| t != ACCESS_TOKEN_TYPE)
+        {
+            return Err(invalid_request("unsupported requested_token_type"));
+        }
+        match (req.actor_token, req.actor_token_type) {
+            (Some(_), Some(ACCESS_TOKEN_TYPE)) | (None, None) => {}
+            (Some(_), Some(_)) => return Err(invalid_request("unsupported actor_token_type")),
+            _ => {
+                return Err(invalid_request(
+                    "actor_token and actor_token_type go together",
+                ));
+            }
+        }
+
+        let audience = target(client, req.audience, req.resource)?;
+        let jkt = self
+            .tokens
+            .verify_issue_proof(dpop_proof, method, url)
+            .await?;
+
+        let subject = self
+            .access
+            .decode(req.subject_token)
+            .map_err(|_| invalid_request("invalid subject_token"))?;
+        let session = self
+            .sessions
+            .get_active_by_id(subject.session_id)
+            .await
+            .map_err(|e| {
+                error!(session_id = %subject.session_id, error = %e, "failed to load auth session");
+                AppError::Internal
+            })?;
+        let session = match session {
+            Some(s) if s.user_id == subject.user_id => s,
+            _ => return Err(invalid_request("invalid subject_token")),
+        };
+
+        let actor = match req.actor_token {
+            Some(token) => {
+                let actor = self
+                    .access
+                    .verify(token)
+                    .map_err(|_| invalid_request("invalid actor_token"))?;
+                if actor.client_id.as_deref() != Some(client.client_id.as_str())
+                    || actor.jkt.as_deref() != Some(jkt.as_str())
+                {
+                    warn!(target: "security", event = "token_exchange_rejected", client_id = %client.client_id, "actor_token not issued to the client or not bound to the proof key");
+                    return Err(invalid_request("invalid actor_token"));
+                }
+                actor.sub
+            }
+            None => client.client_id.clone(),
+        };
+
+        // Narrow: the subject token's scopes, the client's scopes, then the request.
+        let allowed = scope::split(subject.scope.as_deref())
+            .into_iter()
+            .filter(|s| client.scopes.contains(s))
+            .collect::<Vec<_>>();
+        let requested = scope::join(scope::narrow(&allowed, req.scope)?);
+        // Drops roles and scopes the user lost since the subject token was issued.
+        let grant = self
+            .scopes
+            .regrant_for_user(subject.user_id, requested.as_deref())
+            .await?;
+
+        let (access_token, expires_in) = self
+            .issuer
+            .issue_delegated_access_token(DelegatedAccessToken {
+                sub: subject.user_id,
+                session_id: subject.session_id,
+                audience,
+                client_id: &client.client_id,
+                scope: grant.scope.clone(),
+                roles: grant.roles,
+                // The delegated call is as strong as the user's sign-in, no stronger.
+                authn: AuthnContext::from_session(session.acr, session.amr),
+                jkt: jkt.clone(),
+                act: ActorClaim {
+                    sub: actor,
+                    // Prior actors of a token that was itself delegated.
+                    act: subject.act.map(Box::new),
+                },
+                not_after: subject.exp,
+            })
+            .await?;
+
+        Ok(ExchangedToken {
+            access_token,
+            issued_token_type: ACCESS_TOKEN_TYPE,
+            token_type: token_type(Some(&jkt)),
+            expires_in,
+            scope: grant.scope,
+            user_id: subject.user_id,
+            session_id: subject.session_id,
+            jkt,
+        })
+    }
+}
+
+// `audience` (logical name) or `resource` (URI, RFC 8707) of the target service; a
+// single value, registered for the client.
+fn target<'a>(
+    client: &AuthenticatedClient,
+    audience: Option<&'a str>,
+    resource: Option<&'a str>,
+) -> Result<&'a str, AppError> {
+    let target = match (audience, resource) {
+        (Some(a), Some(r)) if a != r => return Err(OAuthError::InvalidTarget.into()),
+        (Some(t), _) | (None, Some(t)) => t,
+        (None, None) => return Err(invalid_request("audience or resource is required")),
+    };
+    if !client.audiences.iter().any(|a| a == target) {
+        warn!(client_id = %client.client_id, target, "token exchange for an audience not registered for the client");
+        return Err(OAuthError::InvalidTarget.into());
+    }
+    Ok(target)
+}
+
+fn invalid_request(msg: &str) -> AppError {
+    OAuthError::InvalidRequest(msg.to_string()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::repos::auth_session_repo::NewAuthSession;
+    use crate::repos::memory_store::MemoryAuthStore;
+    use crate::repos::role_repo::RoleRepo;
+    use crate::services::auth::access_token_issuer::UserAccessToken;
+    use crate::services::auth::client_auth::ClientAuthMethod;
+    use crate::services::auth::refresh_token_issuer::RefreshTokenService;
+    use crate::services::auth::session_policy::ClientSessionLimits;
+    use crate::services::auth::test_support::{self, ProofKey, TOKEN_URL};
+
+    struct Fixture {
+        store: MemoryAuthStore,
+        issuer: AccessTokenService,
+        service: TokenExchangeService,
+    }
+
+    fn fixture() -> Fixture {
+        let keys = test_support::key_ring();
+        let store = MemoryAuthStore::new();
+        let sessions: Arc<dyn AuthSessionStore> = Arc::new(store.clone());
+        let dpop = test_support::dpop_verifier();
+        let access =
+            test_support::access_token_verifier(keys.clone(), dpop.clone(), sessions.clone());
+        let issuer = AccessTokenService::new(test_support::jwt_issuer(keys));
+        // Never queried: every case below is rejected before scopes are re-granted.
+        let scopes = ScopeService::new(RoleRepo::new(
+            sqlx::PgPool::connect_lazy("postgres://localhost/unused").unwrap(),
+        ));
+        let tokens = Arc::new(TokenService::new(
+            issuer.clone(),
+            RefreshTokenService::new(Arc::new(store.clone()), sessions.clone(), 3_600),
+            sessions.clone(),
+            dpop,
+            scopes.clone(),
+        ));
+
+        Fixture {
+            service: TokenExchangeService::new(access, issuer.clone(), sessions, scopes, tokens),
+            issuer,
+            store,
+        }
+    }
+
+    impl Fixture {
+        // A user's access token, bound to `key`, and its session.
+        async fn subject_token(&self, key: &ProofKey) -> (String, Uuid) {
+            let user_id = Uuid::new_v4();
+            let session_id = self
+                .store
+                .create(NewAuthSession {
+                    user_id,
+                    dpop_jkt: Some(key.jkt.clone()),
+                    client_id: Some("web"),
+                    scope: Some("read"),
+                    expires_at: None,
+                    idle_timeout_seconds: None,
+                    acr: Some("aal1"),
+                    amr: &["pwd".to_string()],
+                })
+                .await
+                .unwrap()
+                .id;
+            let token = self
+                .issuer
+                .issue_access_token(UserAccessToken {
+                    sub: &user_id.to_string(),
+                    session_id,
+                    jkt: Some(key.jkt.clone()),
+                    client_id: Some("web".into()),
+                    scope: Some("read".into()),
+                    roles: Vec::new(),
+                    authn: None,
+                })
+                .await
+                .unwrap();
+            (token, session_id)
+        }
+
+        async fn exchange(
+            &self,
+            client: &AuthenticatedClient,
+            subject_token: &str,
+            actor_token: Option<&str>,
+            key: &ProofKey,
+        ) -> Result<ExchangedToken, AppError> {
+            let req = ExchangeRequest {
+                client,
+                subject_token,
+                subject_token_type: ACCESS_TOKEN_TYPE,
+                actor_token,
+                actor_token_type: actor_token.map(|_| ACCESS_TOKEN_TYPE),
+                requested_token_type: None,
+                audience: Some("orders"),
+                resource: None,
+                scope: None,
+            };
+            self.service
+                .exchange(req, &key.proof(), "POST", TOKEN_URL)
+                .await
+        }
+    }
+
+    fn bff() -> AuthenticatedClient {
+        AuthenticatedClient {
+            client_id: "bff".into(),
+            method: ClientAuthMethod::ClientSecretBasic,
+            grant_types: vec![TOKEN_EXCHANGE_GRANT.into()],
+            scopes: vec!["read".into()],
+            access_token_ttl_seconds: None,
+            audiences: vec!["orders".into()],
+            session_limits: ClientSessionLimits::default(),
+        }
+    }
+
+    fn is_invalid_request(result: Result<ExchangedToken, AppError>) -> bool {
+        matches!(result, Err(AppError::OAuth(OAuthError::InvalidRequest(_))))
+    }
+
+    #[test]
+    fn target_must_be_a_single_registered_audience() {
+        let client = bff();
+        assert_eq!(target(&client, Some("orders"), None).unwrap(), "orders");
+        assert_eq!(target(&client, None, Some("orders")).unwrap(), "orders");
+        assert_eq!(
+            target(&client, Some("orders"), Some("orders")).unwrap(),
+            "orders"
+        );
+        assert!(matches!(
+            target(&client, Some("orders"), Some("billing")),
+            Err(AppError::OAuth(OAuthError::InvalidTarget))
+        ));
+        assert!(matches!(
+            target(&client, Some("billing"), None),
+            Err(AppError::OAuth(OAuthError::InvalidTarget))
+        ));
+        assert!(matches!(
+            target(&client, None, None),
+            Err(AppError::OAuth(OAuthError::InvalidRequest(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn only_confidential_clients_with_the_grant_may_exchange() {
+        let f = fixture();
+        let key = ProofKey::generate();
+        let (subject, _) = f.subject_token(&key).await;
+
+        let public = AuthenticatedClient {
+            method: ClientAuthMethod::None,
+            ..bff()
+        };
+        let without_grant = AuthenticatedClient {
+            grant_types: vec!["client_credentials".into()],
+            ..bff()
+        };
+        for client in [public, without_grant] {
+            assert!(matches!(
+                f.exchange(&client, &subject, None, &key).await,
+                Err(AppError::OAuth(OAuthError::UnauthorizedClient))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn subject_token_of_a_revoked_session_is_rejected() {
+        let f = fixture();
+        let key = ProofKey::generate();
+        let (subject, session_id) = f.subject_token(&key).await;
+        f.store.revoke(session_id, Utc::now()).await.unwrap();
+
+        assert!(is_invalid_request(
+            f.exchange(&bff(), &subject, None, &key).await
+        ));
+        assert!(is_invalid_request(
+            f.exchange(&bff(), "not-a-token", None, &key).await
+        ));
+    }
+
+    #[tokio::test]
+    async fn actor_token_must_belong_to_the_client_and_the_proof_key() {
+        let f = fixture();
+        let key = ProofKey::generate();
+        let (subject, _) = f.subject_token(&key).await;
+
+        // Issued to another client.
+        let other_client = f
+            .issuer
+            .issue_client_access_token("batch", None, key.jkt.clone(), None)
+            .await
+            .unwrap();
+        assert!(is_invalid_request(
+            f.exchange(&bff(), &subject, Some(&other_client), &key)
+                .await
+        ));
+
+        // Issued to the client, but bound to a different key than the proof's.
+        let other_key = f
+            .issuer
+            .issue_client_access_token("bff", None, ProofKey::generate().jkt, None)
+            .await
+            .unwrap();
+        assert!(is_invalid_request(
+            f.exchange(&bff(), &subject, Some(&other_key), &key).await
+        ));
+    }
+
+    #[tokio::test]
+    async fn unsupported_token_types_are_rejected() {
+        let f = fixture();
+        let key = ProofKey::generate();
+        let (subject, _) = f.subject_token(&key).await;
+        let client = bff();
+        let base = ExchangeRequest {
+            client: &client,
+            subject_token: &subject,
+            subject_token_type: ACCESS_TOKEN_TYPE,
+            actor_token: None,
+            actor_token_type: None,
+            requested_token_type: None,
+            audience: Some("orders"),
+            resource: None,
+            scope: None,
+        };
+
+        for req in [
+            ExchangeRequest {
+                subject_token_type: "urn:ietf:params:oauth:token-type:id_token",
+                ..base
+            },
+            ExchangeRequest {
+                requested_token_type: Some("urn:ietf:params:oauth:token-type:refresh_token"),
+                ..base
+            },
+            ExchangeRequest {
+                actor_token: Some(&subject),
+                ..base
+            },
+        ] {
+            let result = f
+                .service
+                .exchange(req, &key.proof(), "POST", TOKEN_URL)
+                .await;
+            assert!(is_invalid_request(result));
+        }
+    }
+}
@@ -1,13 +1,20 @@
 use chrono::{DateTime, Utc};
 use std::sync::Arc;
-use tracing::error;
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
-use crate::error::AppError;
-use crate::repos::auth_session_repo::AuthSessionRepo;
+use crate::error::{AppError, OAuthError};
+use crate::repos::auth_session_repo::{AuthSessionStore, NewAuthSession};
 use crate::services::auth::{
-    access_token_issuer::AccessTokenService, dpop::verifier::DpopVerifier,
+    access_token_issuer::{AccessTokenService, UserAccessToken},
+    audit::{AuditEvent, AuditEventType, AuditLog},
+    authn_context::AuthnContext,
+    client_auth::AuthenticatedClient,
+    denylist::Denylist,
+    dpop::{error::DpopError, verifier::DpopVerifier},
     refresh_token_issuer::RefreshTokenService,
+    scope::{self, ScopeService},
+    session_policy::{SessionEnd, SessionEviction, SessionLimits, SessionPolicy},
 };
 
 /// Service that orchestrates access-token issuance and refresh-token issuance/rotation.
@@ -18,90 +25,256 @@ use crate::services::auth::{
 pub struct TokenService {
     access_issuer: AccessTokenService,
     refresh_issuer: RefreshTokenService,
-    auth_session_repo: AuthSessionRepo,
+    auth_session_repo: Arc<dyn AuthSessionStore>,
     dpop_verifier: Arc<DpopVerifier>,
+    scopes: ScopeService,
+    // Issue tokens for a bare `sub` without authenticating it (development only).
+    subject_grant: bool,
+    session_policy: SessionPolicy,
+    // Sessions evicted over the per-user cap are published here.
+    denylist: Denylist,
+    audit: AuditLog,
 }
 
 impl TokenService {
     pub fn new(
         access_issuer: AccessTokenService,
         refresh_issuer: RefreshTokenService,
-        auth_session_repo: AuthSessionRepo,
+        auth_session_repo: Arc<dyn AuthSessionStore>,
         dpop_verifier: Arc<DpopVerifier>,
+        scopes: ScopeService,
     ) -> Self {
         Self {
             access_issuer,
             refresh_issuer,
             auth_session_repo,
             dpop_verifier,
+            scopes,
+            subject_grant: false,
+            session_policy: SessionPolicy::default(),
+            denylist: Denylist::disabled(),
+            audit: AuditLog::disabled(),
         }
     }
 
+    // Idle timeout, maximum lifetime and per-user cap for new sessions.
+    pub fn with_session_policy(mut self, policy: SessionPolicy) -> Self {
+        self.session_policy = policy;
+        self
+    }
+
+    pub fn with_denylist(mut self, denylist: Denylist) -> Self {
+        self.denylist = denylist;
+        self
+    }
+
+    // Record sessions evicted over the per-user cap.
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    pub fn with_subject_grant(mut self, enabled: bool) -> Self {
+        self.subject_grant = enabled;
+        self
+    }
+
+    /// Whether `/token` may issue tokens for a caller-supplied `sub`.
+    pub fn subject_grant_enabled(&self) -> bool {
+        self.subject_grant
+    }
+
+    /// Current DPoP nonce to return in the `DPoP-Nonce` response header, if nonces are enabled.
+    pub fn dpop_nonce(&self) -> Option<String> {
+        self.dpop_verifier.issue_nonce(Utc::now())
+    }
+
+    /// Thumbprint of the key that signed a DPoP proof, checking the signature only.
+    pub fn dpop_signing_key(&self, dpop_proof: &str) -> Option<String> {
+        self.dpop_verifier.signing_key_thumbprint(dpop_proof)
+    }
+
     /// Issue a new token pair for an authenticated subject.
     ///
     /// This creates a new session_id, issues an access token, and issues a refresh token bound to
-    /// that session.
+    /// that session. `scope` is validated against the user's roles (default: all they may have).
+    /// `authn` is how the subject signed in (None: not authenticated, subject grant).
     pub async fn issue_token_pair(
         &self,
         sub: Uuid,
+        scope: Option<&str>,
+        authn: Option<&AuthnContext>,
         dpop_proof: &str,
         method: &str,
         url: &str,
     ) -> Result<IssuedTokenPair, AppError> {
+        let jkt = self.verify_issue_proof(dpop_proof, method, url).await?;
+        self.issue_token_pair_for_key(sub, jkt, None, scope, authn)
+            .await
+    }
+
+    /// Issue an access token to a client acting on its own behalf (client_credentials).
+    ///
+    /// - the client must be confidential and allowed to use the grant
+    /// - `scope` must be a subset of the client's scopes (default: all of them)
+    /// - no session and no refresh token; the client simply asks again
+    pub async fn issue_client_token(
+        &self,
+        client: &AuthenticatedClient,
+        scope: Option<&str>,
+        dpop_proof: &str,
+        method: &str,
+        url: &str,
+    ) -> Result<IssuedClientToken, AppError> {
+        if !client.is_confidential() || !client.allows_grant("client_credentials") {
+            warn!(target: "security", event = "unauthorized_client", client_id = %client.client_id, "client may not use client_credentials");
+            return Err(OAuthError::UnauthorizedClient.into());
+        }
+
+        let granted = scope::join(scope::narrow(&client.scopes, scope).inspect_err(|_| {
+            warn!(client_id = %client.client_id, requested = ?scope, "client requested a scope it is not allowed");
+        })?);
+
+        let jkt = self.verify_issue_proof(dpop_proof, method, url).await?;
+        let ttl_seconds = self
+            .access_issuer
+            .client_access_token_ttl_seconds(&client.client_id, client.access_token_ttl_seconds);
+        let access_token = self
+            .access_issuer
+            .issue_client_access_token(
+                &client.client_id,
+                granted.clone(),
+                jkt.clone(),
+                Some(ttl_seconds),
+            )
+            .await?;
+
+        Ok(IssuedClientToken {
+            access_token,
+            token_type: token_type(Some(&jkt)),
+            expires_in: ttl_seconds,
+            scope: granted,
+            jkt,
+        })
+    }
+
+    /// Verify the DPoP proof of an issue request and return its key thumbprint.
+    ///
+    /// Split from issuance so grants can check the key (e.g. an authorization code's
+    /// `dpop_jkt`) before anything is created.
+    pub async fn verify_issue_proof(
+        &self,
+        dpop_proof: &str,
+        method: &str,
+        url: &str,
+    ) -> Result<String, AppError> {
         if dpop_proof.trim().is_empty() {
-            return Err(AppError::Unauthorized);
+            return Err(OAuthError::InvalidDpopProof("DPoP proof is required".into()).into());
         }
 
-        let now: DateTime<Utc> = Utc::now();
         let verified = self
             .dpop_verifier
-            .verify_proof(dpop_proof, method, url, None, None, now)
+            .verify_proof(dpop_proof, method, url, None, None, Utc::now())
+            .await
             .map_err(|e| {
-                error!(user_id = %sub, error = ?e, "DPoP proof verification failed (issue)");
-                AppError::Unauthorized
+                // A nonce challenge is part of the normal flow, not a failure worth an error log.
+                if !matches!(e, DpopError::UseNonce(_)) {
+                    error!(error = ?e, "DPoP proof verification failed (issue)");
+                }
+                e.into_token_error()
             })?;
 
-        // Issue-side: bind jkt immediately (no BOFU).
-        let jkt = verified.jkt;
+        Ok(verified.jkt)
+    }
 
+    /// Create a session bound to `jkt` (from a verified proof) and issue its token pair.
+    ///
+    /// `client` is the client the user signed in through (authorization code grant); it
+    /// also limits the scopes that can be granted and may override the session policy.
+    /// `authn` is recorded on the session and copied into its access tokens.
+    /// Sessions over the user's cap are ended once the new one exists.
+    pub async fn issue_token_pair_for_key(
+        &self,
+        sub: Uuid,
+        jkt: String,
+        client: Option<&AuthenticatedClient>,
+        scope: Option<&str>,
+        authn: Option<&AuthnContext>,
+    ) -> Result<IssuedTokenPair, AppError> {
+        let grant = self
+            .scopes
+            .grant_for_user(sub, client.map(|c| c.scopes.as_slice()), scope)
+            .await?;
+        let client_id = client.map(|c| c.client_id.as_str());
+        let limits = self.session_policy.for_session(client, Utc::now());
+
+        // Issue-side: bind jkt immediately (no BOFU).
         let session = self
             .auth_session_repo
-            .create(sub, Some(jkt))
+            .create(NewAuthSession {
+                user_id: sub,
+                dpop_jkt: Some(jkt),
+                client_id,
+                scope: grant.scope.as_deref(),
+                expires_at: limits.expires_at,
+                idle_timeout_seconds: limits
+                    .idle_timeout_seconds
+                    .map(|v| i32::try_from(v).unwrap_or(i32::MAX)),
+                acr: authn.map(|a| a.acr.as_str()),
+                amr: authn.map(|a| a.amr.as_slice()).unwrap_or_default(),
+            })
             .await
             .map_err(|e| {
                 error!(user_id = %sub, error = %e, "Failed to create auth session");
                 AppError::Internal
             })?;
         let session_id = session.id;
+        self.evict_over_cap(sub, &limits).await?;
 
         // Access token (JWT)
         let access_token = self
             .access_issuer
-            .issue_access_token(&sub.to_string(), session.dpop_jkt.clone())
+            .issue_access_token(UserAccessToken {
+                sub: &sub.to_string(),
+                session_id,
+                jkt: session.dpop_jkt.clone(),
+                client_id: client_id.map(str::to_string),
+                scope: grant.scope.clone(),
+                roles: grant.roles,
+                authn: authn.cloned(),
+            })
             .await?;
 
         // Refresh token (opaque)
-        let refresh_token = self.refresh_issuer.issue_refresh_token(session_id).await?;
+        let refresh_token = self
+            .refresh_issuer
+            .issue_refresh_token(session_id, limits.expires_at)
+            .await?;
 
         Ok(IssuedTokenPair {
             access_token,
             refresh_token,
-            token_type: "Bearer",
+            token_type: token_type(session.dpop_jkt.as_deref()),
             expires_in: self.access_issuer.access_token_ttl_seconds(),
+            user_id: sub,
             session_id,
+            jkt: session.dpop_jkt,
+            scope: grant.scope,
         })
     }
 
     /// Refresh an access token using a refresh token.
     ///
-    /// Step 2 (DPoP-bound refresh):
+    /// Step 3 (DPoP-bound refresh with rotation):
     /// - validate the refresh token (active + not expired + not revoked)
-    /// - (later) verify the DPoP proof and enforce binding (session.dpop_jkt)
-    /// - issue a new access token for the same subject
-    /// - return the same refresh token (no rotation yet; rotation is step 3)
+    /// - verify the DPoP proof and enforce binding (session.dpop_jkt)
+    /// - rotate the refresh token (reuse of a rotated token revokes the session)
+    /// - issue a new access token for the same subject, with `scope` narrowed if requested
+    ///   and the user's current roles
     pub async fn refresh(
         &self,
         refresh_token: &str,
+        scope: Option<&str>,
         dpop_proof: &str,
         method: &str,
         url: &str,
@@ -109,29 +282,98 @@ impl TokenService {
         let now: DateTime<Utc> = Utc::now();
         // Step2: require DPoP header to be present (full cryptographic verification is done later).
         if dpop_proof.trim().is_empty() {
-            return Err(AppError::Unauthorized);
+            return Err(OAuthError::InvalidDpopProof("DPoP proof is required".into()).into());
         }
 
-        let v = self
+        let rotated = self
             .refresh_issuer
-            .validate_refresh_token(refresh_token, now, dpop_proof, method, url)
-            .await?
-            .ok_or(AppError::Unauthorized)?;
+            .rotate(refresh_token, scope, now, dpop_proof, method, url)
+            .await?;
+
+        let user_id = Uuid::parse_str(&rotated.sub).map_err(|_| AppError::Internal)?;
+        let grant = self
+            .scopes
+            .regrant_for_user(user_id, rotated.scope.as_deref())
+            .await?;
 
         // Access token (JWT)
         let access_token = self
             .access_issuer
-            .issue_access_token(&v.user_id.to_string(), v.jkt)
+            .issue_access_token(UserAccessToken {
+                sub: &rotated.sub,
+                session_id: rotated.session_id,
+                jkt: rotated.jkt.clone(),
+                client_id: rotated.client_id,
+                scope: grant.scope.clone(),
+                roles: grant.roles,
+                authn: rotated.authn,
+            })
             .await?;
 
+        // Best effort: the refresh token is already rotated, so don't fail the response.
+        if let Err(e) = self
+            .auth_session_repo
+            .touch_last_used(rotated.session_id, now)
+            .await
+        {
+            warn!(session_id = %rotated.session_id, error = %e, "Failed to update session last_used_at");
+        }
+
         Ok(IssuedTokenPair {
             access_token,
-            refresh_token: refresh_token.to_string(),
-            token_type: "Bearer",
+            refresh_token: rotated.refresh_token,
+            token_type: token_type(rotated.jkt.as_deref()),
             expires_in: self.access_issuer.access_token_ttl_seconds(),
-            session_id: v.session_id,
+            user_id,
+            session_id: rotated.session_id,
+            jkt: rotated.jkt,
+            scope: grant.scope,
         })
     }
+
+    // End the user's sessions beyond the cap, newest kept (the one just created included).
+    async fn evict_over_cap(&self, user_id: Uuid, limits: &SessionLimits) -> Result<(), AppError> {
+        let Some(cap) = &limits.cap else {
+            return Ok(());
+        };
+
+        let evicted = self
+            .auth_session_repo
+            .evict_over_cap(
+                user_id,
+                cap.client_id.as_deref(),
+                i64::from(cap.max),
+                limits.eviction == SessionEviction::LeastRecentlyUsed,
+                Utc::now(),
+            )
+            .await
+            .map_err(|e| {
+                error!(user_id = %user_id, error = %e, "Failed to evict sessions over the cap");
+                AppError::Internal
+            })?;
+        if !evicted.is_empty() {
+            self.denylist.deny_sessions(&evicted).await;
+            info!(user_id = %user_id, evicted = evicted.len(), max = cap.max, "Sessions over the per-user cap ended");
+        }
+        for session_id in evicted {
+            self.audit
+                .record(
+                    AuditEvent::success(AuditEventType::SessionEnded)
+                        .with_reason(SessionEnd::Evicted.as_str())
+                        .with_user(user_id)
+                        .with_session(session_id)
+                        .with_client(cap.client_id.as_deref()),
+                )
+                .await;
+        }
+        Ok(())
+    }
+}
+
+/// `token_type` for an access token: `DPoP` when it is bound to a key (RFC 9449
+/// Section 5), so clients pick the matching `Authorization` scheme.
+pub fn token_type(jkt: Option<&str>) -> &'static str {
+    if jkt.is_some() { "DPoP" } else { "Bearer" }
 }
 
 /// Service-level return type to keep handlers thin.
@@ -143,7 +385,22 @@ pub struct IssuedTokenPair {
     pub refresh_token: String,
     pub token_type: &'static str,
     pub expires_in: u64,
+    pub user_id: Uuid,
     pub session_id: Uuid,
+    // Thumbprint of the DPoP key the session is bound to.
+    pub jkt: Option<String>,
+    pub scope: Option<String>,
+}
+
+/// Access token issued to a client (client_credentials); there is no refresh token.
+#[derive(Clone, Debug)]
+pub struct IssuedClientToken {
+    pub access_token: String,
+    pub token_type: &'static str,
+    pub expires_in: u64,
+    pub scope: Option<String>,
+    // Thumbprint of the DPoP key the token is bound to.
+    pub jkt: String,
 }
 
 /// Return type for refresh rotation.
@@ -155,6 +412,26 @@ pub struct RotatedRefreshToken {
     pub session_id: Uuid,
     pub sub: String,
 
-    // Reserved for later steps (DPoP-bound refresh, etc.).
+    // Session-bound DPoP key (cnf.jkt for the new access token).
     pub jkt: Option<String>,
+
+    // Client the session was created through (client_id claim).
+    pub client_id: Option<String>,
+
+    // Scope for the new access token (the session's, or narrower if requested).
+    pub scope: Option<String>,
+
+    // How the user signed in to the session (acr / amr claims).
+    pub authn: Option<AuthnContext>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bound_tokens_are_dpop_tokens() {
+        assert_eq!(token_type(Some("jkt")), "DPoP");
+        assert_eq!(token_type(None), "Bearer");
+    }
 }
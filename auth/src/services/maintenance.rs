@@ -0,0 +1,243 @@
+use chrono::{DateTime, Duration, Utc};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{error, info};
+
+use crate::error::AppError;
+use crate::repos::error::RepoResult;
+use crate::repos::maintenance_repo::MaintenanceRepo;
+
+/// What to purge and how.
+#[derive(Debug, Clone)]
+pub struct MaintenancePolicy {
+    /// Expired / revoked rows are kept this long (audit, reuse detection) before purging.
+    pub retention: Duration,
+    /// Rows deleted per statement.
+    pub batch_size: i64,
+}
+
+/// Rows purged by one run.
+#[derive(Debug, Clone, Default)]
+pub struct MaintenanceReport {
+    pub sessions: u64,
+    pub refresh_tokens: u64,
+    pub authorization_codes: u64,
+    pub dpop_replay: u64,
+    pub batches: u32,
+    pub elapsed: std::time::Duration,
+}
+
+impl MaintenanceReport {
+    pub fn total(&self) -> u64 {
+        self.sessions + self.refresh_tokens + self.authorization_codes + self.dpop_replay
+    }
+}
+
+/// Purges expired and revoked sessions, refresh tokens, authorization codes and DPoP
+/// replay entries.
+///
+/// Runs on a timer in every auth server instance (and as `auth maintenance`); a Postgres
+/// advisory lock lets only one of them work at a time, the others skip the run.
+#[derive(Clone, Debug)]
+pub struct MaintenanceService {
+    repo: MaintenanceRepo,
+    policy: MaintenancePolicy,
+}
+
+impl MaintenanceService {
+    pub fn new(repo: MaintenanceRepo, policy: MaintenancePolicy) -> Self {
+        Self { repo, policy }
+    }
+
+    /// Purge once. `None` when another instance holds the lock.
+    pub async fn run_once(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<Option<MaintenanceReport>, AppError> {
+        let lock = self.repo.try_lock().await.map_err(|e| {
+            error!(error = %e, "failed to take the maintenance lock");
+            AppError::Internal
+        })?;
+        let Some(lock) = lock else {
+            info!("maintenance skipped: another instance is running it");
+            return Ok(None);
+        };
+
+        let started = Instant::now();
+        let res = self.purge(now).await;
+        if let Err(e) = self.repo.unlock(lock).await {
+            error!(error = %e, "failed to release the maintenance lock; its connection was closed");
+        }
+        let mut report = res?;
+        report.elapsed = started.elapsed();
+
+        info!(
+            target: "metrics",
+            event = "auth_maintenance",
+            sessions = report.sessions,
+            refresh_tokens = report.refresh_tokens,
+            authorization_codes = report.authorization_codes,
+            dpop_replay = report.dpop_replay,
+            batches = report.batches,
+            elapsed_ms = report.elapsed.as_millis() as u64,
+        );
+        info!(
+            purged = report.total(),
+            elapsed_ms = report.elapsed.as_millis() as u64,
+            "maintenance finished: {} sessions, {} refresh tokens, {} authorization codes, {} DPoP replay entries purged",
+            report.sessions,
+            report.refresh_tokens,
+            report.authorization_codes,
+            report.dpop_replay,
+        );
+        Ok(Some(report))
+    }
+
+    /// Run `run_once` every `every` in the background.
+    pub fn spawn_scheduler(self: &Arc<Self>, every: std::time::Duration) {
+        let service = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(every);
+            loop {
+                ticker.tick().await;
+                // Errors are already logged; try again next time.
+                let _ = service.run_once(Utc::now()).await;
+            }
+        });
+    }
+
+    async fn purge(&self, now: DateTime<Utc>) -> Result<MaintenanceReport, AppError> {
+        let cutoff = now - self.policy.retention;
+        let limit = self.policy.batch_size;
+        let mut report = MaintenanceReport::default();
+
+        // Sessions first: their refresh tokens go with them.
+        report.sessions = drain("auth_sessions", limit, &mut report.batches, |n| {
+            self.repo.purge_sessions(cutoff, n)
+        })
+        .await?;
+        report.refresh_tokens = drain("refresh_tokens", limit, &mut report.batches, |n| {
+            self.repo.purge_refresh_tokens(cutoff, n)
+        })
+        .await?;
+        report.authorization_codes =
+            drain("authorization_codes", limit, &mut report.batches, |n| {
+                self.repo.purge_authorization_codes(cutoff, n)
+            })
+            .await?;
+        // Expired replay entries protect nothing; no retention.
+        report.dpop_replay = drain("dpop_proof_jtis", limit, &mut report.batches, |n| {
+            self.repo.purge_dpop_replay(now, n)
+        })
+        .await?;
+
+        Ok(report)
+    }
+}
+
+// Repeat a bounded delete of at most `limit` rows until a batch comes back short.
+async fn drain<F, Fut>(
+    table: &'static str,
+    limit: i64,
+    batches: &mut u32,
+    purge: F,
+) -> Result<u64, AppError>
+where
+    F: Fn(i64) -> Fut,
+    Fut: Future<Output = RepoResult<u64>>,
+{
+    let mut total = 0;
+    loop {
+        let deleted = purge(limit).await.map_err(|e| {
+            error!(table, error = %e, "maintenance purge failed");
+            AppError::Internal
+        })?;
+        *batches += 1;
+        total += deleted;
+        if deleted < limit as u64 {
+            return Ok(total);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::repos::error::RepoError;
+
+    // A table with `rows` purgeable rows; records the limit of every batch.
+    struct Table {
+        rows: Mutex<u64>,
+        limits: Mutex<Vec<i64>>,
+    }
+
+    impl Table {
+        fn new(rows: u64) -> Self {
+            Self {
+                rows: Mutex::new(rows),
+                limits: Mutex::new(Vec::new()),
+            }
+        }
+
+        async fn purge(&self, limit: i64) -> RepoResult<u64> {
+            self.limits.lock().unwrap().push(limit);
+            let mut rows = self.rows.lock().unwrap();
+            let deleted = (*rows).min(limit as u64);
+            *rows -= deleted;
+            Ok(deleted)
+        }
+    }
+
+    #[tokio::test]
+    async fn drains_in_batches_until_one_comes_back_short() {
+        let table = Table::new(250);
+        let mut batches = 0;
+        let purged = drain("t", 100, &mut batches, |n| table.purge(n))
+            .await
+            .unwrap();
+
+        assert_eq!(purged, 250);
+        assert_eq!(batches, 3);
+        assert_eq!(*table.limits.lock().unwrap(), vec![100, 100, 100]);
+        assert_eq!(*table.rows.lock().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn a_full_last_batch_takes_one_more_empty_batch() {
+        let table = Table::new(200);
+        let mut batches = 1; // counts across tables
+        let purged = drain("t", 100, &mut batches, |n| table.purge(n))
+            .await
+            .unwrap();
+
+        assert_eq!(purged, 200);
+        assert_eq!(batches, 4);
+    }
+
+    #[tokio::test]
+    async fn a_failed_batch_fails_the_run() {
+        let mut batches = 0;
+        let res = drain("t", 100, &mut batches, |_| async {
+            Err(RepoError::Db(sqlx::Error::PoolTimedOut))
+        })
+        .await;
+
+        assert!(matches!(res, Err(AppError::Internal)));
+        assert_eq!(batches, 0);
+    }
+
+    #[test]
+    fn report_total_sums_every_table() {
+        let report = MaintenanceReport {
+            sessions: 1,
+            refresh_tokens: 2,
+            authorization_codes: 3,
+            dpop_replay: 4,
+            ..MaintenanceReport::default()
+        };
+        assert_eq!(report.total(), 10);
+    }
+}
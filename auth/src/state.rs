@@ -1,14 +1,29 @@
 use std::sync::Arc;
 
-use crate::services::auth::token_service::TokenService;
+use crate::api::well_known::AuthorizationServerMetadata;
+use crate::services::auth::{
+    access_token_verifier::AccessTokenVerifier, audit::AuditLog,
+    authorization_code_service::AuthorizationCodeService, client_auth::ClientAuthenticator,
+    introspection_service::IntrospectionService, mfa::service::MfaService,
+    password_grant::PasswordGrantService, revocation_service::RevocationService,
+    session_service::SessionService, signing_keys::key_ring::SigningKeyRing,
+    throttle::TokenThrottle, token_exchange::TokenExchangeService, token_service::TokenService,
+};
 
 #[derive(Clone)]
 pub struct AppState {
     pub auth: Arc<TokenService>,
-}
-
-impl AppState {
-    pub fn new(auth: Arc<TokenService>) -> Self {
-        Self { auth }
-    }
+    pub metadata: Arc<AuthorizationServerMetadata>,
+    pub access: Arc<AccessTokenVerifier>,
+    pub sessions: Arc<SessionService>,
+    pub revocation: Arc<RevocationService>,
+    pub introspection: Arc<IntrospectionService>,
+    pub signing_keys: Arc<SigningKeyRing>,
+    pub password_grant: Arc<PasswordGrantService>,
+    pub authorization: Arc<AuthorizationCodeService>,
+    pub clients: Arc<ClientAuthenticator>,
+    pub token_exchange: Arc<TokenExchangeService>,
+    pub audit: Arc<AuditLog>,
+    pub throttle: Arc<TokenThrottle>,
+    pub mfa: Arc<MfaService>,
 }
@@ -0,0 +1,132 @@
+//! Server-issued DPoP nonces (RFC 9449 Section 8 and 9), shared by the auth server and
+//! the resource server.
+//!
+//! Nonces are derived statelessly: `base64url(HMAC-SHA256(secret, label || window))`,
+//! where `window = now / ttl_seconds`. A nonce is accepted during the window it was
+//! minted in and the following one, so it lives between `ttl` and `2 * ttl` seconds and
+//! every instance sharing the secret agrees on it without a round-trip to storage.
+
+use base64::Engine as _;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Truncated MAC length (bytes). 128 bits is plenty for an unguessable nonce.
+const MAC_LEN: usize = 16;
+
+#[derive(Clone)]
+pub struct DpopNonceIssuer {
+    label: &'static str,
+    secret: Vec<u8>,
+    ttl_seconds: i64,
+}
+
+impl std::fmt::Debug for DpopNonceIssuer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Do not print the secret
+        f.debug_struct("DpopNonceIssuer")
+            .field("label", &self.label)
+            .field("ttl_seconds", &self.ttl_seconds)
+            .finish()
+    }
+}
+
+impl DpopNonceIssuer {
+    /// `label` separates domains, so the same secret can't produce nonces that another
+    /// server (or another purpose) would accept.
+    pub fn new(label: &'static str, secret: Vec<u8>, ttl_seconds: u64) -> Self {
+        Self {
+            label,
+            secret,
+            ttl_seconds: ttl_seconds.clamp(1, i64::MAX as u64) as i64,
+        }
+    }
+
+    /// Build an issuer with a random secret.
+    ///
+    /// Only suitable for a single instance: nonces won't validate on other replicas
+    /// and are invalidated by a restart.
+    pub fn with_random_secret(label: &'static str, ttl_seconds: u64) -> Self {
+        let mut secret = vec![0u8; 32];
+        getrandom::fill(&mut secret).expect("getrandom failed");
+        Self::new(label, secret, ttl_seconds)
+    }
+
+    /// Mint the nonce for the current window (`now` is epoch seconds).
+    pub fn issue(&self, now: i64) -> String {
+        let mac = self.mac_for_window(self.window(now));
+        URL_SAFE_NO_PAD.encode(&mac.finalize().into_bytes()[..MAC_LEN])
+    }
+
+    /// Check whether `nonce` was minted in the current or the previous window.
+    pub fn verify(&self, nonce: &str, now: i64) -> bool {
+        let Ok(got) = URL_SAFE_NO_PAD.decode(nonce) else {
+            return false;
+        };
+        if got.len() != MAC_LEN {
+            return false;
+        }
+
+        let current = self.window(now);
+        [current, current - 1].into_iter().any(|w| {
+            // Constant-time comparison.
+            self.mac_for_window(w).verify_truncated_left(&got).is_ok()
+        })
+    }
+
+    fn window(&self, now: i64) -> i64 {
+        now.div_euclid(self.ttl_seconds)
+    }
+
+    fn mac_for_window(&self, window: i64) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts any key size");
+        mac.update(self.label.as_bytes());
+        mac.update(&window.to_be_bytes());
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const T0: i64 = 1_700_000_040;
+
+    fn issuer() -> DpopNonceIssuer {
+        DpopNonceIssuer::new("dpop-nonce:test:", b"test-secret".to_vec(), 60)
+    }
+
+    #[test]
+    fn nonce_is_valid_for_current_and_next_window() {
+        let n = issuer();
+        let nonce = n.issue(T0);
+
+        assert!(n.verify(&nonce, T0));
+        assert!(n.verify(&nonce, T0 + 60));
+    }
+
+    #[test]
+    fn stale_nonce_is_rejected() {
+        let n = issuer();
+        let nonce = n.issue(T0);
+
+        assert!(!n.verify(&nonce, T0 + 120));
+        // Nor is a nonce from the future.
+        assert!(!n.verify(&n.issue(T0 + 60), T0));
+    }
+
+    #[test]
+    fn nonce_rejects_garbage_and_forgeries() {
+        let n = issuer();
+        let other_secret = DpopNonceIssuer::new("dpop-nonce:test:", b"another-secret".to_vec(), 60);
+        let other_label = DpopNonceIssuer::new("dpop-nonce:other:", b"test-secret".to_vec(), 60);
+
+        assert!(!n.verify("", T0));
+        assert!(!n.verify("not base64!", T0));
+        assert!(!n.verify(&URL_SAFE_NO_PAD.encode([0u8; MAC_LEN]), T0));
+        assert!(!n.verify(&other_secret.issue(T0), T0));
+        assert!(!n.verify(&other_label.issue(T0), T0));
+    }
+}
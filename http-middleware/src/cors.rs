@@ -0,0 +1,141 @@
+//! CORS policy for browser clients.
+//!
+//! Note:
+//! - CORS is enforced by browsers. Native mobile apps and server-to-server calls are not
+//!   restricted by CORS.
+//! - This middleware should be applied at the Router level (not inside handlers).
+//!
+//! Responsibility:
+//! - Provide one consistent CORS policy for both servers.
+//! - Let browser clients send DPoP proofs and read DPoP nonces (`/token`, `/revoke` and
+//!   DPoP-bound resource requests).
+//!
+//! Policy:
+//! - Development: permissive (Allow-Origin: *), WITHOUT credentials.
+//! - Production: allowlist origins from config (comma-separated env var), WITHOUT credentials.
+
+use axum::Router;
+use axum::http::{HeaderName, HeaderValue, Method, header};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+/// Origins allowed to call the API from a browser.
+#[derive(Clone, Debug)]
+pub enum AllowedOrigins {
+    Any,
+    /// Exact matches only; an empty list allows no origin.
+    List(Vec<HeaderValue>),
+}
+
+impl AllowedOrigins {
+    /// Any origin in development; only `origins` in production.
+    ///
+    /// Entries that are not valid header values are ignored.
+    pub fn for_env(production: bool, origins: &[String]) -> Self {
+        if !production {
+            return Self::Any;
+        }
+
+        Self::List(
+            origins
+                .iter()
+                .filter_map(|s| HeaderValue::from_str(s).ok())
+                .collect(),
+        )
+    }
+}
+
+/// Apply CORS policy to the given Router.
+///
+/// IMPORTANT:
+/// - Do not combine wildcard origin (`Any`) with `allow_credentials(true)`.
+pub fn apply(router: Router, origins: AllowedOrigins) -> Router {
+    let cors = match origins {
+        // If the allowlist is empty, we intentionally allow none (no CORS headers),
+        // which is safer than accidentally allowing all.
+        AllowedOrigins::List(allowed) => {
+            let allow_origin = AllowOrigin::predicate(move |origin: &HeaderValue, _req| {
+                allowed.iter().any(|v| v == origin)
+            });
+            CorsLayer::new().allow_origin(allow_origin)
+        }
+        // Development: permissive (no credentials)
+        AllowedOrigins::Any => CorsLayer::new().allow_origin(Any),
+    }
+    .allow_methods([
+        Method::GET,
+        Method::POST,
+        Method::PUT,
+        Method::PATCH,
+        Method::DELETE,
+        Method::OPTIONS,
+    ])
+    .allow_headers([
+        header::AUTHORIZATION,
+        header::CONTENT_TYPE,
+        header::ACCEPT,
+        HeaderName::from_static("x-request-id"),
+        HeaderName::from_static("dpop"),
+    ])
+    // Let browser clients read DPoP nonces, auth challenges and throttling delays.
+    .expose_headers([
+        header::WWW_AUTHENTICATE,
+        header::RETRY_AFTER,
+        HeaderName::from_static("dpop-nonce"),
+    ])
+    .max_age(std::time::Duration::from_secs(60 * 10));
+
+    router.layer(cors)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::routing::post;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn preflight(origin: &str) -> Request<Body> {
+        Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/token")
+            .header(header::ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "dpop,content-type")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn preflight_allows_dpop_from_listed_origins_only() {
+        let router = apply(
+            Router::new().route("/token", post(|| async { "ok" })),
+            AllowedOrigins::for_env(true, &["https://app.example.com".to_string()]),
+        );
+
+        let res = router
+            .clone()
+            .oneshot(preflight("https://app.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+        let allowed = res.headers()[header::ACCESS_CONTROL_ALLOW_HEADERS]
+            .to_str()
+            .unwrap();
+        assert!(allowed.split(',').any(|h| h.trim() == "dpop"));
+
+        let res = router
+            .oneshot(preflight("https://evil.example.com"))
+            .await
+            .unwrap();
+        assert!(
+            !res.headers()
+                .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        );
+    }
+}
@@ -11,7 +11,7 @@
 //!
 //! Notes:
 //! - Defaults are intentionally conservative for production-ish behavior.
-//! - Later, we can make these configurable via `Config` without changing call sites.
+//! - Each server may tune the limits from its own config (see `HttpLimits`).
 
 use std::time::Duration;
 
@@ -24,13 +24,27 @@ use tower_http::limit::RequestBodyLimitLayer;
 use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
 use tower_http::trace::TraceLayer;
 
+/// Request body and time limits.
+#[derive(Clone, Copy, Debug)]
+pub struct HttpLimits {
+    pub body_limit_bytes: usize,
+    pub timeout: Duration,
+}
+
+impl Default for HttpLimits {
+    /// 1 MiB bodies, 30 second timeout.
+    fn default() -> Self {
+        Self {
+            body_limit_bytes: 1024 * 1024,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
 /// Apply HTTP-level middleware to the given Router.
 ///
-/// Defaults:
-/// - Request-Id header: `x-request-id`
-/// - Body limit: 1 MiB
-/// - Timeout: 30 seconds
-pub fn apply(router: Router) -> Router {
+/// The request id header is `x-request-id`; a client-supplied id is kept.
+pub fn apply(router: Router, limits: &HttpLimits) -> Router {
     let request_id_header = HeaderName::from_static("x-request-id");
 
     let layers = ServiceBuilder::new()
@@ -49,9 +63,9 @@ pub fn apply(router: Router) -> Router {
         ))
         .layer(PropagateRequestIdLayer::new(request_id_header))
         // Limit request body size (protects against accidental/hostile large payloads).
-        .layer(RequestBodyLimitLayer::new(1024 * 1024))
+        .layer(RequestBodyLimitLayer::new(limits.body_limit_bytes))
         // Bound request time (protects against hanging upstreams / slow clients).
-        .layer(TimeoutLayer::new(Duration::from_secs(30)))
+        .layer(TimeoutLayer::new(limits.timeout))
         // Access log / tracing for all requests.
         .layer(TraceLayer::new_for_http());
 
@@ -0,0 +1,13 @@
+//! Router-level HTTP middleware shared by the auth server and the resource server.
+//!
+//! Apply after `with_state`, in this order, so the HTTP layers wrap everything else:
+//!
+//! ```ignore
+//! let router = http_middleware::security_headers::apply(router);
+//! let router = http_middleware::cors::apply(router, allowed_origins);
+//! http_middleware::http::apply(router, &limits)
+//! ```
+
+pub mod cors;
+pub mod http;
+pub mod security_headers;
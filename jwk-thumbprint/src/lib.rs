@@ -0,0 +1,152 @@
+//! RFC 7638 JWK thumbprints, shared by the auth server and the resource server.
+//!
+//! Both sides compare thumbprints (`cnf.jkt`, `dpop_jkt`, the DPoP proof key), so they
+//! must agree byte for byte. Members are decoded and re-encoded as base64url without
+//! padding before hashing, so a key is either rejected or gets the same thumbprint
+//! everywhere.
+
+use base64::Engine as _;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum ThumbprintError {
+    #[error("unsupported JWK key type or curve")]
+    UnsupportedKey,
+
+    #[error("invalid JWK structure")]
+    InvalidJwk,
+}
+
+/// Thumbprint of an OKP/Ed25519 public key: `{"crv":"Ed25519","kty":"OKP","x":...}`.
+pub fn okp_ed25519(x_b64url: &str) -> Result<String, ThumbprintError> {
+    let x = decode(x_b64url)?;
+    // Ed25519 public keys are 32 bytes.
+    if x.len() != 32 {
+        return Err(ThumbprintError::InvalidJwk);
+    }
+
+    Ok(digest(&format!(
+        "{{\"crv\":\"Ed25519\",\"kty\":\"OKP\",\"x\":\"{}\"}}",
+        URL_SAFE_NO_PAD.encode(x)
+    )))
+}
+
+/// Thumbprint of an EC public key (P-256 / P-384): `{"crv":...,"kty":"EC","x":...,"y":...}`.
+pub fn ec(crv: &str, x_b64url: &str, y_b64url: &str) -> Result<String, ThumbprintError> {
+    let coord_len = match crv {
+        "P-256" => 32,
+        "P-384" => 48,
+        _ => return Err(ThumbprintError::UnsupportedKey),
+    };
+
+    let x = decode(x_b64url)?;
+    let y = decode(y_b64url)?;
+    // Coordinates are fixed-size field elements for the curve.
+    if x.len() != coord_len || y.len() != coord_len {
+        return Err(ThumbprintError::InvalidJwk);
+    }
+
+    Ok(digest(&format!(
+        "{{\"crv\":\"{crv}\",\"kty\":\"EC\",\"x\":\"{}\",\"y\":\"{}\"}}",
+        URL_SAFE_NO_PAD.encode(x),
+        URL_SAFE_NO_PAD.encode(y)
+    )))
+}
+
+/// Thumbprint of an RSA public key: `{"e":...,"kty":"RSA","n":...}`.
+pub fn rsa(n_b64url: &str, e_b64url: &str) -> Result<String, ThumbprintError> {
+    let n = decode(n_b64url)?;
+    let e = decode(e_b64url)?;
+    if n.is_empty() || e.is_empty() {
+        return Err(ThumbprintError::InvalidJwk);
+    }
+
+    Ok(digest(&format!(
+        "{{\"e\":\"{}\",\"kty\":\"RSA\",\"n\":\"{}\"}}",
+        URL_SAFE_NO_PAD.encode(e),
+        URL_SAFE_NO_PAD.encode(n)
+    )))
+}
+
+/// Thumbprint of a parsed JWK (OKP/Ed25519, EC P-256/P-384 or RSA).
+pub fn from_jwk(jwk: &Jwk) -> Result<String, ThumbprintError> {
+    // `EllipticCurve` doesn't implement Display/ToString, so match explicitly.
+    match &jwk.algorithm {
+        AlgorithmParameters::OctetKeyPair(params) => match params.curve {
+            EllipticCurve::Ed25519 => okp_ed25519(&params.x),
+            _ => Err(ThumbprintError::UnsupportedKey),
+        },
+        AlgorithmParameters::EllipticCurve(params) => {
+            let crv = match params.curve {
+                EllipticCurve::P256 => "P-256",
+                EllipticCurve::P384 => "P-384",
+                _ => return Err(ThumbprintError::UnsupportedKey),
+            };
+            ec(crv, &params.x, &params.y)
+        }
+        AlgorithmParameters::RSA(params) => rsa(&params.n, &params.e),
+        // Symmetric keys can never be used for DPoP.
+        AlgorithmParameters::OctetKey(_) => Err(ThumbprintError::UnsupportedKey),
+    }
+}
+
+// Strict base64url without padding; anything else (padding, `+`/`/`, whitespace) is
+// rejected rather than normalized differently on each side.
+fn decode(b64url: &str) -> Result<Vec<u8>, ThumbprintError> {
+    URL_SAFE_NO_PAD
+        .decode(b64url)
+        .map_err(|_| ThumbprintError::InvalidJwk)
+}
+
+fn digest(canonical: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 7638 Section 3.1
+    const RSA_N: &str = "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw";
+
+    #[test]
+    fn okp_matches_rfc8037_vector() {
+        // RFC 8037 Appendix A.3
+        assert_eq!(
+            okp_ed25519("11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo").unwrap(),
+            "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k"
+        );
+    }
+
+    #[test]
+    fn rsa_matches_rfc7638_vector() {
+        assert_eq!(
+            rsa(RSA_N, "AQAB").unwrap(),
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+    }
+
+    #[test]
+    fn rejects_members_that_are_not_canonical_base64url() {
+        assert_eq!(rsa(RSA_N, "AQAB="), Err(ThumbprintError::InvalidJwk));
+        assert_eq!(
+            okp_ed25519("11qYAYKxCrfVS/7TyWQHOg7hcvPapiMlrwIaaPcHURo"),
+            Err(ThumbprintError::InvalidJwk)
+        );
+    }
+
+    #[test]
+    fn ec_checks_curve_and_coordinate_size() {
+        let x32 = URL_SAFE_NO_PAD.encode([1u8; 32]);
+        let y32 = URL_SAFE_NO_PAD.encode([2u8; 32]);
+        assert!(ec("P-256", &x32, &y32).is_ok());
+        assert_eq!(ec("P-384", &x32, &y32), Err(ThumbprintError::InvalidJwk));
+        assert_eq!(
+            ec("P-521", &x32, &y32),
+            Err(ThumbprintError::UnsupportedKey)
+        );
+    }
+}
@@ -0,0 +1,11 @@
+-- DPoP proof replay cache for the auth server (Postgres fallback when Valkey is not configured).
+
+-- One row per accepted proof, keyed by "<jkt>:<jti>".
+-- Rows are only meaningful until expires_at; expired rows may be reused or purged.
+CREATE TABLE IF NOT EXISTS dpop_proof_jtis (
+    key         text PRIMARY KEY,
+    expires_at  timestamptz NOT NULL
+);
+
+-- Cleanup by expiry.
+CREATE INDEX IF NOT EXISTS idx_dpop_proof_jtis_expires_at ON dpop_proof_jtis(expires_at);
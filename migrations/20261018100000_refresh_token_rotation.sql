@@ -0,0 +1,12 @@
+-- Refresh token rotation.
+--
+-- Rotation marks the presented token used and points it at its successor before the
+-- successor row exists (the "current per session" index only allows one live token).
+-- Defer the self-reference check to commit so both writes can happen in one transaction.
+ALTER TABLE refresh_tokens
+    DROP CONSTRAINT IF EXISTS refresh_tokens_replaced_by_fkey;
+
+ALTER TABLE refresh_tokens
+    ADD CONSTRAINT refresh_tokens_replaced_by_fkey
+    FOREIGN KEY (replaced_by) REFERENCES refresh_tokens(id)
+    DEFERRABLE INITIALLY DEFERRED;
@@ -0,0 +1,24 @@
+-- Access token signing keys managed by the auth server.
+--
+-- Lifecycle: pending -> active -> retiring -> retired.
+-- - pending:  generated ahead of time, becomes active at activates_at
+-- - active:   signs new tokens (exactly one at a time)
+-- - retiring: no longer signs, still published in the JWKS until tokens it signed expire
+-- - retired:  dropped from the JWKS; private key material is erased
+CREATE TABLE IF NOT EXISTS signing_keys (
+    kid              text PRIMARY KEY,                 -- RFC 7638 thumbprint of the public JWK
+    alg              text NOT NULL DEFAULT 'EdDSA',
+    public_x         text NOT NULL,                    -- JWK "x" (base64url Ed25519 public key)
+    private_key_enc  bytea,                            -- AES-256-GCM nonce || ciphertext of the PKCS#8 DER
+    state            text NOT NULL CHECK (state IN ('pending', 'active', 'retiring', 'retired')),
+    created_at       timestamptz NOT NULL DEFAULT now(),
+    activates_at     timestamptz NOT NULL,
+    activated_at     timestamptz,
+    retiring_at      timestamptz,
+    retired_at       timestamptz,
+    CHECK (state = 'retired' OR private_key_enc IS NOT NULL)
+);
+
+-- At most one active and one scheduled key; concurrent instances race on these.
+CREATE UNIQUE INDEX IF NOT EXISTS uq_signing_keys_active ON signing_keys(state) WHERE state = 'active';
+CREATE UNIQUE INDEX IF NOT EXISTS uq_signing_keys_pending ON signing_keys(state) WHERE state = 'pending';
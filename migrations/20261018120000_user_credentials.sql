@@ -0,0 +1,10 @@
+-- Password credentials for the auth server's password grant.
+--
+-- The login name is users."userName"; this table only holds the secret.
+-- password_hash is a PHC string (Argon2id), e.g. $argon2id$v=19$m=19456,t=2,p=1$<salt>$<hash>.
+CREATE TABLE IF NOT EXISTS user_credentials (
+    user_id        uuid PRIMARY KEY REFERENCES users ("userId") ON DELETE CASCADE,
+    password_hash  text NOT NULL,
+    created_at     timestamptz NOT NULL DEFAULT now(),
+    updated_at     timestamptz NOT NULL DEFAULT now()
+);
@@ -0,0 +1,32 @@
+-- Registered OAuth clients (first-party web and mobile apps) and authorization codes.
+--
+-- Clients are public (no secret): the authorization code is protected by PKCE (S256)
+-- and redirect URIs are matched exactly against redirect_uris.
+CREATE TABLE IF NOT EXISTS oauth_clients (
+    client_id      text PRIMARY KEY,
+    client_name    text NOT NULL,
+    redirect_uris  text[] NOT NULL,
+    created_at     timestamptz NOT NULL DEFAULT now()
+);
+
+-- Authorization codes (RFC 6749 Section 4.1). Only the SHA-256 of the code is stored.
+-- consumed_at makes them single-use; session_id records the session the code was
+-- exchanged for, so presenting the code again revokes it (RFC 6749 Section 4.1.2).
+CREATE TABLE IF NOT EXISTS authorization_codes (
+    code_hash       bytea PRIMARY KEY,
+    client_id       text NOT NULL REFERENCES oauth_clients (client_id) ON DELETE CASCADE,
+    user_id         uuid NOT NULL REFERENCES users ("userId") ON DELETE CASCADE,
+    redirect_uri    text NOT NULL,
+    code_challenge  text NOT NULL,                 -- PKCE S256 (base64url SHA-256 of the verifier)
+    dpop_jkt        text,                          -- RFC 9449 Section 10 (dpop_jkt on the authorization request)
+    created_at      timestamptz NOT NULL DEFAULT now(),
+    expires_at      timestamptz NOT NULL,
+    consumed_at     timestamptz,
+    session_id      uuid REFERENCES auth_sessions (id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_authorization_codes_expires_at ON authorization_codes(expires_at);
+
+-- Example client registration:
+-- INSERT INTO oauth_clients (client_id, client_name, redirect_uris)
+-- VALUES ('web', 'Example Web', ARRAY['https://app.example.com/callback']);
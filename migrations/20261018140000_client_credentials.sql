@@ -0,0 +1,31 @@
+-- Confidential clients and the client_credentials grant.
+--
+-- Client authentication at /token:
+-- - none:                 no secret and no jwk (public clients; PKCE protects the code)
+-- - client_secret_basic / client_secret_post: client_secret_hash
+-- - private_key_jwt:      jwk (RFC 7523 client assertion signed with the matching private key)
+ALTER TABLE oauth_clients
+    ADD COLUMN IF NOT EXISTS client_secret_hash bytea,            -- SHA-256 of the client secret
+    ADD COLUMN IF NOT EXISTS jwk text,                            -- public JWK (JSON) for private_key_jwt
+    ADD COLUMN IF NOT EXISTS grant_types text[] NOT NULL DEFAULT ARRAY['authorization_code', 'refresh_token'],
+    ADD COLUMN IF NOT EXISTS scopes text[] NOT NULL DEFAULT '{}', -- scopes the client may request
+    ADD COLUMN IF NOT EXISTS access_token_ttl_seconds integer CHECK (access_token_ttl_seconds > 0);
+
+ALTER TABLE oauth_clients ALTER COLUMN redirect_uris SET DEFAULT '{}';
+
+-- Client tokens carry sub = client_id; a UUID-shaped client_id could be taken for a user id.
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'oauth_clients_client_id_not_uuid') THEN
+        ALTER TABLE oauth_clients ADD CONSTRAINT oauth_clients_client_id_not_uuid
+            CHECK (client_id !~* '^[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}$');
+    END IF;
+END
+$$;
+
+-- Client a user session was created through (client_id claim of its access tokens).
+ALTER TABLE auth_sessions ADD COLUMN IF NOT EXISTS client_id text;
+
+-- Example service client (secret hashed in SQL):
+-- INSERT INTO oauth_clients (client_id, client_name, client_secret_hash, grant_types, scopes)
+-- VALUES ('batch', 'Batch jobs', sha256(convert_to('<secret>', 'UTF8')), ARRAY['client_credentials'], ARRAY['posts:read']);
@@ -0,0 +1,27 @@
+-- Roles and scopes in access tokens.
+--
+-- A user may request the scopes granted to any of their roles; when the request
+-- comes through a client, also only scopes registered for that client.
+CREATE TABLE IF NOT EXISTS role_scopes (
+    role   text NOT NULL,
+    scope  text NOT NULL,
+    PRIMARY KEY (role, scope)
+);
+
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_id     uuid NOT NULL REFERENCES users ("userId") ON DELETE CASCADE,
+    role        text NOT NULL,
+    created_at  timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, role)
+);
+
+-- Scope granted when the session was created (space-separated). Refreshes may narrow
+-- it per token, never widen it.
+ALTER TABLE auth_sessions ADD COLUMN IF NOT EXISTS scope text;
+
+-- Scope approved at /authorize, carried to the token exchange.
+ALTER TABLE authorization_codes ADD COLUMN IF NOT EXISTS scope text;
+
+-- Example:
+-- INSERT INTO role_scopes (role, scope) VALUES ('member', 'posts:read'), ('member', 'posts:write');
+-- INSERT INTO user_roles (user_id, role) VALUES ('<user uuid>', 'member');
@@ -0,0 +1,13 @@
+-- OAuth 2.0 Token Exchange (RFC 8693).
+--
+-- A client with 'urn:ietf:params:oauth:grant-type:token-exchange' in grant_types may
+-- exchange a user's access token for one addressed to another service. The target
+-- (`audience` / `resource` parameter) must be listed here.
+ALTER TABLE oauth_clients
+    ADD COLUMN IF NOT EXISTS audiences text[] NOT NULL DEFAULT '{}';
+
+-- Example backend-for-frontend:
+-- UPDATE oauth_clients
+-- SET grant_types = array_append(grant_types, 'urn:ietf:params:oauth:grant-type:token-exchange'),
+--     audiences = ARRAY['https://posts.internal']
+-- WHERE client_id = 'bff';
@@ -0,0 +1,32 @@
+-- Session lifetime policies.
+--
+-- The effective policy is fixed on each session when it is created:
+-- - expires_at: absolute end of the session; refresh token rotation never goes past it
+-- - idle_timeout_seconds: the session ends when it has not been refreshed for this long
+--   (measured from last_used_at, or created_at before the first refresh)
+-- NULL means no limit.
+ALTER TABLE auth_sessions
+    ADD COLUMN IF NOT EXISTS expires_at timestamptz,
+    ADD COLUMN IF NOT EXISTS idle_timeout_seconds integer CHECK (idle_timeout_seconds > 0);
+
+-- Why a session ended, when a policy ended it ('idle_timeout', 'max_lifetime',
+-- 'evicted'); NULL for sign-out / revocation.
+ALTER TABLE auth_sessions ADD COLUMN IF NOT EXISTS revoked_reason text;
+
+CREATE INDEX IF NOT EXISTS idx_auth_sessions_user_active
+ON auth_sessions(user_id, created_at)
+WHERE revoked_at IS NULL;
+
+-- Per-client overrides of the server-wide SESSION_* settings (NULL: use the server's).
+-- max_sessions_per_user counts only the user's sessions of that client.
+ALTER TABLE oauth_clients
+    ADD COLUMN IF NOT EXISTS session_idle_timeout_seconds integer CHECK (session_idle_timeout_seconds > 0),
+    ADD COLUMN IF NOT EXISTS session_max_lifetime_seconds integer CHECK (session_max_lifetime_seconds > 0),
+    ADD COLUMN IF NOT EXISTS max_sessions_per_user integer CHECK (max_sessions_per_user > 0);
+
+-- Example: a kiosk app with short, single sessions.
+-- UPDATE oauth_clients
+-- SET session_idle_timeout_seconds = 1800,
+--     session_max_lifetime_seconds = 43200,
+--     max_sessions_per_user = 1
+-- WHERE client_id = 'kiosk';
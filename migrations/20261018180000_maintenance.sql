@@ -0,0 +1,20 @@
+-- Background purge of expired / revoked rows (`auth maintenance`).
+
+-- Purging an expired token must not fail because an older row of the same family still
+-- points at it as its successor.
+ALTER TABLE refresh_tokens
+    DROP CONSTRAINT IF EXISTS refresh_tokens_replaced_by_fkey;
+
+ALTER TABLE refresh_tokens
+    ADD CONSTRAINT refresh_tokens_replaced_by_fkey
+    FOREIGN KEY (replaced_by) REFERENCES refresh_tokens(id)
+    ON DELETE SET NULL
+    DEFERRABLE INITIALLY DEFERRED;
+
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_revoked_at
+ON refresh_tokens(revoked_at)
+WHERE revoked_at IS NOT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_auth_sessions_expires_at
+ON auth_sessions(expires_at)
+WHERE expires_at IS NOT NULL;
@@ -0,0 +1,47 @@
+-- Security audit trail of the auth server (append-only).
+--
+-- One row per token issuance / refresh / exchange, DPoP proof rejection and revocation,
+-- successful or not. user_id / session_id / client_id are not foreign keys: the trail
+-- outlives the users, sessions and clients it mentions.
+CREATE TABLE IF NOT EXISTS auth_audit_events (
+    id           bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    occurred_at  timestamptz NOT NULL DEFAULT now(),
+    event_type   text NOT NULL,
+    outcome      text NOT NULL CHECK (outcome IN ('success', 'failure')),
+    -- Why it failed (OAuth error code and description), or why the server acted.
+    reason       text,
+    user_id      uuid,
+    session_id   uuid,
+    client_id    text,
+    jkt          text,
+    -- Request that caused the event; NULL for events the server triggers itself.
+    client_ip    text,
+    user_agent   text,
+    request_id   text
+);
+
+CREATE INDEX IF NOT EXISTS idx_auth_audit_events_occurred_at ON auth_audit_events(occurred_at);
+CREATE INDEX IF NOT EXISTS idx_auth_audit_events_user_id ON auth_audit_events(user_id, id);
+CREATE INDEX IF NOT EXISTS idx_auth_audit_events_session_id ON auth_audit_events(session_id, id);
+CREATE INDEX IF NOT EXISTS idx_auth_audit_events_event_type ON auth_audit_events(event_type, id);
+
+-- Rows can be added, never changed or removed.
+CREATE OR REPLACE FUNCTION auth_audit_events_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'auth_audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS auth_audit_events_no_update_delete ON auth_audit_events;
+CREATE TRIGGER auth_audit_events_no_update_delete
+BEFORE UPDATE OR DELETE ON auth_audit_events
+FOR EACH ROW EXECUTE FUNCTION auth_audit_events_append_only();
+
+DROP TRIGGER IF EXISTS auth_audit_events_no_truncate ON auth_audit_events;
+CREATE TRIGGER auth_audit_events_no_truncate
+BEFORE TRUNCATE ON auth_audit_events
+FOR EACH STATEMENT EXECUTE FUNCTION auth_audit_events_append_only();
+
+-- Reading the trail (GET /api/v1/admin/audit-events) takes the `audit:read` scope:
+-- INSERT INTO role_scopes (role, scope) VALUES ('admin', 'audit:read');
+-- INSERT INTO user_roles (user_id, role) VALUES ('<user uuid>', 'admin');
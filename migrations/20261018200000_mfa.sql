@@ -0,0 +1,35 @@
+-- Multi-factor authentication: TOTP (RFC 6238) and recovery codes.
+--
+-- A user has at most one TOTP factor. It is pending until the user proves the
+-- authenticator works (confirmed_at); only confirmed factors are asked for at sign-in.
+CREATE TABLE IF NOT EXISTS user_totp_factors (
+    user_id            uuid PRIMARY KEY REFERENCES users ("userId") ON DELETE CASCADE,
+    -- AES-256-GCM sealed secret (SIGNING_KEY_ENCRYPTION_KEY, bound to the user id).
+    secret_ciphertext  bytea NOT NULL,
+    created_at         timestamptz NOT NULL DEFAULT now(),
+    confirmed_at       timestamptz,
+    -- Time step of the last accepted code; a code is accepted at most once.
+    last_used_step     bigint
+);
+
+-- Single-use codes for when the authenticator is lost. Only SHA-256 hashes are stored.
+CREATE TABLE IF NOT EXISTS user_recovery_codes (
+    id          uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id     uuid NOT NULL REFERENCES users ("userId") ON DELETE CASCADE,
+    code_hash   bytea NOT NULL,
+    created_at  timestamptz NOT NULL DEFAULT now(),
+    used_at     timestamptz,
+    UNIQUE (user_id, code_hash)
+);
+
+-- How the user authenticated when the session was created, copied into its access
+-- tokens: acr is the assurance level ('aal1' password, 'aal2' password + one-time
+-- code), amr the methods (RFC 8176: 'pwd', 'otp'). NULL / empty for older sessions.
+ALTER TABLE auth_sessions
+    ADD COLUMN IF NOT EXISTS acr text,
+    ADD COLUMN IF NOT EXISTS amr text[] NOT NULL DEFAULT '{}';
+
+-- Carried from the sign-in at /authorize to the session created for the code.
+ALTER TABLE authorization_codes
+    ADD COLUMN IF NOT EXISTS acr text,
+    ADD COLUMN IF NOT EXISTS amr text[] NOT NULL DEFAULT '{}';
@@ -8,31 +8,44 @@
  * - ここは「型（契約）」として固定化し、Scaffold で増える領域から切り離す
  */
 
-use uuid::Uuid;
+pub use crate::services::auth::access_jwt::Principal;
 
 /// 認証済みのリクエストに付与されるコンテキスト
 ///
-/// - `user_id` は内部ユーザーID（ここでは UUID を採用）
+/// - `principal` はユーザー (内部ユーザーID, UUID) かクライアント (client_credentials) のどちらか
+///   ユーザー前提の handler は `Principal::User` 以外を拒否する
+/// - `client_id` はトークンの発行先クライアント（ユーザーのトークンでは無いこともある）
 /// - `scopes` / `roles` は coarse-grained な権限情報（BOLA は policy 層で別途チェック）
 /// - `jti` は監査/相関用（denylist 等は必要になった時点で追加）
 /// - `dpop_jkt` は sender-constrained (DPoP) の鍵指紋（ログ相関用。必須ではない）
+/// - `acr` / `amr` はログイン時の認証強度と方式 (step-up の判定は `middleware::auth::step_up`)
+/// - `actors` は token exchange (RFC 8693) で委任されたトークンの actor chain
+///   (`act` claim。現在の actor が先頭、空なら principal 本人の呼び出し)
 #[derive(Debug, Clone)]
 pub struct AuthCtx {
-    pub user_id: Uuid,
+    pub principal: Principal,
+    pub client_id: Option<String>,
     pub scopes: Vec<String>,
     pub roles: Vec<String>,
     pub jti: Option<String>,
     pub dpop_jkt: Option<String>,
+    pub acr: Option<String>,
+    pub amr: Vec<String>,
+    pub actors: Vec<String>,
 }
 
 impl AuthCtx {
-    pub fn new(user_id: Uuid) -> Self {
+    pub fn new(principal: Principal) -> Self {
         Self {
-            user_id,
+            principal,
+            client_id: None,
             scopes: Vec::new(),
             roles: Vec::new(),
             jti: None,
             dpop_jkt: None,
+            acr: None,
+            amr: Vec::new(),
+            actors: Vec::new(),
         }
     }
 }
@@ -37,7 +37,7 @@ pub async fn list_posts(
     AuthCtxExtractor(auth): AuthCtxExtractor,
     State(state): State<AppState>,
 ) -> Result<Json<Vec<PostResponse>>, AppError> {
-    tracing::info!(user_id=%auth.user_id, "authed");
+    tracing::info!(principal=%auth.principal, "authed");
     /*
     let rows = post_repo::list(&state.db, 50, 0)
         .await
@@ -3,11 +3,15 @@
  * - v1 の URL 構造を定義
  * - /users, /posts, /bookmarks を next/merge
  * - Bearer が必要な範囲を route_layer などで適用する設計もここで決める
+ * - 破壊的な操作 (ユーザー削除) は step-up (MFA でのログイン) を要求する
  */
-use axum::{Router, routing::get};
+use axum::{
+    Router,
+    routing::{delete, get},
+};
 
 use crate::api::v1::handlers::{posts, users};
-use crate::middleware::auth;
+use crate::middleware::auth::{self, step_up};
 use crate::state::AppState;
 
 pub fn routes(state: AppState) -> Router<AppState> {
@@ -18,7 +22,11 @@ pub fn routes(state: AppState) -> Router<AppState> {
             "/users/{user_id}",
             get(users::get_user)
                 .put(users::update_user)
-                .delete(users::delete_user),
+                .merge(step_up::require(
+                    delete(users::delete_user),
+                    state.clone(),
+                    step_up::ACR_MFA,
+                )),
         )
         // posts
         .route("/posts", get(posts::list_posts).post(posts::create_post))
@@ -0,0 +1,67 @@
+/*
+ * Responsibility
+ * - GET /.well-known/oauth-protected-resource (RFC 9728)
+ * - Config から起動時に 1 回だけ組み立て、そのまま返す
+ */
+use axum::{Json, extract::State};
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::services::auth::dpop::core::alg_name;
+use crate::state::AppState;
+
+pub const PROTECTED_RESOURCE_PATH: &str = "/.well-known/oauth-protected-resource";
+
+/// OAuth 2.0 Protected Resource Metadata (RFC 9728).
+#[derive(Debug, Clone, Serialize)]
+pub struct ProtectedResourceMetadata {
+    pub resource: String,
+    pub authorization_servers: Vec<String>,
+    pub bearer_methods_supported: Vec<&'static str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub scopes_supported: Vec<String>,
+    pub dpop_signing_alg_values_supported: Vec<&'static str>,
+    pub dpop_bound_access_tokens_required: bool,
+}
+
+impl ProtectedResourceMetadata {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            resource: resource_identifier(config),
+            authorization_servers: vec![config.auth_issuer.clone()],
+            bearer_methods_supported: vec!["header"],
+            scopes_supported: config.scopes_supported.clone(),
+            dpop_signing_alg_values_supported: config
+                .dpop_allowed_algs
+                .iter()
+                .map(|alg| alg_name(*alg))
+                .collect(),
+            dpop_bound_access_tokens_required: config.dpop_required,
+        }
+    }
+}
+
+// 公開 URL があればそれを resource 識別子にする (無ければ token の aud)
+fn resource_identifier(config: &Config) -> String {
+    config
+        .public_base_url
+        .as_deref()
+        .map(|base| base.trim_end_matches('/').to_string())
+        .unwrap_or_else(|| config.auth_audience.clone())
+}
+
+/// Absolute URL of the metadata document, for `resource_metadata` in challenges.
+///
+/// Only available when the public base URL is known.
+pub fn protected_resource_metadata_url(config: &Config) -> Option<String> {
+    config
+        .public_base_url
+        .as_deref()
+        .map(|base| format!("{}{}", base.trim_end_matches('/'), PROTECTED_RESOURCE_PATH))
+}
+
+pub async fn oauth_protected_resource(
+    State(state): State<AppState>,
+) -> Json<ProtectedResourceMetadata> {
+    Json(state.metadata.as_ref().clone())
+}
@@ -6,14 +6,17 @@
  */
 use anyhow::Result;
 use axum::{Router, routing::get};
+use http_middleware::{cors::AllowedOrigins, http::HttpLimits};
 use sqlx::postgres::PgPoolOptions;
-use std::{panic, process};
+use std::{panic, process, sync::Arc};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 use crate::{
-    api,
+    api::{
+        self,
+        well_known::{PROTECTED_RESOURCE_PATH, ProtectedResourceMetadata},
+    },
     config::Config,
-    middleware,
     services::{auth::build_auth_service, id_codec::IdCodec},
     state::AppState,
 };
@@ -86,8 +89,9 @@ async fn build_state(config: &Config) -> Result<AppState> {
     let id_codec = IdCodec::new(config.sqids_min_length, &config.sqids_alphabet)?;
 
     let auth = build_auth_service(config).await?;
+    let metadata = Arc::new(ProtectedResourceMetadata::from_config(config));
 
-    Ok(AppState::new(db, id_codec, auth))
+    Ok(AppState::new(db, id_codec, auth, metadata))
 }
 
 /**
@@ -96,12 +100,19 @@ async fn build_state(config: &Config) -> Result<AppState> {
 fn build_router(state: AppState, config: &Config) -> Router {
     let router = Router::new()
         .route("/health", get(api::health::health))
+        .route(
+            PROTECTED_RESOURCE_PATH,
+            get(api::well_known::oauth_protected_resource),
+        )
         .nest("/api/v1", api::v1::routes(state.clone()))
         .with_state(state);
 
     // Cross-cutting middleware (policy/infrastructure)
-    let router = middleware::security_headers::apply(router);
-    let router = middleware::cors::apply(router, config);
+    let router = http_middleware::security_headers::apply(router);
+    let router = http_middleware::cors::apply(
+        router,
+        AllowedOrigins::for_env(config.app_env.is_production(), &config.cors_allowed_origins),
+    );
 
-    middleware::http::apply(router)
+    http_middleware::http::apply(router, &HttpLimits::default())
 }
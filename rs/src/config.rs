@@ -7,6 +7,10 @@ use std::fmt;
 use std::net::SocketAddr;
 use std::str::FromStr;
 
+use jsonwebtoken::Algorithm;
+
+use crate::services::auth::dpop::core::{DEFAULT_ALLOWED_ALGS, parse_alg};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AppEnv {
     Development,
@@ -59,9 +63,16 @@ pub struct Config {
 
     pub auth_issuer: String,
     pub auth_audience: String,
+    // Advertised in the protected resource metadata (RFC 9728)
+    pub scopes_supported: Vec<String>,
     pub access_token_leeway_seconds: u64,
 
-    pub access_jwt_public_key_pem: String,
+    // Static verification key; fallback when a JWKS is configured
+    pub access_jwt_public_key_pem: Option<String>,
+    pub access_jwt_jwks_url: Option<String>,
+    pub access_jwt_jwks_file: Option<String>,
+    pub jwks_min_refresh_interval_seconds: u64,
+    pub jwks_refresh_seconds: u64,
     pub public_base_url: Option<String>,
 
     pub dpop_required: bool,
@@ -70,6 +81,9 @@ pub struct Config {
     pub dpop_replay_ttl_seconds: u64,
     pub dpop_required_ath: bool,
     pub dpop_require_nonce: bool,
+    pub dpop_allowed_algs: Vec<Algorithm>,
+    pub dpop_nonce_secret: Option<String>,
+    pub dpop_nonce_ttl_seconds: u64,
 
     pub valkey_url: String,
 }
@@ -113,14 +127,53 @@ impl Config {
         let auth_audience =
             std::env::var("AUTH_AUDIENCE").map_err(|_| ConfigError::Missing("AUTH_AUDIENCE"))?;
 
+        let scopes_supported = std::env::var("SCOPES_SUPPORTED")
+            .unwrap_or_default()
+            .split([',', ' '])
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+
         let access_token_leeway_seconds = std::env::var("ACCESS_TOKEN_LEEWAY_SECONDS")
             .ok()
             .and_then(|v| v.parse::<u64>().ok())
             .unwrap_or(60);
 
         let access_jwt_public_key_pem = std::env::var("ACCESS_JWT_PUBLIC_KEY_PEM")
-            .map_err(|_| ConfigError::Missing("ACCESS_JWT_PUBLIC_KEY_PEM"))?
-            .replace("\\n", "\n");
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .map(|v| v.replace("\\n", "\n"));
+
+        // --- JWKS (kid ごとの検証鍵。URL と file の両方があれば URL を優先) ---
+        let access_jwt_jwks_url = std::env::var("ACCESS_JWT_JWKS_URL")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+
+        let access_jwt_jwks_file = std::env::var("ACCESS_JWT_JWKS_FILE")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+
+        if access_jwt_public_key_pem.is_none()
+            && access_jwt_jwks_url.is_none()
+            && access_jwt_jwks_file.is_none()
+        {
+            return Err(ConfigError::Missing(
+                "ACCESS_JWT_JWKS_URL, ACCESS_JWT_JWKS_FILE or ACCESS_JWT_PUBLIC_KEY_PEM",
+            ));
+        }
+
+        // Minimum gap between refetches triggered by an unknown `kid`.
+        let jwks_min_refresh_interval_seconds = std::env::var("JWKS_MIN_REFRESH_INTERVAL_SECONDS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(30);
+
+        let jwks_refresh_seconds = std::env::var("JWKS_REFRESH_SECONDS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(300);
 
         let public_base_url = std::env::var("PUBLIC_BASE_URL")
             .ok()
@@ -158,6 +211,30 @@ impl Config {
             .map(|v| v.eq_ignore_ascii_case("true") || v == "1")
             .unwrap_or(false);
 
+        // Comma-separated JWS alg names, e.g. "EdDSA,ES256". `none` and HS* are rejected.
+        let dpop_allowed_algs = match std::env::var("DPOP_ALLOWED_ALGS") {
+            Ok(v) if !v.trim().is_empty() => v
+                .split(',')
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+                .map(|s| parse_alg(s).ok_or(ConfigError::Invalid("DPOP_ALLOWED_ALGS")))
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => DEFAULT_ALLOWED_ALGS.to_vec(),
+        };
+        if dpop_allowed_algs.is_empty() {
+            return Err(ConfigError::Invalid("DPOP_ALLOWED_ALGS"));
+        }
+
+        // Shared HMAC secret for stateless nonces (all replicas must use the same value).
+        let dpop_nonce_secret = std::env::var("DPOP_NONCE_SECRET")
+            .ok()
+            .filter(|v| !v.trim().is_empty());
+
+        let dpop_nonce_ttl_seconds = std::env::var("DPOP_NONCE_TTL_SECONDS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(300);
+
         let valkey_url =
             std::env::var("VALKEY_URL").map_err(|_| ConfigError::Missing("VALKEY_URL"))?;
 
@@ -170,8 +247,13 @@ impl Config {
             sqids_alphabet,
             auth_issuer,
             auth_audience,
+            scopes_supported,
             access_token_leeway_seconds,
             access_jwt_public_key_pem,
+            access_jwt_jwks_url,
+            access_jwt_jwks_file,
+            jwks_min_refresh_interval_seconds,
+            jwks_refresh_seconds,
             public_base_url,
             dpop_required,
             dpop_iat_leeway_seconds,
@@ -179,6 +261,9 @@ impl Config {
             dpop_replay_ttl_seconds,
             dpop_required_ath,
             dpop_require_nonce,
+            dpop_allowed_algs,
+            dpop_nonce_secret,
+            dpop_nonce_ttl_seconds,
             valkey_url,
         })
     }
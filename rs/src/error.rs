@@ -6,15 +6,19 @@
  */
 use axum::{
     Json,
-    http::StatusCode,
+    http::{HeaderName, HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
 };
 use serde::Serialize;
 use thiserror::Error;
 
 use crate::repos::error::RepoError;
+use crate::services::auth::challenge::AuthChallenge;
 use crate::services::id_codec::IdCodecError;
 
+/// Response header carrying a server-issued DPoP nonce.
+pub const DPOP_NONCE: HeaderName = HeaderName::from_static("dpop-nonce");
+
 #[derive(Debug, Serialize)]
 pub struct ErrorResponse {
     pub error: ErrorBody,
@@ -34,12 +38,16 @@ pub enum AppError {
     NotFound { resource: &'static str },
     //#[error("{code}: {message}")]
     //Conflict { code: &'static str, message: String },
-    #[error("unauthorized")]
-    Unauthorized,
+    // 401 は WWW-Authenticate が必須なので Challenge を使う
+    //#[error("unauthorized")]
+    //Unauthorized,
     //#[error("forbidden")]
     //Forbidden,
     #[error("internal server error")]
     Internal,
+    // 認証失敗 (RFC 6750 / RFC 9449 の WWW-Authenticate 付き)
+    #[error("{0}")]
+    Challenge(Box<AuthChallenge>),
 }
 
 impl AppError {
@@ -54,6 +62,10 @@ impl AppError {
         Self::NotFound { resource }
     }
 
+    pub fn challenge(challenge: AuthChallenge) -> Self {
+        Self::Challenge(Box::new(challenge))
+    }
+
     /*
     pub fn conflict(code: &'static str, message: impl Into<String>) -> Self {
         Self::Conflict {
@@ -65,6 +77,26 @@ impl AppError {
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        if let AppError::Challenge(challenge) = self {
+            let body = ErrorResponse {
+                error: ErrorBody {
+                    code: challenge.error.map(|e| e.code()).unwrap_or("UNAUTHORIZED"),
+                    message: challenge.to_string(),
+                },
+            };
+            let mut res = (challenge.status(), Json(body)).into_response();
+            let headers = res.headers_mut();
+            headers.insert(header::WWW_AUTHENTICATE, challenge.header_value());
+            if let Some(v) = challenge
+                .nonce
+                .as_deref()
+                .and_then(|n| HeaderValue::from_str(n).ok())
+            {
+                headers.insert(DPOP_NONCE, v);
+            }
+            return res;
+        }
+
         let (status, code, message) = match self {
             AppError::BadRequest { code, message } => (StatusCode::BAD_REQUEST, code, message),
             AppError::NotFound { resource } => (
@@ -73,14 +105,15 @@ impl IntoResponse for AppError {
                 format!("{resource} not found."),
             ),
             //AppError::Conflict { code, message } => (StatusCode::CONFLICT, code, message),
+            /*
             AppError::Unauthorized => (
                 StatusCode::UNAUTHORIZED,
                 "UNAUTHORIZED",
                 "unauthorized".into(),
-            ),
+            ),*/
             /*
             AppError::Forbidden => (StatusCode::FORBIDDEN, "FORBIDDEN", "forbidden".into()),*/
-            AppError::Internal => (
+            AppError::Internal | AppError::Challenge(_) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "INTERNAL_SERVER_ERROR",
                 "internal server error".into(),
@@ -12,13 +12,14 @@ use axum::{
     Router,
     body::Body,
     extract::{OriginalUri, State},
-    http::{Request, header},
+    http::{HeaderValue, Request, header},
     middleware::{self, Next},
     response::Response,
 };
 
 use crate::api::v1::extractors::AuthCtx;
-use crate::error::AppError;
+use crate::error::{AppError, DPOP_NONCE};
+use crate::services::auth::challenge::{self, ChallengeError};
 use crate::services::auth::dpop::core as dpop_core;
 use crate::state::AppState;
 
@@ -43,15 +44,13 @@ async fn access_middleware(
 ) -> Result<Response, AppError> {
     // Step0: `Authorization: Bearer <uuid>` を受け取り、AuthCtx を入れるだけ。
     // 本来は JWT を decode/verify して sub を取り出す。
-    // Step1: Authorization: Bearer <jwt> を検証し、sub を user_id として AuthCtx に入れる
+    // Step1: Authorization: Bearer <jwt> を検証し、sub を principal (ユーザー or クライアント) として AuthCtx に入れる
 
-    let auth = req
-        .headers()
-        .get(header::AUTHORIZATION)
-        .and_then(|v| v.to_str().ok())
-        .ok_or(AppError::Unauthorized)?;
-
-    let token = auth.strip_prefix("Bearer ").ok_or(AppError::Unauthorized)?;
+    let token = match bearer_token(&req) {
+        Ok(token) => token.to_owned(),
+        Err(described) => return Err(auth_challenge(&state, described, None)),
+    };
+    let token = token.as_str();
 
     // dev 用の簡易フォーマット: Bearer <uuid>
     //let user_id = Uuid::parse_str(bearer).map_err(|_| AppError::Unauthorized)?;
@@ -63,22 +62,46 @@ async fn access_middleware(
         .verify(token)
         .map_err(|_| AppError::Unauthorized)?;
     */
-    let claims = match state.auth.verify_verified(token) {
+    let claims = match state.auth.verify_verified(token).await {
         Ok(claims) => claims,
         Err(err) => {
             tracing::warn!(
                 error = ?err,
                 "access token verification failed"
             );
-            return Err(AppError::Unauthorized);
+            let description = challenge::describe_access_jwt_error(&err);
+            return Err(auth_challenge(
+                &state,
+                Some((ChallengeError::InvalidToken, description)),
+                Some(&err),
+            ));
         }
     };
 
+    // 署名と claims が正しくても、auth server 側で失効済みなら受け付けない
+    let revoked = state.auth.is_revoked(&claims).await.map_err(|err| {
+        // Not the client's fault: fail closed without a challenge.
+        tracing::warn!(error = %err, "denylist backend failure");
+        AppError::Internal
+    })?;
+    if revoked {
+        tracing::warn!(principal = %claims.principal, sid = ?claims.sid, jti = ?claims.jti, "revoked access token presented");
+        return Err(auth_challenge(
+            &state,
+            Some((
+                ChallengeError::InvalidToken,
+                "access token has been revoked",
+            )),
+            None,
+        ));
+    }
+
     let expected_jkt = claims.cnf_jkt.as_deref();
     let uri = &original_uri;
 
+    let dpop_policy = state.auth.dpop_policy();
     let verified_dpop = match dpop_core::verify_proof(
-        state.auth.dpop_policy(),
+        dpop_policy.clone(),
         req.headers(),
         req.method(),
         uri,
@@ -89,12 +112,20 @@ async fn access_middleware(
         Ok(v) => v,
         Err(err) => {
             tracing::warn!(error = ?err, "dpop verification failed");
-            return Err(AppError::Unauthorized);
+            let described = challenge::describe_dpop_error(&err);
+            return Err(auth_challenge(&state, Some(described), Some(&err)));
         }
     };
 
     if let Some(dpop) = verified_dpop {
-        let key = format!("dpop:{}:{}", claims.user_id, dpop.jti);
+        // Server-issued nonce must be one we minted recently.
+        if dpop_policy.require_nonce && !state.auth.verify_dpop_nonce(dpop.nonce.as_deref()) {
+            tracing::debug!("dpop nonce stale");
+            let described = challenge::describe_dpop_error(&dpop_core::DpopError::NonceRequired);
+            return Err(auth_challenge(&state, Some(described), None));
+        }
+
+        let key = format!("dpop:{}:{}", claims.principal, dpop.jti);
         let ttl = state.auth.dpop_policy().replay_ttl_seconds;
 
         let first_time = state
@@ -103,20 +134,102 @@ async fn access_middleware(
             .check_and_store(&key, ttl)
             .await
             .map_err(|err| {
+                // Not the client's fault: fail closed without a challenge.
                 tracing::warn!(error = ?err, "replay backend failure");
-                AppError::Unauthorized
+                AppError::Internal
             })?;
 
         if !first_time {
             tracing::warn!(key = %key, "dpop replay detected");
-            return Err(AppError::Unauthorized);
+            return Err(auth_challenge(
+                &state,
+                Some((
+                    ChallengeError::InvalidDpopProof,
+                    "DPoP proof has already been used",
+                )),
+                None,
+            ));
         }
     }
 
-    let auth_ctx = AuthCtx::new(claims.user_id);
+    let mut auth_ctx = AuthCtx::new(claims.principal);
+    auth_ctx.client_id = claims.client_id;
+    // scope は空白区切り (RFC 6749 Section 3.3)
+    auth_ctx.scopes = claims
+        .scope
+        .as_deref()
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    auth_ctx.roles = claims.roles.unwrap_or_default();
+    auth_ctx.jti = claims.jti;
+    auth_ctx.dpop_jkt = claims.cnf_jkt;
+    auth_ctx.acr = claims.acr;
+    auth_ctx.amr = claims.amr;
+    auth_ctx.actors = claims.actors;
 
     // middleware → extractor への受け渡し
     req.extensions_mut().insert(auth_ctx);
 
-    Ok(next.run(req).await)
+    let mut res = next.run(req).await;
+
+    // Keep clients supplied with the current nonce (it rotates every window).
+    if let Some(nonce) = state.auth.dpop_nonce()
+        && let Ok(v) = HeaderValue::from_str(&nonce)
+    {
+        res.headers_mut().insert(DPOP_NONCE, v);
+    }
+
+    Ok(res)
+}
+
+type Described = (ChallengeError, &'static str);
+
+/// Extract the access token from `Authorization: Bearer|DPoP <token>`.
+///
+/// Err(None): no credentials (challenge without `error`).
+/// Err(Some(..)): malformed credentials.
+fn bearer_token(req: &Request<Body>) -> Result<&str, Option<Described>> {
+    let value = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .ok_or(None)?
+        .to_str()
+        .map_err(|_| {
+            Some((
+                ChallengeError::InvalidRequest,
+                "Authorization header is malformed",
+            ))
+        })?;
+
+    // auth scheme は大文字小文字を区別しない (RFC 9110 Section 11.1)
+    let (scheme, token) = value.split_once(' ').ok_or(Some((
+        ChallengeError::InvalidRequest,
+        "Authorization header is malformed",
+    )))?;
+    if !scheme.eq_ignore_ascii_case("Bearer") && !scheme.eq_ignore_ascii_case("DPoP") {
+        return Err(None);
+    }
+
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(Some((
+            ChallengeError::InvalidRequest,
+            "Authorization header is malformed",
+        )));
+    }
+    Ok(token)
+}
+
+fn auth_challenge(
+    state: &AppState,
+    described: Option<Described>,
+    detail: Option<&dyn std::fmt::Display>,
+) -> AppError {
+    let (error, description) = match described {
+        Some((error, description)) => (Some(error), description),
+        None => (None, ""),
+    };
+    AppError::challenge(state.auth.challenge(error, description, detail))
 }
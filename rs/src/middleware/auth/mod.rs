@@ -1 +1,2 @@
 pub mod access;
+pub mod step_up;
@@ -0,0 +1,86 @@
+//! route 単位の step-up 認証 (RFC 9470)
+//!
+//! - `access` middleware が入れた AuthCtx の `acr` が要求レベル未満なら
+//!   401 `error="insufficient_user_authentication"` + `acr_values` を返す
+//! - クライアントは `acr_values` を付けて再ログイン (MFA) し、新しいトークンで再送する
+//!
+//! 例：
+//! ```ignore
+//! get(users::get_user).merge(step_up::require(delete(users::delete_user), state.clone(), step_up::ACR_MFA))
+//! ```
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::Request,
+    middleware::{self, Next},
+    response::Response,
+    routing::MethodRouter,
+};
+
+use crate::api::v1::extractors::AuthCtx;
+use crate::error::AppError;
+use crate::state::AppState;
+
+/// パスワード + ワンタイムコード
+pub const ACR_MFA: &str = "aal2";
+
+// auth server が発行する acr (弱い順)
+const ACR_LEVELS: [&str; 3] = ["aal1", "aal2", "aal3"];
+
+/// `route` を `acr` 以上でログインしたトークンに限定する。
+///
+/// `access::apply` の内側で動く (AuthCtx が必要) ので、v1 の route にだけ使う。
+pub fn require(
+    route: MethodRouter<AppState>,
+    state: AppState,
+    acr: &'static str,
+) -> MethodRouter<AppState> {
+    route.layer(middleware::from_fn_with_state(
+        (state, acr),
+        step_up_middleware,
+    ))
+}
+
+async fn step_up_middleware(
+    State((state, required)): State<(AppState, &'static str)>,
+    req: Request<Body>,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(ctx) = req.extensions().get::<AuthCtx>() else {
+        // access middleware の外で使われた (配線ミス)
+        tracing::error!("step-up requires the access middleware");
+        return Err(AppError::Internal);
+    };
+
+    if !acr_satisfies(ctx.acr.as_deref(), required) {
+        tracing::info!(principal = %ctx.principal, acr = ?ctx.acr, required, "step-up authentication required");
+        return Err(AppError::challenge(state.auth.step_up_challenge(required)));
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// `acr` が `required` 以上か。未知の値や acr 無し (古いトークン、client_credentials) は満たさない
+fn acr_satisfies(acr: Option<&str>, required: &str) -> bool {
+    let rank = |v: &str| ACR_LEVELS.iter().position(|l| *l == v);
+    match (acr.and_then(rank), rank(required)) {
+        (Some(have), Some(need)) => have >= need,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stronger_acr_satisfies_weaker_requirement() {
+        assert!(acr_satisfies(Some(ACR_MFA), ACR_MFA));
+        assert!(acr_satisfies(Some("aal3"), ACR_MFA));
+        assert!(acr_satisfies(Some(ACR_MFA), "aal1"));
+        assert!(!acr_satisfies(Some("aal1"), ACR_MFA));
+        assert!(!acr_satisfies(None, "aal1"));
+        assert!(!acr_satisfies(Some("urn:example:custom"), "aal1"));
+    }
+}
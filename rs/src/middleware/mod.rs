@@ -4,6 +4,3 @@
  * - pub fn cors(...), pub fn bearer_auth(...) など
  */
 pub mod auth;
-pub mod cors;
-pub mod http;
-pub mod security_headers;
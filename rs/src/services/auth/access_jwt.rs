@@ -1,9 +1,13 @@
+use dpop_nonce::DpopNonceIssuer;
 use jsonwebtoken::{Algorithm, DecodingKey, Validation};
 use serde::Deserialize;
 use std::{error::Error as StdError, fmt, sync::Arc};
 use uuid::Uuid;
 
-use crate::services::auth::dpop::core::DpopPolicy;
+use crate::services::auth::challenge::{AuthChallenge, ChallengeError, ChallengeScheme};
+use crate::services::auth::denylist::{Denylist, DenylistError};
+use crate::services::auth::dpop::core::{DpopPolicy, alg_name};
+use crate::services::auth::jwks::JwksCache;
 use crate::services::auth::replay::store::ReplayStore;
 
 // Errors returned by access-token verification + strict claim validation.
@@ -12,7 +16,10 @@ pub enum AccessJwtError {
     Jwt(jsonwebtoken::errors::Error),
     MissingOrInvalidAud,
     EmptyClaim(&'static str),
+    // `sub` は UUID (ユーザー) か、client_id と一致する文字列 (クライアント) のどちらか
     InvalidSubUuid,
+    // No verification key for the token's `kid` (and no static fallback key).
+    UnknownKey,
 }
 
 impl fmt::Display for AccessJwtError {
@@ -21,7 +28,10 @@ impl fmt::Display for AccessJwtError {
             Self::Jwt(e) => write!(f, "jwt verification failed: {}", e),
             Self::MissingOrInvalidAud => write!(f, "missing or invalid 'aud' claim"),
             Self::EmptyClaim(name) => write!(f, "empty '{}' claim", name),
-            Self::InvalidSubUuid => write!(f, "invalid 'sub' (expected UUID)"),
+            Self::InvalidSubUuid => {
+                write!(f, "invalid 'sub' (expected UUID or the token's client_id)")
+            }
+            Self::UnknownKey => write!(f, "no verification key for token 'kid'"),
         }
     }
 }
@@ -76,6 +86,13 @@ pub struct AccessTokenClaims {
     pub iat: Option<u64>,
     #[serde(default)]
     pub jti: Option<String>,
+    // auth server の session (client_credentials のトークンには無い)
+    #[serde(default)]
+    pub sid: Option<String>,
+
+    // トークンの発行先クライアント (RFC 9068)。client_credentials では sub と同じ値
+    #[serde(default)]
+    pub client_id: Option<String>,
 
     #[serde(default)]
     pub scope: Option<String>,
@@ -84,21 +101,82 @@ pub struct AccessTokenClaims {
 
     #[serde(default)]
     pub cnf: Option<CnfClaim>,
+
+    // ログイン時の認証強度 (aal1 / aal2) と方式 (RFC 8176)。client_credentials には無い
+    #[serde(default)]
+    pub acr: Option<String>,
+    #[serde(default)]
+    pub amr: Option<Vec<String>>,
+
+    // token exchange (RFC 8693) で発行された委任トークンの actor
+    #[serde(default)]
+    pub act: Option<ActorClaim>,
+}
+
+/// `act` claim (RFC 8693 Section 4.1)
+///
+/// 入れ子の `act` はさらに前の actor (委任が重なった場合)
+#[derive(Debug, Clone, Deserialize)]
+pub struct ActorClaim {
+    pub sub: String,
+    #[serde(default)]
+    pub act: Option<Box<ActorClaim>>,
+}
+
+impl ActorClaim {
+    /// 現在の actor から順に並べた actor の `sub`
+    pub fn chain(&self) -> Vec<String> {
+        let mut chain = vec![self.sub.clone()];
+        let mut prior = self.act.as_deref();
+        while let Some(actor) = prior {
+            chain.push(actor.sub.clone());
+            prior = actor.act.as_deref();
+        }
+        chain
+    }
+}
+
+/// トークンの主体 (誰として振る舞うか)
+///
+/// - `User`: ユーザーのトークン。`sub` はプロジェクト規約どおり UUID
+/// - `Client`: client_credentials で発行されたサービス間呼び出し用トークン。`sub` = `client_id`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Principal {
+    User(Uuid),
+    Client(String),
+}
+
+impl fmt::Display for Principal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::User(id) => write!(f, "user:{id}"),
+            Self::Client(id) => write!(f, "client:{id}"),
+        }
+    }
 }
 
 /// AuthService が返す「検証済み・アプリ側で使う型」
 ///
-/// - 'sub' はプロジェクト規約として UUID なので、ここでは `Uuid` に昇格させる
+/// - `sub` は `Principal` に昇格させる (UUID ならユーザー、client_id と一致すればクライアント)
 /// - `iss/aud/exp` の整合性は `verify_strict` の中（jsonwebtoken + 追加チェック）で保証される前提
 #[derive(Debug, Clone)]
 pub struct VerifiedAccessToken {
-    pub user_id: Uuid,
+    pub principal: Principal,
+    // ユーザーのトークンでは、ログインに使われたクライアント (あれば)
+    pub client_id: Option<String>,
 
     pub jti: Option<String>,
+    pub sid: Option<String>,
     pub scope: Option<String>,
     pub roles: Option<Vec<String>>,
 
     pub cnf_jkt: Option<String>,
+
+    pub acr: Option<String>,
+    pub amr: Vec<String>,
+
+    // 委任トークンの actor chain (現在の actor が先頭)。委任でなければ空
+    pub actors: Vec<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -109,29 +187,42 @@ pub struct CnfClaim {
 
 /// EdDSA (Ed25519) access-token verifier.
 ///
+/// - Keys come from the auth server's JWKS (by `kid`) and/or a static PEM.
+///   The static key is used for tokens whose `kid` is absent or not in the JWKS.
 /// - Key material is intentionally not printable via Debug.
 #[derive(Clone)]
 pub struct AuthService {
-    decoding_key: DecodingKey,
+    static_key: Option<DecodingKey>,
+    jwks: Option<Arc<JwksCache>>,
     validation: Validation,
     dpop_policy: DpopPolicy,
     replay_store: Arc<dyn ReplayStore>,
     public_base_url: Option<String>,
+    nonce_issuer: Option<DpopNonceIssuer>,
+    // Append internal error detail to error_description (development only).
+    expose_error_detail: bool,
+    // Protected resource metadata URL advertised in challenges (RFC 9728).
+    resource_metadata_url: Option<String>,
+    // 失効済み session / token (auth server から Valkey 経由で届く)
+    denylist: Option<Arc<Denylist>>,
 }
 
 impl std::fmt::Debug for AuthService {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // Do not print key material
         f.debug_struct("AuthService")
+            .field("has_static_key", &self.static_key.is_some())
+            .field("jwks", &self.jwks)
             .field("validation", &self.validation)
             .field("dpop_policy", &self.dpop_policy)
+            .field("denylist", &self.denylist)
             .finish()
     }
 }
 
 impl AuthService {
     pub fn new(
-        access_public_key_pem: &str,
+        access_public_key_pem: Option<&str>,
         issuer: &str,
         audience: &str,
         leeway_seconds: u64,
@@ -139,7 +230,9 @@ impl AuthService {
         replay_store: Arc<dyn ReplayStore>,
         public_base_url: Option<String>,
     ) -> Result<Self, String> {
-        let decoding_key = DecodingKey::from_ed_pem(access_public_key_pem.as_bytes())
+        let static_key = access_public_key_pem
+            .map(|pem| DecodingKey::from_ed_pem(pem.as_bytes()))
+            .transpose()
             .map_err(|e| format!("invalid ed25519 public key pem: {}", e))?;
 
         let mut validation = Validation::new(Algorithm::EdDSA);
@@ -148,22 +241,69 @@ impl AuthService {
         validation.leeway = leeway_seconds;
 
         Ok(Self {
-            decoding_key,
+            static_key,
+            jwks: None,
             validation,
             dpop_policy,
             replay_store,
             public_base_url,
+            nonce_issuer: None,
+            expose_error_detail: false,
+            resource_metadata_url: None,
+            denylist: None,
         })
     }
 
+    // Resolve verification keys by `kid` from a JWKS.
+    pub fn with_jwks(mut self, jwks: Arc<JwksCache>) -> Self {
+        self.jwks = Some(jwks);
+        self
+    }
+
+    pub fn with_resource_metadata(mut self, url: Option<String>) -> Self {
+        self.resource_metadata_url = url;
+        self
+    }
+
+    pub fn with_error_detail(mut self, expose: bool) -> Self {
+        self.expose_error_detail = expose;
+        self
+    }
+
+    // Reject tokens whose session (`sid`) or `jti` the auth server has revoked.
+    pub fn with_denylist(mut self, denylist: Arc<Denylist>) -> Self {
+        self.denylist = Some(denylist);
+        self
+    }
+
+    // Enable server-issued DPoP nonces (required when `dpop_policy.require_nonce`).
+    pub fn with_nonce_issuer(mut self, issuer: DpopNonceIssuer) -> Self {
+        self.nonce_issuer = Some(issuer);
+        self
+    }
+
     // Verify and decode a JWT access token.
-    pub fn verify(&self, token: &str) -> Result<AccessTokenClaims, jsonwebtoken::errors::Error> {
+    pub async fn verify(&self, token: &str) -> Result<AccessTokenClaims, AccessJwtError> {
+        let decoding_key = self.decoding_key(token).await?;
         let data =
-            jsonwebtoken::decode::<AccessTokenClaims>(token, &self.decoding_key, &self.validation)?;
+            jsonwebtoken::decode::<AccessTokenClaims>(token, &decoding_key, &self.validation)?;
 
         Ok(data.claims)
     }
 
+    // kid があれば JWKS から、無ければ (または未知の kid なら) 静的 PEM を使う
+    async fn decoding_key(&self, token: &str) -> Result<DecodingKey, AccessJwtError> {
+        let header = jsonwebtoken::decode_header(token)?;
+
+        if let (Some(jwks), Some(kid)) = (&self.jwks, header.kid.as_deref())
+            && let Some(key) = jwks.get(kid).await
+        {
+            return Ok(key);
+        }
+
+        self.static_key.clone().ok_or(AccessJwtError::UnknownKey)
+    }
+
     /// Verify + strict claim validation.
     ///
     /// `jsonwebtoken::Validation` already checks:
@@ -173,8 +313,8 @@ impl AuthService {
     ///
     /// This method additionally checks:
     /// - required claims are present *and not empty* (`iss`, `aud`, `sub`, `exp`)
-    pub fn verify_strict(&self, token: &str) -> Result<AccessTokenClaims, AccessJwtError> {
-        let claims = self.verify(token)?;
+    pub async fn verify_strict(&self, token: &str) -> Result<AccessTokenClaims, AccessJwtError> {
+        let claims = self.verify(token).await?;
 
         // Required (non-empty) checks. `exp` is `u64` so serde guarantees presence,
         // but we still defend against a meaningless value.
@@ -191,10 +331,8 @@ impl AuthService {
             return Err(AccessJwtError::MissingOrInvalidAud);
         }
 
-        // Project convention: subject is a UUID
-        if Self::parse_sub_uuid(&claims.sub).is_err() {
-            return Err(AccessJwtError::InvalidSubUuid);
-        }
+        // Project convention: subject is a UUID, except for client tokens (sub == client_id)
+        Self::principal(&claims)?;
 
         Ok(claims)
     }
@@ -202,26 +340,57 @@ impl AuthService {
     /// Verify + strict claim validation, then convert claims into an application-friendly type.
     ///
     /// This is the recommended entry-point for middleware/handlers.
-    pub fn verify_verified(&self, token: &str) -> Result<VerifiedAccessToken, AccessJwtError> {
-        let claims = self.verify_strict(token)?;
-
-        let user_id =
-            Self::parse_sub_uuid(&claims.sub).map_err(|_| AccessJwtError::InvalidSubUuid)?;
+    pub async fn verify_verified(
+        &self,
+        token: &str,
+    ) -> Result<VerifiedAccessToken, AccessJwtError> {
+        let claims = self.verify_strict(token).await?;
+        let principal = Self::principal(&claims)?;
 
         Ok(VerifiedAccessToken {
-            user_id,
+            principal,
+            client_id: claims.client_id,
             jti: claims.jti,
+            sid: claims.sid,
             scope: claims.scope,
             roles: claims.roles,
             cnf_jkt: claims.cnf.and_then(|c| c.jkt),
+            acr: claims.acr,
+            amr: claims.amr.unwrap_or_default(),
+            actors: claims.act.map(|a| a.chain()).unwrap_or_default(),
         })
     }
 
+    /// Whether the auth server revoked the token (or its session) before `exp`.
+    pub async fn is_revoked(&self, token: &VerifiedAccessToken) -> Result<bool, DenylistError> {
+        match &self.denylist {
+            Some(denylist) => {
+                denylist
+                    .is_denied(token.sid.as_deref(), token.jti.as_deref())
+                    .await
+            }
+            None => Ok(false),
+        }
+    }
+
     // Helper: parse `sub` into UUID
     pub fn parse_sub_uuid(sub: &str) -> Result<Uuid, ()> {
         Uuid::parse_str(sub).map_err(|_| ())
     }
 
+    // sub == client_id のときだけクライアントとして扱う (auth 側は UUID 形式の client_id を登録させない)
+    fn principal(claims: &AccessTokenClaims) -> Result<Principal, AccessJwtError> {
+        if let Ok(user_id) = Self::parse_sub_uuid(&claims.sub) {
+            return Ok(Principal::User(user_id));
+        }
+        match claims.client_id.as_deref() {
+            Some(client_id) if client_id == claims.sub => {
+                Ok(Principal::Client(client_id.to_string()))
+            }
+            _ => Err(AccessJwtError::InvalidSubUuid),
+        }
+    }
+
     pub fn dpop_policy(&self) -> DpopPolicy {
         self.dpop_policy.clone()
     }
@@ -233,4 +402,119 @@ impl AuthService {
     pub fn replay_store(&self) -> &dyn ReplayStore {
         self.replay_store.as_ref()
     }
+
+    /// Current nonce for the `DPoP-Nonce` response header (None when nonces are disabled).
+    pub fn dpop_nonce(&self) -> Option<String> {
+        let now = chrono::Utc::now().timestamp();
+        self.nonce_issuer.as_ref().map(|n| n.issue(now))
+    }
+
+    /// Validate the `nonce` claim of a proof. Fails closed when no issuer is configured.
+    pub fn verify_dpop_nonce(&self, nonce: Option<&str>) -> bool {
+        let now = chrono::Utc::now().timestamp();
+        match (&self.nonce_issuer, nonce) {
+            (Some(issuer), Some(nonce)) => issuer.verify(nonce, now),
+            _ => false,
+        }
+    }
+
+    /// Build a `WWW-Authenticate` challenge for this resource server.
+    ///
+    /// The scheme is `DPoP` (with `algs` and a fresh nonce) when DPoP is required.
+    /// `detail` is only appended to the description when error detail is enabled.
+    pub fn challenge(
+        &self,
+        error: Option<ChallengeError>,
+        description: &'static str,
+        detail: Option<&dyn fmt::Display>,
+    ) -> AuthChallenge {
+        let description = error.map(|_| match detail {
+            Some(d) if self.expose_error_detail => format!("{description} ({d})"),
+            _ => description.to_string(),
+        });
+
+        if !self.dpop_policy.required {
+            return AuthChallenge {
+                scheme: ChallengeScheme::Bearer,
+                error,
+                description,
+                algs: None,
+                nonce: None,
+                resource_metadata: self.resource_metadata_url.clone(),
+                acr_values: None,
+            };
+        }
+
+        let algs = self
+            .dpop_policy
+            .allowed_algs
+            .iter()
+            .map(|a| alg_name(*a))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        AuthChallenge {
+            scheme: ChallengeScheme::DPoP,
+            error,
+            description,
+            algs: Some(algs),
+            nonce: self.dpop_nonce(),
+            resource_metadata: self.resource_metadata_url.clone(),
+            acr_values: None,
+        }
+    }
+
+    /// Challenge asking the user to sign in again at `acr_values` (RFC 9470 step-up).
+    pub fn step_up_challenge(&self, acr_values: &str) -> AuthChallenge {
+        AuthChallenge {
+            acr_values: Some(acr_values.to_string()),
+            ..self.challenge(
+                Some(ChallengeError::InsufficientUserAuthentication),
+                "A stronger authentication is required",
+                None,
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(sub: &str, client_id: Option<&str>) -> AccessTokenClaims {
+        serde_json::from_value(serde_json::json!({
+            "iss": "https://auth.test",
+            "aud": "api",
+            "sub": sub,
+            "exp": 1,
+            "client_id": client_id,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn principal_from_sub() {
+        let user = "11111111-1111-1111-1111-111111111111";
+        assert_eq!(
+            AuthService::principal(&claims(user, Some("web"))).unwrap(),
+            Principal::User(Uuid::parse_str(user).unwrap())
+        );
+        assert_eq!(
+            AuthService::principal(&claims("batch", Some("batch"))).unwrap(),
+            Principal::Client("batch".into())
+        );
+        // A non-UUID sub is only a client when the token says so.
+        assert!(AuthService::principal(&claims("batch", None)).is_err());
+        assert!(AuthService::principal(&claims("batch", Some("web"))).is_err());
+    }
+
+    #[test]
+    fn actor_chain_starts_with_current_actor() {
+        let act: ActorClaim = serde_json::from_value(serde_json::json!({
+            "sub": "orders",
+            "act": { "sub": "bff" },
+        }))
+        .unwrap();
+        assert_eq!(act.chain(), vec!["orders", "bff"]);
+    }
 }
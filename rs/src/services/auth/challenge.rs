@@ -0,0 +1,293 @@
+/*
+ * Responsibility
+ * - 認証失敗を RFC 6750 / RFC 9449 の WWW-Authenticate challenge に変換する
+ * - error_description は外部に出しても安全な固定文言のみ (詳細は development のみ付与)
+ */
+use std::fmt;
+
+use axum::http::{HeaderValue, StatusCode};
+use jsonwebtoken::errors::ErrorKind;
+
+use crate::services::auth::access_jwt::AccessJwtError;
+use crate::services::auth::dpop::core::DpopError;
+
+/// Authentication scheme advertised in the challenge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChallengeScheme {
+    Bearer,
+    DPoP,
+}
+
+impl ChallengeScheme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Bearer => "Bearer",
+            Self::DPoP => "DPoP",
+        }
+    }
+}
+
+/// `error` parameter values (RFC 6750 Section 3.1, RFC 9449 Sections 7.1 / 9).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChallengeError {
+    InvalidRequest,
+    InvalidToken,
+    InvalidDpopProof,
+    UseDpopNonce,
+    // RFC 9470: トークンは有効だがログイン時の認証強度 (acr) が足りない
+    InsufficientUserAuthentication,
+}
+
+impl ChallengeError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidRequest => "invalid_request",
+            Self::InvalidToken => "invalid_token",
+            Self::InvalidDpopProof => "invalid_dpop_proof",
+            Self::UseDpopNonce => "use_dpop_nonce",
+            Self::InsufficientUserAuthentication => "insufficient_user_authentication",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            // RFC 6750 Section 3.1: malformed requests are 400.
+            Self::InvalidRequest => StatusCode::BAD_REQUEST,
+            _ => StatusCode::UNAUTHORIZED,
+        }
+    }
+}
+
+/// A rendered `WWW-Authenticate` challenge.
+///
+/// `error == None` is the "no credentials" case: only the scheme (and `algs`) are sent.
+#[derive(Debug, Clone)]
+pub struct AuthChallenge {
+    pub scheme: ChallengeScheme,
+    pub error: Option<ChallengeError>,
+    pub description: Option<String>,
+    // Space-separated JWS alg names (DPoP only)
+    pub algs: Option<String>,
+    // Fresh nonce for the `DPoP-Nonce` header
+    pub nonce: Option<String>,
+    // RFC 9728 Section 5.1: where clients find the protected resource metadata
+    pub resource_metadata: Option<String>,
+    // RFC 9470 Section 3: the acr the client should sign in again with
+    pub acr_values: Option<String>,
+}
+
+impl AuthChallenge {
+    pub fn status(&self) -> StatusCode {
+        self.error
+            .map(|e| e.status())
+            .unwrap_or(StatusCode::UNAUTHORIZED)
+    }
+
+    pub fn header_value(&self) -> HeaderValue {
+        let mut params = Vec::new();
+        if let Some(error) = self.error {
+            params.push(format!("error=\"{}\"", error.code()));
+        }
+        if let Some(description) = &self.description {
+            params.push(format!("error_description=\"{}\"", quote(description)));
+        }
+        if let Some(algs) = &self.algs {
+            params.push(format!("algs=\"{}\"", quote(algs)));
+        }
+        if let Some(acr_values) = &self.acr_values {
+            params.push(format!("acr_values=\"{}\"", quote(acr_values)));
+        }
+        if let Some(url) = &self.resource_metadata {
+            params.push(format!("resource_metadata=\"{}\"", quote(url)));
+        }
+
+        let value = if params.is_empty() {
+            self.scheme.as_str().to_string()
+        } else {
+            format!("{} {}", self.scheme.as_str(), params.join(", "))
+        };
+
+        // `quote` keeps the value visible ASCII, so this cannot fail in practice.
+        HeaderValue::from_str(&value).unwrap_or_else(|_| HeaderValue::from_static("Bearer"))
+    }
+}
+
+impl fmt::Display for AuthChallenge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.error, &self.description) {
+            (Some(_), Some(d)) => f.write_str(d),
+            (Some(e), None) => f.write_str(e.code()),
+            (None, _) => f.write_str("authentication required"),
+        }
+    }
+}
+
+// Quoted-string content: escape `"`/`\` and drop anything that is not visible ASCII.
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            ' '..='~' => out.push(c),
+            _ => out.push('?'),
+        }
+    }
+    out
+}
+
+/// Challenge classification + safe description for a DPoP proof failure.
+pub fn describe_dpop_error(err: &DpopError) -> (ChallengeError, &'static str) {
+    let description = match err {
+        DpopError::NonceRequired => {
+            return (
+                ChallengeError::UseDpopNonce,
+                "Resource server requires nonce in DPoP proof",
+            );
+        }
+        DpopError::MissingProof => "DPoP proof is missing",
+        DpopError::UnsupportedAlg(_) => "DPoP proof alg is not supported",
+        DpopError::MissingJwk | DpopError::UnsupportedJwk => "DPoP proof key is not supported",
+        DpopError::InvalidIat => "DPoP proof iat is outside the acceptable window",
+        DpopError::JktMismatch => "DPoP proof key does not match the access token",
+        DpopError::AthMismatch => "DPoP proof is not bound to the access token",
+        DpopError::MethodMismatch | DpopError::UriMismatch => {
+            "DPoP proof does not match the request"
+        }
+        DpopError::InvalidJwt | DpopError::InvalidTyp | DpopError::MissingClaim(_) => {
+            "DPoP proof is invalid"
+        }
+    };
+    (ChallengeError::InvalidDpopProof, description)
+}
+
+/// Safe description for an access token failure (always `invalid_token`).
+pub fn describe_access_jwt_error(err: &AccessJwtError) -> &'static str {
+    match err {
+        AccessJwtError::Jwt(e) => match e.kind() {
+            ErrorKind::ExpiredSignature => "The access token expired",
+            ErrorKind::ImmatureSignature => "The access token is not yet valid",
+            ErrorKind::InvalidAudience | ErrorKind::InvalidIssuer => {
+                "The access token is not valid for this resource"
+            }
+            _ => "The access token is invalid",
+        },
+        AccessJwtError::UnknownKey => "The access token is signed with an unknown key",
+        _ => "The access token is invalid",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::header;
+    use axum::response::IntoResponse;
+
+    use super::*;
+    use crate::error::{AppError, DPOP_NONCE};
+
+    fn challenge(scheme: ChallengeScheme, error: Option<ChallengeError>) -> AuthChallenge {
+        AuthChallenge {
+            scheme,
+            error,
+            description: None,
+            algs: None,
+            nonce: None,
+            resource_metadata: None,
+            acr_values: None,
+        }
+    }
+
+    #[test]
+    fn no_credentials_sends_only_the_scheme() {
+        let bearer = challenge(ChallengeScheme::Bearer, None);
+        assert_eq!(bearer.header_value(), "Bearer");
+        assert_eq!(bearer.status(), StatusCode::UNAUTHORIZED);
+
+        let dpop = AuthChallenge {
+            algs: Some("ES256 EdDSA".into()),
+            ..challenge(ChallengeScheme::DPoP, None)
+        };
+        assert_eq!(dpop.header_value(), "DPoP algs=\"ES256 EdDSA\"");
+    }
+
+    #[test]
+    fn renders_error_description_and_algs_in_order() {
+        let c = AuthChallenge {
+            description: Some("DPoP proof is invalid".into()),
+            algs: Some("EdDSA".into()),
+            resource_metadata: Some(
+                "https://api.example/.well-known/oauth-protected-resource".into(),
+            ),
+            ..challenge(
+                ChallengeScheme::DPoP,
+                Some(ChallengeError::InvalidDpopProof),
+            )
+        };
+        assert_eq!(
+            c.header_value(),
+            "DPoP error=\"invalid_dpop_proof\", error_description=\"DPoP proof is invalid\", \
+             algs=\"EdDSA\", resource_metadata=\"https://api.example/.well-known/oauth-protected-resource\""
+        );
+        assert_eq!(c.to_string(), "DPoP proof is invalid");
+    }
+
+    #[test]
+    fn escapes_quoted_strings() {
+        let c = AuthChallenge {
+            description: Some("bad \"token\" \\ caf\u{e9}\n".into()),
+            ..challenge(ChallengeScheme::Bearer, Some(ChallengeError::InvalidToken))
+        };
+        assert_eq!(
+            c.header_value(),
+            "Bearer error=\"invalid_token\", error_description=\"bad \\\"token\\\" \\\\ caf??\""
+        );
+    }
+
+    #[test]
+    fn malformed_requests_are_400() {
+        let c = challenge(
+            ChallengeScheme::Bearer,
+            Some(ChallengeError::InvalidRequest),
+        );
+        assert_eq!(c.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(c.header_value(), "Bearer error=\"invalid_request\"");
+    }
+
+    #[test]
+    fn nonce_challenge_sets_dpop_nonce_header() {
+        let (error, description) = describe_dpop_error(&DpopError::NonceRequired);
+        assert_eq!(error, ChallengeError::UseDpopNonce);
+
+        let c = AuthChallenge {
+            description: Some(description.into()),
+            nonce: Some("n-1".into()),
+            ..challenge(ChallengeScheme::DPoP, Some(error))
+        };
+        let res = AppError::challenge(c).into_response();
+
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(res.headers()[DPOP_NONCE], "n-1");
+        assert_eq!(
+            res.headers()[header::WWW_AUTHENTICATE],
+            "DPoP error=\"use_dpop_nonce\", error_description=\"Resource server requires nonce in DPoP proof\""
+        );
+    }
+
+    #[test]
+    fn step_up_challenge_carries_acr_values() {
+        let c = AuthChallenge {
+            acr_values: Some("aal2".into()),
+            ..challenge(
+                ChallengeScheme::DPoP,
+                Some(ChallengeError::InsufficientUserAuthentication),
+            )
+        };
+        assert_eq!(c.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            c.header_value(),
+            "DPoP error=\"insufficient_user_authentication\", acr_values=\"aal2\""
+        );
+    }
+}
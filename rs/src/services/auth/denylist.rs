@@ -0,0 +1,240 @@
+/*
+ * Responsibility
+ * - auth server が失効させた session (`sid`) / token (`jti`) の denylist をローカルに保持する
+ * - Valkey pub/sub (`auth:denylist`) を購読して即時に反映し、(再)接続のたびに既存のキーを読み直す
+ * - 購読が切れている間は Valkey を直接引く (取れなければ fail-closed)
+ *
+ * auth 側の形式:
+ * - key:     `auth:denylist:<kind>:<id>` = exp (unix 秒)、exp まで EX 付きで残る
+ * - message: `{"kind":"sid"|"jti","id":"...","exp":<unix 秒>}`
+ */
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use serde::Deserialize;
+
+const CHANNEL: &str = "auth:denylist";
+const KEY_PREFIX: &str = "auth:denylist";
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Debug, thiserror::Error)]
+pub enum DenylistError {
+    #[error("valkey error: {0}")]
+    Valkey(#[from] redis::RedisError),
+    #[error("denylist subscription closed")]
+    Closed,
+}
+
+#[derive(Debug, Deserialize)]
+struct DenylistMessage {
+    kind: String,
+    id: String,
+    exp: u64,
+}
+
+/// `<kind>:<id>` -> exp (unix 秒)。期限切れのエントリは無いものとして扱う
+#[derive(Debug, Default)]
+struct Entries(HashMap<String, u64>);
+
+impl Entries {
+    fn contains(&self, key: &str, now: u64) -> bool {
+        self.0.get(key).is_some_and(|exp| *exp > now)
+    }
+
+    fn insert(&mut self, key: String, exp: u64, now: u64) {
+        // 失効通知はまれなので、ついでに掃除する
+        self.0.retain(|_, e| *e > now);
+        if exp > now {
+            self.0.insert(key, exp);
+        }
+    }
+}
+
+/// Revoked sessions / tokens published by the auth server.
+pub struct Denylist {
+    client: redis::Client,
+    manager: redis::aio::ConnectionManager,
+    entries: RwLock<Entries>,
+    // true の間はローカルの entries だけで判定できる (購読中 + 読み込み済み)
+    synced: AtomicBool,
+}
+
+impl std::fmt::Debug for Denylist {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Denylist")
+            .field("entries", &self.entries.read().map(|e| e.0.len()).ok())
+            .field("synced", &self.synced.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl Denylist {
+    pub async fn new(redis_url: &str) -> Result<Self, DenylistError> {
+        let client = redis::Client::open(redis_url)?;
+        let manager = client.get_connection_manager().await?;
+
+        Ok(Self {
+            client,
+            manager,
+            entries: RwLock::new(Entries::default()),
+            synced: AtomicBool::new(false),
+        })
+    }
+
+    /// Whether the token's session or the token itself was revoked.
+    ///
+    /// Err: the subscription is down and Valkey could not be asked (caller fails closed).
+    pub async fn is_denied(
+        &self,
+        sid: Option<&str>,
+        jti: Option<&str>,
+    ) -> Result<bool, DenylistError> {
+        let keys = [
+            sid.map(|s| format!("sid:{s}")),
+            jti.map(|j| format!("jti:{j}")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        if keys.is_empty() {
+            return Ok(false);
+        }
+
+        let now = unix_now();
+        {
+            let entries = self.entries.read().unwrap_or_else(|e| e.into_inner());
+            if keys.iter().any(|k| entries.contains(k, now)) {
+                return Ok(true);
+            }
+        }
+        if self.synced.load(Ordering::Acquire) {
+            return Ok(false);
+        }
+
+        // 購読が切れている間に発行された失効を取りこぼさないよう Valkey を直接見る
+        let mut conn = self.manager.clone();
+        let values: Vec<Option<u64>> = redis::cmd("MGET")
+            .arg(
+                keys.iter()
+                    .map(|k| format!("{KEY_PREFIX}:{k}"))
+                    .collect::<Vec<_>>(),
+            )
+            .query_async(&mut conn)
+            .await?;
+        Ok(values.iter().flatten().any(|exp| *exp > now))
+    }
+
+    /// Keep the local copy in sync, reconnecting forever.
+    pub fn spawn_subscriber(self: &std::sync::Arc<Self>) {
+        let denylist = std::sync::Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = denylist.subscribe().await {
+                    tracing::warn!(error = %err, "denylist subscription lost; falling back to valkey lookups");
+                }
+                denylist.synced.store(false, Ordering::Release);
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        });
+    }
+
+    async fn subscribe(&self) -> Result<(), DenylistError> {
+        let mut pubsub = self.client.get_async_pubsub().await?;
+        pubsub.subscribe(CHANNEL).await?;
+
+        // 購読を始めてから読み込むので、その間に publish されたものも取りこぼさない
+        let count = self.reload().await?;
+        self.synced.store(true, Ordering::Release);
+        tracing::info!(entries = count, "denylist subscribed");
+
+        let mut messages = pubsub.on_message();
+        while let Some(msg) = messages.next().await {
+            let parsed = msg
+                .get_payload::<String>()
+                .ok()
+                .and_then(|p| serde_json::from_str::<DenylistMessage>(&p).ok());
+            let Some(m) = parsed else {
+                tracing::warn!("ignoring malformed denylist message");
+                continue;
+            };
+
+            tracing::debug!(kind = %m.kind, id = %m.id, "denylist entry received");
+            self.entries
+                .write()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(format!("{}:{}", m.kind, m.id), m.exp, unix_now());
+        }
+
+        Err(DenylistError::Closed)
+    }
+
+    // Replace the local copy with the entries currently stored in Valkey.
+    async fn reload(&self) -> Result<usize, DenylistError> {
+        let mut conn = self.manager.clone();
+        let pattern = format!("{KEY_PREFIX}:*");
+
+        let mut keys = Vec::new();
+        let mut cursor = 0u64;
+        loop {
+            let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(500)
+                .query_async(&mut conn)
+                .await?;
+            keys.extend(batch);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+
+        let mut fresh = Entries::default();
+        if !keys.is_empty() {
+            let values: Vec<Option<u64>> =
+                redis::cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+            let now = unix_now();
+            for (key, exp) in keys.into_iter().zip(values) {
+                if let (Some(entry), Some(exp)) = (key.strip_prefix(&format!("{KEY_PREFIX}:")), exp)
+                    && exp > now
+                {
+                    fresh.0.insert(entry.to_string(), exp);
+                }
+            }
+        }
+
+        let count = fresh.0.len();
+        *self.entries.write().unwrap_or_else(|e| e.into_inner()) = fresh;
+        Ok(count)
+    }
+}
+
+fn unix_now() -> u64 {
+    chrono::Utc::now().timestamp().max(0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_expire() {
+        let mut entries = Entries::default();
+        entries.insert("sid:a".into(), 200, 100);
+        entries.insert("sid:expired".into(), 50, 100);
+
+        assert!(entries.contains("sid:a", 100));
+        assert!(!entries.contains("sid:a", 200));
+        assert!(!entries.contains("sid:expired", 100));
+        assert!(!entries.contains("jti:a", 100));
+
+        // 期限切れは次の insert で掃除される
+        entries.insert("jti:b".into(), 400, 300);
+        assert_eq!(entries.0.len(), 1);
+    }
+}
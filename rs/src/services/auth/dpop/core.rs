@@ -5,7 +5,10 @@
 //! do replay protection and nonce flows.
 
 use axum::http::{HeaderMap, Method, Uri, header};
-use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, jwk::Jwk};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, Validation, decode,
+    jwk::{AlgorithmParameters, EllipticCurve, Jwk},
+};
 use serde::Deserialize;
 use tracing::warn;
 
@@ -13,7 +16,7 @@ use tracing::warn;
 ///
 /// Note: We keep this struct here (instead of depending on `Config`) so the core
 /// logic stays testable and resusable.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct DpopPolicy {
     // If false, `verify_proof` becomes a no-op.
     pub required: bool,
@@ -26,8 +29,22 @@ pub struct DpopPolicy {
     // If true, we require `nonce` claim.
     pub require_nonce: bool,
     pub replay_ttl_seconds: u64,
+    // Accepted proof signing algorithms (`alg` header). Asymmetric only.
+    pub allowed_algs: Vec<Algorithm>,
 }
 
+/// Algorithms accepted when `DPOP_ALLOWED_ALGS` is not set.
+/// ES256 is what browsers produce with WebCrypto non-extractable keys.
+pub const DEFAULT_ALLOWED_ALGS: &[Algorithm] = &[
+    Algorithm::EdDSA,
+    Algorithm::ES256,
+    Algorithm::RS256,
+    Algorithm::PS256,
+];
+
+// Smallest RSA modulus we accept for RS*/PS* proofs.
+const MIN_RSA_MODULUS_BITS: usize = 2048;
+
 /// Verified DPoP proof information useful for downstream checks.
 #[derive(Debug, Clone)]
 pub struct VerifiedDpop {
@@ -130,13 +147,15 @@ pub fn verify_proof(
         return Err(DpopError::InvalidTyp);
     }
 
-    // We keep this strict for now: DPoP proof must be EdDSA.
-    // (You can widen this later if you want to accept ES256 as well.)
-    if header.alg != Algorithm::EdDSA {
+    // Only the configured asymmetric algorithms are accepted.
+    // `alg: none` never gets here (jsonwebtoken cannot parse it), and HMAC is
+    // rejected by config parsing, but we check the family again to fail closed.
+    if !policy.allowed_algs.contains(&header.alg) || is_symmetric(header.alg) {
         return Err(DpopError::UnsupportedAlg(header.alg));
     }
 
     let jwk: Jwk = header.jwk.ok_or(DpopError::MissingJwk)?;
+    ensure_jwk_matches_alg(header.alg, &jwk)?;
     let decoding_key = DecodingKey::from_jwk(&jwk).map_err(|e| {
         warn!(error = ?e, "invalid DPoP jwk");
         DpopError::InvalidJwt
@@ -151,7 +170,7 @@ pub fn verify_proof(
     }
 
     // 2) Verify signature and parse claims.
-    let mut validation = Validation::new(Algorithm::EdDSA);
+    let mut validation = Validation::new(header.alg);
     // DPoP proof is not an access token , so we don't validate iss/ and here.
     // We do validate exp = none (DPop proof uses iat/max-age instead).
     validation.validate_exp = false;
@@ -322,26 +341,131 @@ fn compute_ath(access_token: &str) -> String {
     base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(digest)
 }
 
-fn compute_jwk_thumbprint(jwk: &Jwk) -> Result<String, DpopError> {
+/// Parse a JWS `alg` name usable for DPoP proofs.
+///
+/// Returns `None` for unknown names, `none`, and symmetric (HMAC) algorithms.
+pub fn parse_alg(name: &str) -> Option<Algorithm> {
+    let alg: Algorithm = name.trim().parse().ok()?;
+    if is_symmetric(alg) { None } else { Some(alg) }
+}
+
+/// JWS name of `alg` (as used in `algs` challenge parameters and metadata).
+pub fn alg_name(alg: Algorithm) -> &'static str {
+    match alg {
+        Algorithm::HS256 => "HS256",
+        Algorithm::HS384 => "HS384",
+        Algorithm::HS512 => "HS512",
+        Algorithm::ES256 => "ES256",
+        Algorithm::ES384 => "ES384",
+        Algorithm::RS256 => "RS256",
+        Algorithm::RS384 => "RS384",
+        Algorithm::RS512 => "RS512",
+        Algorithm::PS256 => "PS256",
+        Algorithm::PS384 => "PS384",
+        Algorithm::PS512 => "PS512",
+        Algorithm::EdDSA => "EdDSA",
+    }
+}
+
+fn is_symmetric(alg: Algorithm) -> bool {
+    matches!(alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512)
+}
+
+// The embedded jwk must be a key that can legitimately produce `alg`
+// (e.g. ES384 needs a P-384 key, RS*/PS* need a large enough RSA key).
+fn ensure_jwk_matches_alg(alg: Algorithm, jwk: &Jwk) -> Result<(), DpopError> {
     use base64::Engine as _;
-    use jsonwebtoken::jwk::AlgorithmParameters;
-    use sha2::{Digest, Sha256};
 
-    // OKP / Ed25519 only
-    let x = match &jwk.algorithm {
-        AlgorithmParameters::OctetKeyPair(params) => {
-            // `EllipticCurve` does not implement Display/ToString, so match explicitly.
-            match params.curve {
-                jsonwebtoken::jwk::EllipticCurve::Ed25519 => params.x.clone(),
-                _ => return Err(DpopError::UnsupportedJwk),
+    match (&jwk.algorithm, alg) {
+        (AlgorithmParameters::OctetKeyPair(p), Algorithm::EdDSA) => match p.curve {
+            EllipticCurve::Ed25519 => Ok(()),
+            _ => Err(DpopError::UnsupportedJwk),
+        },
+        (AlgorithmParameters::EllipticCurve(p), Algorithm::ES256) => match p.curve {
+            EllipticCurve::P256 => Ok(()),
+            _ => Err(DpopError::UnsupportedJwk),
+        },
+        (AlgorithmParameters::EllipticCurve(p), Algorithm::ES384) => match p.curve {
+            EllipticCurve::P384 => Ok(()),
+            _ => Err(DpopError::UnsupportedJwk),
+        },
+        (
+            AlgorithmParameters::RSA(p),
+            Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512,
+        ) => {
+            let n = base64::engine::general_purpose::URL_SAFE_NO_PAD
+                .decode(&p.n)
+                .map_err(|_| DpopError::UnsupportedJwk)?;
+            // Leading zero bytes do not count towards the modulus size.
+            let significant = n.iter().skip_while(|b| **b == 0).count();
+            if significant * 8 < MIN_RSA_MODULUS_BITS {
+                return Err(DpopError::UnsupportedJwk);
             }
+            Ok(())
         }
-        _ => return Err(DpopError::UnsupportedJwk),
-    };
+        _ => Err(DpopError::UnsupportedJwk),
+    }
+}
+
+// auth server と同じ実装 (jwk-thumbprint crate) を使い、cnf.jkt と必ず一致させる
+fn compute_jwk_thumbprint(jwk: &Jwk) -> Result<String, DpopError> {
+    jwk_thumbprint::from_jwk(jwk).map_err(|_| DpopError::UnsupportedJwk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // RFC7638 canonical JSON (lexicographic member order): crv, kty, x
-    let canonical = format!("{{\"crv\":\"Ed25519\",\"kty\":\"OKP\",\"x\":\"{}\"}}", x);
+    fn jwk(value: serde_json::Value) -> Jwk {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn thumbprint_matches_rfc7638_vector() {
+        // RFC 7638 Section 3.1
+        let key = jwk(serde_json::json!({
+            "kty": "RSA",
+            "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+            "e": "AQAB",
+            "alg": "RS256",
+            "kid": "2011-04-29",
+        }));
+        assert_eq!(
+            compute_jwk_thumbprint(&key).unwrap(),
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+    }
 
-    let digest = Sha256::digest(canonical.as_bytes());
-    Ok(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(digest))
+    #[test]
+    fn thumbprint_matches_rfc8037_vector() {
+        // RFC 8037 Appendix A.3
+        let key = jwk(serde_json::json!({
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo",
+        }));
+        assert_eq!(
+            compute_jwk_thumbprint(&key).unwrap(),
+            "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k"
+        );
+    }
+
+    #[test]
+    fn thumbprint_rejects_non_canonical_members() {
+        // 生の文字列をそのまま hash すると auth server と値が食い違う入力
+        let padded = jwk(serde_json::json!({
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo=",
+        }));
+        assert!(matches!(
+            compute_jwk_thumbprint(&padded),
+            Err(DpopError::UnsupportedJwk)
+        ));
+    }
 }
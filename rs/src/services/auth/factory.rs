@@ -1,11 +1,17 @@
 use std::convert::TryFrom;
 /// Factory: build `AuthService` from application `Config`.
 use std::sync::Arc;
+use std::time::Duration;
 
+use dpop_nonce::DpopNonceIssuer;
+
+use crate::api::well_known::protected_resource_metadata_url;
 use crate::config::Config;
 use crate::error::AppError;
 use crate::services::auth::AuthService;
+use crate::services::auth::denylist::Denylist;
 use crate::services::auth::dpop::core::DpopPolicy;
+use crate::services::auth::jwks::{JwksCache, JwksSource};
 use crate::services::auth::replay::store::ReplayStore;
 use crate::services::auth::replay::valkey::ValkeyReplayStore;
 
@@ -24,6 +30,7 @@ pub async fn build_auth_service(config: &Config) -> Result<Arc<AuthService>, App
         require_ath: config.dpop_required_ath,
         require_nonce: config.dpop_require_nonce,
         replay_ttl_seconds: config.dpop_replay_ttl_seconds,
+        allowed_algs: config.dpop_allowed_algs.clone(),
     };
 
     // Replay store -- fail-closed: backend failure becomes Internal.
@@ -34,7 +41,7 @@ pub async fn build_auth_service(config: &Config) -> Result<Arc<AuthService>, App
     );
 
     let auth = AuthService::new(
-        &config.access_jwt_public_key_pem,
+        config.access_jwt_public_key_pem.as_deref(),
         &config.auth_issuer,
         &config.auth_audience,
         config.access_token_leeway_seconds,
@@ -42,7 +49,78 @@ pub async fn build_auth_service(config: &Config) -> Result<Arc<AuthService>, App
         replay_store,
         config.public_base_url.clone(),
     )
-    .map_err(|_| AppError::Internal)?;
+    .map_err(|_| AppError::Internal)?
+    .with_error_detail(!config.app_env.is_production())
+    .with_resource_metadata(protected_resource_metadata_url(config))
+    .with_denylist(build_denylist(config).await?);
+
+    let auth = match build_jwks(config).await? {
+        Some(jwks) => auth.with_jwks(jwks),
+        None => auth,
+    };
+
+    let auth = if config.dpop_require_nonce {
+        // The auth server uses its own label (and normally its own secret).
+        const NONCE_LABEL: &str = "dpop-nonce:rs:";
+        let issuer = match &config.dpop_nonce_secret {
+            Some(secret) => DpopNonceIssuer::new(
+                NONCE_LABEL,
+                secret.as_bytes().to_vec(),
+                config.dpop_nonce_ttl_seconds,
+            ),
+            None => {
+                // Replicas would reject each other's nonces; fine for local development.
+                tracing::warn!(
+                    "DPOP_NONCE_SECRET is not set; using a random per-process nonce secret"
+                );
+                DpopNonceIssuer::with_random_secret(NONCE_LABEL, config.dpop_nonce_ttl_seconds)
+            }
+        };
+        auth.with_nonce_issuer(issuer)
+    } else {
+        auth
+    };
 
     Ok(Arc::new(auth))
 }
+
+// Revoked sessions / tokens, kept in sync via Valkey pub/sub.
+async fn build_denylist(config: &Config) -> Result<Arc<Denylist>, AppError> {
+    let denylist = Denylist::new(&config.valkey_url).await.map_err(|err| {
+        tracing::error!(error = %err, "failed to connect to valkey (denylist)");
+        AppError::Internal
+    })?;
+    let denylist = Arc::new(denylist);
+    denylist.spawn_subscriber();
+    Ok(denylist)
+}
+
+async fn build_jwks(config: &Config) -> Result<Option<Arc<JwksCache>>, AppError> {
+    let source = match (&config.access_jwt_jwks_url, &config.access_jwt_jwks_file) {
+        (Some(url), _) => JwksSource::Url(url.clone()),
+        (None, Some(path)) => JwksSource::File(path.into()),
+        (None, None) => return Ok(None),
+    };
+
+    let jwks = JwksCache::new(
+        source,
+        Duration::from_secs(config.jwks_min_refresh_interval_seconds),
+    )
+    .map_err(|_| AppError::Internal)?;
+    let jwks = Arc::new(jwks);
+
+    // The auth server may not be up yet: start empty and fetch again on the first token.
+    match jwks.refresh().await {
+        Ok(count) => tracing::info!(keys = count, "jwks loaded"),
+        Err(err) if config.access_jwt_public_key_pem.is_some() => {
+            tracing::warn!(error = %err, "initial jwks load failed; using ACCESS_JWT_PUBLIC_KEY_PEM until it succeeds")
+        }
+        Err(err) => tracing::warn!(error = %err, "initial jwks load failed; retrying on demand"),
+    }
+
+    if config.jwks_refresh_seconds > 0 {
+        jwks.spawn_periodic_refresh(Duration::from_secs(config.jwks_refresh_seconds));
+    }
+
+    Ok(Some(jwks))
+}
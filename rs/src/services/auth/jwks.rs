@@ -0,0 +1,305 @@
+/*
+ * Responsibility
+ * - auth server の JWKS (URL またはローカルファイル) から access token 検証鍵を読み込む
+ * - `kid` ごとに DecodingKey をキャッシュし、未知の `kid` ではレート制限付きで再取得する
+ * - 取得に失敗しても直前に成功した鍵セット (last-known-good) を使い続ける
+ */
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::DecodingKey;
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, JwkSet, PublicKeyUse};
+use tokio::sync::Mutex;
+
+// Upper bound for one fetch so a hung auth server can't stall request handling.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where the key set comes from.
+#[derive(Debug, Clone)]
+pub enum JwksSource {
+    Url(String),
+    File(PathBuf),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum JwksError {
+    #[error("jwks fetch failed: {0}")]
+    Fetch(#[from] reqwest::Error),
+    #[error("jwks read failed: {0}")]
+    Read(#[from] std::io::Error),
+    #[error("jwks parse failed: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("jwks contains no usable Ed25519 signing keys")]
+    NoUsableKeys,
+}
+
+type KeySet = HashMap<String, DecodingKey>;
+
+/// `kid` -> access token verification key cache.
+pub struct JwksCache {
+    source: JwksSource,
+    client: reqwest::Client,
+    keys: RwLock<Arc<KeySet>>,
+    // Time of the last fetch attempt (success or failure). Held across the fetch so
+    // concurrent misses for the same unknown `kid` trigger only one request.
+    last_attempt: Mutex<Option<Instant>>,
+    min_refresh_interval: Duration,
+}
+
+impl std::fmt::Debug for JwksCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JwksCache")
+            .field("source", &self.source)
+            .field("kids", &self.kids())
+            .field("min_refresh_interval", &self.min_refresh_interval)
+            .finish()
+    }
+}
+
+impl JwksCache {
+    pub fn new(source: JwksSource, min_refresh_interval: Duration) -> Result<Self, JwksError> {
+        let client = reqwest::Client::builder().timeout(FETCH_TIMEOUT).build()?;
+
+        Ok(Self {
+            source,
+            client,
+            keys: RwLock::new(Arc::new(HashMap::new())),
+            last_attempt: Mutex::new(None),
+            min_refresh_interval,
+        })
+    }
+
+    /// Currently cached `kid`s.
+    pub fn kids(&self) -> Vec<String> {
+        let keys = self.snapshot();
+        let mut kids = keys.keys().cloned().collect::<Vec<_>>();
+        kids.sort();
+        kids
+    }
+
+    /// Fetch the key set now, ignoring the rate limit.
+    ///
+    /// On failure the previously loaded keys stay in place.
+    pub async fn refresh(&self) -> Result<usize, JwksError> {
+        let mut last_attempt = self.last_attempt.lock().await;
+        self.refresh_locked(&mut last_attempt).await
+    }
+
+    /// Look up the key for `kid`, refreshing once if it is unknown and the last
+    /// attempt is older than `min_refresh_interval`.
+    pub async fn get(&self, kid: &str) -> Option<DecodingKey> {
+        if let Some(key) = self.snapshot().get(kid) {
+            return Some(key.clone());
+        }
+
+        let mut last_attempt = self.last_attempt.lock().await;
+
+        // Another request may have refreshed while we waited for the lock.
+        if let Some(key) = self.snapshot().get(kid) {
+            return Some(key.clone());
+        }
+        if last_attempt.is_some_and(|t| t.elapsed() < self.min_refresh_interval) {
+            tracing::debug!(kid = %kid, "unknown jwks kid; refresh rate limited");
+            return None;
+        }
+
+        tracing::info!(kid = %kid, "unknown jwks kid; refreshing key set");
+        if let Err(err) = self.refresh_locked(&mut last_attempt).await {
+            tracing::warn!(error = %err, "jwks refresh failed; keeping last-known-good keys");
+            return None;
+        }
+
+        self.snapshot().get(kid).cloned()
+    }
+
+    /// Refresh every `interval` so retired keys eventually drop out.
+    pub fn spawn_periodic_refresh(self: &Arc<Self>, interval: Duration) {
+        let cache = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await; // the first tick completes immediately
+            loop {
+                ticker.tick().await;
+                if let Err(err) = cache.refresh().await {
+                    tracing::warn!(error = %err, "periodic jwks refresh failed; keeping last-known-good keys");
+                }
+            }
+        });
+    }
+
+    fn snapshot(&self) -> Arc<KeySet> {
+        // A poisoned lock still holds a complete key set (we only ever swap the Arc).
+        self.keys.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    async fn refresh_locked(&self, last_attempt: &mut Option<Instant>) -> Result<usize, JwksError> {
+        *last_attempt = Some(Instant::now());
+
+        let set = self.load().await?;
+        let keys = usable_keys(&set);
+        if keys.is_empty() {
+            return Err(JwksError::NoUsableKeys);
+        }
+
+        let count = keys.len();
+        *self.keys.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(keys);
+        tracing::debug!(keys = count, "jwks loaded");
+        Ok(count)
+    }
+
+    async fn load(&self) -> Result<JwkSet, JwksError> {
+        match &self.source {
+            JwksSource::Url(url) => Ok(self
+                .client
+                .get(url)
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<JwkSet>()
+                .await?),
+            JwksSource::File(path) => {
+                let raw = tokio::fs::read(path).await?;
+                Ok(serde_json::from_slice(&raw)?)
+            }
+        }
+    }
+}
+
+// Access tokens are EdDSA-only, so keep Ed25519 signing keys that have a `kid`.
+fn usable_keys(set: &JwkSet) -> KeySet {
+    set.keys
+        .iter()
+        .filter(|jwk| !matches!(jwk.common.public_key_use, Some(PublicKeyUse::Encryption)))
+        .filter(|jwk| {
+            matches!(
+                &jwk.algorithm,
+                AlgorithmParameters::OctetKeyPair(p) if p.curve == EllipticCurve::Ed25519
+            )
+        })
+        .filter_map(|jwk| {
+            let kid = jwk.common.key_id.clone()?;
+            match DecodingKey::from_jwk(jwk) {
+                Ok(key) => Some((kid, key)),
+                Err(err) => {
+                    tracing::warn!(kid = %kid, error = %err, "skipping unusable jwk");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use axum::{Router, http::StatusCode, routing::get};
+
+    // Test-only Ed25519 public key (x coordinate).
+    const X: &str = "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo";
+
+    fn jwks(kids: &[&str]) -> String {
+        let keys = kids
+            .iter()
+            .map(|kid| {
+                format!(r#"{{"kty":"OKP","crv":"Ed25519","x":"{X}","kid":"{kid}","use":"sig","alg":"EdDSA"}}"#)
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(r#"{{"keys":[{keys}]}}"#)
+    }
+
+    struct Stub {
+        url: String,
+        hits: Arc<AtomicUsize>,
+        kids: Arc<std::sync::Mutex<Vec<&'static str>>>,
+        failing: Arc<AtomicBool>,
+    }
+
+    async fn stub(kids: &[&'static str]) -> Stub {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let current = Arc::new(std::sync::Mutex::new(kids.to_vec()));
+        let failing = Arc::new(AtomicBool::new(false));
+
+        let (h, c, f) = (hits.clone(), current.clone(), failing.clone());
+        let app = Router::new().route(
+            "/jwks.json",
+            get(move || {
+                let (h, c, f) = (h.clone(), c.clone(), f.clone());
+                async move {
+                    h.fetch_add(1, Ordering::SeqCst);
+                    if f.load(Ordering::SeqCst) {
+                        return (StatusCode::INTERNAL_SERVER_ERROR, String::new());
+                    }
+                    (StatusCode::OK, jwks(&c.lock().unwrap()))
+                }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        Stub {
+            url: format!("http://{addr}/jwks.json"),
+            hits,
+            kids: current,
+            failing,
+        }
+    }
+
+    #[tokio::test]
+    async fn loads_keys_by_kid() {
+        let stub = stub(&["k1", "k2"]).await;
+        let cache = JwksCache::new(JwksSource::Url(stub.url), Duration::ZERO).unwrap();
+
+        assert_eq!(cache.refresh().await.unwrap(), 2);
+        assert_eq!(cache.kids(), vec!["k1", "k2"]);
+        assert!(cache.get("k1").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn unknown_kid_refreshes_with_rate_limit() {
+        let stub = stub(&["k1"]).await;
+        let cache = JwksCache::new(JwksSource::Url(stub.url), Duration::from_secs(60)).unwrap();
+        cache.refresh().await.unwrap();
+        assert_eq!(stub.hits.load(Ordering::SeqCst), 1);
+
+        // Rotated on the auth server, but we refreshed too recently to look again.
+        stub.kids.lock().unwrap().push("k2");
+        assert!(cache.get("k2").await.is_none());
+        assert!(cache.get("k2").await.is_none());
+        assert_eq!(stub.hits.load(Ordering::SeqCst), 1);
+
+        *cache.last_attempt.lock().await = None;
+        assert!(cache.get("k2").await.is_some());
+        assert_eq!(stub.hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn failed_refresh_keeps_last_known_good() {
+        let stub = stub(&["k1"]).await;
+        let cache = JwksCache::new(JwksSource::Url(stub.url), Duration::ZERO).unwrap();
+        cache.refresh().await.unwrap();
+
+        stub.failing.store(true, Ordering::SeqCst);
+        assert!(cache.refresh().await.is_err());
+        assert!(cache.get("unknown").await.is_none());
+        assert!(cache.get("k1").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn reads_local_file() {
+        let path = std::env::temp_dir().join(format!("jwks-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, jwks(&["file-key"])).unwrap();
+
+        let cache = JwksCache::new(JwksSource::File(path.clone()), Duration::ZERO).unwrap();
+        assert_eq!(cache.refresh().await.unwrap(), 1);
+        assert!(cache.get("file-key").await.is_some());
+
+        std::fs::remove_file(path).ok();
+    }
+}
@@ -1,6 +1,9 @@
 pub mod access_jwt;
+pub mod challenge;
+pub mod denylist;
 pub mod dpop;
 pub mod factory;
+pub mod jwks;
 pub mod replay;
 
 pub use access_jwt::AuthService;
@@ -6,6 +6,7 @@
  */
 use std::sync::Arc;
 
+use crate::api::well_known::ProtectedResourceMetadata;
 use crate::services::{auth::AuthService, id_codec::IdCodec};
 
 #[derive(Clone, Debug)]
@@ -13,10 +14,21 @@ pub struct AppState {
     pub db: sqlx::PgPool,
     pub id_codec: IdCodec,
     pub auth: Arc<AuthService>,
+    pub metadata: Arc<ProtectedResourceMetadata>,
 }
 
 impl AppState {
-    pub fn new(db: sqlx::PgPool, id_codec: IdCodec, auth: Arc<AuthService>) -> Self {
-        Self { db, id_codec, auth }
+    pub fn new(
+        db: sqlx::PgPool,
+        id_codec: IdCodec,
+        auth: Arc<AuthService>,
+        metadata: Arc<ProtectedResourceMetadata>,
+    ) -> Self {
+        Self {
+            db,
+            id_codec,
+            auth,
+            metadata,
+        }
     }
 }